@@ -0,0 +1,73 @@
+// industrial-io/build.rs
+//
+// The builder for the Rust Industrial I/O crate.
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+// Generates the gRPC service stubs.
+//
+// The messages are defined by hand in `src/grpc.rs`, to match
+// `proto/iio.proto`, so that building the crate doesn't require `protoc`.
+#[cfg(feature = "grpc")]
+fn gen_grpc() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let method = |name: &str, route: &str, input: &str, output: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(format!("super::{}", input))
+            .output_type(format!("super::{}", output))
+            .codec_path("tonic::codec::ProstCodec")
+    };
+
+    let service = Service::builder()
+        .name("Iio")
+        .package("iio")
+        .method(
+            method(
+                "list_devices",
+                "ListDevices",
+                "ListDevicesRequest",
+                "ListDevicesResponse",
+            )
+            .build(),
+        )
+        .method(
+            method(
+                "read_attributes",
+                "ReadAttributes",
+                "ReadAttributesRequest",
+                "ReadAttributesResponse",
+            )
+            .build(),
+        )
+        .method(
+            method(
+                "write_attribute",
+                "WriteAttribute",
+                "WriteAttributeRequest",
+                "WriteAttributeResponse",
+            )
+            .build(),
+        )
+        .method(
+            method("capture", "Capture", "CaptureRequest", "Frame")
+                .server_streaming()
+                .build(),
+        )
+        .build();
+
+    Builder::new().compile(&[service]);
+}
+
+fn main() {
+    #[cfg(feature = "grpc")]
+    gen_grpc();
+}
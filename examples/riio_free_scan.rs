@@ -87,7 +87,12 @@ fn main() {
     }
 
     for chan in dev.channels() {
-        let data: Vec<u16> = buf.channel_iter::<u16>(&chan).map(|&x| x).collect();
-        println!("{}: {:?}", chan.id().unwrap_or_default(), data);
+        match buf.channel_iter::<u16>(&chan) {
+            Ok(iter) => {
+                let data: Vec<u16> = iter.map(|&x| x).collect();
+                println!("{}: {:?}", chan.id().unwrap_or_default(), data);
+            }
+            Err(err) => eprintln!("{}", err),
+        }
     }
 }
@@ -24,20 +24,22 @@ fn main() {
     use industrial_io as iio;
     use std::process;
 
-    for backend in &["local", "ip", "usb"] {
-        let scan_ctx = iio::ScanContext::new(backend).unwrap_or_else(|err| {
-            eprintln!("Can't create scan context: {}", err);
-            process::exit(1);
-        });
+    let scan_ctx = iio::ScanContext::new_all().unwrap_or_else(|err| {
+        eprintln!("Can't create scan context: {}", err);
+        process::exit(1);
+    });
+
+    let results: Vec<_> = scan_ctx.iter().collect();
 
-        let n = scan_ctx.len();
+    for backend in &["local", "ip", "usb"] {
+        let n = results.iter().filter(|res| res.backend == *backend).count();
         if n == 0 {
             continue;
         }
 
         println!("{}: [{}]", backend, n);
-        for ctx in scan_ctx.iter() {
-            println!("  {}: {}", ctx.0, ctx.1);
+        for res in results.iter().filter(|res| res.backend == *backend) {
+            println!("  {}: {}", res.uri, res.description);
         }
     }
 }
@@ -13,12 +13,13 @@
 // to those terms.
 //
 
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use chrono::{offset::Utc, DateTime};
 use clap::{arg, value_parser, ArgAction, Command};
+use iio::cookbook::{self, StreamConfig};
 use industrial_io as iio;
 use std::{
-    cmp, process,
+    process,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -29,11 +30,6 @@ use std::{
 const DFLT_DEV_NAME: &str = "ads1015";
 const DFLT_CHAN_NAME: &str = "voltage0";
 
-const SAMPLING_FREQ_ATTR: &str = "sampling_frequency";
-
-const DFLT_FREQ: i64 = 100;
-const DFLT_NUM_SAMPLE: usize = 100;
-
 // --------------------------------------------------------------------------
 
 fn run() -> Result<()> {
@@ -57,7 +53,7 @@ fn run() -> Result<()> {
                 .value_parser(value_parser!(usize)),
             arg!(-f --frequency "Specifies the sampling frequency")
                 .action(ArgAction::Set)
-                .value_parser(value_parser!(i64)),
+                .value_parser(value_parser!(f64)),
             arg!(-'v' --version "Print version information").action(ArgAction::Version),
             arg!(-'?' --help "Print help information")
                 .global(true)
@@ -83,64 +79,22 @@ fn run() -> Result<()> {
         .find_device(dev_name)
         .context(format!("No IIO device named '{}'", dev_name))?;
 
-    // ----- Find the timestamp channel and a voltage channel -----
-
-    let ts_chan = dev
-        .find_channel("timestamp", iio::Direction::Input)
-        .context("No timestamp channel on this device")?;
-
-    let sample_chan = dev
-        .find_channel(chan_name, iio::Direction::Input)
-        .context(format!("No '{}' channel on this device", chan_name))?;
+    // ----- Configure the capture -----
 
-    ts_chan.enable();
-    sample_chan.enable();
+    let mut cfg = StreamConfig::new(chan_name).require_timestamp(true);
 
-    // ----- Set sample frequency and trigger -----
-
-    let freq = *args.get_one("frequency").unwrap_or(&DFLT_FREQ);
-
-    // If the user asked for a trigger device, see if we can use it
     if let Some(trig_name) = args.get_one::<String>("trigger") {
-        let trig = ctx
-            .find_device(trig_name)
-            .context(format!("Couldn't find requested trigger: {}", trig_name))?;
-
-        // Set the sampling rate on the trigger device
-        trig.attr_write(SAMPLING_FREQ_ATTR, freq)
-            .with_context(|| format!("Can't set sampling rate to {}Hz on {}", freq, trig_name))?;
-
-        dev.set_trigger(&trig)
-            .context("Error setting the trigger on the device")?;
+        cfg = cfg.trigger(trig_name);
     }
-    else if dev.has_attr(SAMPLING_FREQ_ATTR) {
-        // Try to set the sampling rate on the device itself, if supported
-        dev.attr_write(SAMPLING_FREQ_ATTR, freq).with_context(|| {
-            format!(
-                "Can't set sampling rate to {}Hz on {}",
-                freq,
-                dev.name().unwrap()
-            )
-        })?;
+    if let Some(&freq) = args.get_one::<f64>("frequency") {
+        cfg = cfg.sample_rate(freq);
     }
-    else {
-        bail!("No suitable trigger device found");
+    if let Some(&n_sample) = args.get_one::<usize>("num_sample") {
+        cfg = cfg.samples(n_sample);
     }
 
-    // ----- Create a buffer -----
-
-    let n_sample = *args.get_one("num_sample").unwrap_or(&DFLT_NUM_SAMPLE);
-
-    let mut buf = dev
-        .create_buffer(n_sample, false)
-        .context("Unable to create buffer")?;
-
-    // Make sure the timeout is more than enough to gather each buffer
-    // Give 50% extra time, or at least 5sec.
-    let ms = cmp::max(5000, 1500 * (n_sample as u64) / (freq as u64));
-    if let Err(err) = ctx.set_timeout_ms(ms) {
-        eprintln!("Error setting timeout of {}ms: {}", ms, err);
-    }
+    let stream = cookbook::timestamped_sample_stream(&dev, &cfg)
+        .with_context(|| format!("Unable to start capturing '{}'", chan_name))?;
 
     // ---- Handle ^C for a graceful shutdown -----
 
@@ -156,22 +110,20 @@ fn run() -> Result<()> {
 
     println!("Staring buffer capture...");
 
-    while !quit.load(Ordering::SeqCst) {
-        buf.refill().context("Error filling the buffer")?;
-
-        // Extract and print the data
-
-        let ts_data = buf.channel_iter::<u64>(&ts_chan);
+    for block in stream {
+        if quit.load(Ordering::SeqCst) {
+            break;
+        }
 
         // The timestamp is represented as a 64-bit integer number of
         // nanoseconds since the Unix Epoch. We convert to a Rust SystemTime,
         // then a chrono DataTime for pretty printing.
-        buf.channel_iter::<u16>(&sample_chan)
-            .zip(ts_data.map(|&ts| {
-                DateTime::<Utc>::from(SystemTime::UNIX_EPOCH + Duration::from_nanos(ts))
-                    .format("%T%.6f")
-            }))
-            .for_each(|(data, time)| println!("{}: {}", time, data));
+        for sample in block.context("Error reading the buffer")? {
+            let time = DateTime::<Utc>::from(
+                SystemTime::UNIX_EPOCH + Duration::from_nanos(sample.timestamp),
+            );
+            println!("{}: {}", time.format("%T%.6f"), sample.raw);
+        }
     }
 
     Ok(())
@@ -0,0 +1,144 @@
+// industrial-io/src/abi.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Names of the standard attributes of the IIO ABI.
+//!
+//! The kernel documents a common set of attribute names for IIO devices,
+//! channels, buffers, and events in
+//! `Documentation/ABI/testing/sysfs-bus-iio`. Using these constants
+//! rather than string literals turns a typo in an attribute name into a
+//! compile error, instead of an `ENOENT` at runtime. The attribute
+//! functions of devices, channels, and buffers accept any
+//! `impl AsRef<str>`, so they can be passed directly.
+//!
+//! The names are those used by _libiio_, without the `in_`/`out_` prefix
+//! or the channel name.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::abi;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ads1015").unwrap();
+//! let chan = dev.find_input_channel("voltage0").unwrap();
+//!
+//! dev.attr_write_float(abi::dev::SAMPLING_FREQUENCY, 100.0).unwrap();
+//! let raw = chan.attr_read_int(abi::chan::RAW).unwrap();
+//! let scale = chan.attr_read_float(abi::chan::SCALE).unwrap();
+//! println!("{} mV", raw as f64 * scale);
+//! ```
+
+/// The suffix on an attribute name for the list of its available values.
+pub const AVAILABLE_SUFFIX: &str = "_available";
+
+/// Device attributes.
+pub mod dev {
+    /// The rate at which the device takes samples, in Hz
+    pub const SAMPLING_FREQUENCY: &str = "sampling_frequency";
+    /// The sampling frequencies that the device supports
+    pub const SAMPLING_FREQUENCY_AVAILABLE: &str = "sampling_frequency_available";
+    /// The number of samples averaged into each reading
+    pub const OVERSAMPLING_RATIO: &str = "oversampling_ratio";
+    /// The oversampling ratios that the device supports
+    pub const OVERSAMPLING_RATIO_AVAILABLE: &str = "oversampling_ratio_available";
+    /// The clock used for the timestamps of the device, like "realtime"
+    pub const CURRENT_TIMESTAMP_CLOCK: &str = "current_timestamp_clock";
+    /// Writing to this fires a sysfs trigger
+    pub const TRIGGER_NOW: &str = "trigger_now";
+}
+
+/// Channel attributes.
+pub mod chan {
+    /// The raw, unscaled value of the channel
+    pub const RAW: &str = "raw";
+    /// The range, or list, of raw values that the channel accepts
+    pub const RAW_AVAILABLE: &str = "raw_available";
+    /// The value of the channel, already scaled to the ABI units
+    pub const INPUT: &str = "input";
+    /// The scale to apply to a raw value, after the offset, to get the
+    /// value in the ABI units
+    pub const SCALE: &str = "scale";
+    /// The scales that the channel supports
+    pub const SCALE_AVAILABLE: &str = "scale_available";
+    /// The offset to add to a raw value, before it is scaled
+    pub const OFFSET: &str = "offset";
+    /// A calibration offset applied by the hardware
+    pub const CALIBBIAS: &str = "calibbias";
+    /// A calibration scale applied by the hardware
+    pub const CALIBSCALE: &str = "calibscale";
+    /// The gain of the hardware amplifier for the channel
+    pub const HARDWAREGAIN: &str = "hardwaregain";
+    /// The rate at which the channel takes samples, in Hz
+    pub const SAMPLING_FREQUENCY: &str = "sampling_frequency";
+    /// The frequency of an output, like a DDS tone, in Hz
+    pub const FREQUENCY: &str = "frequency";
+    /// The phase of an output, in radians
+    pub const PHASE: &str = "phase";
+    /// Whether an output channel is powered down
+    pub const POWERDOWN: &str = "powerdown";
+    /// The label of the channel
+    pub const LABEL: &str = "label";
+}
+
+/// Buffer attributes.
+pub mod buf {
+    /// The number of samples in the kernel buffer
+    pub const LENGTH: &str = "length";
+    /// The number of samples to wait for before waking up a reader
+    pub const WATERMARK: &str = "watermark";
+    /// The number of samples that are ready to be read
+    pub const DATA_AVAILABLE: &str = "data_available";
+    /// Whether the buffer is enabled
+    pub const ENABLE: &str = "enable";
+}
+
+/// The info parts of event attribute names.
+///
+/// An event attribute is named like `in_<channel>_<type>_<dir>_<info>`.
+/// Use these with [`EventAttr`](crate::events::EventAttr) to build the
+/// full name.
+pub mod event {
+    /// Whether the event is enabled
+    pub const EN: &str = "en";
+    /// The threshold, or other value, at which the event fires
+    pub const VALUE: &str = "value";
+    /// The hysteresis of the threshold
+    pub const HYSTERESIS: &str = "hysteresis";
+    /// How long the condition must hold before the event fires, in
+    /// seconds
+    pub const PERIOD: &str = "period";
+    /// How long before an adaptive threshold is updated, in seconds
+    pub const TIMEOUT: &str = "timeout";
+}
+
+/// Gets the name of the attribute that lists the available values of
+/// another one, like `scale_available` for `scale`.
+pub fn available(attr: &str) -> String {
+    format!("{}{}", attr, AVAILABLE_SUFFIX)
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn available_names() {
+        assert_eq!(available(chan::SCALE), chan::SCALE_AVAILABLE);
+        assert_eq!(available(chan::RAW), chan::RAW_AVAILABLE);
+        assert_eq!(
+            available(dev::SAMPLING_FREQUENCY),
+            dev::SAMPLING_FREQUENCY_AVAILABLE
+        );
+    }
+}
@@ -0,0 +1,112 @@
+// industrial-io/src/async_attr.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Asynchronous attribute I/O with _tokio_.
+//!
+//! With the network backend, each attribute read or write is a round trip
+//! to the IIO daemon, which blocks the calling thread. In an async
+//! application, like a web dashboard, that stalls the executor thread and
+//! every other task on it.
+//!
+//! The `attr_read_async()` and `attr_write_async()` functions of devices
+//! and channels run the operation on the _tokio_ blocking thread pool
+//! instead, and return a future that can be awaited. The futures are
+//! `Send` and `'static`, so they can also be spawned as tasks or joined
+//! to run several requests at once.
+//!
+//! The operations are still serialized over the single connection of the
+//! context, so use a [`ContextPool`](crate::ContextPool) to overlap them.
+//! These must be called from within a _tokio_ runtime.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//!
+//! # async fn refresh() -> iio::Result<()> {
+//! let ctx = iio::Context::from_uri("ip:192.168.2.1")?;
+//! let dev = ctx.find_device("ad9361-phy").unwrap();
+//! let lo = dev.find_output_channel("altvoltage0").unwrap();
+//!
+//! let mode = dev.attr_read_async("ensm_mode").await?;
+//! lo.attr_write_async("frequency", "2400000000").await?;
+//! println!("{}", mode);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Channel, Device, Error, Result};
+use std::{future::Future, io};
+
+/// Runs a blocking operation on the _tokio_ blocking thread pool.
+pub(crate) async fn blocking<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(io::Error::from)?
+}
+
+impl Device {
+    /// Reads a device-specific attribute as a string, without blocking
+    /// the executor.
+    pub fn attr_read_async(&self, attr: &str) -> impl Future<Output = Result<String>> + Send {
+        let dev = self.clone();
+        let attr = attr.to_string();
+        blocking(move || dev.attr_read_str(&attr))
+    }
+
+    /// Writes a device-specific attribute as a string, without blocking
+    /// the executor.
+    pub fn attr_write_async(
+        &self,
+        attr: &str,
+        val: &str,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let dev = self.clone();
+        let (attr, val) = (attr.to_string(), val.to_string());
+        blocking(move || dev.attr_write_str(&attr, &val))
+    }
+}
+
+impl Channel {
+    /// Finds the channel again, in its device, on the blocking thread.
+    ///
+    /// A channel can't be sent to another thread, but its device can.
+    fn locate(&self) -> impl FnOnce() -> Result<Channel> + Send {
+        let dev = self.device();
+        let id = self.id().unwrap_or_default();
+        let dir = self.direction();
+        move || {
+            dev.find_channel(&id, dir)
+                .ok_or_else(|| Error::General(format!("Channel not found: '{}'", id)))
+        }
+    }
+
+    /// Reads a channel-specific attribute as a string, without blocking
+    /// the executor.
+    pub fn attr_read_async(&self, attr: &str) -> impl Future<Output = Result<String>> + Send {
+        let chan = self.locate();
+        let attr = attr.to_string();
+        blocking(move || chan()?.attr_read_str(&attr))
+    }
+
+    /// Writes a channel-specific attribute as a string, without blocking
+    /// the executor.
+    pub fn attr_write_async(
+        &self,
+        attr: &str,
+        val: &str,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let chan = self.locate();
+        let (attr, val) = (attr.to_string(), val.to_string());
+        blocking(move || chan()?.attr_write_str(&attr, &val))
+    }
+}
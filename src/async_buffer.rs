@@ -0,0 +1,149 @@
+// industrial-io/src/async_buffer.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Asynchronous buffer I/O with _tokio_.
+//!
+//! An [`AsyncBuffer`] puts a [`Buffer`] into non-blocking mode, and
+//! registers its pollable file descriptor with the _tokio_ reactor, so that
+//! a task can `await` a refill or push rather than dedicating a blocking
+//! thread to each device.
+//!
+//! This requires a backend that provides a pollable file descriptor for
+//! the buffer, which is the local backend. It must be created from within
+//! a _tokio_ runtime.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::AsyncBuffer;
+//!
+//! # async fn capture() -> iio::Result<()> {
+//! let ctx = iio::Context::new()?;
+//! let dev = ctx.find_device("ads1015").unwrap();
+//! let chan = dev.find_input_channel("voltage0").unwrap();
+//! chan.enable();
+//!
+//! let mut buf = AsyncBuffer::new(dev.create_buffer(256, false)?)?;
+//! loop {
+//!     buf.refill().await?;
+//!     let data: Vec<i16> = chan.read(&buf)?;
+//!     println!("{:?}", data);
+//! }
+//! # }
+//! ```
+
+use crate::{Buffer, Error, Result};
+use nix::errno::Errno;
+use std::{
+    ops::Deref,
+    os::fd::{AsRawFd, RawFd},
+};
+use tokio::io::{unix::AsyncFd, Interest};
+
+/// The pollable file descriptor of a buffer.
+///
+/// This is owned by the buffer, and must not outlive it.
+#[derive(Debug, Clone, Copy)]
+struct PollFd(RawFd);
+
+impl AsRawFd for PollFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// A buffer that can be refilled or pushed asynchronously.
+///
+/// This dereferences to the underlying [`Buffer`] to get at the data.
+#[derive(Debug)]
+pub struct AsyncBuffer {
+    /// The registration of the poll file descriptor with the reactor.
+    /// This is declared first so that it's dropped before the buffer
+    /// closes the descriptor.
+    fd: AsyncFd<PollFd>,
+    /// The underlying buffer, in non-blocking mode
+    buf: Buffer,
+}
+
+impl AsyncBuffer {
+    /// Wraps the buffer for asynchronous I/O.
+    ///
+    /// This puts the buffer into non-blocking mode. It fails if the
+    /// backend doesn't provide a pollable file descriptor for the buffer,
+    /// or if it isn't called from within a _tokio_ runtime.
+    pub fn new(buf: Buffer) -> Result<Self> {
+        let fd = PollFd(buf.poll_fd_raw()?);
+        buf.set_blocking_mode(false)?;
+        let fd = AsyncFd::with_interest(fd, Interest::READABLE | Interest::WRITABLE)?;
+        Ok(Self { fd, buf })
+    }
+
+    /// Gets a mutable reference to the underlying buffer.
+    pub fn get_mut(&mut self) -> &mut Buffer {
+        &mut self.buf
+    }
+
+    /// Gets the underlying buffer, back in blocking mode.
+    pub fn into_inner(self) -> Result<Buffer> {
+        let Self { fd, buf } = self;
+        drop(fd);
+        buf.set_blocking_mode(true)?;
+        Ok(buf)
+    }
+
+    /// Fetches more samples from the hardware, waiting until they are
+    /// available.
+    ///
+    /// This is only valid for input buffers.
+    pub async fn refill(&mut self) -> Result<usize> {
+        loop {
+            let mut guard = self.fd.readable().await?;
+            match self.buf.refill() {
+                Err(Error::Nix(Errno::EAGAIN)) => guard.clear_ready(),
+                res => return res,
+            }
+        }
+    }
+
+    /// Sends the samples to the hardware, waiting until there is room for
+    /// them.
+    ///
+    /// This is only valid for output buffers.
+    pub async fn push(&mut self) -> Result<usize> {
+        loop {
+            let mut guard = self.fd.writable().await?;
+            match self.buf.push() {
+                Err(Error::Nix(Errno::EAGAIN)) => guard.clear_ready(),
+                res => return res,
+            }
+        }
+    }
+
+    /// Sends the number of samples to the hardware, waiting until there is
+    /// room for them.
+    ///
+    /// This is only valid for output buffers.
+    pub async fn push_partial(&mut self, num_samples: usize) -> Result<usize> {
+        loop {
+            let mut guard = self.fd.writable().await?;
+            match self.buf.push_partial(num_samples) {
+                Err(Error::Nix(Errno::EAGAIN)) => guard.clear_ready(),
+                res => return res,
+            }
+        }
+    }
+}
+
+impl Deref for AsyncBuffer {
+    type Target = Buffer;
+
+    fn deref(&self) -> &Buffer {
+        &self.buf
+    }
+}
@@ -0,0 +1,162 @@
+// industrial-io/src/async_io.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Runtime-agnostic asynchronous buffer I/O with _async-io_.
+//!
+//! This is the counterpart of [`AsyncBuffer`](crate::AsyncBuffer) for
+//! applications that use _smol_, _async-std_, or any other executor,
+//! rather than _tokio_. An [`AsyncIoBuffer`] puts a [`Buffer`] into
+//! non-blocking mode, and registers its pollable file descriptor with the
+//! _async-io_ reactor, so that a task can `await` a refill or push. The
+//! [`AsyncBufferExt`] trait converts a buffer into one.
+//!
+//! This requires a backend that provides a pollable file descriptor for
+//! the buffer, which is the local backend.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::async_io::AsyncBufferExt;
+//!
+//! # async fn capture() -> iio::Result<()> {
+//! let ctx = iio::Context::new()?;
+//! let dev = ctx.find_device("ads1015").unwrap();
+//! let chan = dev.find_input_channel("voltage0").unwrap();
+//! chan.enable();
+//!
+//! let mut buf = dev.create_buffer(256, false)?.into_async_io()?;
+//! loop {
+//!     buf.refill().await?;
+//!     let data: Vec<i16> = chan.read(&buf)?;
+//!     println!("{:?}", data);
+//! }
+//! # }
+//! ```
+
+use crate::{Buffer, Error, Result};
+use ::async_io::Async;
+use nix::errno::Errno;
+use std::{
+    ops::Deref,
+    os::fd::{AsFd, BorrowedFd, RawFd},
+};
+
+/// The pollable file descriptor of a buffer.
+///
+/// This is owned by the buffer, and must not outlive it.
+#[derive(Debug, Clone, Copy)]
+struct PollFd(RawFd);
+
+impl AsFd for PollFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // The buffer outlives the registration. See AsyncIoBuffer.
+        unsafe { BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+/// A buffer that can be refilled or pushed asynchronously on any
+/// executor.
+///
+/// This dereferences to the underlying [`Buffer`] to get at the data.
+#[derive(Debug)]
+pub struct AsyncIoBuffer {
+    /// The registration of the poll file descriptor with the reactor.
+    /// This is declared first so that it's dropped before the buffer
+    /// closes the descriptor.
+    fd: Async<PollFd>,
+    /// The underlying buffer, in non-blocking mode
+    buf: Buffer,
+}
+
+impl AsyncIoBuffer {
+    /// Wraps the buffer for asynchronous I/O.
+    ///
+    /// This puts the buffer into non-blocking mode. It fails if the
+    /// backend doesn't provide a pollable file descriptor for the buffer.
+    pub fn new(buf: Buffer) -> Result<Self> {
+        let fd = PollFd(buf.poll_fd_raw()?);
+        buf.set_blocking_mode(false)?;
+        let fd = Async::new_nonblocking(fd)?;
+        Ok(Self { fd, buf })
+    }
+
+    /// Gets a mutable reference to the underlying buffer.
+    pub fn get_mut(&mut self) -> &mut Buffer {
+        &mut self.buf
+    }
+
+    /// Gets the underlying buffer, back in blocking mode.
+    pub fn into_inner(self) -> Result<Buffer> {
+        let Self { fd, buf } = self;
+        drop(fd);
+        buf.set_blocking_mode(true)?;
+        Ok(buf)
+    }
+
+    /// Fetches more samples from the hardware, waiting until they are
+    /// available.
+    ///
+    /// This is only valid for input buffers.
+    pub async fn refill(&mut self) -> Result<usize> {
+        loop {
+            match self.buf.refill() {
+                Err(Error::Nix(Errno::EAGAIN)) => self.fd.readable().await?,
+                res => return res,
+            }
+        }
+    }
+
+    /// Sends the samples to the hardware, waiting until there is room for
+    /// them.
+    ///
+    /// This is only valid for output buffers.
+    pub async fn push(&mut self) -> Result<usize> {
+        loop {
+            match self.buf.push() {
+                Err(Error::Nix(Errno::EAGAIN)) => self.fd.writable().await?,
+                res => return res,
+            }
+        }
+    }
+
+    /// Sends the number of samples to the hardware, waiting until there is
+    /// room for them.
+    ///
+    /// This is only valid for output buffers.
+    pub async fn push_partial(&mut self, num_samples: usize) -> Result<usize> {
+        loop {
+            match self.buf.push_partial(num_samples) {
+                Err(Error::Nix(Errno::EAGAIN)) => self.fd.writable().await?,
+                res => return res,
+            }
+        }
+    }
+}
+
+impl Deref for AsyncIoBuffer {
+    type Target = Buffer;
+
+    fn deref(&self) -> &Buffer {
+        &self.buf
+    }
+}
+
+/// Converts a buffer for asynchronous I/O with _async-io_.
+pub trait AsyncBufferExt {
+    /// Wraps the buffer for asynchronous I/O on any executor.
+    ///
+    /// See [`AsyncIoBuffer::new()`].
+    fn into_async_io(self) -> Result<AsyncIoBuffer>;
+}
+
+impl AsyncBufferExt for Buffer {
+    fn into_async_io(self) -> Result<AsyncIoBuffer> {
+        AsyncIoBuffer::new(self)
+    }
+}
@@ -0,0 +1,152 @@
+// industrial-io/src/attr_reader.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Streaming reads of large attributes.
+//!
+//! Attributes are normally read into a buffer of a fixed size, set for
+//! the context with [`Context::set_attr_buf_size()`](crate::Context::set_attr_buf_size).
+//! Some devices expose much larger attributes, like calibration blobs of
+//! hundreds of KB. An [`AttrReader`] reads one of these as a stream. With
+//! the local backend, the attribute file is read directly from sysfs.
+//! Otherwise the value is read through the C library with a buffer that
+//! grows until the whole value fits, up to [`MAX_ATTR_READ_SIZE`].
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use std::io::Read;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ad9361-phy").unwrap();
+//!
+//! let mut blob = Vec::new();
+//! dev.attr_read_reader("calib_blob")
+//!     .unwrap()
+//!     .read_to_end(&mut blob)
+//!     .unwrap();
+//! ```
+
+use crate::{sys_result, Result};
+use std::{
+    fs::File,
+    io::{self, Cursor, Read},
+    os::raw::c_char,
+};
+
+/// The largest attribute value that is read through the C library, in
+/// bytes.
+pub const MAX_ATTR_READ_SIZE: usize = 16 * 1024 * 1024;
+
+/// The source of the attribute data.
+#[derive(Debug)]
+enum Source {
+    /// The attribute file in sysfs
+    File(File),
+    /// The value, read up front
+    Mem(Cursor<Vec<u8>>),
+}
+
+/// A reader for the value of an attribute.
+#[derive(Debug)]
+pub struct AttrReader {
+    /// The source of the data
+    src: Source,
+}
+
+impl AttrReader {
+    /// Creates a reader for an attribute file.
+    pub(crate) fn from_file(file: File) -> Self {
+        Self {
+            src: Source::File(file),
+        }
+    }
+
+    /// Creates a reader for a value that was already read.
+    pub(crate) fn from_vec(data: Vec<u8>) -> Self {
+        Self {
+            src: Source::Mem(Cursor::new(data)),
+        }
+    }
+}
+
+impl Read for AttrReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.src {
+            Source::File(file) => file.read(buf),
+            Source::Mem(cur) => cur.read(buf),
+        }
+    }
+}
+
+/// Reads an attribute value through the C library, growing the buffer
+/// until the whole value fits.
+///
+/// The C library truncates a value that doesn't fit in the buffer, so
+/// the read is retried with a buffer twice the size whenever the value
+/// fills it, up to [`MAX_ATTR_READ_SIZE`].
+///
+/// `size` The initial size of the buffer
+/// `read` Reads the attribute into a buffer, returning the length or a
+///     negative error code
+pub(crate) fn read_growing<F>(size: usize, mut read: F) -> Result<Vec<u8>>
+where
+    F: FnMut(&mut [c_char]) -> isize,
+{
+    let mut size = size.clamp(2, MAX_ATTR_READ_SIZE);
+    loop {
+        let mut buf = vec![0 as c_char; size];
+        let ret = read(&mut buf);
+        sys_result(ret as i32, ())?;
+
+        let n = ret as usize;
+        if n + 1 < size || size == MAX_ATTR_READ_SIZE {
+            return Ok(buf
+                .iter()
+                .take(n)
+                .map(|&c| c as u8)
+                .take_while(|&b| b != 0)
+                .collect());
+        }
+        size = (2 * size).min(MAX_ATTR_READ_SIZE);
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn growing() {
+        let val = "x".repeat(1000);
+        let mut nread = 0;
+
+        // Mimic the C lib, truncating the value to fit the buffer
+        let data = read_growing(16, |buf| {
+            nread += 1;
+            let n = val.len().min(buf.len() - 1);
+            for (b, c) in buf.iter_mut().zip(val.bytes().take(n)) {
+                *b = c as c_char;
+            }
+            buf[n] = 0;
+            (n + 1) as isize
+        })
+        .unwrap();
+
+        assert_eq!(data, val.as_bytes());
+        assert_eq!(nread, 7);
+
+        let mut s = String::new();
+        AttrReader::from_vec(data).read_to_string(&mut s).unwrap();
+        assert_eq!(s, val);
+    }
+}
@@ -0,0 +1,381 @@
+// industrial-io/src/auth.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Security settings for services that expose devices on the network.
+//!
+//! An [`Auth`] collects the settings shared by the network utilities:
+//!
+//! * Bearer tokens that clients must present to connect.
+//! * TLS certificate and key files, optionally with a CA to verify
+//!   client certificates. Applying them requires the **tls** feature.
+//! * An [`AllowList`] of the device and channel attributes that clients
+//!   may write.
+//!
+//! The defaults are read-only: with an empty allow list, all attribute
+//! writes are rejected.
+//!
+//! ```
+//! use industrial_io::{
+//!     auth::{AllowList, Auth},
+//!     Direction,
+//! };
+//!
+//! let auth = Auth::new()
+//!     .token("s3cret")
+//!     .writes(AllowList::new().allow("ad9361-phy/*/hardwaregain").unwrap());
+//!
+//! assert!(auth.check_token(Some("Bearer s3cret")));
+//! assert!(!auth.check_token(None));
+//! assert!(auth.is_write_allowed("ad9361-phy", Some(("voltage0", Direction::Input)), "hardwaregain"));
+//! assert!(!auth.is_write_allowed("ad9361-phy", None, "calib_mode"));
+//! ```
+
+use crate::{Direction, Error, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// A rule in an [`AllowList`].
+///
+/// Each part is a pattern that can contain `*` wildcards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Rule {
+    /// The device name or ID
+    device: String,
+    /// The channel ID, or `None` for device attributes
+    channel: Option<String>,
+    /// The direction of the channel, if the rule is limited to one
+    dir: Option<Direction>,
+    /// The attribute name
+    attr: String,
+}
+
+impl Rule {
+    /// Determines if the rule matches the attribute of the device, or one
+    /// of its channels.
+    pub(crate) fn matches(
+        &self,
+        device: &str,
+        channel: Option<(&str, Direction)>,
+        attr: &str,
+    ) -> bool {
+        glob_match(&self.device, device)
+            && glob_match(&self.attr, attr)
+            && match (&self.channel, channel) {
+                (Some(pat), Some((chan, dir))) => {
+                    self.dir.map_or(true, |d| d == dir) && glob_match(pat, chan)
+                }
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = Error;
+
+    /// Parses a rule from "device/attr" or "device/channel/attr".
+    ///
+    /// A channel prefixed with "in_" or "out_" only matches input or
+    /// output channels, like the names in sysfs.
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<_> = s.trim().split('/').collect();
+        if parts.iter().any(|p| p.is_empty()) {
+            return Err(Error::General(format!("Invalid allow rule: '{}'", s)));
+        }
+        match parts[..] {
+            [dev, attr] => Ok(Self {
+                device: dev.into(),
+                channel: None,
+                dir: None,
+                attr: attr.into(),
+            }),
+            [dev, chan, attr] => {
+                let (dir, chan) = match (chan.strip_prefix("in_"), chan.strip_prefix("out_")) {
+                    (Some(chan), _) => (Some(Direction::Input), chan),
+                    (_, Some(chan)) => (Some(Direction::Output), chan),
+                    _ => (None, chan),
+                };
+                if chan.is_empty() {
+                    return Err(Error::General(format!("Invalid allow rule: '{}'", s)));
+                }
+                Ok(Self {
+                    device: dev.into(),
+                    channel: Some(chan.into()),
+                    dir,
+                    attr: attr.into(),
+                })
+            }
+            _ => Err(Error::General(format!("Invalid allow rule: '{}'", s))),
+        }
+    }
+}
+
+/// A list of the attributes that can be written.
+///
+/// Each rule is given as "device/attr" for a device attribute, or
+/// "device/channel/attr" for a channel attribute, where any part can use
+/// `*` as a wildcard, like "ad9361-phy/voltage*/hardwaregain". A channel
+/// with an "in_" or "out_" prefix, like "ad9361-phy/in_voltage0/rf_port",
+/// only matches a channel in that direction. An empty list allows
+/// nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllowList {
+    rules: Vec<Rule>,
+}
+
+impl AllowList {
+    /// Creates an empty list, which doesn't allow any writes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a list that allows writing any attribute.
+    pub fn any() -> Self {
+        Self::new().allow("*/*").unwrap().allow("*/*/*").unwrap()
+    }
+
+    /// Adds a rule to the list.
+    pub fn allow(mut self, rule: &str) -> Result<Self> {
+        self.rules.push(rule.parse()?);
+        Ok(self)
+    }
+
+    /// Determines if the list has no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Determines if the attribute of the device, or one of its channels,
+    /// can be written.
+    pub fn is_allowed(&self, device: &str, channel: Option<(&str, Direction)>, attr: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.matches(device, channel, attr))
+    }
+}
+
+impl FromStr for AllowList {
+    type Err = Error;
+
+    /// Parses a list of rules separated by commas or whitespace.
+    fn from_str(s: &str) -> Result<Self> {
+        s.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|rule| !rule.is_empty())
+            .try_fold(Self::new(), |list, rule| list.allow(rule))
+    }
+}
+
+/// Matches a name against a pattern in which `*` matches any sequence of
+/// characters.
+fn glob_match(pat: &str, name: &str) -> bool {
+    match pat.split_once('*') {
+        None => pat == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix)
+            else {
+                return false;
+            };
+            (0..=name.len())
+                .filter(|&i| name.is_char_boundary(i))
+                .any(|i| glob_match(rest, &name[i..]))
+        }
+    }
+}
+
+/// Compares two byte strings in a time that doesn't depend on where they
+/// differ.
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The files for serving over TLS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsFiles {
+    /// The server certificate chain, in PEM format
+    pub cert: PathBuf,
+    /// The server private key, in PEM format
+    pub key: PathBuf,
+    /// A CA certificate, in PEM format, to require and verify client
+    /// certificates.
+    pub client_ca: Option<PathBuf>,
+}
+
+impl TlsFiles {
+    /// Creates the settings for a server certificate and key.
+    pub fn new<P: Into<PathBuf>, Q: Into<PathBuf>>(cert: P, key: Q) -> Self {
+        Self {
+            cert: cert.into(),
+            key: key.into(),
+            client_ca: None,
+        }
+    }
+
+    /// Requires clients to present a certificate signed by the CA.
+    pub fn client_ca<P: Into<PathBuf>>(mut self, ca: P) -> Self {
+        self.client_ca = Some(ca.into());
+        self
+    }
+}
+
+/// The security settings for a network service.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Auth {
+    /// The accepted bearer tokens. If empty, no token is required.
+    tokens: Vec<String>,
+    /// The TLS settings, if any
+    tls: Option<TlsFiles>,
+    /// The attributes that can be written
+    writes: AllowList,
+}
+
+impl Auth {
+    /// Creates the default settings, which don't require a token or use
+    /// TLS, and don't allow any attribute writes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a token that clients can present to connect.
+    pub fn token(mut self, token: &str) -> Self {
+        self.tokens.push(token.to_string());
+        self
+    }
+
+    /// Adds the tokens from a file, one per line.
+    ///
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn token_file<P: AsRef<Path>>(mut self, path: P) -> Result<Self> {
+        let s = fs::read_to_string(path)?;
+        self.tokens.extend(
+            s.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from),
+        );
+        Ok(self)
+    }
+
+    /// Sets the TLS certificate and key files.
+    pub fn tls(mut self, tls: TlsFiles) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Sets the attributes that clients can write.
+    pub fn writes(mut self, writes: AllowList) -> Self {
+        self.writes = writes;
+        self
+    }
+
+    /// Determines if clients need to present a token.
+    pub fn requires_token(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// Gets the TLS settings, if any.
+    pub fn tls_files(&self) -> Option<&TlsFiles> {
+        self.tls.as_ref()
+    }
+
+    /// Gets the list of attributes that clients can write.
+    pub fn write_allow_list(&self) -> &AllowList {
+        &self.writes
+    }
+
+    /// Checks a token presented by a client, typically from an
+    /// "Authorization" header, with or without the "Bearer " prefix.
+    ///
+    /// This always succeeds if no tokens are configured.
+    pub fn check_token(&self, presented: Option<&str>) -> bool {
+        if self.tokens.is_empty() {
+            return true;
+        }
+        let Some(presented) = presented
+        else {
+            return false;
+        };
+        let presented = presented.strip_prefix("Bearer ").unwrap_or(presented);
+        self.tokens.iter().fold(false, |ok, tok| {
+            ct_eq(tok.as_bytes(), presented.as_bytes()) | ok
+        })
+    }
+
+    /// Determines if clients can write the attribute of the device or one
+    /// of its channels.
+    pub fn is_write_allowed(
+        &self,
+        device: &str,
+        channel: Option<(&str, Direction)>,
+        attr: &str,
+    ) -> bool {
+        self.writes.is_allowed(device, channel, attr)
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_list() {
+        use Direction::*;
+
+        let list: AllowList = "adc/sampling_frequency, dac/voltage*/raw, adc/in_voltage0/scale"
+            .parse()
+            .unwrap();
+        assert!(list.is_allowed("adc", None, "sampling_frequency"));
+        assert!(!list.is_allowed("adc", Some(("voltage0", Input)), "sampling_frequency"));
+        assert!(list.is_allowed("dac", Some(("voltage0", Output)), "raw"));
+        assert!(list.is_allowed("dac", Some(("voltage0", Input)), "raw"));
+        assert!(list.is_allowed("dac", Some(("voltage12", Output)), "raw"));
+        assert!(!list.is_allowed("dac", Some(("altvoltage0", Output)), "raw"));
+        assert!(!list.is_allowed("dac", None, "raw"));
+
+        // Limited to the input channel
+        assert!(list.is_allowed("adc", Some(("voltage0", Input)), "scale"));
+        assert!(!list.is_allowed("adc", Some(("voltage0", Output)), "scale"));
+
+        assert!(!AllowList::new().is_allowed("adc", None, "sampling_frequency"));
+        assert!(AllowList::any().is_allowed("adc", Some(("voltage0", Input)), "scale"));
+
+        assert!("adc".parse::<AllowList>().is_err());
+        assert!("adc//raw".parse::<AllowList>().is_err());
+        assert!("adc/in_/raw".parse::<AllowList>().is_err());
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("in_*_raw", "in_voltage0_raw"));
+        assert!(!glob_match("in_*_raw", "in_voltage0_scale"));
+        assert!(glob_match("*0*", "voltage0_raw"));
+        assert!(!glob_match("voltage", "voltage0"));
+    }
+
+    #[test]
+    fn tokens() {
+        let auth = Auth::new();
+        assert!(auth.check_token(None));
+
+        let auth = auth.token("abc").token("xyz");
+        assert!(auth.requires_token());
+        assert!(auth.check_token(Some("abc")));
+        assert!(auth.check_token(Some("Bearer xyz")));
+        assert!(!auth.check_token(Some("Bearer abcd")));
+        assert!(!auth.check_token(None));
+    }
+}
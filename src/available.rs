@@ -0,0 +1,209 @@
+// industrial-io/src/available.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! The values available for an attribute.
+//!
+//! Many drivers describe the values that an attribute accepts with a
+//! companion `<attr>_available` attribute. This is either a list of the
+//! discrete values, like "250 490 920 1600", or a range given as
+//! "[min step max]".
+//!
+//! [`Device::validated_write()`] and [`Channel::validated_write()`] use
+//! these to check a value before it is written, so that a bad value gives
+//! an error that says what would be accepted, rather than an opaque
+//! `EINVAL` from the driver.
+//!
+//! [`Device::validated_write()`]: crate::Device::validated_write
+//! [`Channel::validated_write()`]: crate::Channel::validated_write
+
+use crate::{abi, Error, Result};
+use std::{fmt, str::FromStr};
+
+/// The values available for an attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Available {
+    /// A list of discrete values.
+    List(Vec<String>),
+    /// A range of numeric values, from `min` to `max`, in increments of
+    /// `step`. A step of zero allows any value in the range.
+    Range {
+        /// The minimum value
+        min: f64,
+        /// The increment between values
+        step: f64,
+        /// The maximum value
+        max: f64,
+    },
+}
+
+impl Available {
+    /// The name of the attribute that lists the values available for
+    /// another attribute.
+    pub fn attr_name(attr: &str) -> String {
+        abi::available(attr)
+    }
+
+    /// Determines if the value is one of the available values.
+    pub fn contains(&self, val: &str) -> bool {
+        let val = val.trim();
+        match self {
+            Available::List(items) => {
+                items.iter().any(|item| item == val)
+                    || val.parse::<f64>().is_ok_and(|x| {
+                        items
+                            .iter()
+                            .filter_map(|item| item.parse::<f64>().ok())
+                            .any(|y| (x - y).abs() <= f64::EPSILON * x.abs().max(1.0))
+                    })
+            }
+            Available::Range { min, step, max } => {
+                let Ok(x) = val.parse::<f64>()
+                else {
+                    return false;
+                };
+                if x < *min || x > *max {
+                    return false;
+                }
+                if *step <= 0.0 {
+                    return true;
+                }
+                let n = (x - min) / step;
+                (n - n.round()).abs() < 1e-6
+            }
+        }
+    }
+
+    /// Gets the lowest and highest of the available values.
+    ///
+    /// This is `None` for a list that doesn't have any numeric values.
+    pub fn bounds(&self) -> Option<(f64, f64)> {
+        match self {
+            Available::Range { min, max, .. } => Some((*min, *max)),
+            Available::List(items) => items
+                .iter()
+                .filter_map(|item| item.parse::<f64>().ok())
+                .fold(None, |bounds, x| match bounds {
+                    Some((lo, hi)) => Some((x.min(lo), x.max(hi))),
+                    None => Some((x, x)),
+                }),
+        }
+    }
+
+    /// Checks that the value is one of the available values.
+    ///
+    /// The error describes the available values.
+    pub fn check(&self, val: &str) -> Result<()> {
+        if self.contains(val) {
+            Ok(())
+        }
+        else {
+            Err(Error::NotAvailable {
+                value: val.trim().to_string(),
+                available: self.clone(),
+            })
+        }
+    }
+}
+
+impl FromStr for Available {
+    type Err = Error;
+
+    /// Parses the value of an `<attr>_available` attribute.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            Some(range) => {
+                let vals = range
+                    .split_whitespace()
+                    .map(|v| v.parse::<f64>())
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|_| Error::StringConversionError)?;
+                match vals[..] {
+                    [min, step, max] => Ok(Available::Range { min, step, max }),
+                    [min, max] => Ok(Available::Range {
+                        min,
+                        step: 0.0,
+                        max,
+                    }),
+                    _ => Err(Error::StringConversionError),
+                }
+            }
+            None => Ok(Available::List(
+                s.split_whitespace().map(String::from).collect(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Available {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Available::List(items) => write!(f, "{{{}}}", items.join(", ")),
+            Available::Range { min, step, max } if *step > 0.0 => {
+                write!(f, "[{}, {}] in steps of {}", min, max, step)
+            }
+            Available::Range { min, max, .. } => write!(f, "[{}, {}]", min, max),
+        }
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list() {
+        let avail: Available = "250 490 920 1600 2400 3300\n".parse().unwrap();
+        assert!(avail.contains("920"));
+        assert!(avail.contains("920.0"));
+        assert!(!avail.contains("800"));
+        assert_eq!(
+            avail.check("800").unwrap_err().to_string(),
+            "800 not in {250, 490, 920, 1600, 2400, 3300}"
+        );
+
+        assert_eq!(avail.bounds(), Some((250.0, 3300.0)));
+
+        let avail: Available = "fdd tdd sleep".parse().unwrap();
+        assert!(avail.check("tdd").is_ok());
+        assert!(avail.check("alert").is_err());
+        assert_eq!(avail.bounds(), None);
+    }
+
+    #[test]
+    fn range() {
+        let avail: Available = "[-3 0.25 71]".parse().unwrap();
+        assert_eq!(
+            avail,
+            Available::Range {
+                min: -3.0,
+                step: 0.25,
+                max: 71.0
+            }
+        );
+        assert_eq!(avail.bounds(), Some((-3.0, 71.0)));
+        assert!(avail.contains("-3"));
+        assert!(avail.contains("10.75"));
+        assert!(!avail.contains("10.1"));
+        assert!(!avail.contains("72"));
+        assert!(!avail.contains("max"));
+        assert_eq!(
+            avail.check("72").unwrap_err().to_string(),
+            "72 not in [-3, 71] in steps of 0.25"
+        );
+
+        assert!("[1 2 3 4]".parse::<Available>().is_err());
+        assert!("[1 x 3]".parse::<Available>().is_err());
+    }
+}
@@ -0,0 +1,170 @@
+// industrial-io/src/bin/riio_grpcd.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Rust application to serve Industrial I/O devices over gRPC.
+//!
+//! The service is defined in `proto/iio.proto`.
+//!
+//! By default, clients don't need a token, but can't write any attributes.
+//! Use `--token` and `--allow-write` to change that, and `--tls-cert` and
+//! `--tls-key` to serve over TLS, which requires the `tls` feature.
+//!
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use iio::auth::{AllowList, Auth, TlsFiles};
+use industrial_io as iio;
+use std::{net::SocketAddr, process};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const DFLT_ADDR: &str = "0.0.0.0:50051";
+
+// Gets the security settings from the command line.
+fn build_auth(args: &ArgMatches) -> iio::Result<Auth> {
+    let mut auth = Auth::new();
+
+    for token in args.get_many::<String>("token").into_iter().flatten() {
+        auth = auth.token(token);
+    }
+    if let Some(path) = args.get_one::<String>("token-file") {
+        auth = auth.token_file(path)?;
+    }
+
+    let writes = args
+        .get_many::<String>("allow-write")
+        .into_iter()
+        .flatten()
+        .try_fold(AllowList::new(), |list, rule| list.allow(rule))?;
+    auth = auth.writes(writes);
+
+    if let (Some(cert), Some(key)) = (
+        args.get_one::<String>("tls-cert"),
+        args.get_one::<String>("tls-key"),
+    ) {
+        let mut tls = TlsFiles::new(cert, key);
+        if let Some(ca) = args.get_one::<String>("tls-client-ca") {
+            tls = tls.client_ca(ca);
+        }
+        auth = auth.tls(tls);
+    }
+    Ok(auth)
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Command::new("riio_grpcd")
+        .version(VERSION)
+        .author("Frank Pagliughi")
+        .about("Rust IIO gRPC server.")
+        .disable_help_flag(true)
+        .arg(
+            Arg::new("help")
+                .short('?')
+                .long("help")
+                .global(true)
+                .action(ArgAction::Help)
+                .help("Print help information"),
+        )
+        .arg(
+            Arg::new("uri")
+                .short('u')
+                .long("uri")
+                .action(ArgAction::Set)
+                .help("Use the context with the provided URI"),
+        )
+        .arg(
+            Arg::new("addr")
+                .short('a')
+                .long("addr")
+                .action(ArgAction::Set)
+                .default_value(DFLT_ADDR)
+                .help("The address and port to listen on"),
+        )
+        .arg(
+            Arg::new("token")
+                .short('t')
+                .long("token")
+                .action(ArgAction::Append)
+                .help("A token that clients must present to connect"),
+        )
+        .arg(
+            Arg::new("token-file")
+                .long("token-file")
+                .action(ArgAction::Set)
+                .help("A file with the tokens that clients can present, one per line"),
+        )
+        .arg(
+            Arg::new("allow-write")
+                .short('w')
+                .long("allow-write")
+                .action(ArgAction::Append)
+                .help("Allow writing attributes matching device/attr or device/channel/attr"),
+        )
+        .arg(
+            Arg::new("tls-cert")
+                .long("tls-cert")
+                .action(ArgAction::Set)
+                .requires("tls-key")
+                .help("The server certificate, in PEM format, to serve over TLS"),
+        )
+        .arg(
+            Arg::new("tls-key")
+                .long("tls-key")
+                .action(ArgAction::Set)
+                .requires("tls-cert")
+                .help("The server private key, in PEM format"),
+        )
+        .arg(
+            Arg::new("tls-client-ca")
+                .long("tls-client-ca")
+                .action(ArgAction::Set)
+                .requires("tls-cert")
+                .help("Require client certificates signed by this CA"),
+        )
+        .get_matches();
+
+    let auth = build_auth(&args).unwrap_or_else(|err| {
+        eprintln!("Error in the security settings: {}", err);
+        process::exit(2);
+    });
+
+    let ctx = if let Some(uri) = args.get_one::<String>("uri") {
+        iio::Context::from_uri(uri)
+    }
+    else {
+        iio::Context::new()
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("Error getting the IIO Context: {}", err);
+        process::exit(1);
+    });
+
+    let addr: SocketAddr = args
+        .get_one::<String>("addr")
+        .unwrap()
+        .parse()
+        .unwrap_or_else(|err| {
+            eprintln!("Invalid address: {}", err);
+            process::exit(2);
+        });
+
+    println!("Serving {} device(s) on {}", ctx.num_devices(), addr);
+    if !auth.requires_token() {
+        println!("Warning: Clients are not required to present a token");
+    }
+    if auth.write_allow_list().is_empty() {
+        println!("Attribute writes are disabled");
+    }
+
+    if let Err(err) = iio::grpc::serve_with_auth(ctx, addr, &auth).await {
+        eprintln!("Server error: {}", err);
+        process::exit(1);
+    }
+}
@@ -88,31 +88,17 @@ fn main() {
                 chan.num_attrs()
             );
 
-            // Note: We could get all the attr into a map and then print
-            //let attrs = chan.attr_read_all();
-
-            for attr in chan.attrs() {
-                print!("\t\t\t\t'{}': ", attr);
-                if let Ok(val) = chan.attr_read_float(&attr) {
-                    println!("{}", val);
-                }
-                else if let Ok(val) = chan.attr_read_int(&attr) {
-                    println!("{}", val);
-                }
-                else if let Ok(val) = chan.attr_read_bool(&attr) {
-                    println!("{}", val);
-                }
-                else {
-                    println!("{}", chan.find_attr(&attr).unwrap());
+            for (attr, val) in chan.attrs_with_values() {
+                match val {
+                    Ok(val) => println!("\t\t\t\t'{}': {}", attr, val),
+                    Err(err) => println!("\t\t\t\t'{}': <{}>", attr, err),
                 }
             }
         }
         if dev.has_attrs() {
             println!("\t\tAttributes:");
-            for attr in dev.attributes() {
-                let val_str = dev
-                    .attr_read_str(&attr)
-                    .unwrap_or_else(|_| String::from("Unknown"));
+            for (attr, val) in dev.attrs_with_values() {
+                let val_str = val.unwrap_or_else(|_| String::from("Unknown"));
                 println!("\t\t\t{}: {}", attr, val_str);
             }
         }
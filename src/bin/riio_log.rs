@@ -0,0 +1,481 @@
+// industrial-io/src/bin/riio_log.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Rust application to log Industrial I/O devices to files and the
+//! network, as described by a TOML configuration file.
+//!
+//! The configuration has a list of device profiles to apply at startup,
+//! the devices to capture, and the sinks to write the captured frames to.
+//! Each device is captured in its own thread by a duty-cycle scheduler,
+//! and the frames are fanned out to the sinks through a pipeline, tagged
+//! with the name of the device.
+//!
+//! ```toml
+//! uri = "local:"
+//! duration = 3600.0
+//!
+//! [[profile]]
+//! device = "ads1015"
+//! attrs = { sampling_frequency = "128" }
+//!
+//! [[device]]
+//! name = "ads1015"
+//! channels = ["voltage0", "voltage1"]
+//! trigger = "trigger0"
+//! rate = 100.0
+//! burst = 100
+//!
+//! [[sink]]
+//! type = "csv"
+//! path = "logs/ads1015.csv"
+//! devices = ["ads1015"]
+//! rotate_secs = 600.0
+//!
+//! [[sink]]
+//! type = "udp"
+//! addr = "192.168.1.10:5000"
+//! ```
+//!
+//! The sink types are "csv", "binary", "sigmf", "udp", and "tcp". The
+//! file sinks can be rotated after a number of samples (`rotate_samples`)
+//! or seconds (`rotate_secs`), in which case the index of each file is
+//! put in place of `{n}` in the path, or appended to the file name if
+//! there isn't one.
+//!
+//! A device captures continuously unless it has a `period`, in seconds,
+//! in which case it's only captured for the `active` window at the start
+//! of each period. The logger runs for the `duration`, in seconds, if
+//! given, or until it gets SIGINT or SIGTERM.
+//!
+
+use clap::{Arg, ArgAction, Command};
+use iio::{
+    power::DutyCycle,
+    profile::{DeviceProfile, ProfileReport},
+    sigmf::{Datatype, Recorder, SampleFormat},
+    sink::{CsvSink, FileSink, FrameSink, Pipeline, RotatingSink, TcpStreamer, UdpStreamer},
+    Frame,
+};
+use industrial_io as iio;
+use nix::sys::signal::{self, SigHandler, Signal};
+use serde::Deserialize;
+use std::{
+    fs,
+    os::raw::c_int,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Set by the signal handler to stop logging.
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// The top-level configuration.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// The URI of the context, if not the default one
+    uri: Option<String>,
+    /// The number of seconds to log, if limited
+    duration: Option<f64>,
+    /// The number of frames to queue for each sink
+    queue_len: Option<usize>,
+    /// The profiles to apply before logging
+    #[serde(default, rename = "profile")]
+    profiles: Vec<DeviceProfile>,
+    /// The devices to capture
+    #[serde(default, rename = "device")]
+    devices: Vec<DeviceConfig>,
+    /// The destinations for the captured frames
+    #[serde(default, rename = "sink")]
+    sinks: Vec<SinkConfig>,
+}
+
+/// The configuration of a device to capture.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DeviceConfig {
+    /// The ID, name, or label of the device
+    name: String,
+    /// The channels to capture, or all the scan elements if empty
+    #[serde(default)]
+    channels: Vec<String>,
+    /// The trigger to assign to the device
+    trigger: Option<String>,
+    /// The sampling rate to set, in Hz
+    rate: Option<f64>,
+    /// The number of samples in each frame
+    #[serde(default = "dflt_burst")]
+    burst: usize,
+    /// The period of the duty cycle, in seconds
+    period: Option<f64>,
+    /// The time at the start of each period to capture, in seconds
+    active: Option<f64>,
+}
+
+/// The default number of samples in each frame.
+fn dflt_burst() -> usize {
+    256
+}
+
+/// The kind of sink, and where it writes.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum SinkKind {
+    /// A CSV file
+    Csv { path: PathBuf },
+    /// A file of little-endian f64 values
+    Binary { path: PathBuf },
+    /// A SigMF recording, for a single device
+    Sigmf { path: PathBuf },
+    /// A UDP stream
+    Udp { addr: String },
+    /// A TCP stream
+    Tcp { addr: String },
+}
+
+/// The configuration of a sink.
+#[derive(Debug, Deserialize)]
+struct SinkConfig {
+    /// The kind of sink
+    #[serde(flatten)]
+    kind: SinkKind,
+    /// The devices that the sink receives, or all of them if empty
+    #[serde(default)]
+    devices: Vec<String>,
+    /// The number of samples after which to rotate files
+    rotate_samples: Option<usize>,
+    /// The number of seconds after which to rotate files
+    rotate_secs: Option<f64>,
+}
+
+/// A device, resolved and set up for capture.
+struct Source {
+    /// The name used to tag the frames of the device
+    name: String,
+    /// The device
+    dev: iio::Device,
+    /// The scheduler for the captures
+    sched: DutyCycle,
+    /// The SigMF data type of the captured samples
+    datatype: Datatype,
+    /// The number of captured channels
+    num_channels: usize,
+    /// The sample rate, if known
+    rate: Option<f64>,
+}
+
+// Reports an error and exits.
+fn fail<T>(msg: &str, err: impl std::fmt::Display) -> T {
+    eprintln!("{}: {}", msg, err);
+    process::exit(1);
+}
+
+// Converts seconds from the config file to a duration.
+fn secs(s: f64) -> iio::Result<Duration> {
+    Duration::try_from_secs_f64(s).map_err(|_| iio::Error::General(format!("Invalid time: {}", s)))
+}
+
+extern "C" fn on_signal(_: c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+// Resolves a device from the config, and sets it up for capture.
+fn setup_device(ctx: &iio::Context, cfg: &DeviceConfig) -> iio::Result<Source> {
+    let dev = ctx
+        .find_device(&cfg.name)
+        .ok_or_else(|| iio::Error::General(format!("Device not found: '{}'", cfg.name)))?;
+
+    if let Some(name) = &cfg.trigger {
+        let trig = ctx
+            .find_trigger(name)
+            .ok_or_else(|| iio::Error::General(format!("Trigger not found: '{}'", name)))?;
+        dev.set_trigger(&trig)?;
+    }
+
+    let rate = match cfg.rate {
+        Some(hz) => Some(dev.set_sampling_frequency(hz)?),
+        None => dev.sampling_frequency().ok(),
+    };
+
+    let chans: Vec<_> = if cfg.channels.is_empty() {
+        dev.channels().filter(|ch| ch.is_scan_element()).collect()
+    }
+    else {
+        cfg.channels
+            .iter()
+            .map(|id| {
+                dev.find_input_channel(id).ok_or_else(|| {
+                    iio::Error::General(format!("Channel not found: '{}/{}'", cfg.name, id))
+                })
+            })
+            .collect::<iio::Result<_>>()?
+    };
+    let first = chans
+        .first()
+        .ok_or_else(|| iio::Error::General(format!("No channels to capture: '{}'", cfg.name)))?;
+    let datatype = Datatype::real(SampleFormat::from_data_format(&first.data_format())?);
+
+    for chan in dev.channels() {
+        chan.disable();
+    }
+    for chan in &chans {
+        chan.enable();
+    }
+
+    let sched = match cfg.period {
+        Some(period) => {
+            let active = cfg.active.unwrap_or(period);
+            DutyCycle::new(secs(period)?, secs(active)?)
+        }
+        None => DutyCycle::new(Duration::from_secs(1), Duration::from_secs(1)),
+    }
+    .burst_size(cfg.burst);
+
+    Ok(Source {
+        name: cfg.name.clone(),
+        dev,
+        sched,
+        datatype,
+        num_channels: chans.len(),
+        rate,
+    })
+}
+
+// Gets the path of a rotated file, with its index in place of "{n}", or
+// appended to the file name.
+fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let s = path.to_string_lossy();
+    if s.contains("{n}") {
+        return PathBuf::from(s.replace("{n}", &n.to_string()));
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, n, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+// Creates a file sink, rotated if the config asks for it.
+fn file_sink<F>(cfg: &SinkConfig, path: &Path, create: F) -> iio::Result<Box<dyn FrameSink>>
+where
+    F: Fn(&Path) -> iio::Result<Box<dyn FrameSink>> + Send + 'static,
+{
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    if cfg.rotate_samples.is_none() && cfg.rotate_secs.is_none() {
+        return create(path);
+    }
+
+    let path = path.to_path_buf();
+    let mut sink = RotatingSink::new(move |n| create(&rotated_path(&path, n)));
+    if let Some(n) = cfg.rotate_samples {
+        sink = sink.max_samples(n);
+    }
+    if let Some(s) = cfg.rotate_secs {
+        sink = sink.max_age(secs(s)?);
+    }
+    Ok(Box::new(sink))
+}
+
+// Creates a sink from the config.
+fn build_sink(cfg: &SinkConfig, sources: &[&Source]) -> iio::Result<Box<dyn FrameSink>> {
+    match &cfg.kind {
+        SinkKind::Csv { path } => file_sink(cfg, path, |p| Ok(Box::new(CsvSink::create(p)?))),
+        SinkKind::Binary { path } => file_sink(cfg, path, |p| Ok(Box::new(FileSink::create(p)?))),
+        SinkKind::Sigmf { path } => {
+            let [src] = sources
+            else {
+                return Err(iio::Error::General(
+                    "A SigMF sink needs exactly one device".into(),
+                ));
+            };
+            let (datatype, num_channels, rate) = (src.datatype, src.num_channels, src.rate);
+            file_sink(cfg, path, move |p| {
+                let mut rec = Recorder::create(p, datatype)?.num_channels(num_channels);
+                if let Some(hz) = rate {
+                    rec = rec.sample_rate(hz);
+                }
+                Ok(Box::new(rec))
+            })
+        }
+        SinkKind::Udp { addr } => Ok(Box::new(UdpStreamer::connect(addr.as_str())?)),
+        SinkKind::Tcp { addr } => Ok(Box::new(TcpStreamer::connect(addr.as_str())?)),
+    }
+}
+
+fn main() {
+    let args = Command::new("riio_log")
+        .version(VERSION)
+        .author("Frank Pagliughi")
+        .about("Rust IIO data logger.")
+        .disable_help_flag(true)
+        .arg(
+            Arg::new("help")
+                .short('?')
+                .long("help")
+                .global(true)
+                .action(ArgAction::Help)
+                .help("Print help information"),
+        )
+        .arg(
+            Arg::new("uri")
+                .short('u')
+                .long("uri")
+                .action(ArgAction::Set)
+                .help("Use the context with the provided URI, overriding the config"),
+        )
+        .arg(
+            Arg::new("check")
+                .short('c')
+                .long("check")
+                .action(ArgAction::SetTrue)
+                .help("Check the config against the context, without logging"),
+        )
+        .arg(
+            Arg::new("config")
+                .action(ArgAction::Set)
+                .required(true)
+                .help("The TOML configuration file"),
+        )
+        .get_matches();
+
+    let path = args.get_one::<String>("config").unwrap();
+    let cfg: Config = fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|s| toml::from_str(&s).map_err(|err| err.to_string()))
+        .unwrap_or_else(|err| fail("Error reading the config", err));
+
+    let uri = args.get_one::<String>("uri").or(cfg.uri.as_ref());
+    let ctx = match uri {
+        Some(uri) => iio::Context::from_uri(uri),
+        None => iio::Context::new(),
+    }
+    .unwrap_or_else(|err| fail("Error getting the IIO Context", err));
+
+    // ----- Profiles -----
+
+    let mut report = ProfileReport::default();
+    for prof in &cfg.profiles {
+        report.merge(if args.get_flag("check") {
+            prof.check(&ctx)
+        }
+        else {
+            prof.apply(&ctx)
+        });
+    }
+    if !report.is_ok() {
+        fail::<()>("Error applying the profiles", &report);
+    }
+
+    // ----- Devices & Sinks -----
+
+    let sources: Vec<Source> = cfg
+        .devices
+        .iter()
+        .map(|dev| setup_device(&ctx, dev))
+        .collect::<iio::Result<_>>()
+        .unwrap_or_else(|err| fail("Error setting up the devices", err));
+
+    if sources.is_empty() {
+        fail::<()>("Error in the config", "No devices to log");
+    }
+
+    let mut pipeline = Pipeline::new();
+    if let Some(n) = cfg.queue_len {
+        pipeline = pipeline.queue_len(n);
+    }
+
+    for sink_cfg in &cfg.sinks {
+        let srcs: Vec<&Source> = sources
+            .iter()
+            .filter(|src| sink_cfg.devices.is_empty() || sink_cfg.devices.contains(&src.name))
+            .collect();
+        let sink =
+            build_sink(sink_cfg, &srcs).unwrap_or_else(|err| fail("Error creating a sink", err));
+        let tags: Vec<&str> = srcs.iter().map(|src| src.name.as_str()).collect();
+        pipeline.add_tagged_sink(&tags, sink);
+    }
+
+    if args.get_flag("check") {
+        println!(
+            "Config OK: {} device(s), {} sink(s)",
+            sources.len(),
+            pipeline.num_sinks()
+        );
+        return;
+    }
+
+    // ----- Capture -----
+
+    for sig in [Signal::SIGINT, Signal::SIGTERM] {
+        unsafe { signal::signal(sig, SigHandler::Handler(on_signal)) }
+            .unwrap_or_else(|err| fail("Error setting the signal handler", err));
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel::<(String, Frame)>();
+
+    for src in sources {
+        let (tx, stop) = (tx.clone(), Arc::clone(&stop));
+        thread::spawn(move || {
+            let res = src.sched.run(&src.dev, |frame| {
+                !stop.load(Ordering::Relaxed) && tx.send((src.name.clone(), frame)).is_ok()
+            });
+            if let Err(err) = res {
+                eprintln!("Error capturing '{}': {}", src.name, err);
+            }
+        });
+    }
+    drop(tx);
+
+    let deadline = cfg
+        .duration
+        .map(|s| secs(s).unwrap_or_else(|err| fail("Error in the config", err)))
+        .map(|dur| Instant::now() + dur);
+
+    let mut nframes: u64 = 0;
+    while !STOP.load(Ordering::SeqCst) && deadline.map_or(true, |t| Instant::now() < t) {
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok((tag, frame)) => {
+                pipeline.send_tagged(&tag, frame);
+                nframes += 1;
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    stop.store(true, Ordering::Relaxed);
+
+    // The capture threads stop after their current window, but the
+    // pipeline is closed now so that the sinks are flushed.
+    let dropped: Vec<_> = (0..pipeline.num_sinks())
+        .map(|i| pipeline.dropped(i).unwrap_or_default())
+        .collect();
+
+    if let Err(err) = pipeline.close() {
+        fail::<()>("Error writing the sinks", err);
+    }
+
+    println!("Logged {} frame(s)", nframes);
+    for (i, n) in dropped.into_iter().enumerate().filter(|(_, n)| *n > 0) {
+        println!("Sink {} dropped {} frame(s)", i, n);
+    }
+}
@@ -49,14 +49,26 @@
 //! [triggers assigned]: crate::device::Device::set_trigger()
 
 use std::{
+    cell::Cell,
     collections::HashMap,
     marker::PhantomData,
     mem::size_of,
     os::raw::{c_int, c_longlong},
 };
 
+#[cfg(unix)]
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+#[cfg(unix)]
+use std::{
+    os::fd::BorrowedFd,
+    time::{Duration, Instant},
+};
+
 use super::*;
-use crate::ffi;
+use crate::{
+    ffi,
+    journal::{AttrKind, AttrTarget},
+};
 
 /// An Industrial I/O input or output buffer.
 ///
@@ -72,6 +84,8 @@ pub struct Buffer {
     pub(crate) cap: usize,
     /// Copy of the device to which this device is attached.
     pub(crate) dev: Device,
+    /// Whether the buffer is in blocking mode
+    pub(crate) blocking: Cell<bool>,
 }
 
 impl Buffer {
@@ -91,8 +105,32 @@ impl Buffer {
     /// Gets a pollable file descriptor for the buffer.
     ///
     /// This can be used to determine when [`Buffer::refill()`] or
-    /// [`Buffer::push()`] can be called without blocking.
-    pub fn poll_fd(&self) -> Result<c_int> {
+    /// [`Buffer::push()`] can be called without blocking, with `poll`,
+    /// `epoll`, _tokio's_ `AsyncFd`, and the like. The buffer doesn't
+    /// implement `AsFd` or `AsRawFd`, since not every backend has a
+    /// descriptor, so use this to borrow it instead.
+    ///
+    /// The descriptor is owned by the buffer, and is closed when the
+    /// buffer is dropped, so it's only borrowed for the lifetime of the
+    /// buffer. It stays open after a [`cancel()`](Buffer::cancel), at
+    /// which point it's reported as ready, so that anything waiting on it
+    /// wakes up, and the next refill or push fails.
+    ///
+    /// This fails if the backend doesn't provide a file descriptor for
+    /// the buffer, such as for a network context with an older server.
+    #[cfg(unix)]
+    pub fn poll_fd(&self) -> Result<BorrowedFd<'_>> {
+        let fd = self.poll_fd_raw()?;
+        // The descriptor is owned by the C buffer, so stays open as long
+        // as the buffer isn't dropped.
+        Ok(unsafe { BorrowedFd::borrow_raw(fd) })
+    }
+
+    /// Gets the raw value of the pollable file descriptor for the buffer.
+    ///
+    /// This is the same descriptor as [`poll_fd()`](Buffer::poll_fd), but
+    /// it's up to the caller not to use it after the buffer is dropped.
+    pub fn poll_fd_raw(&self) -> Result<c_int> {
         let ret = unsafe { ffi::iio_buffer_get_poll_fd(self.buf) };
         sys_result(i32::from(ret), ret)
     }
@@ -103,7 +141,86 @@ impl Buffer {
     /// A [`Device`] is blocking by default.
     pub fn set_blocking_mode(&self, blocking: bool) -> Result<()> {
         let ret = unsafe { ffi::iio_buffer_set_blocking_mode(self.buf, blocking) };
-        sys_result(ret, ())
+        sys_result(ret, ())?;
+        self.blocking.set(blocking);
+        Ok(())
+    }
+
+    /// Determines if calls to [`push()`](Buffer::push) or
+    /// [`refill()`](Buffer::refill) are blocking.
+    pub fn is_blocking(&self) -> bool {
+        self.blocking.get()
+    }
+
+    /// Fetch more samples from the hardware, waiting no longer than the
+    /// timeout.
+    ///
+    /// This puts the buffer into non-blocking mode and polls it for the
+    /// duration of the call, so it bounds a single refill without
+    /// changing the timeout of the whole context. It fails with
+    /// `ETIMEDOUT` if the samples aren't ready in time. The buffer is then
+    /// put back into its previous mode.
+    ///
+    /// This is only valid for input buffers.
+    #[cfg(unix)]
+    pub fn refill_timeout(&mut self, timeout: Duration) -> Result<usize> {
+        self.with_timeout(timeout, PollFlags::POLLIN, |buf| {
+            let ret = unsafe { ffi::iio_buffer_refill(buf.buf) };
+            sys_result(ret as i32, ret as usize)
+        })
+    }
+
+    /// Send the samples to the hardware, waiting no longer than the
+    /// timeout.
+    ///
+    /// This fails with `ETIMEDOUT` if the hardware isn't ready for the
+    /// samples in time. See [`refill_timeout()`](Buffer::refill_timeout).
+    ///
+    /// This is only valid for output buffers.
+    #[cfg(unix)]
+    pub fn push_timeout(&self, timeout: Duration) -> Result<usize> {
+        self.with_timeout(timeout, PollFlags::POLLOUT, Buffer::push)
+    }
+
+    /// Runs a buffer operation in non-blocking mode, polling for the
+    /// buffer to be ready whenever it would block, until the timeout
+    /// expires.
+    #[cfg(unix)]
+    fn with_timeout<F>(&self, timeout: Duration, flags: PollFlags, mut op: F) -> Result<usize>
+    where
+        F: FnMut(&Self) -> Result<usize>,
+    {
+        let fd = self.poll_fd()?;
+        let blocking = self.is_blocking();
+        if blocking {
+            self.set_blocking_mode(false)?;
+        }
+
+        let deadline = Instant::now() + timeout;
+        let res = loop {
+            match op(self) {
+                Err(Error::Nix(Errno::EAGAIN)) => {}
+                res => break res,
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break Err(Errno::ETIMEDOUT.into());
+            }
+            let timeout = PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX);
+            let mut fds = [PollFd::new(fd, flags)];
+            match poll(&mut fds, timeout) {
+                Ok(_) | Err(Errno::EINTR) => {}
+                Err(err) => break Err(err.into()),
+            }
+        };
+
+        // Report the result of the operation first, then any failure to
+        // put the buffer back into blocking mode.
+        let restored = match blocking {
+            true => self.set_blocking_mode(true),
+            false => Ok(()),
+        };
+        res.and_then(|n| restored.map(|_| n))
     }
 
     /// Fetch more samples from the hardware.
@@ -182,7 +299,8 @@ impl Buffer {
     }
 
     /// Determines if a buffer-specific attribute exists
-    pub fn has_attr(&self, name: &str) -> bool {
+    pub fn has_attr(&self, name: impl AsRef<str>) -> bool {
+        let name = name.as_ref();
         let cname = cstring_or_bail_false!(name);
         let pstr = unsafe { ffi::iio_device_find_buffer_attr(self.dev.dev, cname.as_ptr()) };
         !pstr.is_null()
@@ -191,7 +309,8 @@ impl Buffer {
     /// Reads a buffer-specific attribute
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read<T: FromAttribute>(&self, attr: &str) -> Result<T> {
+    pub fn attr_read<T: FromAttribute>(&self, attr: impl AsRef<str>) -> Result<T> {
+        let attr = attr.as_ref();
         let sval = self.attr_read_str(attr)?;
         T::from_attr(&sval)
     }
@@ -199,59 +318,59 @@ impl Buffer {
     /// Reads a buffer-specific attribute as a string
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read_str(&self, attr: &str) -> Result<String> {
-        let mut buf = vec![0 as c_char; ATTR_BUF_SIZE];
-        let attr = CString::new(attr)?;
-        let ret = unsafe {
-            ffi::iio_device_buffer_attr_read(
-                self.dev.dev,
-                attr.as_ptr(),
-                buf.as_mut_ptr(),
-                buf.len(),
-            )
-        };
-        sys_result(ret as i32, ())?;
-        let s = unsafe {
-            CStr::from_ptr(buf.as_ptr())
-                .to_str()
-                .map_err(|_| Error::StringConversionError)?
-        };
-        Ok(s.into())
+    pub fn attr_read_str(&self, attr: impl AsRef<str>) -> Result<String> {
+        let attr = attr.as_ref();
+        self.dev.buffer_attr_read_str(attr)
     }
 
     /// Reads a buffer-specific attribute as a boolean
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read_bool(&self, attr: &str) -> Result<bool> {
+    pub fn attr_read_bool(&self, attr: impl AsRef<str>) -> Result<bool> {
+        let attr = attr.as_ref();
         let mut val: bool = false;
-        let attr = CString::new(attr)?;
-        let ret =
-            unsafe { ffi::iio_device_buffer_attr_read_bool(self.dev.dev, attr.as_ptr(), &mut val) };
-        sys_result(ret, val)
+        let cattr = CString::new(attr)?;
+        let ret = unsafe {
+            ffi::iio_device_buffer_attr_read_bool(self.dev.dev, cattr.as_ptr(), &mut val)
+        };
+        self.dev.attr_result(attr, ret, val)
     }
 
     /// Reads a buffer-specific attribute as an integer (i64)
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read_int(&self, attr: &str) -> Result<i64> {
+    pub fn attr_read_int(&self, attr: impl AsRef<str>) -> Result<i64> {
+        let attr = attr.as_ref();
         let mut val: c_longlong = 0;
-        let attr = CString::new(attr)?;
+        let cattr = CString::new(attr)?;
         let ret = unsafe {
-            ffi::iio_device_buffer_attr_read_longlong(self.dev.dev, attr.as_ptr(), &mut val)
+            ffi::iio_device_buffer_attr_read_longlong(self.dev.dev, cattr.as_ptr(), &mut val)
         };
-        sys_result(ret, val as i64)
+        self.dev.attr_result(attr, ret, val as i64)
     }
 
     /// Reads a buffer-specific attribute as a floating-point (f64) number
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read_float(&self, attr: &str) -> Result<f64> {
+    pub fn attr_read_float(&self, attr: impl AsRef<str>) -> Result<f64> {
+        let attr = attr.as_ref();
         let mut val: f64 = 0.0;
-        let attr = CString::new(attr)?;
+        let cattr = CString::new(attr)?;
         let ret = unsafe {
-            ffi::iio_device_buffer_attr_read_double(self.dev.dev, attr.as_ptr(), &mut val)
+            ffi::iio_device_buffer_attr_read_double(self.dev.dev, cattr.as_ptr(), &mut val)
         };
-        sys_result(ret, val)
+        self.dev.attr_result(attr, ret, val)
+    }
+
+    /// Reads a buffer-specific attribute as a list of values
+    ///
+    /// The values can be separated by whitespace or commas. An error
+    /// reports the first value that couldn't be parsed.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_vec<T: FromAttribute>(&self, attr: impl AsRef<str>) -> Result<Vec<T>> {
+        let attr = attr.as_ref();
+        parse_attr_list(&self.attr_read_str(attr)?)
     }
 
     /// Reads all the buffer-specific attributes.
@@ -266,11 +385,33 @@ impl Buffer {
         sys_result(ret, map)
     }
 
+    /// Gets an iterator over the names and values of the buffer-specific
+    /// attributes.
+    ///
+    /// With the network backend, the values are all fetched up front with
+    /// a single call to [`attr_read_all()`](Self::attr_read_all). Otherwise
+    /// each one is read as the iterator reaches it.
+    pub fn attrs_with_values(&self) -> impl Iterator<Item = (String, Result<String>)> + '_ {
+        let mut vals = if self.dev.ctx.name() == "network" {
+            self.attr_read_all().unwrap_or_default()
+        }
+        else {
+            HashMap::new()
+        };
+        self.attributes().map(move |name| {
+            let val = vals
+                .remove(&name)
+                .map_or_else(|| self.attr_read_str(&name), Ok);
+            (name, val)
+        })
+    }
+
     /// Writes a buffer-specific attribute
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write<T: ToAttribute>(&self, attr: &str, val: T) -> Result<()> {
+    pub fn attr_write<T: ToAttribute>(&self, attr: impl AsRef<str>, val: T) -> Result<()> {
+        let attr = attr.as_ref();
         let sval = T::to_attr(&val)?;
         self.attr_write_str(attr, &sval)
     }
@@ -279,46 +420,98 @@ impl Buffer {
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_str(&self, attr: &str, val: &str) -> Result<()> {
-        let attr = CString::new(attr)?;
+    pub fn attr_write_str(&self, attr: impl AsRef<str>, val: &str) -> Result<()> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
         let sval = CString::new(val)?;
-        let ret = unsafe {
-            ffi::iio_device_buffer_attr_write(self.dev.dev, attr.as_ptr(), sval.as_ptr())
-        };
-        sys_result(ret as i32, ())
+        self.journaled(attr, val, || {
+            let ret = unsafe {
+                ffi::iio_device_buffer_attr_write(self.dev.dev, cattr.as_ptr(), sval.as_ptr())
+            };
+            self.dev.attr_result(attr, ret as i32, ())
+        })
     }
 
     /// Writes a buffer-specific attribute as a boolean
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_bool(&self, attr: &str, val: bool) -> Result<()> {
-        let attr = CString::new(attr)?;
-        let ret =
-            unsafe { ffi::iio_device_buffer_attr_write_bool(self.dev.dev, attr.as_ptr(), val) };
-        sys_result(ret, ())
+    pub fn attr_write_bool(&self, attr: impl AsRef<str>, val: bool) -> Result<()> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
+        self.journaled(attr, if val { "1" } else { "0" }, || {
+            let ret = unsafe {
+                ffi::iio_device_buffer_attr_write_bool(self.dev.dev, cattr.as_ptr(), val)
+            };
+            self.dev.attr_result(attr, ret, ())
+        })
     }
 
     /// Writes a buffer-specific attribute as an integer (i64)
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_int(&self, attr: &str, val: i64) -> Result<()> {
-        let attr = CString::new(attr)?;
-        let ret =
-            unsafe { ffi::iio_device_buffer_attr_write_longlong(self.dev.dev, attr.as_ptr(), val) };
-        sys_result(ret, ())
+    pub fn attr_write_int(&self, attr: impl AsRef<str>, val: i64) -> Result<()> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
+        self.journaled(attr, &val.to_string(), || {
+            let ret = unsafe {
+                ffi::iio_device_buffer_attr_write_longlong(self.dev.dev, cattr.as_ptr(), val)
+            };
+            self.dev.attr_result(attr, ret, ())
+        })
     }
 
     /// Writes a buffer-specific attribute as a floating-point (f64) number
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_float(&self, attr: &str, val: f64) -> Result<()> {
-        let attr = CString::new(attr)?;
-        let ret =
-            unsafe { ffi::iio_device_buffer_attr_write_double(self.dev.dev, attr.as_ptr(), val) };
-        sys_result(ret, ())
+    pub fn attr_write_float(&self, attr: impl AsRef<str>, val: f64) -> Result<()> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
+        self.journaled(attr, &val.to_string(), || {
+            let ret = unsafe {
+                ffi::iio_device_buffer_attr_write_double(self.dev.dev, cattr.as_ptr(), val)
+            };
+            self.dev.attr_result(attr, ret, ())
+        })
+    }
+
+    /// Writes a buffer-specific attribute as a list of values, separated by
+    /// spaces
+    ///
+    /// `attr` The name of the attribute
+    /// `vals` The values to write
+    pub fn attr_write_vec<T: ToAttribute>(&self, attr: impl AsRef<str>, vals: &[T]) -> Result<()> {
+        let attr = attr.as_ref();
+        self.attr_write_str(attr, &format_attr_list(vals)?)
+    }
+
+    /// Performs a write of a buffer attribute, recording it in the
+    /// journal of the context, if enabled.
+    fn journaled<W>(&self, attr: &str, val: &str, write: W) -> Result<()>
+    where
+        W: FnOnce() -> Result<()>,
+    {
+        self.dev.ctx.journaled(
+            || AttrTarget {
+                kind: AttrKind::Buffer,
+                device: self.dev.id().unwrap_or_default(),
+                channel: None,
+            },
+            attr,
+            val,
+            || self.attr_read_str(attr).ok(),
+            || {
+                if self.has_attr(attr) {
+                    Ok(())
+                }
+                else {
+                    Err(self.dev.attr_err(attr, Errno::ENOENT.into()))
+                }
+            },
+            write,
+        )
     }
 
     /// Gets an iterator for the buffer attributes in the device
@@ -326,13 +519,117 @@ impl Buffer {
         AttrIterator { buf: self, idx: 0 }
     }
 
+    /// Gets the raw contents of the buffer.
+    ///
+    /// This is the interleaved samples of all the enabled channels, in the
+    /// hardware format, from the start of the buffer to the end of the
+    /// data from the last refill. It can be used to process or copy the
+    /// samples without demultiplexing them into a vector per channel.
+    pub fn as_bytes(&self) -> &[u8] {
+        let (start, len) = self.raw_span();
+        if len == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(start, len) }
+    }
+
+    /// Gets the raw contents of the buffer, mutably.
+    ///
+    /// For an output buffer, this can be used to fill in the interleaved
+    /// samples of all the enabled channels, in the hardware format, before
+    /// a [`push()`](Buffer::push).
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        let (start, len) = self.raw_span();
+        if len == 0 {
+            return &mut [];
+        }
+        unsafe { slice::from_raw_parts_mut(start, len) }
+    }
+
+    /// Gets the distance, in bytes, between consecutive samples of a
+    /// channel.
+    ///
+    /// This is the size of one scan: a sample from each enabled channel,
+    /// with any padding.
+    pub fn step(&self) -> usize {
+        unsafe { ffi::iio_buffer_step(self.buf) as usize }
+    }
+
+    /// Gets the start of the data in the buffer and its length, in bytes.
+    fn raw_span(&self) -> (*mut u8, usize) {
+        unsafe {
+            let start = ffi::iio_buffer_start(self.buf) as *mut u8;
+            let end = ffi::iio_buffer_end(self.buf) as *mut u8;
+            if start.is_null() || end <= start {
+                (start, 0)
+            }
+            else {
+                (start, end.offset_from(start) as usize)
+            }
+        }
+    }
+
+    /// Gets an iterator over the scans in the buffer.
+    ///
+    /// Each scan holds one sample from each of the enabled channels, which
+    /// can be read as raw bytes or converted to values. This steps through
+    /// the buffer by the scan size, so it handles channels with different
+    /// sample sizes.
+    pub fn frames(&self) -> ScanIter<'_> {
+        ScanIter {
+            buf: self,
+            data: self.as_bytes(),
+            step: self.step(),
+        }
+    }
+
+    /// Checks that the samples of a channel can be accessed in the buffer
+    /// as values of type `T`.
+    ///
+    /// The channel must be enabled in the buffer, and both the step and
+    /// the offset of the channel in a scan must be multiples of the size
+    /// of `T`. Otherwise this fails with `Error::LayoutMismatch`, rather
+    /// than letting the samples be accessed with the wrong stride.
+    pub fn check_layout<T>(&self, chan: &Channel) -> Result<()> {
+        let step = self.step();
+        let sample_size = size_of::<T>();
+
+        let fits = chan.is_enabled()
+            && sample_size != 0
+            && step % sample_size == 0
+            && unsafe {
+                let first = ffi::iio_buffer_first(self.buf, chan.chan) as usize;
+                let start = ffi::iio_buffer_start(self.buf) as usize;
+                first
+                    .checked_sub(start)
+                    .is_some_and(|off| off % sample_size == 0)
+            };
+
+        if fits {
+            Ok(())
+        }
+        else {
+            Err(Error::LayoutMismatch {
+                step,
+                sample_size,
+                channel: chan.id().unwrap_or_default(),
+            })
+        }
+    }
+
     /// Gets an iterator for the data from a channel.
-    pub fn channel_iter<T>(&self, chan: &Channel) -> Iter<'_, T> {
+    ///
+    /// This fails if the samples of the channel can't be accessed as type
+    /// `T`. See [`check_layout()`](Buffer::check_layout).
+    pub fn channel_iter<T>(&self, chan: &Channel) -> Result<Iter<'_, T>> {
         Iter::new(self, chan)
     }
 
     /// Gets a mutable iterator for the data to a channel.
-    pub fn channel_iter_mut<T>(&mut self, chan: &Channel) -> IterMut<'_, T> {
+    ///
+    /// This fails if the samples of the channel can't be accessed as type
+    /// `T`. See [`check_layout()`](Buffer::check_layout).
+    pub fn channel_iter_mut<T>(&mut self, chan: &Channel) -> Result<IterMut<'_, T>> {
         IterMut::new(self, chan)
     }
 }
@@ -344,6 +641,228 @@ impl Drop for Buffer {
     }
 }
 
+// The Buffer has exclusive use of the C buffer, so it can be sent to
+// another thread, such as to be awaited by a task on a multi-threaded
+// runtime.
+unsafe impl Send for Buffer {}
+
+/// Multiplexes the I/O for several buffers on a single thread.
+///
+/// Each buffer added to the poller is put into non-blocking mode, and
+/// [`poll()`](Poller::poll) waits for any of them to be ready, so that
+/// the buffers for several devices can be serviced without a blocking
+/// thread for each one.
+///
+/// ```no_run
+/// use industrial_io as iio;
+///
+/// let ctx = iio::Context::new().unwrap();
+/// let mut poller = iio::buffer::Poller::new();
+/// for dev in ctx.devices().filter(|dev| dev.is_buffer_capable()) {
+///     dev.scan_elements().for_each(|chan| chan.enable());
+///     poller.add(dev.create_buffer(1024, false).unwrap()).unwrap();
+/// }
+///
+/// loop {
+///     for idx in poller.refill_ready(None).unwrap() {
+///         let buf = poller.get(idx).unwrap();
+///         println!("{}: {} bytes", buf.device().id().unwrap(), buf.as_bytes().len());
+///     }
+/// }
+/// ```
+#[cfg(unix)]
+#[derive(Debug, Default)]
+pub struct Poller {
+    /// The buffers, in the order they were added
+    bufs: Vec<Buffer>,
+}
+
+#[cfg(unix)]
+impl Poller {
+    /// Creates a poller without any buffers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a buffer to the poller, returning its index.
+    ///
+    /// This puts the buffer into non-blocking mode. It fails if the
+    /// backend doesn't provide a pollable file descriptor for the buffer.
+    pub fn add(&mut self, buf: Buffer) -> Result<usize> {
+        buf.poll_fd()?;
+        buf.set_blocking_mode(false)?;
+        self.bufs.push(buf);
+        Ok(self.bufs.len() - 1)
+    }
+
+    /// Gets the number of buffers in the poller.
+    pub fn len(&self) -> usize {
+        self.bufs.len()
+    }
+
+    /// Determines if there are no buffers in the poller.
+    pub fn is_empty(&self) -> bool {
+        self.bufs.is_empty()
+    }
+
+    /// Gets a reference to a buffer by its index.
+    pub fn get(&self, idx: usize) -> Option<&Buffer> {
+        self.bufs.get(idx)
+    }
+
+    /// Gets a mutable reference to a buffer by its index.
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut Buffer> {
+        self.bufs.get_mut(idx)
+    }
+
+    /// Waits for any of the buffers to be ready, returning the indexes of
+    /// the ones that are.
+    ///
+    /// An input buffer is ready when it can be refilled, and an output
+    /// buffer when it can be pushed, without blocking. This waits forever
+    /// if `timeout` is `None`, and returns an empty list if it expires or
+    /// the wait is interrupted by a signal.
+    pub fn poll(&self, timeout: Option<Duration>) -> Result<Vec<usize>> {
+        let timeout = timeout
+            .map(|dur| PollTimeout::try_from(dur).unwrap_or(PollTimeout::MAX))
+            .unwrap_or(PollTimeout::NONE);
+
+        let flags = PollFlags::POLLIN | PollFlags::POLLOUT;
+        let mut fds = self
+            .bufs
+            .iter()
+            .map(|buf| Ok(PollFd::new(buf.poll_fd()?, flags)))
+            .collect::<Result<Vec<_>>>()?;
+
+        match poll(&mut fds, timeout) {
+            Ok(0) | Err(Errno::EINTR) => return Ok(Vec::new()),
+            Ok(_) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        Ok(fds
+            .iter()
+            .enumerate()
+            .filter(|(_, fd)| fd.revents().is_some_and(|ev| !ev.is_empty()))
+            .map(|(idx, _)| idx)
+            .collect())
+    }
+
+    /// Waits for any of the input buffers to be ready, and refills them,
+    /// returning the indexes of the ones that were refilled.
+    ///
+    /// A buffer that turns out not to have data yet is skipped. See
+    /// [`poll()`](Poller::poll).
+    pub fn refill_ready(&mut self, timeout: Option<Duration>) -> Result<Vec<usize>> {
+        let mut refilled = Vec::new();
+        for idx in self.poll(timeout)? {
+            match self.bufs[idx].refill() {
+                Ok(_) => refilled.push(idx),
+                Err(Error::Nix(Errno::EAGAIN)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(refilled)
+    }
+
+    /// Removes the buffers from the poller, putting them back into
+    /// blocking mode.
+    pub fn into_buffers(self) -> Result<Vec<Buffer>> {
+        for buf in &self.bufs {
+            buf.set_blocking_mode(true)?;
+        }
+        Ok(self.bufs)
+    }
+}
+
+/// One scan from a buffer: a sample from each of the enabled channels.
+#[derive(Debug, Clone, Copy)]
+pub struct Scan<'a> {
+    /// The buffer that the scan is from
+    buf: &'a Buffer,
+    /// The raw bytes of the scan
+    data: &'a [u8],
+}
+
+impl<'a> Scan<'a> {
+    /// Gets the raw bytes of the scan, in the hardware format.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Gets the raw bytes of the sample for a channel, in the hardware
+    /// format.
+    ///
+    /// This is `None` if the channel isn't enabled in the buffer.
+    pub fn channel_bytes(&self, chan: &Channel) -> Option<&'a [u8]> {
+        if !chan.is_enabled() {
+            return None;
+        }
+        let off = unsafe {
+            let first = ffi::iio_buffer_first(self.buf.buf, chan.chan) as usize;
+            let start = ffi::iio_buffer_start(self.buf.buf) as usize;
+            first.checked_sub(start)?
+        };
+        let len = chan.data_format().byte_length();
+        self.data.get(off..off + len)
+    }
+
+    /// Gets the value of the sample for a channel.
+    ///
+    /// The value is converted to the host format, but not scaled. This
+    /// fails with `Error::LayoutMismatch` if the channel isn't enabled in
+    /// the buffer.
+    pub fn value(&self, chan: &Channel) -> Result<f64> {
+        let sample = self
+            .channel_bytes(chan)
+            .ok_or_else(|| Error::LayoutMismatch {
+                step: self.buf.step(),
+                sample_size: chan.data_format().byte_length(),
+                channel: chan.id().unwrap_or_default(),
+            })?;
+        chan.convert_f64(sample)
+    }
+
+    /// Gets the values of the samples for the channels.
+    pub fn values(&self, chans: &[Channel]) -> Result<Vec<f64>> {
+        chans.iter().map(|chan| self.value(chan)).collect()
+    }
+}
+
+/// An iterator over the scans in a buffer.
+#[derive(Debug)]
+pub struct ScanIter<'a> {
+    /// The buffer being scanned
+    buf: &'a Buffer,
+    /// The remaining data in the buffer
+    data: &'a [u8],
+    /// The size of a scan, in bytes
+    step: usize,
+}
+
+impl<'a> Iterator for ScanIter<'a> {
+    type Item = Scan<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.step == 0 || self.data.len() < self.step {
+            return None;
+        }
+        let (data, rest) = self.data.split_at(self.step);
+        self.data = rest;
+        Some(Scan {
+            buf: self.buf,
+            data,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.data.len().checked_div(self.step).unwrap_or(0);
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for ScanIter<'_> {}
+
 /// An iterator that moves channel data out of a buffer.
 #[derive(Debug)]
 pub struct Iter<'a, T: 'a> {
@@ -358,19 +877,23 @@ pub struct Iter<'a, T: 'a> {
 
 impl<T> Iter<'_, T> {
     /// Create an iterator to move channel data out of a buffer.
-    pub fn new(buf: &Buffer, chan: &Channel) -> Self {
+    ///
+    /// This fails if the samples of the channel can't be accessed as type
+    /// `T`. See [`Buffer::check_layout()`].
+    pub fn new(buf: &Buffer, chan: &Channel) -> Result<Self> {
+        buf.check_layout::<T>(chan)?;
         unsafe {
             let begin = ffi::iio_buffer_first(buf.buf, chan.chan).cast();
             let end = ffi::iio_buffer_end(buf.buf).cast();
             let ptr = begin;
             let step: isize = ffi::iio_buffer_step(buf.buf) / size_of::<T>() as isize;
 
-            Self {
+            Ok(Self {
                 _phantom: PhantomData,
                 ptr,
                 end,
                 step,
-            }
+            })
         }
     }
 }
@@ -407,19 +930,23 @@ pub struct IterMut<'a, T: 'a> {
 
 impl<'a, T: 'a> IterMut<'a, T> {
     /// Create a mutable iterator to move channel data into a buffer.
-    pub fn new(buf: &'a mut Buffer, chan: &Channel) -> Self {
+    ///
+    /// This fails if the samples of the channel can't be accessed as type
+    /// `T`. See [`Buffer::check_layout()`].
+    pub fn new(buf: &'a mut Buffer, chan: &Channel) -> Result<Self> {
+        buf.check_layout::<T>(chan)?;
         unsafe {
             let begin = ffi::iio_buffer_first(buf.buf, chan.chan).cast();
             let end = ffi::iio_buffer_end(buf.buf).cast();
             let ptr = begin;
             let step: isize = ffi::iio_buffer_step(buf.buf) / size_of::<T>() as isize;
 
-            Self {
+            Ok(Self {
                 _phantom: PhantomData,
                 ptr,
                 end,
                 step,
-            }
+            })
         }
     }
 }
@@ -475,7 +1002,21 @@ impl Iterator for AttrIterator<'_> {
 
 #[cfg(test)]
 mod tests {
-    //use super::*;
+    use super::*;
 
     //const DEV_ID: &str = "dummydev";
+
+    #[cfg(unix)]
+    #[test]
+    fn empty_poller() {
+        let mut poller = Poller::new();
+        assert!(poller.is_empty());
+        assert!(poller.get(0).is_none());
+        assert!(poller.poll(Some(Duration::ZERO)).unwrap().is_empty());
+        assert!(poller
+            .refill_ready(Some(Duration::ZERO))
+            .unwrap()
+            .is_empty());
+        assert!(poller.into_buffers().unwrap().is_empty());
+    }
 }
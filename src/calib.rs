@@ -0,0 +1,240 @@
+// industrial-io/src/calib.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Software calibration of channel data.
+//!
+//! Drivers often expose `calibbias` and `calibscale` attributes to trim the
+//! hardware, but a system may also need a user-space calibration on top
+//! of that, such as for the response of a particular sensor or front end.
+//! A [`Corrector`] holds a per-channel [`Correction`] and applies it
+//! consistently to the samples in a [`Frame`], keeping that calibration
+//! separate from the driver's.
+//!
+//! Corrections can be loaded from a simple text file with one channel per
+//! line, giving the channel ID, the type of correction, and its values:
+//!
+//! ```text
+//! # Polynomial coefficients, lowest order first: y = c0 + c1*x + c2*x^2
+//! voltage0  poly  0.012 1.0021
+//! # Lookup table of x:y points, with linear interpolation between them
+//! voltage1  lut   0:0  100:101.5  200:203.2
+//! ```
+//!
+//! Blank lines and anything after a '#' are ignored.
+
+use crate::{Error, Frame, Result};
+use std::{collections::HashMap, fs, path::Path, str::FromStr};
+
+/// A correction for the samples of a single channel.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Correction {
+    /// A polynomial with the coefficients in increasing order, so that
+    /// `y = c[0] + c[1]*x + c[2]*x^2 + ...`
+    Polynomial(Vec<f64>),
+    /// A lookup table of `(x, y)` points, sorted by `x`. Values between
+    /// the points are linearly interpolated. Values outside the table are
+    /// extrapolated from the first or last segment.
+    Lut(Vec<(f64, f64)>),
+}
+
+impl Correction {
+    /// Creates a linear correction with a gain and offset, such that
+    /// `y = gain*x + offset`.
+    pub fn gain_offset(gain: f64, offset: f64) -> Self {
+        Correction::Polynomial(vec![offset, gain])
+    }
+
+    /// Creates a lookup table correction from a set of points.
+    ///
+    /// The points are sorted by their `x` values.
+    pub fn lut(mut points: Vec<(f64, f64)>) -> Self {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Correction::Lut(points)
+    }
+
+    /// Applies the correction to a single value.
+    pub fn apply(&self, x: f64) -> f64 {
+        match self {
+            Correction::Polynomial(coeffs) => coeffs.iter().rev().fold(0.0, |y, c| y * x + c),
+            Correction::Lut(pts) => match pts.len() {
+                0 => x,
+                1 => pts[0].1 + (x - pts[0].0),
+                n => {
+                    let i = pts.partition_point(|p| p.0 < x).clamp(1, n - 1);
+                    let (x0, y0) = pts[i - 1];
+                    let (x1, y1) = pts[i];
+                    if x1 == x0 {
+                        y0
+                    }
+                    else {
+                        y0 + (x - x0) * (y1 - y0) / (x1 - x0)
+                    }
+                }
+            },
+        }
+    }
+}
+
+impl FromStr for Correction {
+    type Err = Error;
+
+    /// Parses a correction from the type and values, like "poly 0.1 1.0"
+    /// or "lut 0:0 100:101.5"
+    fn from_str(s: &str) -> Result<Self> {
+        let err = |tok: &str| Error::General(format!("Invalid calibration value: '{}'", tok));
+        let num = |tok: &str| tok.parse::<f64>().map_err(|_| err(tok));
+
+        let mut toks = s.split_whitespace();
+        match toks.next() {
+            Some("poly") => {
+                let coeffs = toks.map(num).collect::<Result<Vec<_>>>()?;
+                Ok(Correction::Polynomial(coeffs))
+            }
+            Some("lut") => {
+                let pts = toks
+                    .map(|tok| {
+                        let (x, y) = tok.split_once(':').ok_or_else(|| err(tok))?;
+                        Ok((num(x)?, num(y)?))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Correction::lut(pts))
+            }
+            Some(kind) => Err(Error::General(format!(
+                "Unknown calibration type: '{}'",
+                kind
+            ))),
+            None => Err(Error::General("Missing calibration type".into())),
+        }
+    }
+}
+
+/// A set of per-channel software corrections.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Corrector {
+    /// The corrections, by channel ID
+    corrections: HashMap<String, Correction>,
+}
+
+impl Corrector {
+    /// Creates a new corrector with no corrections.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the corrections from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Sets the correction for a channel, replacing any previous one.
+    pub fn set(&mut self, chan_id: &str, corr: Correction) {
+        self.corrections.insert(chan_id.to_string(), corr);
+    }
+
+    /// Adds a correction for a channel, in builder style.
+    pub fn with(mut self, chan_id: &str, corr: Correction) -> Self {
+        self.set(chan_id, corr);
+        self
+    }
+
+    /// Gets the correction for a channel, if any.
+    pub fn get(&self, chan_id: &str) -> Option<&Correction> {
+        self.corrections.get(chan_id)
+    }
+
+    /// Applies the corrections to all the matching channels in a frame.
+    ///
+    /// Channels in the frame without a correction are left unchanged.
+    pub fn apply(&self, frame: &mut Frame) {
+        for ch in &mut frame.channels {
+            if let Some(corr) = self.corrections.get(&ch.id) {
+                for x in ch.samples.iter_mut() {
+                    *x = corr.apply(*x);
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Corrector {
+    type Err = Error;
+
+    /// Parses the corrections from the text file format.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut corr = Self::new();
+        for line in s.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (id, rest) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| Error::General(format!("Invalid calibration line: '{}'", line)))?;
+            corr.set(id, rest.parse()?);
+        }
+        Ok(corr)
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameChannel;
+
+    #[test]
+    fn polynomial() {
+        let corr = Correction::Polynomial(vec![1.0, 2.0, 3.0]);
+        assert_eq!(corr.apply(2.0), 1.0 + 4.0 + 12.0);
+
+        let corr = Correction::gain_offset(2.0, 0.5);
+        assert_eq!(corr.apply(3.0), 6.5);
+    }
+
+    #[test]
+    fn lut() {
+        let corr = Correction::lut(vec![(100.0, 110.0), (0.0, 0.0)]);
+        assert_eq!(corr.apply(50.0), 55.0);
+        assert_eq!(corr.apply(0.0), 0.0);
+        assert_eq!(corr.apply(200.0), 220.0);
+        assert_eq!(corr.apply(-10.0), -11.0);
+    }
+
+    #[test]
+    fn parse_and_apply() {
+        let corr: Corrector = "
+            # A comment
+            voltage0  poly  1 2   # trailing comment
+            voltage1  lut   0:0 10:20
+        "
+        .parse()
+        .unwrap();
+
+        let mut frame = Frame {
+            channels: vec![
+                FrameChannel::new("voltage0", vec![1.0, 2.0]),
+                FrameChannel::new("voltage1", vec![5.0]),
+                FrameChannel::new("voltage2", vec![7.0]),
+            ],
+            ..Frame::default()
+        };
+        corr.apply(&mut frame);
+
+        assert_eq!(frame.samples("voltage0").unwrap(), &[3.0, 5.0]);
+        assert_eq!(frame.samples("voltage1").unwrap(), &[10.0]);
+        assert_eq!(frame.samples("voltage2").unwrap(), &[7.0]);
+
+        assert!("voltage0 cubic 1 2".parse::<Corrector>().is_err());
+        assert!("voltage0 lut 1".parse::<Corrector>().is_err());
+    }
+}
@@ -0,0 +1,473 @@
+// industrial-io/src/capture.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Reconfiguring a capture while it's running.
+//!
+//! A [`Capture`] runs the acquisition loop for a device, refilling a
+//! buffer and sending each block to a [`Pipeline`] as a frame. Other
+//! threads, like the UI of an interactive acquisition app, can change it
+//! while it's running through a [`Controller`]: setting the sampling rate,
+//! enabling and disabling channels, and adding, swapping, or removing
+//! sinks.
+//!
+//! Commands are applied between blocks. Most drivers refuse changes to
+//! the device while it's streaming, so for those the buffer is destroyed
+//! before the change, and recreated afterward, losing no more than the
+//! block that was in flight. Changes to the sinks leave the buffer alone.
+//!
+//! A capture can also be paused and resumed, which stops refilling the
+//! buffer, and optionally releases it, without tearing anything down, so
+//! that the configuration, sinks, and statistics are all kept.
+//!
+//! Each command gives back a [`Reply`] that can be used to wait for it to
+//! be applied, and to get the result.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::{capture::Capture, sink::{CsvSink, Pipeline}};
+//! use std::thread;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ads1015").unwrap();
+//! dev.find_input_channel("voltage0").unwrap().enable();
+//!
+//! let mut pipeline = Pipeline::new();
+//! pipeline.add_sink(Box::new(CsvSink::create("capture.csv").unwrap()));
+//!
+//! let (capture, ctrl) = Capture::new(dev, pipeline, 256);
+//! let thr = thread::spawn(move || capture.run());
+//!
+//! ctrl.set_sample_rate(860.0).unwrap().wait().unwrap();
+//! ctrl.enable_channel("voltage1").unwrap().wait().unwrap();
+//!
+//! ctrl.stop().unwrap();
+//! thr.join().unwrap().unwrap().close().unwrap();
+//! ```
+
+use crate::{
+    sink::{FrameSink, Pipeline},
+    Buffer, Channel, Device, Error, Frame, Result,
+};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+};
+
+/// A change to make to a running capture.
+pub enum Command {
+    /// Sets the sampling rate of the device, in Hz.
+    SetSampleRate(f64),
+    /// Enables an input channel, by ID or name.
+    EnableChannel(String),
+    /// Disables an input channel, by ID or name.
+    DisableChannel(String),
+    /// Adds a sink that receives the frames with any of the tags, or the
+    /// untagged frames if there are none.
+    AddSink(Vec<String>, Box<dyn FrameSink>),
+    /// Replaces the sink at the index in the pipeline.
+    ReplaceSink(usize, Box<dyn FrameSink>),
+    /// Removes the sink at the index in the pipeline.
+    RemoveSink(usize),
+    /// Pauses the capture, optionally releasing the buffer.
+    Pause(bool),
+    /// Resumes a paused capture.
+    Resume,
+    /// Stops the capture.
+    Stop,
+}
+
+impl Command {
+    /// Determines if the command changes the device, so that the buffer
+    /// needs to be recreated.
+    pub fn changes_device(&self) -> bool {
+        matches!(
+            self,
+            Command::SetSampleRate(_) | Command::EnableChannel(_) | Command::DisableChannel(_)
+        )
+    }
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::SetSampleRate(hz) => f.debug_tuple("SetSampleRate").field(hz).finish(),
+            Command::EnableChannel(id) => f.debug_tuple("EnableChannel").field(id).finish(),
+            Command::DisableChannel(id) => f.debug_tuple("DisableChannel").field(id).finish(),
+            Command::AddSink(tags, _) => f.debug_tuple("AddSink").field(tags).finish(),
+            Command::ReplaceSink(idx, _) => f.debug_tuple("ReplaceSink").field(idx).finish(),
+            Command::RemoveSink(idx) => f.debug_tuple("RemoveSink").field(idx).finish(),
+            Command::Pause(release) => f.debug_tuple("Pause").field(release).finish(),
+            Command::Resume => f.write_str("Resume"),
+            Command::Stop => f.write_str("Stop"),
+        }
+    }
+}
+
+/// A command, with the channel to send back its result.
+type Request = (Command, Sender<Result<()>>);
+
+/// The pending result of a command sent to a running capture.
+#[derive(Debug)]
+pub struct Reply(Receiver<Result<()>>);
+
+impl Reply {
+    /// Waits for the command to be applied, and gets the result.
+    ///
+    /// This fails if the capture stopped before applying the command.
+    pub fn wait(self) -> Result<()> {
+        self.0.recv().unwrap_or_else(|_| Err(stopped()))
+    }
+
+    /// Gets the result of the command, if it has been applied, without
+    /// waiting.
+    pub fn try_get(&self) -> Option<Result<()>> {
+        match self.0.try_recv() {
+            Ok(res) => Some(res),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(stopped())),
+        }
+    }
+}
+
+/// The state of a capture that's shared with its controllers.
+#[derive(Debug, Default)]
+struct State {
+    /// Whether the capture is paused
+    paused: AtomicBool,
+    /// The number of blocks captured so far
+    blocks: AtomicU64,
+}
+
+/// The error for a command that can't reach the capture.
+fn stopped() -> Error {
+    Error::General("The capture is not running".into())
+}
+
+/// A handle to send commands to a running capture.
+///
+/// This can be cloned to control the capture from multiple threads. The
+/// capture stops when all of its controllers are dropped.
+#[derive(Debug, Clone)]
+pub struct Controller {
+    /// The queue to the capture
+    tx: Sender<Request>,
+    /// The state of the capture
+    state: Arc<State>,
+}
+
+impl Controller {
+    /// Sends a command to the capture, to be applied after the current
+    /// block.
+    pub fn send(&self, cmd: Command) -> Result<Reply> {
+        let (tx, rx) = mpsc::channel();
+        self.tx.send((cmd, tx)).map_err(|_| stopped())?;
+        Ok(Reply(rx))
+    }
+
+    /// Sets the sampling rate of the device, in Hz.
+    pub fn set_sample_rate(&self, hz: f64) -> Result<Reply> {
+        self.send(Command::SetSampleRate(hz))
+    }
+
+    /// Enables an input channel, by ID or name.
+    pub fn enable_channel(&self, id: &str) -> Result<Reply> {
+        self.send(Command::EnableChannel(id.into()))
+    }
+
+    /// Disables an input channel, by ID or name.
+    pub fn disable_channel(&self, id: &str) -> Result<Reply> {
+        self.send(Command::DisableChannel(id.into()))
+    }
+
+    /// Adds a sink to the pipeline that receives the frames with any of
+    /// the tags, or the untagged frames if there are none.
+    pub fn add_sink(&self, tags: &[&str], sink: Box<dyn FrameSink>) -> Result<Reply> {
+        let tags = tags.iter().map(|s| s.to_string()).collect();
+        self.send(Command::AddSink(tags, sink))
+    }
+
+    /// Replaces the sink at the index in the pipeline.
+    ///
+    /// See [`Pipeline::replace_sink()`].
+    pub fn replace_sink(&self, idx: usize, sink: Box<dyn FrameSink>) -> Result<Reply> {
+        self.send(Command::ReplaceSink(idx, sink))
+    }
+
+    /// Removes the sink at the index in the pipeline.
+    ///
+    /// See [`Pipeline::remove_sink()`].
+    pub fn remove_sink(&self, idx: usize) -> Result<Reply> {
+        self.send(Command::RemoveSink(idx))
+    }
+
+    /// Pauses the capture after the current block.
+    ///
+    /// While paused, the buffer isn't refilled, but the configuration of
+    /// the capture, the sinks, and the statistics are kept, and commands
+    /// are still applied. If `release` is set, the buffer is destroyed,
+    /// which disables it in the kernel, and it's recreated on resuming.
+    /// Otherwise it's kept, which makes resuming quicker, but the kernel
+    /// keeps filling it in the meantime, so the first block afterward can
+    /// hold old samples, with a gap after them.
+    pub fn pause(&self, release: bool) -> Result<Reply> {
+        self.send(Command::Pause(release))
+    }
+
+    /// Resumes a paused capture.
+    pub fn resume(&self) -> Result<Reply> {
+        self.send(Command::Resume)
+    }
+
+    /// Determines if the capture is paused.
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of blocks captured so far, including those before
+    /// any pauses.
+    pub fn blocks(&self) -> u64 {
+        self.state.blocks.load(Ordering::Relaxed)
+    }
+
+    /// Stops the capture after the current block.
+    pub fn stop(&self) -> Result<Reply> {
+        self.send(Command::Stop)
+    }
+}
+
+/// The acquisition loop for a device, that can be reconfigured while it's
+/// running.
+#[derive(Debug)]
+pub struct Capture {
+    /// The device to capture
+    dev: Device,
+    /// The destination for the frames
+    pipeline: Pipeline,
+    /// The number of samples in each block
+    block_len: usize,
+    /// The tag for the frames, if any
+    tag: Option<String>,
+    /// The queue of commands
+    rx: Receiver<Request>,
+    /// The state shared with the controllers
+    state: Arc<State>,
+}
+
+impl Capture {
+    /// Creates a capture of the enabled channels of the device, in blocks
+    /// of the specified number of samples, sent to the pipeline.
+    ///
+    /// Returns the capture and a controller for it. The capture doesn't
+    /// start until it's [run](Capture::run).
+    pub fn new(dev: Device, pipeline: Pipeline, block_len: usize) -> (Self, Controller) {
+        let (tx, rx) = mpsc::channel();
+        let state = Arc::new(State::default());
+        let capture = Self {
+            dev,
+            pipeline,
+            block_len: block_len.max(1),
+            tag: None,
+            rx,
+            state: Arc::clone(&state),
+        };
+        (capture, Controller { tx, state })
+    }
+
+    /// Sends the frames to the pipeline with the tag.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// Runs the capture until it's told to stop, all the controllers are
+    /// dropped, or an error occurs reading the device.
+    ///
+    /// Gives back the pipeline when it stops, so that it can be closed, or
+    /// reused. A command that fails is reported back to the controller
+    /// that sent it, and doesn't stop the capture.
+    pub fn run(mut self) -> Result<Pipeline> {
+        let mut buf: Option<Buffer> = None;
+        let mut chans = Vec::new();
+        let mut rate = self.dev.sampling_frequency().ok();
+        let mut seq: u64 = 0;
+        let mut idle = false;
+
+        loop {
+            let mut pending = Vec::new();
+            match self.rx.try_recv() {
+                Ok(req) => pending.push(req),
+                Err(TryRecvError::Empty) => (),
+                Err(TryRecvError::Disconnected) => break,
+            }
+            pending.extend(self.rx.try_iter());
+
+            // When paused, or with nothing enabled, there's nothing to do
+            // but wait to be told to do something.
+            if pending.is_empty() && (idle || self.is_paused()) {
+                match self.rx.recv() {
+                    Ok(req) => pending.push(req),
+                    Err(_) => break,
+                }
+            }
+
+            let mut stop = false;
+            for (cmd, reply) in pending {
+                if cmd.changes_device() {
+                    buf = None;
+                    chans.clear();
+                }
+                let res = match cmd {
+                    Command::Pause(release) => {
+                        if release {
+                            buf = None;
+                        }
+                        self.state.paused.store(true, Ordering::Relaxed);
+                        Ok(())
+                    }
+                    Command::Resume => {
+                        self.state.paused.store(false, Ordering::Relaxed);
+                        Ok(())
+                    }
+                    Command::Stop => {
+                        stop = true;
+                        Ok(())
+                    }
+                    cmd => self.apply(cmd, &mut rate),
+                };
+                let _ = reply.send(res);
+            }
+            if stop {
+                break;
+            }
+            if self.is_paused() {
+                continue;
+            }
+
+            if buf.is_none() {
+                chans = self.enabled_channels();
+                idle = chans.is_empty();
+                if idle {
+                    continue;
+                }
+                buf = Some(self.dev.create_buffer(self.block_len, false)?);
+            }
+
+            let b = buf.as_mut().unwrap();
+            b.refill()?;
+            let mut frame = Frame::from_buffer(b, &chans)?;
+            frame.seq = seq;
+            frame.sample_rate = rate;
+            seq += 1;
+            self.state.blocks.store(seq, Ordering::Relaxed);
+
+            match &self.tag {
+                Some(tag) => self.pipeline.send_tagged(tag, frame),
+                None => self.pipeline.send(frame),
+            }
+        }
+
+        drop(buf);
+        Ok(self.pipeline)
+    }
+
+    /// Determines if the capture is paused.
+    fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::Relaxed)
+    }
+
+    /// Gets the scan elements of the device that are enabled.
+    fn enabled_channels(&self) -> Vec<Channel> {
+        self.dev
+            .channels()
+            .filter(|chan| chan.is_scan_element() && chan.is_enabled())
+            .collect()
+    }
+
+    /// Finds an input channel that can be captured.
+    fn scan_channel(&self, id: &str) -> Result<Channel> {
+        self.dev
+            .find_input_channel(id)
+            .filter(|chan| chan.is_scan_element())
+            .ok_or_else(|| Error::General(format!("Scan channel not found: '{}'", id)))
+    }
+
+    /// Applies a command, other than pausing, resuming, or stopping.
+    ///
+    /// Any buffer must already have been destroyed for commands that
+    /// change the device.
+    fn apply(&mut self, cmd: Command, rate: &mut Option<f64>) -> Result<()> {
+        match cmd {
+            Command::SetSampleRate(hz) => {
+                *rate = Some(self.dev.set_sampling_frequency(hz)?);
+            }
+            Command::EnableChannel(id) => self.scan_channel(&id)?.enable(),
+            Command::DisableChannel(id) => self.scan_channel(&id)?.disable(),
+            Command::AddSink(tags, sink) => {
+                let tags: Vec<_> = tags.iter().map(String::as_str).collect();
+                self.pipeline.add_tagged_sink(&tags, sink);
+            }
+            Command::ReplaceSink(idx, sink) => self.pipeline.replace_sink(idx, sink)?,
+            Command::RemoveSink(idx) => self.pipeline.remove_sink(idx)?,
+            Command::Pause(_) | Command::Resume | Command::Stop => (),
+        }
+        Ok(())
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::CsvSink;
+
+    #[test]
+    fn commands() {
+        assert!(Command::SetSampleRate(100.0).changes_device());
+        assert!(Command::DisableChannel("voltage0".into()).changes_device());
+        assert!(!Command::RemoveSink(0).changes_device());
+        assert!(!Command::Pause(true).changes_device());
+        assert!(!Command::Stop.changes_device());
+
+        let cmd = Command::AddSink(vec!["rf".into()], Box::new(CsvSink::new(Vec::new())));
+        assert_eq!(format!("{:?}", cmd), r#"AddSink(["rf"])"#);
+    }
+
+    #[test]
+    fn replies() {
+        let (tx, rx) = mpsc::channel::<Request>();
+        let ctrl = Controller {
+            tx,
+            state: Arc::default(),
+        };
+        assert!(!ctrl.is_paused());
+        assert_eq!(ctrl.blocks(), 0);
+
+        let reply = ctrl.set_sample_rate(100.0).unwrap();
+        assert!(reply.try_get().is_none());
+
+        let (cmd, res_tx) = rx.recv().unwrap();
+        assert!(matches!(cmd, Command::SetSampleRate(hz) if hz == 100.0));
+        res_tx.send(Ok(())).unwrap();
+        assert!(reply.wait().is_ok());
+
+        // A command that's dropped without being applied
+        let reply = ctrl.stop().unwrap();
+        drop(rx);
+        assert!(reply.wait().is_err());
+        assert!(ctrl.stop().is_err());
+    }
+}
@@ -11,17 +11,30 @@
 //!
 
 use super::*;
-use crate::{ffi, ATTR_BUF_SIZE};
+use crate::{
+    abi,
+    attr_reader::read_growing,
+    events::SYSFS_DEVICES,
+    ffi,
+    journal::{AttrKind, AttrTarget},
+    pool::{PooledVec, SamplePool},
+    units::{self, Hertz},
+};
 use std::{
     any::TypeId,
     collections::HashMap,
     ffi::CString,
+    fs::File,
     mem::{self, size_of, size_of_val},
     os::raw::{c_char, c_int, c_longlong, c_uint, c_void},
+    path::Path,
+    ptr, slice,
+    time::{Duration, Instant},
 };
 
 /// The channel direction
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Direction {
     /// Channel is input
     Input,
@@ -29,10 +42,138 @@ pub enum Direction {
     Output,
 }
 
+/// The modifier of a channel, like the axis of an accelerometer.
+///
+/// The values are the codes in the kernel ABI. These are given here,
+/// rather than taken from the C library, since older versions of the
+/// library don't define all of them.
+#[allow(missing_docs)]
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelModifier {
+    Unmodified = 0,
+    X = 1,
+    Y = 2,
+    Z = 3,
+    XAndY = 4,
+    XAndZ = 5,
+    YAndZ = 6,
+    XAndYAndZ = 7,
+    XOrY = 8,
+    XOrZ = 9,
+    YOrZ = 10,
+    XOrYOrZ = 11,
+    LightBoth = 12,
+    LightIr = 13,
+    RootSumSquaredXY = 14,
+    SumSquaredXYZ = 15,
+    LightClear = 16,
+    LightRed = 17,
+    LightGreen = 18,
+    LightBlue = 19,
+    Quaternion = 20,
+    TempAmbient = 21,
+    TempObject = 22,
+    NorthMagn = 23,
+    NorthTrue = 24,
+    NorthMagnTiltComp = 25,
+    NorthTrueTiltComp = 26,
+    Running = 27,
+    Jogging = 28,
+    Walking = 29,
+    Still = 30,
+    RootSumSquaredXYZ = 31,
+    I = 32,
+    Q = 33,
+    Co2 = 34,
+    Voc = 35,
+    LightUv = 36,
+    LightDuv = 37,
+    Pm1 = 38,
+    Pm2p5 = 39,
+    Pm4 = 40,
+    Pm10 = 41,
+    Ethanol = 42,
+    H2 = 43,
+    O2 = 44,
+    LinearX = 45,
+    LinearY = 46,
+    LinearZ = 47,
+    Pitch = 48,
+    Yaw = 49,
+    Roll = 50,
+    Unknown = u32::MAX,
+}
+
+impl ChannelModifier {
+    /// Gets the modifier from its code in the kernel ABI.
+    ///
+    /// Any value that isn't known is mapped to `Unknown`.
+    pub fn from_raw(n: u32) -> Self {
+        use ChannelModifier::*;
+        match n {
+            0 => Unmodified,
+            1 => X,
+            2 => Y,
+            3 => Z,
+            4 => XAndY,
+            5 => XAndZ,
+            6 => YAndZ,
+            7 => XAndYAndZ,
+            8 => XOrY,
+            9 => XOrZ,
+            10 => YOrZ,
+            11 => XOrYOrZ,
+            12 => LightBoth,
+            13 => LightIr,
+            14 => RootSumSquaredXY,
+            15 => SumSquaredXYZ,
+            16 => LightClear,
+            17 => LightRed,
+            18 => LightGreen,
+            19 => LightBlue,
+            20 => Quaternion,
+            21 => TempAmbient,
+            22 => TempObject,
+            23 => NorthMagn,
+            24 => NorthTrue,
+            25 => NorthMagnTiltComp,
+            26 => NorthTrueTiltComp,
+            27 => Running,
+            28 => Jogging,
+            29 => Walking,
+            30 => Still,
+            31 => RootSumSquaredXYZ,
+            32 => I,
+            33 => Q,
+            34 => Co2,
+            35 => Voc,
+            36 => LightUv,
+            37 => LightDuv,
+            38 => Pm1,
+            39 => Pm2p5,
+            40 => Pm4,
+            41 => Pm10,
+            42 => Ethanol,
+            43 => H2,
+            44 => O2,
+            45 => LinearX,
+            46 => LinearY,
+            47 => LinearZ,
+            48 => Pitch,
+            49 => Yaw,
+            50 => Roll,
+            _ => Unknown,
+        }
+    }
+}
+
 /// The type of data associated with a channel.
 #[allow(missing_docs)]
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChannelType {
     Voltage = ffi::iio_chan_type_IIO_VOLTAGE,
     Current = ffi::iio_chan_type_IIO_CURRENT,
@@ -69,6 +210,51 @@ pub enum ChannelType {
     Unknown = ffi::iio_chan_type_IIO_CHAN_TYPE_UNKNOWN,
 }
 
+impl ChannelType {
+    /// Gets the channel type from its value in the C library, which is
+    /// the same as the value in the kernel ABI.
+    ///
+    /// Any value that isn't known is mapped to `Unknown`.
+    pub(crate) fn from_raw(n: u32) -> Self {
+        use ChannelType::*;
+        match n {
+            ffi::iio_chan_type_IIO_VOLTAGE => Voltage,
+            ffi::iio_chan_type_IIO_CURRENT => Current,
+            ffi::iio_chan_type_IIO_POWER => Power,
+            ffi::iio_chan_type_IIO_ACCEL => Accel,
+            ffi::iio_chan_type_IIO_ANGL_VEL => AnglVel,
+            ffi::iio_chan_type_IIO_MAGN => Magn,
+            ffi::iio_chan_type_IIO_LIGHT => Ligtht,
+            ffi::iio_chan_type_IIO_INTENSITY => Intensity,
+            ffi::iio_chan_type_IIO_PROXIMITY => Proximity,
+            ffi::iio_chan_type_IIO_TEMP => Temp,
+            ffi::iio_chan_type_IIO_INCLI => Incli,
+            ffi::iio_chan_type_IIO_ROT => Rot,
+            ffi::iio_chan_type_IIO_ANGL => Angl,
+            ffi::iio_chan_type_IIO_TIMESTAMP => Timestamp,
+            ffi::iio_chan_type_IIO_CAPACITANCE => Capacitance,
+            ffi::iio_chan_type_IIO_ALTVOLTAGE => AltVoltage,
+            ffi::iio_chan_type_IIO_CCT => Cct,
+            ffi::iio_chan_type_IIO_PRESSURE => Pressure,
+            ffi::iio_chan_type_IIO_HUMIDITYRELATIVE => HumidityRelative,
+            ffi::iio_chan_type_IIO_ACTIVITY => Activity,
+            ffi::iio_chan_type_IIO_STEPS => Steps,
+            ffi::iio_chan_type_IIO_ENERGY => Energy,
+            ffi::iio_chan_type_IIO_DISTANCE => Distance,
+            ffi::iio_chan_type_IIO_VELOCITY => Velocity,
+            ffi::iio_chan_type_IIO_CONCENTRATION => Concentration,
+            ffi::iio_chan_type_IIO_RESISTANCE => Resistance,
+            ffi::iio_chan_type_IIO_PH => Ph,
+            ffi::iio_chan_type_IIO_UVINDEX => UvIndex,
+            ffi::iio_chan_type_IIO_ELECTRICALCONDUCTIVITY => ElectricalConductivity,
+            ffi::iio_chan_type_IIO_COUNT => Count,
+            ffi::iio_chan_type_IIO_INDEX => Index,
+            ffi::iio_chan_type_IIO_GRAVITY => Gravity,
+            _ => Unknown,
+        }
+    }
+}
+
 /// The format of a data sample.
 #[derive(Debug, Copy, Clone)]
 pub struct DataFormat {
@@ -159,6 +345,201 @@ impl DataFormat {
             }
         }
     }
+
+    /// Parses a data format from the type string used by the kernel for
+    /// scan elements, like "le:s12/16>>4".
+    ///
+    /// The string gives the endianness, the sign, the number of valid
+    /// bits, the storage bits, an optional repeat count, and the shift, as
+    /// `[be|le]:[s|u]<bits>/<storage>[X<repeat>]>><shift>`. An upper case
+    /// sign marks a fully defined sample, as in the C library. The scale
+    /// is not part of the string, so is left unset.
+    pub fn parse(s: &str) -> Result<Self> {
+        let err = || Error::General(format!("Invalid data format: '{}'", s));
+        let num = |tok: &str| tok.parse::<u32>().map_err(|_| err());
+
+        let (endian, rest) = s.trim().split_once(':').ok_or_else(err)?;
+        let is_be = match endian {
+            "be" => true,
+            "le" => false,
+            _ => return Err(err()),
+        };
+
+        let mut chars = rest.chars();
+        let sign = chars.next().ok_or_else(err)?;
+        if !matches!(sign, 's' | 'S' | 'u' | 'U') {
+            return Err(err());
+        }
+        let rest = chars.as_str();
+
+        let (sizes, shift) = rest.split_once(">>").ok_or_else(err)?;
+        let (bits, storage) = sizes.split_once('/').ok_or_else(err)?;
+        let (length, repeat) = match storage.split_once('X') {
+            Some((length, repeat)) => (num(length)?, num(repeat)?),
+            None => (num(storage)?, 1),
+        };
+        let bits = num(bits)?;
+        let shift = num(shift)?;
+
+        if length == 0 || bits > length || repeat == 0 {
+            return Err(err());
+        }
+
+        Ok(Self::new(ffi::iio_data_format {
+            length,
+            bits,
+            shift,
+            is_signed: matches!(sign, 's' | 'S'),
+            is_fully_defined: matches!(sign, 'S' | 'U') || bits == length,
+            is_be,
+            with_scale: false,
+            scale: 1.0,
+            repeat,
+        }))
+    }
+
+    /// Gets the data format as the type string used by the kernel for
+    /// scan elements, like "le:s12/16>>4".
+    ///
+    /// This is the inverse of [`DataFormat::parse()`].
+    pub fn to_type_string(&self) -> String {
+        let sign = match (
+            self.is_signed(),
+            self.is_fully_defined() && self.bits() != self.length(),
+        ) {
+            (true, false) => 's',
+            (true, true) => 'S',
+            (false, false) => 'u',
+            (false, true) => 'U',
+        };
+        let repeat = match self.repeat() {
+            0 | 1 => String::new(),
+            n => format!("X{}", n),
+        };
+        format!(
+            "{}:{}{}/{}{}>>{}",
+            if self.is_big_endian() { "be" } else { "le" },
+            sign,
+            self.bits(),
+            self.length(),
+            repeat,
+            self.shift()
+        )
+    }
+
+    /// Converts samples in place from the hardware format to the host
+    /// format, the same way as the C library.
+    ///
+    /// Each element of `length` bits is byte-swapped, shifted, and then
+    /// masked or sign extended to the valid bits. The length must be 8,
+    /// 16, 32, or 64 bits.
+    fn convert_bytes(&self, data: &mut [u8]) {
+        let n = (self.length() / 8) as usize;
+        for elem in data.chunks_exact_mut(n) {
+            let mut val = Self::read_elem(elem, self.is_big_endian());
+            val = val.checked_shr(self.shift()).unwrap_or(0);
+            let bits = self.bits();
+            if !self.is_fully_defined() && bits > 0 && bits < 64 {
+                let mask = (1u64 << bits) - 1;
+                val &= mask;
+                if self.is_signed() && val & (1 << (bits - 1)) != 0 {
+                    val |= !mask;
+                }
+            }
+            Self::write_elem(elem, val, cfg!(target_endian = "big"));
+        }
+    }
+
+    /// Converts samples in place from the host format to the hardware
+    /// format, the same way as the C library.
+    ///
+    /// This is the inverse of [`convert_bytes()`](DataFormat::convert_bytes).
+    fn convert_inverse_bytes(&self, data: &mut [u8]) {
+        let n = (self.length() / 8) as usize;
+        for elem in data.chunks_exact_mut(n) {
+            let mut val = Self::read_elem(elem, cfg!(target_endian = "big"));
+            let bits = self.bits();
+            if bits > 0 && bits < 64 {
+                val &= (1u64 << bits) - 1;
+            }
+            val = val.checked_shl(self.shift()).unwrap_or(0);
+            Self::write_elem(elem, val, self.is_big_endian());
+        }
+    }
+
+    /// Reads a sample element of up to 8 bytes as an integer.
+    fn read_elem(elem: &[u8], is_be: bool) -> u64 {
+        let n = elem.len();
+        let mut raw = [0u8; 8];
+        if is_be {
+            raw[8 - n..].copy_from_slice(elem);
+            u64::from_be_bytes(raw)
+        }
+        else {
+            raw[..n].copy_from_slice(elem);
+            u64::from_le_bytes(raw)
+        }
+    }
+
+    /// Writes the low bytes of an integer into a sample element of up to
+    /// 8 bytes.
+    fn write_elem(elem: &mut [u8], val: u64, is_be: bool) {
+        let n = elem.len();
+        if is_be {
+            elem.copy_from_slice(&val.to_be_bytes()[8 - n..]);
+        }
+        else {
+            elem.copy_from_slice(&val.to_le_bytes()[..n]);
+        }
+    }
+}
+
+impl FromStr for DataFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for DataFormat {
+    /// Displays the data format as its kernel type string.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_type_string())
+    }
+}
+
+/// A data format is serialized as its kernel type string.
+///
+/// The scale, which isn't part of the string, is not included.
+#[cfg(feature = "serde")]
+impl serde::Serialize for DataFormat {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> std::result::Result<S::Ok, S::Error> {
+        ser.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DataFormat {
+    fn deserialize<D: serde::Deserializer<'de>>(de: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(de)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl PartialEq for DataFormat {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (&self.data_fmt, &other.data_fmt);
+        a.length == b.length
+            && a.bits == b.bits
+            && a.shift == b.shift
+            && a.is_signed == b.is_signed
+            && a.is_fully_defined == b.is_fully_defined
+            && a.is_be == b.is_be
+            && a.with_scale == b.with_scale
+            && a.scale == b.scale
+            && a.repeat == b.repeat
+    }
 }
 
 /// An Industrial I/O Device Channel
@@ -166,6 +547,8 @@ impl DataFormat {
 pub struct Channel {
     /// Pointer to the underlying IIO channel object
     pub(crate) chan: *mut ffi::iio_channel,
+    /// A data format overriding the one reported by the driver
+    pub(crate) fmt: Option<DataFormat>,
     #[allow(dead_code)]
     /// Holder for the Device's lifetime for libiio safety.
     pub(crate) ctx: Context,
@@ -231,7 +614,8 @@ impl Channel {
     }
 
     /// Determines if the channel has the specified attribute.
-    pub fn has_attr(&self, attr: &str) -> bool {
+    pub fn has_attr(&self, attr: impl AsRef<str>) -> bool {
+        let attr = attr.as_ref();
         let attr = cstring_or_bail_false!(attr);
         unsafe { !ffi::iio_channel_find_attr(self.chan, attr.as_ptr()).is_null() }
     }
@@ -249,24 +633,71 @@ impl Channel {
         cstring_opt(pstr)
     }
 
+    /// Resolves an attribute name to the form reported by the backend.
+    ///
+    /// Some backends report the fully-qualified sysfs names for channel
+    /// attributes rather than the short ones. This tries the name as
+    /// given, then the type-qualified form (like `in_voltage_scale`), then
+    /// the channel-qualified form (like `in_voltage0_scale`). If given a
+    /// qualified name, it also tries the short one. It returns the name
+    /// that the channel actually has, if any.
+    pub fn resolve_attr(&self, name: &str) -> Option<String> {
+        let id = self.id().unwrap_or_default();
+        attr_candidates(name, &id, self.is_output())
+            .iter()
+            .find_map(|attr| self.find_attr(attr))
+    }
+
     /// Reads a channel-specific attribute
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read<T: FromAttribute>(&self, attr: &str) -> Result<T> {
+    pub fn attr_read<T: FromAttribute>(&self, attr: impl AsRef<str>) -> Result<T> {
+        let attr = attr.as_ref();
         let sval = self.attr_read_str(attr)?;
         T::from_attr(&sval)
     }
 
+    /// Reads a channel-specific attribute, giving up at the deadline.
+    ///
+    /// The deadline is checked on the Rust side, so this never changes the
+    /// I/O timeout of the context, which other threads may be relying on.
+    /// It fails with `ETIMEDOUT` without reading if the deadline has
+    /// already passed, and reports a read that completes after the
+    /// deadline as `ETIMEDOUT` too, discarding the value.
+    ///
+    /// The read itself is still bounded only by the context timeout. To
+    /// keep an interactive application from freezing on a sensor that is
+    /// not responding, read from a separate context with a short timeout,
+    /// such as one from a [`ContextPool`](crate::ContextPool).
+    ///
+    /// `attr` The name of the attribute
+    /// `deadline` The time by which the read must complete
+    pub fn attr_read_with_deadline<T: FromAttribute>(
+        &self,
+        attr: impl AsRef<str>,
+        deadline: Instant,
+    ) -> Result<T> {
+        if Instant::now() >= deadline {
+            return Err(Error::Nix(Errno::ETIMEDOUT));
+        }
+        let val = self.attr_read(attr)?;
+        if Instant::now() > deadline {
+            return Err(Error::Nix(Errno::ETIMEDOUT));
+        }
+        Ok(val)
+    }
+
     /// Reads a channel-specific attribute as a string
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read_str(&self, attr: &str) -> Result<String> {
-        let mut buf = vec![0 as c_char; ATTR_BUF_SIZE];
-        let attr = CString::new(attr)?;
+    pub fn attr_read_str(&self, attr: impl AsRef<str>) -> Result<String> {
+        let attr = attr.as_ref();
+        let mut buf = vec![0 as c_char; self.ctx.attr_buf_size()];
+        let cattr = CString::new(attr)?;
         let ret = unsafe {
-            ffi::iio_channel_attr_read(self.chan, attr.as_ptr(), buf.as_mut_ptr(), buf.len())
+            ffi::iio_channel_attr_read(self.chan, cattr.as_ptr(), buf.as_mut_ptr(), buf.len())
         };
-        sys_result(ret as i32, ())?;
+        self.attr_result(attr, ret as i32, ())?;
         let s = unsafe {
             CStr::from_ptr(buf.as_ptr())
                 .to_str()
@@ -275,34 +706,85 @@ impl Channel {
         Ok(s.into())
     }
 
+    /// Gets a reader for the value of a channel-specific attribute.
+    ///
+    /// This is for attributes that may be too large to read as a string,
+    /// like calibration blobs. See the [`attr_reader`](crate::attr_reader)
+    /// module.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_reader(&self, attr: impl AsRef<str>) -> Result<AttrReader> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
+        let fname = unsafe { ffi::iio_channel_attr_get_filename(self.chan, cattr.as_ptr()) };
+        let fname = cstring_opt(fname).ok_or_else(|| self.attr_err(attr, Errno::ENOENT.into()))?;
+
+        if let (true, Some(id)) = (self.ctx.name() == "local", self.device_id()) {
+            let path = Path::new(SYSFS_DEVICES).join(id).join(fname);
+            if let Ok(file) = File::open(path) {
+                return Ok(AttrReader::from_file(file));
+            }
+        }
+        let data = read_growing(self.ctx.attr_buf_size(), |buf| unsafe {
+            ffi::iio_channel_attr_read(self.chan, cattr.as_ptr(), buf.as_mut_ptr(), buf.len())
+        })?;
+        Ok(AttrReader::from_vec(data))
+    }
+
     /// Reads a channel-specific attribute as a boolean
     /// `attr` The name of the attribute
-    pub fn attr_read_bool(&self, attr: &str) -> Result<bool> {
+    pub fn attr_read_bool(&self, attr: impl AsRef<str>) -> Result<bool> {
+        let attr = attr.as_ref();
         let mut val: bool = false;
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_channel_attr_read_bool(self.chan, attr.as_ptr(), &mut val) };
-        sys_result(ret, val)
+        let cattr = CString::new(attr)?;
+        let ret = unsafe { ffi::iio_channel_attr_read_bool(self.chan, cattr.as_ptr(), &mut val) };
+        self.attr_result(attr, ret, val)
     }
 
     /// Reads a channel-specific attribute as an integer (i64)
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read_int(&self, attr: &str) -> Result<i64> {
+    pub fn attr_read_int(&self, attr: impl AsRef<str>) -> Result<i64> {
+        let attr = attr.as_ref();
         let mut val: c_longlong = 0;
-        let attr = CString::new(attr)?;
+        let cattr = CString::new(attr)?;
         let ret =
-            unsafe { ffi::iio_channel_attr_read_longlong(self.chan, attr.as_ptr(), &mut val) };
-        sys_result(ret, val as i64)
+            unsafe { ffi::iio_channel_attr_read_longlong(self.chan, cattr.as_ptr(), &mut val) };
+        self.attr_result(attr, ret, val as i64)
     }
 
     /// Reads a channel-specific attribute as a floating-point (f64) number
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read_float(&self, attr: &str) -> Result<f64> {
+    pub fn attr_read_float(&self, attr: impl AsRef<str>) -> Result<f64> {
+        let attr = attr.as_ref();
         let mut val: f64 = 0.0;
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_channel_attr_read_double(self.chan, attr.as_ptr(), &mut val) };
-        sys_result(ret, val)
+        let cattr = CString::new(attr)?;
+        let ret = unsafe { ffi::iio_channel_attr_read_double(self.chan, cattr.as_ptr(), &mut val) };
+        self.attr_result(attr, ret, val)
+    }
+
+    /// Reads a time-like channel-specific attribute as a duration
+    ///
+    /// The value is converted from the units of the attribute, found by
+    /// name with [`units::attr_unit()`]. This fails if the attribute
+    /// doesn't have known units of time.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_duration(&self, attr: impl AsRef<str>) -> Result<Duration> {
+        let attr = attr.as_ref();
+        units::duration_from_attr(attr, self.attr_read_float(attr)?)
+    }
+
+    /// Reads a frequency channel-specific attribute
+    ///
+    /// This fails if the attribute doesn't have known units of frequency.
+    /// See [`units::attr_unit()`].
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_freq(&self, attr: impl AsRef<str>) -> Result<Hertz> {
+        let attr = attr.as_ref();
+        units::hertz_from_attr(attr, self.attr_read_float(attr)?)
     }
 
     // Callback from the C lib to extract the collection of all
@@ -326,6 +808,17 @@ impl Channel {
         0
     }
 
+    /// Reads a channel-specific attribute as a list of values
+    ///
+    /// The values can be separated by whitespace or commas. An error
+    /// reports the first value that couldn't be parsed.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_vec<T: FromAttribute>(&self, attr: impl AsRef<str>) -> Result<Vec<T>> {
+        let attr = attr.as_ref();
+        parse_attr_list(&self.attr_read_str(attr)?)
+    }
+
     /// Reads all the channel-specific attributes.
     /// This is especially useful when using the network backend to
     /// retrieve all the attributes with a single call.
@@ -338,11 +831,80 @@ impl Channel {
         sys_result(ret, map)
     }
 
+    // Callback from the C lib to get the values for a collection of
+    // channel-specific attributes. See attr_write_all().
+    unsafe extern "C" fn attr_write_all_cb(
+        _chan: *mut ffi::iio_channel,
+        attr: *const c_char,
+        buf: *mut c_void,
+        len: usize,
+        pmap: *mut c_void,
+    ) -> isize {
+        attr_write_all_value(attr, buf, len, pmap)
+    }
+
+    /// Writes a set of channel-specific attributes in a single operation.
+    ///
+    /// This is especially useful when using the network backend to
+    /// set all the attributes with a single call. It fails, without writing
+    /// anything, if any of the attributes doesn't exist.
+    pub fn attr_write_all(&self, vals: &HashMap<String, String>) -> Result<()> {
+        for (attr, val) in vals {
+            if !self.has_attr(attr) {
+                return Err(self.attr_err(attr, Errno::ENOENT.into()));
+            }
+            CString::new(val.as_str())?;
+        }
+        self.ctx.journaled_all(
+            || AttrTarget {
+                kind: AttrKind::Channel,
+                device: self.device_id().unwrap_or_default(),
+                channel: self.id(),
+            },
+            vals,
+            || self.attr_read_all().unwrap_or_default(),
+            |attr, val| self.check_write(attr, val),
+            || {
+                let pmap = (vals as *const HashMap<_, _>).cast_mut().cast();
+                let ret = unsafe {
+                    ffi::iio_channel_attr_write_all(
+                        self.chan,
+                        Some(Channel::attr_write_all_cb),
+                        pmap,
+                    )
+                };
+                sys_result(ret, ())
+            },
+        )
+    }
+
+    /// Gets an iterator over the names and values of the channel-specific
+    /// attributes.
+    ///
+    /// With the network backend, the values are all fetched up front with
+    /// a single call to [`attr_read_all()`](Self::attr_read_all). Otherwise
+    /// each one is read as the iterator reaches it.
+    pub fn attrs_with_values(&self) -> impl Iterator<Item = (String, Result<String>)> + '_ {
+        let mut vals = if self.ctx.name() == "network" {
+            self.attr_read_all().unwrap_or_default()
+        }
+        else {
+            HashMap::new()
+        };
+        self.attrs().map(move |name| {
+            let val = vals
+                .remove(&name)
+                .map_or_else(|| self.attr_read_str(&name), Ok);
+            (name, val)
+        })
+    }
+
     /// Writes a channel-specific attribute
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write<T: ToAttribute>(&self, attr: &str, val: T) -> Result<()> {
+    pub fn attr_write<T: ToAttribute>(&self, attr: impl AsRef<str>, val: T) -> Result<()> {
+        let attr = attr.as_ref();
         let sval = T::to_attr(&val)?;
         self.attr_write_str(attr, &sval)
     }
@@ -351,41 +913,172 @@ impl Channel {
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_str(&self, attr: &str, val: &str) -> Result<()> {
-        let attr = CString::new(attr)?;
+    pub fn attr_write_str(&self, attr: impl AsRef<str>, val: &str) -> Result<()> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
         let sval = CString::new(val)?;
-        let ret = unsafe { ffi::iio_channel_attr_write(self.chan, attr.as_ptr(), sval.as_ptr()) };
-        sys_result(ret as i32, ())
+        self.journaled(attr, val, || {
+            let ret =
+                unsafe { ffi::iio_channel_attr_write(self.chan, cattr.as_ptr(), sval.as_ptr()) };
+            self.attr_result(attr, ret as i32, ())
+        })
+    }
+
+    /// Gets the values available for a channel-specific attribute, from its
+    /// `<attr>_available` companion attribute, if it has one.
+    pub fn attr_available(&self, attr: impl AsRef<str>) -> Result<Option<Available>> {
+        let attr = attr.as_ref();
+        let avail = Available::attr_name(attr);
+        if !self.has_attr(&avail) {
+            return Ok(None);
+        }
+        self.attr_read_str(&avail)?.parse().map(Some)
+    }
+
+    /// Writes a channel-specific attribute, after checking the value against
+    /// the values available for it.
+    ///
+    /// If the attribute doesn't have an `<attr>_available` companion, the
+    /// value is written without a check.
+    pub fn validated_write<T: ToAttribute>(&self, attr: impl AsRef<str>, val: T) -> Result<()> {
+        let attr = attr.as_ref();
+        let sval = T::to_attr(&val)?;
+        if let Some(avail) = self.attr_available(attr)? {
+            avail.check(&sval)?;
+        }
+        self.attr_write_str(attr, &sval)
     }
 
     /// Writes a channel-specific attribute as a boolean
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_bool(&self, attr: &str, val: bool) -> Result<()> {
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_channel_attr_write_bool(self.chan, attr.as_ptr(), val) };
-        sys_result(ret, ())
+    pub fn attr_write_bool(&self, attr: impl AsRef<str>, val: bool) -> Result<()> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
+        self.journaled(attr, if val { "1" } else { "0" }, || {
+            let ret = unsafe { ffi::iio_channel_attr_write_bool(self.chan, cattr.as_ptr(), val) };
+            self.attr_result(attr, ret, ())
+        })
     }
 
     /// Writes a channel-specific attribute as an integer (i64)
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_int(&self, attr: &str, val: i64) -> Result<()> {
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_channel_attr_write_longlong(self.chan, attr.as_ptr(), val) };
-        sys_result(ret, ())
+    pub fn attr_write_int(&self, attr: impl AsRef<str>, val: i64) -> Result<()> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
+        self.journaled(attr, &val.to_string(), || {
+            let ret =
+                unsafe { ffi::iio_channel_attr_write_longlong(self.chan, cattr.as_ptr(), val) };
+            self.attr_result(attr, ret, ())
+        })
     }
 
     /// Writes a channel-specific attribute as a floating-point (f64) number
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_float(&self, attr: &str, val: f64) -> Result<()> {
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_channel_attr_write_double(self.chan, attr.as_ptr(), val) };
-        sys_result(ret, ())
+    pub fn attr_write_float(&self, attr: impl AsRef<str>, val: f64) -> Result<()> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
+        self.journaled(attr, &val.to_string(), || {
+            let ret = unsafe { ffi::iio_channel_attr_write_double(self.chan, cattr.as_ptr(), val) };
+            self.attr_result(attr, ret, ())
+        })
+    }
+
+    /// Writes a time-like channel-specific attribute from a duration
+    ///
+    /// The value is converted to the units of the attribute. See
+    /// [`attr_read_duration()`](Self::attr_read_duration).
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_duration(&self, attr: impl AsRef<str>, val: Duration) -> Result<()> {
+        let attr = attr.as_ref();
+        self.attr_write_float(attr, units::duration_to_attr(attr, val)?)
+    }
+
+    /// Writes a frequency channel-specific attribute
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_freq(&self, attr: impl AsRef<str>, val: Hertz) -> Result<()> {
+        let attr = attr.as_ref();
+        self.attr_write_float(attr, units::hertz_to_attr(attr, val)?)
+    }
+
+    /// Writes a channel-specific attribute as a list of values, separated by
+    /// spaces
+    ///
+    /// `attr` The name of the attribute
+    /// `vals` The values to write
+    pub fn attr_write_vec<T: ToAttribute>(&self, attr: impl AsRef<str>, vals: &[T]) -> Result<()> {
+        let attr = attr.as_ref();
+        self.attr_write_str(attr, &format_attr_list(vals)?)
+    }
+
+    /// Performs a write of a channel attribute, recording it in the
+    /// journal of the context, if enabled.
+    fn journaled<W>(&self, attr: &str, val: &str, write: W) -> Result<()>
+    where
+        W: FnOnce() -> Result<()>,
+    {
+        self.ctx.journaled(
+            || AttrTarget {
+                kind: AttrKind::Channel,
+                device: self.device_id().unwrap_or_default(),
+                channel: self.id(),
+            },
+            attr,
+            val,
+            || self.attr_read_str(attr).ok(),
+            || self.check_write(attr, val),
+            write,
+        )
+    }
+
+    /// Checks that a write of a channel attribute would be accepted, for a
+    /// dry run.
+    fn check_write(&self, attr: &str, val: &str) -> Result<()> {
+        if !self.has_attr(attr) {
+            return Err(self.attr_err(attr, Errno::ENOENT.into()));
+        }
+        if let Some(avail) = self.attr_available(attr)? {
+            avail.check(val)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the attribute, device, and channel to an error from the C
+    /// library.
+    ///
+    /// See [`Error::Attr`].
+    fn attr_err(&self, attr: &str, err: Error) -> Error {
+        err.with_attr(attr, self.device_id().unwrap_or_default(), self.id())
+    }
+
+    /// Converts the return value of an attribute operation in the C
+    /// library to a result, with the attribute, device, and channel in any
+    /// error.
+    fn attr_result<T>(&self, attr: &str, ret: i32, result: T) -> Result<T> {
+        sys_result(ret, result).map_err(|err| self.attr_err(attr, err))
+    }
+
+    /// Gets the ID of the device that the channel belongs to.
+    pub(crate) fn device_id(&self) -> Option<String> {
+        let pstr = unsafe { ffi::iio_device_get_id(ffi::iio_channel_get_device(self.chan)) };
+        cstring_opt(pstr)
+    }
+
+    /// Gets the device that the channel belongs to.
+    pub fn device(&self) -> Device {
+        Device {
+            dev: unsafe { ffi::iio_channel_get_device(self.chan) } as *mut ffi::iio_device,
+            ctx: self.ctx.clone(),
+        }
     }
 
     /// Gets an iterator for the attributes of the channel
@@ -415,12 +1108,63 @@ impl Channel {
 
     /// Gets the data format for the channel
     pub fn data_format(&self) -> DataFormat {
+        if let Some(fmt) = self.fmt {
+            return fmt;
+        }
         unsafe {
             let pfmt = ffi::iio_channel_get_data_format(self.chan);
             DataFormat::new(*pfmt)
         }
     }
 
+    /// Overrides the data format for the channel.
+    ///
+    /// Some drivers misreport the shift or sign of their samples. This
+    /// returns a handle to the channel that uses the corrected format for
+    /// [`data_format()`](Channel::data_format) and all of the conversions,
+    /// typed reads and writes, doing them in Rust rather than in the C
+    /// library. The override belongs to the handle and its clones; other
+    /// handles to the same channel, like a new one from
+    /// [`Device::find_channel()`], still get the format from the driver.
+    ///
+    /// The storage length and repeat count of the new format must match
+    /// those reported by the driver, since they determine the layout of
+    /// the samples in a buffer, and the length must be 8, 16, 32, or 64
+    /// bits. The scale of the channel is kept unless the new format has
+    /// one.
+    ///
+    /// ```no_run
+    /// # use industrial_io as iio;
+    /// let ctx = iio::Context::new().unwrap();
+    /// let dev = ctx.find_device("ads1015").unwrap();
+    /// let chan = dev
+    ///     .find_input_channel("voltage0")
+    ///     .unwrap()
+    ///     .with_data_format("le:s12/16>>4".parse().unwrap())
+    ///     .unwrap();
+    /// ```
+    pub fn with_data_format(mut self, fmt: DataFormat) -> Result<Self> {
+        let cur = self.data_format();
+        if fmt.length() != cur.length() || fmt.repeat() != cur.repeat() {
+            return Err(Error::General(format!(
+                "Data format '{}' doesn't match the layout of '{}'",
+                fmt, cur
+            )));
+        }
+
+        if !matches!(fmt.length(), 8 | 16 | 32 | 64) {
+            return Err(Error::WrongDataType);
+        }
+
+        let mut data_fmt = fmt.data_fmt;
+        if !data_fmt.with_scale {
+            data_fmt.with_scale = cur.data_fmt.with_scale;
+            data_fmt.scale = cur.data_fmt.scale;
+        }
+        self.fmt = Some(DataFormat::new(data_fmt));
+        Ok(self)
+    }
+
     /// Gets the `TypeId` for a single sample from the channel.
     ///
     /// This will get the `TypeId` for a sample if it can fit into a standard
@@ -430,6 +1174,16 @@ impl Channel {
         dfmt.type_of()
     }
 
+    /// Checks that the samples of the channel are of type `T`.
+    pub(crate) fn check_type<T: 'static>(&self) -> Result<()> {
+        if self.type_of() == Some(TypeId::of::<T>()) {
+            Ok(())
+        }
+        else {
+            Err(Error::WrongDataType)
+        }
+    }
+
     /// Gets the type of data associated with the channel
     pub fn channel_type(&self) -> ChannelType {
         // TODO: We're trusting that the lib returns a valid enum.
@@ -439,6 +1193,22 @@ impl Channel {
         }
     }
 
+    /// Gets the modifier of the channel, as its code in the kernel ABI.
+    ///
+    /// This is zero if the channel isn't modified, or a code like 1 for
+    /// the X axis of an accelerometer.
+    pub fn modifier(&self) -> u32 {
+        unsafe { ffi::iio_channel_get_modifier(self.chan) as u32 }
+    }
+
+    /// Gets the modifier of the channel.
+    ///
+    /// This is `Unmodified` if the channel isn't modified, or a value like
+    /// `X` for the X axis of an accelerometer.
+    pub fn channel_modifier(&self) -> ChannelModifier {
+        ChannelModifier::from_raw(self.modifier())
+    }
+
     /// Converts a single sample from the hardware format to the host format.
     ///
     /// To be properly converted, the value must be the same type as that of
@@ -448,19 +1218,42 @@ impl Channel {
     where
         T: Copy + 'static,
     {
-        let mut retval = val;
         if self.type_of() == Some(TypeId::of::<T>()) {
-            unsafe {
-                ffi::iio_channel_convert(
-                    self.chan,
-                    (&mut retval as *mut T).cast(),
-                    (&val as *const T).cast(),
-                );
-            }
+            self.convert_unchecked(val)
+        }
+        else {
+            val
+        }
+    }
+
+    /// Converts a single sample from the hardware format to the host
+    /// format, without checking the type.
+    ///
+    /// The caller must ensure that the value is the same type as that of
+    /// the channel.
+    pub(crate) fn convert_unchecked<T: Copy>(&self, val: T) -> T {
+        let mut retval = val;
+        unsafe {
+            self.convert_ptr((&mut retval as *mut T).cast(), (&val as *const T).cast());
         }
         retval
     }
 
+    /// Converts the sample at `src` from the hardware format to the host
+    /// format at `dst`, with the overriding data format, if any.
+    ///
+    /// The caller must ensure that both point to a full sample.
+    unsafe fn convert_ptr(&self, dst: *mut u8, src: *const u8) {
+        match &self.fmt {
+            Some(fmt) => {
+                let n = fmt.byte_length();
+                ptr::copy(src, dst, n);
+                fmt.convert_bytes(slice::from_raw_parts_mut(dst, n));
+            }
+            None => ffi::iio_channel_convert(self.chan, dst.cast(), src.cast()),
+        }
+    }
+
     /// Converts a sample from the host format to the hardware format.
     ///
     /// To be properly converted, the value must be the same type as that of
@@ -470,34 +1263,95 @@ impl Channel {
     where
         T: Copy + 'static,
     {
-        let mut retval = val;
         if self.type_of() == Some(TypeId::of::<T>()) {
-            unsafe {
-                ffi::iio_channel_convert_inverse(
-                    self.chan,
-                    (&mut retval as *mut T).cast(),
-                    (&val as *const T).cast(),
-                );
-            }
+            self.convert_inverse_unchecked(val)
+        }
+        else {
+            val
+        }
+    }
+
+    /// Converts a sample from the host format to the hardware format,
+    /// without checking the type.
+    ///
+    /// The caller must ensure that the value is the same type as that of
+    /// the channel.
+    pub(crate) fn convert_inverse_unchecked<T: Copy>(&self, val: T) -> T {
+        let mut retval = val;
+        unsafe {
+            self.convert_inverse_ptr((&mut retval as *mut T).cast(), (&val as *const T).cast());
         }
         retval
     }
 
+    /// Converts the sample at `src` from the host format to the hardware
+    /// format at `dst`, with the overriding data format, if any.
+    ///
+    /// The caller must ensure that both point to a full sample.
+    unsafe fn convert_inverse_ptr(&self, dst: *mut u8, src: *const u8) {
+        match &self.fmt {
+            Some(fmt) => {
+                let n = fmt.byte_length();
+                ptr::copy(src, dst, n);
+                fmt.convert_inverse_bytes(slice::from_raw_parts_mut(dst, n));
+            }
+            None => ffi::iio_channel_convert_inverse(self.chan, dst.cast(), src.cast()),
+        }
+    }
+
     /// Demultiplex and convert the samples of a given channel.
     pub fn read<T>(&self, buf: &Buffer) -> Result<Vec<T>>
     where
         T: Default + Copy + 'static,
     {
-        if self.type_of() != Some(TypeId::of::<T>()) {
-            return Err(Error::WrongDataType);
-        }
+        self.check_type::<T>()?;
+        buf.check_layout::<T>(self)?;
+        self.read_unchecked(buf)
+    }
+
+    /// Demultiplex and convert the samples of a given channel into a
+    /// vector from a pool.
+    ///
+    /// This is the same as [`read()`](Channel::read), but the vector is
+    /// returned to the pool when it's dropped, and reused by a later read,
+    /// so that a long-running capture loop doesn't allocate on each
+    /// refill.
+    pub fn read_pooled<T>(&self, buf: &Buffer, pool: &SamplePool<T>) -> Result<PooledVec<T>>
+    where
+        T: Default + Copy + 'static,
+    {
+        self.check_type::<T>()?;
+        buf.check_layout::<T>(self)?;
+        let mut v = pool.get(buf.capacity());
+        self.read_unchecked_into(buf, &mut v)?;
+        Ok(v)
+    }
 
+    /// Demultiplex and convert the samples of a given channel, without
+    /// checking the type.
+    pub(crate) fn read_unchecked<T: Default + Copy>(&self, buf: &Buffer) -> Result<Vec<T>> {
+        let mut v = Vec::new();
+        self.read_unchecked_into(buf, &mut v)?;
+        Ok(v)
+    }
+
+    /// Demultiplex and convert the samples of a given channel into an
+    /// existing vector, replacing its contents, without checking the type.
+    fn read_unchecked_into<T: Default + Copy>(&self, buf: &Buffer, v: &mut Vec<T>) -> Result<()> {
         let n = buf.capacity();
         let sz_item = size_of::<T>();
         let sz_in = n * sz_item;
 
-        let mut v = vec![T::default(); n];
-        let sz = unsafe { ffi::iio_channel_read(self.chan, buf.buf, v.as_mut_ptr().cast(), sz_in) };
+        v.clear();
+        v.resize(n, T::default());
+        let sz = unsafe {
+            match &self.fmt {
+                Some(_) => {
+                    ffi::iio_channel_read_raw(self.chan, buf.buf, v.as_mut_ptr().cast(), sz_in)
+                }
+                None => ffi::iio_channel_read(self.chan, buf.buf, v.as_mut_ptr().cast(), sz_in),
+            }
+        };
 
         if sz > sz_in {
             return Err(Error::BadReturnSize); // This should never happen.
@@ -506,18 +1360,170 @@ impl Channel {
         if sz < sz_in {
             v.truncate(sz / sz_item);
         }
+        if let Some(fmt) = &self.fmt {
+            let data = unsafe { slice::from_raw_parts_mut(v.as_mut_ptr().cast(), sz) };
+            fmt.convert_bytes(data);
+        }
+        Ok(())
+    }
+
+    /// Demultiplex and convert the samples of a given channel into
+    /// floating-point values, regardless of the sample type.
+    ///
+    /// The values are converted to the host format, but not scaled.
+    pub(crate) fn read_f64(&self, buf: &Buffer) -> Result<Vec<f64>> {
+        let mut v = Vec::with_capacity(buf.capacity());
+        self.read_f64_into(buf, &mut v)?;
         Ok(v)
     }
 
+    /// Demultiplex and convert the samples of a given channel into an
+    /// existing vector, replacing its contents.
+    ///
+    /// This allows the caller to reuse the memory of the vector.
+    ///
+    /// This fails with [`Error::WrongDataType`] for channels with samples
+    /// that don't fit into a standard integer type, or that repeat, and
+    /// with [`Error::LayoutMismatch`] if the channel can't be accessed in
+    /// the buffer.
+    pub(crate) fn read_f64_into(&self, buf: &Buffer, v: &mut Vec<f64>) -> Result<()> {
+        let fmt = self.data_format();
+        let nbytes = f64_sample_size(&fmt)?;
+        match nbytes {
+            1 => buf.check_layout::<u8>(self)?,
+            2 => buf.check_layout::<u16>(self)?,
+            4 => buf.check_layout::<u32>(self)?,
+            _ => buf.check_layout::<u64>(self)?,
+        }
+        let signed = fmt.is_signed();
+
+        v.clear();
+        unsafe {
+            let mut p = ffi::iio_buffer_first(buf.buf, self.chan) as *const u8;
+            let end = ffi::iio_buffer_end(buf.buf) as *const u8;
+            let step = ffi::iio_buffer_step(buf.buf);
+            if step <= 0 {
+                return Err(Error::BadReturnSize);
+            }
+
+            while p < end {
+                v.push(self.convert_ptr_f64(p, nbytes, signed));
+                p = p.offset(step);
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert and multiplex floating-point values into the samples of a
+    /// given channel, regardless of the sample type.
+    ///
+    /// The values are converted from the host format, but not scaled.
+    /// They're truncated toward zero, and saturate at the limits of the
+    /// sample type. Returns the number of items written.
+    pub(crate) fn write_f64(&self, buf: &Buffer, data: &[f64]) -> Result<usize> {
+        let fmt = self.data_format();
+        let vals = data.iter().copied();
+        match (f64_sample_size(&fmt)?, fmt.is_signed()) {
+            (1, true) => self.write_from_iter(buf, vals.map(|x| x as i8)),
+            (1, false) => self.write_from_iter(buf, vals.map(|x| x as u8)),
+            (2, true) => self.write_from_iter(buf, vals.map(|x| x as i16)),
+            (2, false) => self.write_from_iter(buf, vals.map(|x| x as u16)),
+            (4, true) => self.write_from_iter(buf, vals.map(|x| x as i32)),
+            (4, false) => self.write_from_iter(buf, vals.map(|x| x as u32)),
+            (8, true) => self.write_from_iter(buf, vals.map(|x| x as i64)),
+            (8, false) => self.write_from_iter(buf, vals.map(|x| x as u64)),
+            _ => Err(Error::WrongDataType),
+        }
+    }
+
+    /// Converts a single sample in the hardware format, from a slice of
+    /// raw bytes, into a floating-point value.
+    ///
+    /// The value is converted to the host format, but not scaled.
+    pub(crate) fn convert_f64(&self, sample: &[u8]) -> Result<f64> {
+        let fmt = self.data_format();
+        let nbytes = f64_sample_size(&fmt)?;
+        if sample.len() < nbytes {
+            return Err(Error::BadReturnSize);
+        }
+        Ok(unsafe { self.convert_ptr_f64(sample.as_ptr(), nbytes, fmt.is_signed()) })
+    }
+
+    /// Converts the sample at `p` into a floating-point value.
+    ///
+    /// The caller must ensure that `p` points to at least `nbytes` of
+    /// valid data.
+    unsafe fn convert_ptr_f64(&self, p: *const u8, nbytes: usize, signed: bool) -> f64 {
+        let mut raw = [0u8; 8];
+        self.convert_ptr(raw.as_mut_ptr(), p);
+        match (nbytes, signed) {
+            (1, true) => f64::from(raw[0] as i8),
+            (1, false) => f64::from(raw[0]),
+            (2, true) => f64::from(i16::from_ne_bytes([raw[0], raw[1]])),
+            (2, false) => f64::from(u16::from_ne_bytes([raw[0], raw[1]])),
+            (4, true) => f64::from(i32::from_ne_bytes([raw[0], raw[1], raw[2], raw[3]])),
+            (4, false) => f64::from(u32::from_ne_bytes([raw[0], raw[1], raw[2], raw[3]])),
+            (_, true) => i64::from_ne_bytes(raw) as f64,
+            (_, false) => u64::from_ne_bytes(raw) as f64,
+        }
+    }
+
+    /// Demultiplex and convert the samples of the channel into physical
+    /// values, in the ABI units of the channel type (millivolts,
+    /// milli-degrees Celsius, etc).
+    ///
+    /// This applies the `offset` and `scale` attributes of the channel to
+    /// each sample, as `(raw + offset) * scale`. The attributes are read
+    /// the first time they're needed, and cached in the context until an
+    /// attribute is written through it. Use
+    /// [`invalidate_scale()`](Channel::invalidate_scale) if they might be
+    /// changed elsewhere.
+    pub fn read_scaled(&self, buf: &Buffer) -> Result<Vec<f64>> {
+        let (offset, scale) = self.offset_scale();
+        let mut v = self.read_f64(buf)?;
+        for x in v.iter_mut() {
+            *x = units::scaled(*x, offset, scale);
+        }
+        Ok(v)
+    }
+
+    /// Gets the offset and scale of the channel, from the cache if they
+    /// were already read.
+    ///
+    /// These default to 0 and 1, respectively, if the channel doesn't
+    /// have the attribute.
+    pub fn offset_scale(&self) -> (f64, f64) {
+        let key = self.chan as usize;
+        if let Some(&vals) = self.ctx.scale_cache().get(&key) {
+            return vals;
+        }
+        let vals = (
+            self.attr_read_float(abi::chan::OFFSET).unwrap_or(0.0),
+            self.attr_read_float(abi::chan::SCALE).unwrap_or(1.0),
+        );
+        self.ctx.scale_cache().insert(key, vals);
+        vals
+    }
+
+    /// Clears the cached offset and scale of the channel, so that they are
+    /// read again the next time they're needed.
+    pub fn invalidate_scale(&self) {
+        self.ctx.scale_cache().remove(&(self.chan as usize));
+    }
+
     /// Demultiplex the samples of a given channel.
     pub fn read_raw<T>(&self, buf: &Buffer) -> Result<Vec<T>>
     where
         T: Default + Copy + 'static,
     {
-        if self.type_of() != Some(TypeId::of::<T>()) {
-            return Err(Error::WrongDataType);
-        }
+        self.check_type::<T>()?;
+        buf.check_layout::<T>(self)?;
+        self.read_raw_unchecked(buf)
+    }
 
+    /// Demultiplex the samples of a given channel, without checking the
+    /// type.
+    pub(crate) fn read_raw_unchecked<T: Default + Copy>(&self, buf: &Buffer) -> Result<Vec<T>> {
         let n = buf.capacity();
         let sz_item = size_of::<T>();
         let sz_in = n * sz_item;
@@ -542,16 +1548,70 @@ impl Channel {
     where
         T: Default + Copy + 'static,
     {
-        if self.type_of() != Some(TypeId::of::<T>()) {
-            return Err(Error::WrongDataType);
-        }
+        self.check_type::<T>()?;
+        buf.check_layout::<T>(self)?;
+        Ok(self.write_unchecked(buf, data))
+    }
 
+    /// Convert and multiplex the samples of a given channel, without
+    /// checking the type.
+    pub(crate) fn write_unchecked<T: Copy>(&self, buf: &Buffer, data: &[T]) -> usize {
         let sz_item = size_of::<T>();
         let sz_in = size_of_val(data);
 
-        let sz = unsafe { ffi::iio_channel_write(self.chan, buf.buf, data.as_ptr().cast(), sz_in) };
+        let sz = match &self.fmt {
+            Some(fmt) => {
+                let mut raw =
+                    unsafe { slice::from_raw_parts(data.as_ptr().cast::<u8>(), sz_in) }.to_vec();
+                fmt.convert_inverse_bytes(&mut raw);
+                unsafe {
+                    ffi::iio_channel_write_raw(self.chan, buf.buf, raw.as_ptr().cast(), sz_in)
+                }
+            }
+            None => unsafe {
+                ffi::iio_channel_write(self.chan, buf.buf, data.as_ptr().cast(), sz_in)
+            },
+        };
+        sz / sz_item
+    }
 
-        Ok(sz / sz_item)
+    /// Convert and multiplex the samples of a given channel, taken
+    /// directly from an iterator.
+    ///
+    /// This fills the samples of the channel in the buffer, from the
+    /// start, until either the buffer is full or the iterator runs out, so
+    /// that generated waveform data doesn't need to be collected into a
+    /// slice first.
+    /// Returns the number of items written.
+    pub fn write_from_iter<T, I>(&self, buf: &Buffer, data: I) -> Result<usize>
+    where
+        T: Default + Copy + 'static,
+        I: IntoIterator<Item = T>,
+    {
+        self.check_type::<T>()?;
+        buf.check_layout::<T>(self)?;
+
+        let mut iter = data.into_iter();
+        let mut n = 0;
+        unsafe {
+            let mut p = ffi::iio_buffer_first(buf.buf, self.chan) as *mut u8;
+            let end = ffi::iio_buffer_end(buf.buf) as *mut u8;
+            let step = ffi::iio_buffer_step(buf.buf);
+            if step <= 0 {
+                return Err(Error::BadReturnSize);
+            }
+
+            while p < end {
+                let Some(val) = iter.next()
+                else {
+                    break;
+                };
+                self.convert_inverse_ptr(p, (&val as *const T).cast());
+                p = p.offset(step);
+                n += 1;
+            }
+        }
+        Ok(n)
     }
 
     /// Multiplex the samples of a given channel.
@@ -560,17 +1620,56 @@ impl Channel {
     where
         T: Default + Copy + 'static,
     {
-        if self.type_of() != Some(TypeId::of::<T>()) {
-            return Err(Error::WrongDataType);
-        }
+        self.check_type::<T>()?;
+        buf.check_layout::<T>(self)?;
+        Ok(self.write_raw_unchecked(buf, data))
+    }
 
+    /// Multiplex the samples of a given channel, without checking the type.
+    pub(crate) fn write_raw_unchecked<T: Copy>(&self, buf: &Buffer, data: &[T]) -> usize {
         let sz_item = size_of::<T>();
         let sz_in = size_of_val(data);
 
         let sz = unsafe { ffi::iio_channel_write(self.chan, buf.buf, data.as_ptr().cast(), sz_in) };
+        sz / sz_item
+    }
+}
 
-        Ok(sz / sz_item)
+/// Gets the size, in bytes, of a sample that can be converted to and from
+/// a floating-point value.
+///
+/// This fails with [`Error::WrongDataType`] if the sample doesn't fit into
+/// a standard integer type, or repeats, since each of the repeated
+/// values would need a value of its own.
+fn f64_sample_size(fmt: &DataFormat) -> Result<usize> {
+    let nbytes = fmt.byte_length();
+    if fmt.repeat() != 1 || !matches!(nbytes, 1 | 2 | 4 | 8) {
+        return Err(Error::WrongDataType);
     }
+    Ok(nbytes)
+}
+
+/// Gets the possible forms of a channel attribute name, in the order
+/// that they should be tried.
+fn attr_candidates(name: &str, id: &str, output: bool) -> Vec<String> {
+    let dir = if output { "out" } else { "in" };
+    let kind = id
+        .split(|c: char| c.is_ascii_digit() || c == '_' || c == '-')
+        .next()
+        .unwrap_or(id);
+
+    let prefixes = [format!("{}_{}_", dir, kind), format!("{}_{}_", dir, id)];
+
+    let mut names = vec![name.to_string()];
+    match prefixes
+        .iter()
+        .find_map(|pre| name.strip_prefix(pre.as_str()))
+    {
+        Some(short) => names.push(short.to_string()),
+        None => names.extend(prefixes.iter().map(|pre| format!("{}{}", pre, name))),
+    }
+    names.dedup();
+    names
 }
 
 impl PartialEq for Channel {
@@ -616,6 +1715,98 @@ impl Iterator for AttrIterator<'_> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn attr_names() {
+        assert_eq!(
+            attr_candidates("scale", "voltage0", false),
+            vec!["scale", "in_voltage_scale", "in_voltage0_scale"]
+        );
+        assert_eq!(
+            attr_candidates("raw", "accel_x", false),
+            vec!["raw", "in_accel_raw", "in_accel_x_raw"]
+        );
+        assert_eq!(
+            attr_candidates("in_voltage0_scale", "voltage0", false),
+            vec!["in_voltage0_scale", "scale"]
+        );
+        assert_eq!(
+            attr_candidates("raw", "altvoltage0", true),
+            vec!["raw", "out_altvoltage_raw", "out_altvoltage0_raw"]
+        );
+    }
+
+    #[test]
+    fn modifiers() {
+        assert_eq!(ChannelModifier::from_raw(0), ChannelModifier::Unmodified);
+        assert_eq!(ChannelModifier::from_raw(1), ChannelModifier::X);
+        assert_eq!(ChannelModifier::from_raw(33), ChannelModifier::Q);
+        assert_eq!(ChannelModifier::from_raw(50), ChannelModifier::Roll);
+        assert_eq!(ChannelModifier::from_raw(1000), ChannelModifier::Unknown);
+        assert_eq!(ChannelModifier::Pm2p5 as u32, 39);
+    }
+
+    #[test]
+    fn data_format_strings() {
+        let fmt = DataFormat::parse("le:s12/16>>4").unwrap();
+        assert_eq!(fmt.bits(), 12);
+        assert_eq!(fmt.length(), 16);
+        assert_eq!(fmt.shift(), 4);
+        assert_eq!(fmt.repeat(), 1);
+        assert!(fmt.is_signed());
+        assert!(!fmt.is_fully_defined());
+        assert!(!fmt.is_big_endian());
+        assert_eq!(fmt.byte_length(), 2);
+        assert_eq!(fmt.type_of(), Some(TypeId::of::<i16>()));
+
+        for s in [
+            "le:s12/16>>4",
+            "be:u16/16>>0",
+            "le:S24/32>>8",
+            "be:u8/8X2>>0",
+        ] {
+            let fmt: DataFormat = s.parse().unwrap();
+            assert_eq!(fmt.to_type_string(), s);
+            assert_eq!(DataFormat::parse(&fmt.to_string()).unwrap(), fmt);
+        }
+        assert_eq!(DataFormat::parse("be:u8/8X2>>0").unwrap().byte_length(), 2);
+
+        for s in [
+            "",
+            "le:s12/16",
+            "xe:s12/16>>4",
+            "le:x12/16>>4",
+            "le:s20/16>>0",
+        ] {
+            assert!(DataFormat::parse(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn data_format_conversion() {
+        let fmt = DataFormat::parse("le:s12/16>>4").unwrap();
+        let mut data = [0xf0, 0xff, 0x10, 0x00, 0xf0, 0x7f];
+        fmt.convert_bytes(&mut data);
+        let vals: Vec<_> = data
+            .chunks_exact(2)
+            .map(|b| i16::from_ne_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(vals, [-1, 1, 2047]);
+
+        fmt.convert_inverse_bytes(&mut data);
+        assert_eq!(data, [0xf0, 0xff, 0x10, 0x00, 0xf0, 0x7f]);
+
+        let fmt = DataFormat::parse("be:u10/16X2>>2").unwrap();
+        let mut data = [0x00, 0x04, 0xff, 0xff];
+        fmt.convert_bytes(&mut data);
+        assert_eq!(u16::from_ne_bytes([data[0], data[1]]), 1);
+        assert_eq!(u16::from_ne_bytes([data[2], data[3]]), 0x3ff);
+
+        let fmt = DataFormat::parse("le:S24/32>>8").unwrap();
+        let mut data = 0x1234_5678_u32.to_le_bytes();
+        fmt.convert_bytes(&mut data);
+        assert_eq!(u32::from_ne_bytes(data), 0x0012_3456);
+    }
+
     const DEV_ID: &str = "dummydev";
 
     // See that we get the default context.
@@ -631,4 +1822,39 @@ mod tests {
         let id_chan = dev.find_channel(&id, dir).unwrap();
         assert_eq!(id_chan, idx_chan);
     }
+
+    #[test]
+    fn override_data_format() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device(DEV_ID).unwrap();
+
+        let chan = dev.scan_elements().next().unwrap();
+        let orig = chan.data_format();
+        let fmt = DataFormat::new(ffi::iio_data_format {
+            shift: orig.shift() + 1,
+            is_signed: !orig.is_signed(),
+            ..orig.data_fmt
+        });
+
+        let chan = chan.with_data_format(fmt).unwrap();
+        assert_eq!(chan.data_format(), fmt);
+        assert_eq!(chan.clone().data_format(), fmt);
+
+        // The library's format for the channel is untouched
+        assert_eq!(
+            dev.find_channel(&chan.id().unwrap(), chan.direction())
+                .unwrap()
+                .data_format(),
+            orig
+        );
+
+        let bad = DataFormat::new(ffi::iio_data_format {
+            length: orig.length() * 2,
+            ..orig.data_fmt
+        });
+        assert!(chan.clone().with_data_format(bad).is_err());
+
+        let chan = chan.with_data_format(orig).unwrap();
+        assert_eq!(chan.data_format(), orig);
+    }
 }
@@ -0,0 +1,163 @@
+// industrial-io/src/codec.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Binary serialization of frames.
+//!
+//! With the **serde** feature, [`Frame`] and [`FrameSet`] implement
+//! `Serialize` and `Deserialize`. This module adds compact binary
+//! encodings for them, with a byte order that doesn't depend on the host,
+//! so that they can be sent over IPC or stored and replayed on another
+//! machine.
+//!
+//! * **bincode** feature - A dense encoding with fixed-size integers and
+//!   a selectable byte order.
+//! * **cbor** feature - The self-describing CBOR format (RFC 8949), which
+//!   is always big-endian.
+//!
+//! ```
+//! # #[cfg(feature = "bincode")] {
+//! use industrial_io::{codec::{self, Codec}, Frame};
+//!
+//! let frame = Frame { seq: 42, ..Frame::default() };
+//! let bytes = codec::encode(Codec::default(), &frame).unwrap();
+//! let decoded: Frame = codec::decode(Codec::default(), &bytes).unwrap();
+//! assert_eq!(decoded, frame);
+//! # }
+//! ```
+//!
+//! [`Frame`]: crate::Frame
+//! [`FrameSet`]: crate::FrameSet
+
+use crate::{Error, Result};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The byte order for encodings that support more than one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Endian {
+    /// Little-endian
+    #[default]
+    Little,
+    /// Big-endian
+    Big,
+}
+
+/// A binary encoding for frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// The bincode format with fixed-size integers in the byte order.
+    #[cfg(feature = "bincode")]
+    Bincode(Endian),
+    /// The CBOR format.
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl Default for Codec {
+    /// Gets the most compact codec that is available.
+    fn default() -> Self {
+        #[cfg(feature = "bincode")]
+        return Codec::Bincode(Endian::Little);
+        #[cfg(not(feature = "bincode"))]
+        return Codec::Cbor;
+    }
+}
+
+/// Encodes a value, such as a frame or frame set, into bytes.
+pub fn encode<T: Serialize>(codec: Codec, val: &T) -> Result<Vec<u8>> {
+    match codec {
+        #[cfg(feature = "bincode")]
+        Codec::Bincode(endian) => {
+            use bincode::Options;
+            let opts = bincode::DefaultOptions::new().with_fixint_encoding();
+            match endian {
+                Endian::Little => opts.with_little_endian().serialize(val),
+                Endian::Big => opts.with_big_endian().serialize(val),
+            }
+            .map_err(|err| Error::General(err.to_string()))
+        }
+        #[cfg(feature = "cbor")]
+        Codec::Cbor => {
+            let mut buf = Vec::new();
+            ciborium::ser::into_writer(val, &mut buf)
+                .map_err(|err| Error::General(err.to_string()))?;
+            Ok(buf)
+        }
+    }
+}
+
+/// Decodes a value, such as a frame or frame set, from bytes.
+pub fn decode<T: DeserializeOwned>(codec: Codec, buf: &[u8]) -> Result<T> {
+    match codec {
+        #[cfg(feature = "bincode")]
+        Codec::Bincode(endian) => {
+            use bincode::Options;
+            let opts = bincode::DefaultOptions::new().with_fixint_encoding();
+            match endian {
+                Endian::Little => opts.with_little_endian().deserialize(buf),
+                Endian::Big => opts.with_big_endian().deserialize(buf),
+            }
+            .map_err(|err| Error::General(err.to_string()))
+        }
+        #[cfg(feature = "cbor")]
+        Codec::Cbor => {
+            ciborium::de::from_reader(buf).map_err(|err| Error::General(err.to_string()))
+        }
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Frame, FrameChannel, FrameSet};
+
+    fn frames() -> FrameSet {
+        let mut frame = Frame {
+            seq: 1,
+            sample_rate: Some(1000.0),
+            channels: vec![FrameChannel::new("voltage0", vec![1.0, -2.5])],
+            ..Frame::default()
+        };
+        frame.attrs.insert("hardwaregain".into(), "10".into());
+        vec![frame.clone(), Frame { seq: 2, ..frame }]
+            .into_iter()
+            .collect()
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_round_trip() {
+        let set = frames();
+        let le = encode(Codec::Bincode(Endian::Little), &set).unwrap();
+        let be = encode(Codec::Bincode(Endian::Big), &set).unwrap();
+        assert_ne!(le, be);
+
+        // The set length is the first value
+        assert_eq!(le[0], 2);
+        assert_eq!(be[7], 2);
+
+        let set_le: FrameSet = decode(Codec::Bincode(Endian::Little), &le).unwrap();
+        let set_be: FrameSet = decode(Codec::Bincode(Endian::Big), &be).unwrap();
+        assert_eq!(set_le, set);
+        assert_eq!(set_be, set);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_round_trip() {
+        let set = frames();
+        let buf = encode(Codec::Cbor, &set).unwrap();
+        let decoded: FrameSet = decode(Codec::Cbor, &buf).unwrap();
+        assert_eq!(decoded, set);
+    }
+}
@@ -0,0 +1,211 @@
+// industrial-io/src/compat.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Version compatibility checks.
+//!
+//! Three different versions of _libiio_ can come into play in an
+//! application: the one that the Rust bindings were generated from
+//! (selected by a cargo feature), the library actually loaded at runtime,
+//! and the one running the backend of a context, such as a remote `iiod`
+//! server. When these disagree, the same code can behave differently
+//! against different targets.
+//!
+//! [`compatibility_report()`] compares the three and returns a list of
+//! warnings about any mismatches that might matter.
+
+use crate::{library_version, Context, Feature, Version};
+use std::fmt;
+
+/// The version of _libiio_ that the bindings were generated from.
+#[cfg(feature = "libiio_v0_25")]
+pub const BINDINGS_VERSION: Version = Version::new(0, 25);
+/// The version of _libiio_ that the bindings were generated from.
+#[cfg(feature = "libiio_v0_24")]
+pub const BINDINGS_VERSION: Version = Version::new(0, 24);
+/// The version of _libiio_ that the bindings were generated from.
+#[cfg(feature = "libiio_v0_23")]
+pub const BINDINGS_VERSION: Version = Version::new(0, 23);
+/// The version of _libiio_ that the bindings were generated from.
+#[cfg(feature = "libiio_v0_21")]
+pub const BINDINGS_VERSION: Version = Version::new(0, 21);
+/// The version of _libiio_ that the bindings were generated from.
+#[cfg(feature = "libiio_v0_19")]
+pub const BINDINGS_VERSION: Version = Version::new(0, 19);
+
+/// Features used by this crate that depend on the backend version.
+const BACKEND_FEATURES: &[Feature] = &[Feature::Labels];
+
+/// A warning about a version mismatch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// The library loaded at runtime is older than the bindings. Calls
+    /// into functions that the library lacks may fail or be missing.
+    LibraryOlderThanBindings {
+        /// The runtime library version
+        library: Version,
+        /// The bindings version
+        bindings: Version,
+    },
+    /// The library loaded at runtime is newer than the bindings. Newer
+    /// library features aren't available through this crate.
+    LibraryNewerThanBindings {
+        /// The runtime library version
+        library: Version,
+        /// The bindings version
+        bindings: Version,
+    },
+    /// The context backend is older than the local library, such as
+    /// with an older remote `iiod` server.
+    BackendOlderThanLibrary {
+        /// The context backend version
+        backend: Version,
+        /// The runtime library version
+        library: Version,
+    },
+    /// The context backend lacks a feature that the bindings can use.
+    MissingFeature {
+        /// The missing feature
+        feature: Feature,
+        /// The context backend version
+        backend: Version,
+        /// The first version that supports the feature
+        required: Version,
+    },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Warning::*;
+        match self {
+            LibraryOlderThanBindings { library, bindings } => write!(
+                f,
+                "libiio {}.{} is older than the bindings for {}.{}",
+                library.major, library.minor, bindings.major, bindings.minor
+            ),
+            LibraryNewerThanBindings { library, bindings } => write!(
+                f,
+                "libiio {}.{} is newer than the bindings for {}.{}",
+                library.major, library.minor, bindings.major, bindings.minor
+            ),
+            BackendOlderThanLibrary { backend, library } => write!(
+                f,
+                "backend {}.{} is older than the local libiio {}.{}",
+                backend.major, backend.minor, library.major, library.minor
+            ),
+            MissingFeature {
+                feature,
+                backend,
+                required,
+            } => write!(
+                f,
+                "backend {}.{} lacks {} (requires {}.{})",
+                backend.major, backend.minor, feature, required.major, required.minor
+            ),
+        }
+    }
+}
+
+/// Compares the bindings, runtime library, and context backend versions,
+/// returning warnings for any mismatches.
+///
+/// An empty report means that all three versions agree.
+pub fn compatibility_report(ctx: &Context) -> Vec<Warning> {
+    check(&library_version(), &BINDINGS_VERSION, &ctx.version())
+}
+
+/// Compares the versions, returning the warnings.
+///
+/// Only the major and minor numbers are compared; differing git tags
+/// aren't considered a mismatch.
+fn check(library: &Version, bindings: &Version, backend: &Version) -> Vec<Warning> {
+    let num = |v: &Version| (v.major, v.minor);
+    let mut warnings = Vec::new();
+
+    if num(library) < num(bindings) {
+        warnings.push(Warning::LibraryOlderThanBindings {
+            library: library.clone(),
+            bindings: bindings.clone(),
+        });
+    }
+    else if num(library) > num(bindings) {
+        warnings.push(Warning::LibraryNewerThanBindings {
+            library: library.clone(),
+            bindings: bindings.clone(),
+        });
+    }
+
+    if num(backend) < num(library) {
+        warnings.push(Warning::BackendOlderThanLibrary {
+            backend: backend.clone(),
+            library: library.clone(),
+        });
+    }
+
+    for &feature in BACKEND_FEATURES {
+        if bindings.supports(feature) && !backend.supports(feature) {
+            warnings.push(Warning::MissingFeature {
+                feature,
+                backend: backend.clone(),
+                required: feature.min_version(),
+            });
+        }
+    }
+    warnings
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_checks() {
+        assert!(check(
+            &Version::new(0, 25),
+            &Version::new(0, 25),
+            &Version::new(0, 25)
+        )
+        .is_empty());
+
+        let warnings = check(
+            &Version::new(0, 25),
+            &Version::new(0, 25),
+            &Version::new(0, 21),
+        );
+        assert_eq!(warnings.len(), 2);
+        assert!(matches!(
+            warnings[0],
+            Warning::BackendOlderThanLibrary { .. }
+        ));
+        assert!(matches!(
+            warnings[1],
+            Warning::MissingFeature {
+                feature: Feature::Labels,
+                ..
+            }
+        ));
+
+        let warnings = check(
+            &Version::new(0, 23),
+            &Version::new(0, 25),
+            &Version::new(0, 23),
+        );
+        assert_eq!(
+            warnings,
+            vec![Warning::LibraryOlderThanBindings {
+                library: Version::new(0, 23),
+                bindings: Version::new(0, 25),
+            }]
+        );
+    }
+}
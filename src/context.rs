@@ -11,13 +11,21 @@
 //! Industrial I/O Contexts.
 //!
 
-use crate::{cstring_opt, ffi, sys_result, Device, Error, Result, Version};
+#[cfg(not(feature = "libiio_v0_19"))]
+use crate::ScanContext;
+use crate::{
+    cstring_opt, ffi, journal::Recorder, sys_result, Device, DeviceIdentity, Error, Result, Version,
+};
 use nix::errno::Errno;
 use std::{
+    collections::HashMap,
     ffi::{CStr, CString},
     os::raw::{c_char, c_uint},
     ptr, slice, str,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, Weak,
+    },
     time::Duration,
 };
 
@@ -35,7 +43,20 @@ use std::{
 /// ergonomic by removing the need to manage the lifetime of the Context.
 #[derive(Debug, Clone)]
 pub struct Context {
-    inner: Arc<InnerContext>,
+    pub(crate) inner: Arc<InnerContext>,
+}
+
+/// A weak reference to an Industrial I/O Context.
+///
+/// This does not keep the underlying `iio_context` alive. It can be held
+/// by long-lived, cache-like structures (alias maps, metrics exporters,
+/// etc) that want to refer to a context without preventing it from being
+/// destroyed when the application is done with it. Use
+/// [`WeakContext::upgrade()`] to get a usable [`Context`] back, if it
+/// still exists.
+#[derive(Debug, Clone, Default)]
+pub struct WeakContext {
+    inner: Weak<InnerContext>,
 }
 
 /// Backends for I/O Contexts.
@@ -105,12 +126,221 @@ pub enum Backend<'a> {
     Local,
 }
 
+/// An owned version of a [`Backend`].
+///
+/// This holds the backend parameters as owned strings, so that a backend
+/// selection can be stored in a configuration struct, returned from a
+/// function, or parsed from a string.
+///
+/// It can be parsed from the URI-like forms used by _libiio_, such as
+/// "ip:192.168.2.1", "usb:3.32.5", "serial:/dev/ttyUSB0,115200",
+/// "xml:/home/user/file.xml", or "local:". An empty string, or "default",
+/// selects the default backend.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OwnedBackend {
+    /// The default backend. See [`Backend::Default`].
+    Default,
+    /// XML file backend. See [`Backend::Xml`].
+    Xml(String),
+    /// In-memory XML backend. See [`Backend::XmlMem`].
+    XmlMem(String),
+    /// Network backend. See [`Backend::Network`].
+    Network(String),
+    /// USB backend. See [`Backend::Usb`].
+    Usb(String),
+    /// Serial backend. See [`Backend::Serial`].
+    Serial(String),
+    /// A backend selected by URI. See [`Backend::Uri`].
+    Uri(String),
+    /// Local backend. See [`Backend::Local`].
+    #[cfg(target_os = "linux")]
+    Local,
+}
+
+impl OwnedBackend {
+    /// Gets a borrowed [`Backend`] that can be used to create a context.
+    pub fn as_backend(&self) -> Backend<'_> {
+        use OwnedBackend::*;
+        match self {
+            Default => Backend::Default,
+            Xml(s) => Backend::Xml(s),
+            XmlMem(s) => Backend::XmlMem(s),
+            Network(s) => Backend::Network(s),
+            Usb(s) => Backend::Usb(s),
+            Serial(s) => Backend::Serial(s),
+            Uri(s) => Backend::Uri(s),
+            #[cfg(target_os = "linux")]
+            Local => Backend::Local,
+        }
+    }
+}
+
+impl From<Backend<'_>> for OwnedBackend {
+    fn from(be: Backend<'_>) -> Self {
+        use OwnedBackend::*;
+        match be {
+            Backend::Default => Default,
+            Backend::Xml(s) => Xml(s.into()),
+            Backend::XmlMem(s) => XmlMem(s.into()),
+            Backend::Network(s) => Network(s.into()),
+            Backend::Usb(s) => Usb(s.into()),
+            Backend::Serial(s) => Serial(s.into()),
+            Backend::Uri(s) => Uri(s.into()),
+            #[cfg(target_os = "linux")]
+            Backend::Local => Local,
+        }
+    }
+}
+
+impl<'a> From<&'a OwnedBackend> for Backend<'a> {
+    fn from(be: &'a OwnedBackend) -> Self {
+        be.as_backend()
+    }
+}
+
+impl str::FromStr for OwnedBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        use OwnedBackend::*;
+        let s = s.trim();
+        if s.is_empty() || s == "default" {
+            return Ok(Default);
+        }
+        #[cfg(target_os = "linux")]
+        if s == "local" || s == "local:" {
+            return Ok(Local);
+        }
+
+        let (scheme, param) = s
+            .split_once(':')
+            .ok_or_else(|| Error::General(format!("Invalid backend: '{}'", s)))?;
+
+        match scheme {
+            "ip" => Ok(Network(param.into())),
+            "usb" => Ok(Usb(param.into())),
+            "serial" => Ok(Serial(param.into())),
+            "xml" => Ok(Xml(param.into())),
+            _ => Err(Error::General(format!("Unknown backend: '{}'", scheme))),
+        }
+    }
+}
+
+/// A builder to create a context with a set of options.
+///
+/// This is created with [`Context::builder()`]. Any options not set are
+/// left at the defaults for the backend.
+///
+/// ```no_run
+/// use industrial_io as iio;
+/// use std::time::Duration;
+///
+/// let ctx = iio::Context::builder()
+///     .uri("ip:pluto.local")
+///     .timeout(Duration::from_secs(2))
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ContextBuilder {
+    /// The backend, or the default if not set
+    backend: Option<OwnedBackend>,
+    /// The I/O timeout
+    timeout: Option<Duration>,
+    /// The size of the buffer for reading attributes, in bytes
+    attr_buf_size: Option<usize>,
+    /// The maximum size of a buffer, in bytes
+    max_buffer_size: Option<usize>,
+    /// Whether to enable the journal of attribute writes
+    journal: bool,
+}
+
+impl ContextBuilder {
+    /// Creates a builder for a context with the default backend.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the backend for the context.
+    pub fn backend(mut self, be: Backend) -> Self {
+        self.backend = Some(be.into());
+        self
+    }
+
+    /// Sets the backend for the context by URI, like "ip:192.168.2.1".
+    pub fn uri(mut self, uri: &str) -> Self {
+        self.backend = Some(OwnedBackend::Uri(uri.into()));
+        self
+    }
+
+    /// Sets the timeout for I/O operations.
+    ///
+    /// See [`Context::set_timeout()`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the size of the buffer used to read attributes, in bytes.
+    ///
+    /// See [`Context::set_attr_buf_size()`].
+    pub fn attr_buf_size(mut self, bytes: usize) -> Self {
+        self.attr_buf_size = Some(bytes);
+        self
+    }
+
+    /// Sets the maximum size of a buffer, in bytes.
+    ///
+    /// See [`Context::set_max_buffer_size()`].
+    pub fn max_buffer_size(mut self, bytes: usize) -> Self {
+        self.max_buffer_size = Some(bytes);
+        self
+    }
+
+    /// Sets whether the journal of attribute writes is enabled.
+    ///
+    /// See [`Context::set_journal_enabled()`].
+    pub fn journal(mut self, on: bool) -> Self {
+        self.journal = on;
+        self
+    }
+
+    /// Creates the context with the options.
+    pub fn build(&self) -> Result<Context> {
+        let ctx = match &self.backend {
+            Some(be) => Context::with_backend(be.as_backend())?,
+            None => Context::new()?,
+        };
+        if let Some(timeout) = self.timeout {
+            ctx.set_timeout(timeout)?;
+        }
+        if let Some(bytes) = self.attr_buf_size {
+            ctx.set_attr_buf_size(bytes);
+        }
+        if let Some(bytes) = self.max_buffer_size {
+            ctx.set_max_buffer_size(bytes);
+        }
+        ctx.set_journal_enabled(self.journal);
+        Ok(ctx)
+    }
+}
+
 /// This holds a pointer to the library context.
 /// When it is dropped, the library context is destroyed.
 #[derive(Debug)]
 pub struct InnerContext {
     /// Pointer to a libiio Context object
     pub(crate) ctx: *mut ffi::iio_context,
+    /// The last I/O timeout set, in ms, or `NO_TIMEOUT_SET`
+    timeout_ms: AtomicU64,
+    /// The journal of attribute writes
+    pub(crate) journal: Recorder,
+    /// The cached offset and scale of channels, by channel pointer
+    pub(crate) scale_cache: Mutex<HashMap<usize, (f64, f64)>>,
+    /// The maximum size of a buffer, in bytes
+    max_buffer_size: AtomicUsize,
+    /// The size of the buffer for reading attributes, in bytes
+    attr_buf_size: AtomicUsize,
 }
 
 impl InnerContext {
@@ -123,7 +353,14 @@ impl InnerContext {
             Err(Error::from(Errno::last()))
         }
         else {
-            Ok(Self { ctx })
+            Ok(Self {
+                ctx,
+                timeout_ms: AtomicU64::new(NO_TIMEOUT_SET),
+                journal: Recorder::default(),
+                scale_cache: Mutex::new(HashMap::new()),
+                max_buffer_size: AtomicUsize::new(DEFAULT_MAX_BUFFER_SIZE),
+                attr_buf_size: AtomicUsize::new(DEFAULT_ATTR_BUF_SIZE),
+            })
         }
     }
 
@@ -132,8 +369,53 @@ impl InnerContext {
     /// This creates a full copy of the actual context held in the underlying
     /// C library. This is useful if you want to give a separate copy to each
     /// thread in an application, which could help performance.
+    ///
+    /// The copy gets the same settings: the I/O timeout, buffer sizes,
+    /// dry-run mode, and whether the journal is enabled. It starts with an
+    /// empty journal.
     pub fn try_clone(&self) -> Result<Self> {
-        Self::new(unsafe { ffi::iio_context_clone(self.ctx) })
+        let mut inner = Self::new(unsafe { ffi::iio_context_clone(self.ctx) })?;
+
+        let ms = self.timeout_ms.load(Ordering::Relaxed);
+        if ms != NO_TIMEOUT_SET {
+            let ret = unsafe { ffi::iio_context_set_timeout(inner.ctx, ms as c_uint) };
+            sys_result(ret, ())?;
+            inner.timeout_ms.store(ms, Ordering::Relaxed);
+        }
+        inner.max_buffer_size.store(
+            self.max_buffer_size.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        inner
+            .attr_buf_size
+            .store(self.attr_buf_size.load(Ordering::Relaxed), Ordering::Relaxed);
+        inner.journal = self.journal.clone_settings();
+        Ok(inner)
+    }
+}
+
+/// The default maximum size of a buffer, in bytes.
+///
+/// See [`Context::set_max_buffer_size()`].
+pub const DEFAULT_MAX_BUFFER_SIZE: usize = 256 * 1024 * 1024;
+
+/// The default size of the buffer for reading attributes, in bytes.
+///
+/// According to the IIO samples, internal buffers need to be big enough
+/// for attributes coming back from the kernel. See
+/// [`Context::set_attr_buf_size()`].
+pub const DEFAULT_ATTR_BUF_SIZE: usize = 16384;
+
+/// Marks that no timeout was set on a context.
+const NO_TIMEOUT_SET: u64 = u64::MAX;
+
+/// Gets the default I/O timeout of a backend, in milliseconds.
+///
+/// These are the defaults that the C library uses for its backends.
+fn default_timeout_ms(backend: &str) -> u64 {
+    match backend {
+        "network" | "usb" => 5000,
+        _ => 1000,
     }
 }
 
@@ -163,10 +445,64 @@ impl Context {
     /// runs. If set to an empty string, the server will be discovered using
     /// `ZeroConf`. If the environment variable is not set, a local context
     /// will be created instead.
+    ///
+    /// This is the same as [`Context::autodetect()`]. Applications that
+    /// should always use the local devices, whatever the environment, can
+    /// use [`Context::local()`] instead.
     pub fn new() -> Result<Self> {
+        Self::autodetect()
+    }
+
+    /// Creates a context as determined by the environment.
+    ///
+    /// If the `IIOD_REMOTE` environment variable is set, this creates a
+    /// network context with the host that it names, or, if it's empty, the
+    /// first IIOD server discovered with ZeroConf. Otherwise it creates a
+    /// local context.
+    pub fn autodetect() -> Result<Self> {
         Self::from_ptr(unsafe { ffi::iio_create_default_context() })
     }
 
+    /// Creates a context for the devices on the local system.
+    ///
+    /// Unlike [`Context::new()`], this ignores the `IIOD_REMOTE`
+    /// environment variable.
+    #[cfg(target_os = "linux")]
+    pub fn local() -> Result<Self> {
+        Self::with_backend(Backend::Local)
+    }
+
+    /// Creates a context with the first backend that has any devices.
+    ///
+    /// This tries the local devices first, on Linux, then scans for USB
+    /// devices, then for IIOD servers on the network, and creates a
+    /// context for the first one found. The network scan can take a few
+    /// seconds. The `IIOD_REMOTE` environment variable is ignored.
+    ///
+    /// This fails with `ENODEV` if nothing is found.
+    #[cfg(not(feature = "libiio_v0_19"))]
+    pub fn any_available() -> Result<Self> {
+        #[cfg(target_os = "linux")]
+        if let Ok(ctx) = Self::local() {
+            if ctx.num_devices() > 0 {
+                return Ok(ctx);
+            }
+        }
+
+        for backend in ["usb", "ip"] {
+            let scan = match ScanContext::new(backend) {
+                Ok(scan) if !scan.is_empty() => scan,
+                _ => continue,
+            };
+            for res in scan.iter() {
+                if let Ok(ctx) = Self::from_uri(&res.uri) {
+                    return Ok(ctx);
+                }
+            }
+        }
+        Err(Error::Nix(Errno::ENODEV))
+    }
+
     /// Create an IIO Context.
     ///
     /// A context contains one or more devices (i.e. sensors) that can provide
@@ -240,6 +576,11 @@ impl Context {
         })
     }
 
+    /// Gets a builder to create a context with a set of options.
+    pub fn builder() -> ContextBuilder {
+        ContextBuilder::new()
+    }
+
     /// Creates a context specified by the `uri`.
     pub fn from_uri(uri: &str) -> Result<Self> {
         Self::with_backend(Backend::Uri(uri))
@@ -284,6 +625,15 @@ impl Context {
         }
     }
 
+    /// Creates a weak reference to this context.
+    ///
+    /// The weak reference does not keep the underlying C context alive.
+    pub fn downgrade(&self) -> WeakContext {
+        WeakContext {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
     /// Make a new context based on a full copy of underlying C context.
     pub fn try_deep_clone(&self) -> Result<Self> {
         let inner = self.inner.try_clone()?;
@@ -371,6 +721,21 @@ impl Context {
         Ok((name.unwrap(), val.unwrap()))
     }
 
+    /// Gets the value of a context-specific attribute by name.
+    pub fn attr_value(&self, name: &str) -> Option<String> {
+        let name = cstring_or_bail!(name);
+        let pstr = unsafe { ffi::iio_context_get_attr_value(self.inner.ctx, name.as_ptr()) };
+        cstring_opt(pstr)
+    }
+
+    /// Gets the URI of the context, if the backend reports one.
+    ///
+    /// This is the URI that could be used to create another context
+    /// connected to the same devices.
+    pub fn uri(&self) -> Option<String> {
+        self.attr_value("uri")
+    }
+
     /// Gets an iterator for the attributes in the context
     pub fn attributes(&self) -> AttrIterator {
         AttrIterator { ctx: self, idx: 0 }
@@ -391,7 +756,67 @@ impl Context {
     ///     timeout should be used.
     pub fn set_timeout_ms(&self, ms: u64) -> Result<()> {
         let ret = unsafe { ffi::iio_context_set_timeout(self.inner.ctx, ms as c_uint) };
-        sys_result(ret, ())
+        sys_result(ret, ())?;
+        self.inner.timeout_ms.store(ms, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Gets the timeout for I/O operations, in milliseconds.
+    ///
+    /// The C library doesn't report the timeout, so this is the last one
+    /// set on the context, or the default for the backend if it was never
+    /// set. Zero means that no timeout is used.
+    pub fn timeout_ms(&self) -> u64 {
+        match self.inner.timeout_ms.load(Ordering::Relaxed) {
+            NO_TIMEOUT_SET => default_timeout_ms(&self.name()),
+            ms => ms,
+        }
+    }
+
+    /// Sets the maximum size of a buffer created for any device in the
+    /// context, in bytes.
+    ///
+    /// Requests for larger buffers fail with
+    /// [`Error::InvalidBufferSize`] before reaching the C library. The
+    /// default is [`DEFAULT_MAX_BUFFER_SIZE`].
+    pub fn set_max_buffer_size(&self, bytes: usize) {
+        self.inner.max_buffer_size.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Gets the maximum size of a buffer, in bytes.
+    pub fn max_buffer_size(&self) -> usize {
+        self.inner.max_buffer_size.load(Ordering::Relaxed)
+    }
+
+    /// Sets the size of the buffer used to read attributes as strings, in
+    /// bytes.
+    ///
+    /// Longer values are truncated. The default is
+    /// [`DEFAULT_ATTR_BUF_SIZE`]. For very large attributes, consider
+    /// reading them as a stream with
+    /// [`Device::attr_read_reader()`](crate::Device::attr_read_reader).
+    pub fn set_attr_buf_size(&self, bytes: usize) {
+        self.inner
+            .attr_buf_size
+            .store(bytes.max(1), Ordering::Relaxed);
+    }
+
+    /// Gets the size of the buffer used to read attributes, in bytes.
+    pub fn attr_buf_size(&self) -> usize {
+        self.inner.attr_buf_size.load(Ordering::Relaxed)
+    }
+
+    /// Locks the cache of channel offsets and scales.
+    pub(crate) fn scale_cache(&self) -> MutexGuard<'_, HashMap<usize, (f64, f64)>> {
+        self.inner
+            .scale_cache
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Clears the cache of channel offsets and scales.
+    pub(crate) fn clear_scale_cache(&self) {
+        self.scale_cache().clear();
     }
 
     /// Get the number of devices in the context
@@ -428,7 +853,37 @@ impl Context {
         }
     }
 
+    /// Try to find the device that has the specified identity.
+    ///
+    /// This can be used to re-find "the same" device in a different
+    /// context, such as after reconnecting to a remote host. A device with
+    /// the same ID, name, and label is preferred. Since device ID's may be
+    /// reassigned by the kernel, if that isn't found, this falls back to a
+    /// device with the same name and label, but only if there is exactly
+    /// one of them in the context.
+    pub fn find_device_by_identity(&self, ident: &DeviceIdentity) -> Option<Device> {
+        let mut candidates = Vec::new();
+        for dev in self.devices() {
+            let id = dev.identity();
+            if id.name == ident.name && id.label == ident.label {
+                if id.id == ident.id {
+                    return Some(dev);
+                }
+                candidates.push(dev);
+            }
+        }
+        if candidates.len() == 1 {
+            candidates.pop()
+        }
+        else {
+            None
+        }
+    }
+
     /// Gets an iterator for all the devices in the context.
+    ///
+    /// Use [`DeviceIterator::by_kind()`] to tell the triggers apart from
+    /// the other devices.
     pub fn devices(&self) -> DeviceIterator {
         DeviceIterator { ctx: self, idx: 0 }
     }
@@ -456,6 +911,35 @@ impl From<InnerContext> for Context {
     }
 }
 
+impl WeakContext {
+    /// Creates a weak reference that doesn't refer to any context.
+    ///
+    /// Calling [`upgrade()`](WeakContext::upgrade) on it always gives `None`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attempts to get a strong [`Context`] from the weak reference.
+    ///
+    /// Returns `None` if the context has already been destroyed.
+    pub fn upgrade(&self) -> Option<Context> {
+        self.inner.upgrade().map(|inner| Context { inner })
+    }
+
+    /// Determines if the context referred to by this weak reference has
+    /// already been destroyed.
+    pub fn is_dropped(&self) -> bool {
+        self.inner.strong_count() == 0
+    }
+}
+
+impl PartialEq for WeakContext {
+    /// Two weak references are the same if they point to the same context.
+    fn eq(&self, other: &Self) -> bool {
+        self.inner.ptr_eq(&other.inner)
+    }
+}
+
 /// Iterator over the Devices in a Context
 #[derive(Debug)]
 pub struct DeviceIterator<'a> {
@@ -516,6 +1000,42 @@ mod tests {
     use super::*;
     use std::thread;
 
+    #[test]
+    fn builder() {
+        let bld = Context::builder()
+            .uri("ip:pluto.local")
+            .timeout(Duration::from_secs(2))
+            .attr_buf_size(65536);
+        assert_eq!(
+            bld.backend,
+            Some(OwnedBackend::Uri("ip:pluto.local".into()))
+        );
+        assert_eq!(bld.timeout, Some(Duration::from_secs(2)));
+        assert_eq!(bld.attr_buf_size, Some(65536));
+        assert_eq!(bld.max_buffer_size, None);
+
+        let bld = bld.backend(Backend::Network("10.0.0.2"));
+        assert_eq!(bld.backend, Some(OwnedBackend::Network("10.0.0.2".into())));
+    }
+
+    #[test]
+    fn parse_backend() {
+        let be: OwnedBackend = "ip:192.168.2.1".parse().unwrap();
+        assert_eq!(be, OwnedBackend::Network("192.168.2.1".into()));
+        assert!(matches!(be.as_backend(), Backend::Network("192.168.2.1")));
+
+        let be: OwnedBackend = "serial:/dev/ttyUSB0,115200".parse().unwrap();
+        assert_eq!(be, OwnedBackend::Serial("/dev/ttyUSB0,115200".into()));
+
+        assert_eq!(
+            "usb:3.2.1".parse::<OwnedBackend>().unwrap(),
+            OwnedBackend::from(Backend::Usb("3.2.1"))
+        );
+        assert_eq!("".parse::<OwnedBackend>().unwrap(), OwnedBackend::Default);
+        assert!("bogus:xyz".parse::<OwnedBackend>().is_err());
+        assert!("192.168.2.1".parse::<OwnedBackend>().is_err());
+    }
+
     // See that we get the default context.
     #[test]
     fn default_context() {
@@ -532,6 +1052,24 @@ mod tests {
         assert!(ctx == ctx2);
     }
 
+    // A deep clone should keep the settings of the context.
+    #[test]
+    fn deep_clone_settings() {
+        let ctx = Context::new().unwrap();
+        ctx.set_timeout_ms(2500).unwrap();
+        ctx.set_attr_buf_size(65536);
+        ctx.set_max_buffer_size(4096);
+        ctx.set_dry_run(true);
+
+        let ctx2 = ctx.try_deep_clone().unwrap();
+        assert!(ctx != ctx2);
+        assert_eq!(ctx2.timeout_ms(), 2500);
+        assert_eq!(ctx2.attr_buf_size(), 65536);
+        assert_eq!(ctx2.max_buffer_size(), 4096);
+        assert!(ctx2.is_dry_run());
+        assert!(ctx2.is_journal_enabled());
+    }
+
     // Clone the inner context and send to another thread.
     #[test]
     fn multi_thread() {
@@ -544,6 +1082,19 @@ mod tests {
         thr.join().unwrap();
     }
 
+    // A weak reference should upgrade only while the context is alive.
+    #[test]
+    fn weak_context() {
+        let ctx = Context::new().unwrap();
+        let weak = ctx.downgrade();
+        assert!(!weak.is_dropped());
+        assert!(weak.upgrade() == Some(ctx.clone()));
+
+        drop(ctx);
+        assert!(weak.is_dropped());
+        assert!(weak.upgrade().is_none());
+    }
+
     // See that device iterator gets the correct number of devices.
     #[test]
     fn dev_iterator_count() {
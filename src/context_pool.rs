@@ -0,0 +1,202 @@
+// industrial-io/src/context_pool.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! A pool of contexts for concurrent requests.
+//!
+//! With the network backend, each context has a single connection to the
+//! IIO daemon, over which requests are serialized. So one thread polling
+//! attributes has to wait behind another reading buffers, and vice versa.
+//!
+//! A [`ContextPool`] holds several separate contexts to the same URI, and
+//! hands them out to threads (or tasks) as they need them. Each one is
+//! returned to the pool when the [`PooledContext`] is dropped.
+//!
+//! Devices and channels belong to the context they were found in, so they
+//! should be found again in each pooled context, and not kept after it is
+//! returned.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::ContextPool;
+//! use std::thread;
+//!
+//! let pool = ContextPool::new("ip:192.168.2.1", 2).unwrap();
+//!
+//! let poller = {
+//!     let pool = pool.clone();
+//!     thread::spawn(move || {
+//!         let ctx = pool.get();
+//!         let dev = ctx.find_device("ad9361-phy").unwrap();
+//!         println!("{:?}", dev.attr_read_str("ensm_mode"));
+//!     })
+//! };
+//!
+//! let ctx = pool.get();
+//! let dev = ctx.find_device("cf-ad9361-lpc").unwrap();
+//! let frame = dev.capture_burst(1024).unwrap();
+//! poller.join().unwrap();
+//! ```
+
+use crate::{Context, Error, Result};
+use std::{
+    ops::Deref,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+};
+
+#[cfg(feature = "async")]
+use tokio::sync::Notify;
+
+/// The shared state of a pool.
+#[derive(Debug)]
+struct PoolInner {
+    /// The contexts that are available
+    idle: Mutex<Vec<Context>>,
+    /// Signaled when a context is returned to the pool
+    returned: Condvar,
+    /// Signaled when a context is returned to the pool, for async waiters
+    #[cfg(feature = "async")]
+    notify: Notify,
+    /// The number of contexts in the pool
+    size: usize,
+}
+
+impl PoolInner {
+    /// Locks the list of available contexts.
+    fn idle(&self) -> MutexGuard<'_, Vec<Context>> {
+        self.idle.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// A pool of separate contexts to the same device server.
+///
+/// Clones of the pool share the same contexts.
+#[derive(Debug, Clone)]
+pub struct ContextPool {
+    inner: Arc<PoolInner>,
+}
+
+impl ContextPool {
+    /// Creates a pool of `n` contexts to the URI.
+    pub fn new(uri: &str, n: usize) -> Result<Self> {
+        let ctxs = (0..n)
+            .map(|_| Context::from_uri(uri))
+            .collect::<Result<Vec<_>>>()?;
+        Self::from_contexts(ctxs)
+    }
+
+    /// Creates a pool with the context, and `n - 1` deep clones of it.
+    pub fn from_context(ctx: Context, n: usize) -> Result<Self> {
+        let mut ctxs = Vec::with_capacity(n);
+        for _ in 1..n {
+            ctxs.push(ctx.try_deep_clone()?);
+        }
+        ctxs.push(ctx);
+        Self::from_contexts(ctxs)
+    }
+
+    /// Creates a pool from a set of contexts.
+    fn from_contexts(ctxs: Vec<Context>) -> Result<Self> {
+        if ctxs.is_empty() {
+            return Err(Error::General("A context pool can't be empty".into()));
+        }
+        Ok(Self {
+            inner: Arc::new(PoolInner {
+                size: ctxs.len(),
+                idle: Mutex::new(ctxs),
+                returned: Condvar::new(),
+                #[cfg(feature = "async")]
+                notify: Notify::new(),
+            }),
+        })
+    }
+
+    /// Gets the number of contexts in the pool.
+    pub fn size(&self) -> usize {
+        self.inner.size
+    }
+
+    /// Gets the number of contexts that are not in use.
+    pub fn available(&self) -> usize {
+        self.inner.idle().len()
+    }
+
+    /// Gets a context from the pool, blocking until one is available.
+    pub fn get(&self) -> PooledContext {
+        let mut idle = self.inner.idle();
+        loop {
+            if let Some(ctx) = idle.pop() {
+                return self.pooled(ctx);
+            }
+            idle = self
+                .inner
+                .returned
+                .wait(idle)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    /// Gets a context from the pool, if one is available.
+    pub fn try_get(&self) -> Option<PooledContext> {
+        self.inner.idle().pop().map(|ctx| self.pooled(ctx))
+    }
+
+    /// Gets a context from the pool, waiting asynchronously until one is
+    /// available.
+    #[cfg(feature = "async")]
+    pub async fn get_async(&self) -> PooledContext {
+        loop {
+            let notified = self.inner.notify.notified();
+            if let Some(ctx) = self.try_get() {
+                return ctx;
+            }
+            notified.await;
+        }
+    }
+
+    /// Wraps a context taken from the pool.
+    fn pooled(&self, ctx: Context) -> PooledContext {
+        PooledContext {
+            ctx: Some(ctx),
+            pool: Arc::clone(&self.inner),
+        }
+    }
+}
+
+/// A context taken from a [`ContextPool`].
+///
+/// This dereferences to the [`Context`], and returns it to the pool when
+/// dropped.
+#[derive(Debug)]
+pub struct PooledContext {
+    /// The context, until it's returned
+    ctx: Option<Context>,
+    /// The pool that the context came from
+    pool: Arc<PoolInner>,
+}
+
+impl Deref for PooledContext {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        // Only taken on drop
+        self.ctx.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledContext {
+    fn drop(&mut self) {
+        if let Some(ctx) = self.ctx.take() {
+            self.pool.idle().push(ctx);
+            self.pool.returned.notify_one();
+            #[cfg(feature = "async")]
+            self.pool.notify.notify_one();
+        }
+    }
+}
@@ -0,0 +1,368 @@
+// industrial-io/src/cookbook.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Ready-made recipes for common capture patterns.
+//!
+//! These are the building blocks of the larger example applications,
+//! like _riio_bufavg_ and _riio_tsbuf_, so that they can be reused rather
+//! than copied. Each one sets up a device for a triggered, timestamped
+//! capture of a channel, and gives the results as an iterator that
+//! refills the buffer as it goes:
+//!
+//! * [`timestamped_sample_stream()`] - Each sample of the channel with
+//!   its timestamp.
+//! * [`timestamped_average_stream()`] - The average of each buffer of
+//!   samples, scaled to physical units, with the timestamp of the last
+//!   sample.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::cookbook::{self, StreamConfig};
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ads1015").unwrap();
+//!
+//! let cfg = StreamConfig::new("voltage0").trigger("trigger0");
+//! for avg in cookbook::timestamped_average_stream(&dev, &cfg).unwrap() {
+//!     let avg = avg.unwrap();
+//!     println!("{}: {:.3} mV", avg.timestamp, avg.value);
+//! }
+//! ```
+
+use crate::{units, Buffer, Channel, Device, Direction, Error, Result};
+use std::{
+    any::TypeId,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The ID of the timestamp channel of a device.
+const TIMESTAMP_CHAN: &str = "timestamp";
+
+/// The shortest I/O timeout for a capture.
+const MIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The configuration for a timestamped stream of a channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamConfig {
+    /// The ID or name of the channel to capture
+    pub channel: String,
+    /// The name of the trigger to assign to the device, if any
+    pub trigger: Option<String>,
+    /// The sample rate, in Hz
+    pub sample_rate: f64,
+    /// The number of samples in each buffer
+    pub samples: usize,
+    /// Whether the device must have a timestamp channel. If not, and
+    /// there is none, the system time is used when each buffer is read.
+    pub require_timestamp: bool,
+}
+
+impl StreamConfig {
+    /// The default sample rate, in Hz
+    pub const DEFAULT_SAMPLE_RATE: f64 = 100.0;
+
+    /// The default number of samples in each buffer
+    pub const DEFAULT_SAMPLES: usize = 100;
+
+    /// Creates a configuration for the channel, with the default sample
+    /// rate and buffer size.
+    pub fn new(channel: &str) -> Self {
+        Self {
+            channel: channel.to_string(),
+            trigger: None,
+            sample_rate: Self::DEFAULT_SAMPLE_RATE,
+            samples: Self::DEFAULT_SAMPLES,
+            require_timestamp: false,
+        }
+    }
+
+    /// Sets the trigger to assign to the device.
+    pub fn trigger(mut self, name: &str) -> Self {
+        self.trigger = Some(name.to_string());
+        self
+    }
+
+    /// Sets the sample rate, in Hz.
+    pub fn sample_rate(mut self, hz: f64) -> Self {
+        self.sample_rate = hz;
+        self
+    }
+
+    /// Sets the number of samples in each buffer.
+    pub fn samples(mut self, n: usize) -> Self {
+        self.samples = n;
+        self
+    }
+
+    /// Sets whether the device must have a timestamp channel.
+    pub fn require_timestamp(mut self, on: bool) -> Self {
+        self.require_timestamp = on;
+        self
+    }
+
+    /// Gets an I/O timeout that is more than enough to fill a buffer.
+    ///
+    /// This gives 50% extra time, or at least 5 seconds.
+    pub fn timeout(&self) -> Duration {
+        let secs = 1.5 * self.samples as f64 / self.sample_rate;
+        Duration::try_from_secs_f64(secs)
+            .unwrap_or(MIN_TIMEOUT)
+            .max(MIN_TIMEOUT)
+    }
+}
+
+/// A sample from a channel, with its timestamp.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimedSample {
+    /// The time of the sample, in nanoseconds
+    pub timestamp: u64,
+    /// The value of the sample, converted to the host format, but not
+    /// scaled
+    pub raw: f64,
+}
+
+/// The average of a buffer of samples, with the time of the last one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimedAverage {
+    /// The time of the last sample, in nanoseconds
+    pub timestamp: u64,
+    /// The average of the raw samples
+    pub raw: f64,
+    /// The average scaled to the ABI units of the channel, like
+    /// millivolts
+    pub value: f64,
+    /// The number of samples that were averaged
+    pub count: usize,
+}
+
+impl TimedAverage {
+    /// Averages a block of raw samples, scaling the result with the
+    /// offset and scale of the channel.
+    ///
+    /// This is `None` if there are no samples.
+    pub fn from_samples(samples: &[TimedSample], offset: f64, scale: f64) -> Option<Self> {
+        let last = samples.last()?;
+        let raw = samples.iter().map(|s| s.raw).sum::<f64>() / samples.len() as f64;
+        Some(Self {
+            timestamp: last.timestamp,
+            raw,
+            value: units::scaled(raw, offset, scale),
+            count: samples.len(),
+        })
+    }
+}
+
+/// Gets the current system time, in nanoseconds since the Unix epoch.
+///
+/// This is a stand-in for devices without a timestamp channel. It's less
+/// accurate, since it's the time the samples were read, not taken.
+pub fn system_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|dur| dur.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// A stream of timestamped blocks of samples from a channel.
+///
+/// Each item is the contents of one refill of the buffer.
+#[derive(Debug)]
+pub struct SampleStream {
+    /// The buffer for the capture
+    buf: Buffer,
+    /// The channel being captured
+    chan: Channel,
+    /// The timestamp channel, if the device has one
+    ts_chan: Option<Channel>,
+}
+
+impl SampleStream {
+    /// Sets up the device and creates the buffer for the stream.
+    fn new(dev: &Device, cfg: &StreamConfig) -> Result<Self> {
+        let chan = dev
+            .find_channel(&cfg.channel, Direction::Input)
+            .ok_or_else(|| Error::General(format!("Channel not found: '{}'", cfg.channel)))?;
+
+        let ts_chan = dev.find_channel(TIMESTAMP_CHAN, Direction::Input);
+        if ts_chan.is_none() && cfg.require_timestamp {
+            return Err(Error::General("The device has no timestamp channel".into()));
+        }
+
+        chan.enable();
+        if let Some(ts_chan) = &ts_chan {
+            ts_chan.enable();
+        }
+
+        if let Some(name) = &cfg.trigger {
+            let trig = dev
+                .context()
+                .find_trigger(name)
+                .ok_or_else(|| Error::General(format!("Trigger not found: '{}'", name)))?;
+            dev.set_trigger(&trig)?;
+        }
+        dev.set_sampling_frequency(cfg.sample_rate)?;
+
+        let buf = dev.create_buffer(cfg.samples, false)?;
+        dev.context().set_timeout(cfg.timeout())?;
+
+        Ok(Self { buf, chan, ts_chan })
+    }
+
+    /// Gets the channel being captured.
+    pub fn channel(&self) -> &Channel {
+        &self.chan
+    }
+
+    /// Determines if the timestamps are from the timestamp channel of the
+    /// device, rather than the system time.
+    pub fn has_timestamp_channel(&self) -> bool {
+        self.ts_chan.is_some()
+    }
+
+    /// Refills the buffer and reads the samples, with their timestamps.
+    pub fn read_block(&mut self) -> Result<Vec<TimedSample>> {
+        self.buf.refill()?;
+        let raw = self.chan.read_f64(&self.buf)?;
+
+        let timestamps = match &self.ts_chan {
+            Some(ts_chan) => read_timestamps(ts_chan, &self.buf)?,
+            None => vec![system_timestamp(); raw.len()],
+        };
+
+        Ok(timestamps
+            .into_iter()
+            .zip(raw)
+            .map(|(timestamp, raw)| TimedSample { timestamp, raw })
+            .collect())
+    }
+}
+
+impl Iterator for SampleStream {
+    type Item = Result<Vec<TimedSample>>;
+
+    /// Blocks until the next buffer of samples is read.
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.read_block())
+    }
+}
+
+/// A stream of the averages of each buffer of samples from a channel.
+#[derive(Debug)]
+pub struct AverageStream {
+    /// The stream of samples
+    samples: SampleStream,
+    /// The offset of the channel
+    offset: f64,
+    /// The scale of the channel
+    scale: f64,
+}
+
+impl AverageStream {
+    /// Gets the stream of samples that are averaged.
+    pub fn samples(&self) -> &SampleStream {
+        &self.samples
+    }
+
+    /// Gets the offset and scale applied to the averages.
+    pub fn offset_scale(&self) -> (f64, f64) {
+        (self.offset, self.scale)
+    }
+}
+
+impl Iterator for AverageStream {
+    type Item = Result<TimedAverage>;
+
+    /// Blocks until the next buffer of samples is read, and averages it.
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.samples.read_block().and_then(|block| {
+            TimedAverage::from_samples(&block, self.offset, self.scale).ok_or(Error::BadReturnSize)
+        });
+        Some(res)
+    }
+}
+
+/// Reads the timestamps from the timestamp channel, as nanoseconds.
+fn read_timestamps(chan: &Channel, buf: &Buffer) -> Result<Vec<u64>> {
+    if chan.type_of() == Some(TypeId::of::<i64>()) {
+        Ok(chan
+            .read::<i64>(buf)?
+            .into_iter()
+            .map(|ts| ts as u64)
+            .collect())
+    }
+    else {
+        chan.read::<u64>(buf)
+    }
+}
+
+/// Sets up a device for a triggered capture of a channel, and gets a
+/// stream of the samples, with their timestamps.
+///
+/// This enables the channel and the timestamp channel, if there is one,
+/// assigns the trigger, if any, sets the sample rate, creates the buffer,
+/// and sets the I/O timeout of the context to be long enough to fill it.
+pub fn timestamped_sample_stream(dev: &Device, cfg: &StreamConfig) -> Result<SampleStream> {
+    SampleStream::new(dev, cfg)
+}
+
+/// Sets up a device for a triggered capture of a channel, and gets a
+/// stream of the averages of each buffer of samples, with the time of the
+/// last sample in each.
+///
+/// The averages are scaled to the ABI units of the channel with its
+/// `offset` and `scale` attributes. See [`timestamped_sample_stream()`].
+pub fn timestamped_average_stream(dev: &Device, cfg: &StreamConfig) -> Result<AverageStream> {
+    let samples = SampleStream::new(dev, cfg)?;
+    let (offset, scale) = samples.chan.offset_scale();
+    Ok(AverageStream {
+        samples,
+        offset,
+        scale,
+    })
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average() {
+        let samples = [
+            TimedSample {
+                timestamp: 100,
+                raw: 10.0,
+            },
+            TimedSample {
+                timestamp: 200,
+                raw: 20.0,
+            },
+        ];
+        let avg = TimedAverage::from_samples(&samples, 5.0, 0.5).unwrap();
+        assert_eq!(avg.timestamp, 200);
+        assert_eq!(avg.raw, 15.0);
+        assert_eq!(avg.value, 10.0);
+        assert_eq!(avg.count, 2);
+
+        assert!(TimedAverage::from_samples(&[], 0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn timeout() {
+        let cfg = StreamConfig::new("voltage0");
+        assert_eq!(cfg.timeout(), MIN_TIMEOUT);
+
+        let cfg = cfg.sample_rate(10.0).samples(100);
+        assert_eq!(cfg.timeout(), Duration::from_secs(15));
+    }
+}
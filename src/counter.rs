@@ -0,0 +1,187 @@
+// industrial-io/src/counter.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Helpers for counter channels, such as quadrature encoders.
+//!
+//! Counter drivers that use the IIO interface expose each counter as a
+//! `count` channel, and each encoder index input as an `index` channel.
+//! A [`Counter`] maps the attributes of the IIO counter ABI:
+//!
+//! * `raw` - The current count. Writing it sets the count.
+//! * `preset` - The value loaded into the count when it's preset, such
+//!   as on an index pulse.
+//! * `preset_enable` - Whether the preset is applied.
+//! * `enable` - Whether the counter is counting.
+//! * `count_direction` - The direction of the last count, "forward" or
+//!   "backward".
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::counter::Counter;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("104-quad-8").unwrap();
+//! let enc = Counter::new(dev.find_input_channel("count0").unwrap()).unwrap();
+//!
+//! enc.reset().unwrap();
+//! enc.set_preset(0).unwrap();
+//! println!("Position: {}", enc.read_count().unwrap());
+//! ```
+
+use crate::{Channel, ChannelType, Error, Result};
+use std::{fmt, ops::Deref, str::FromStr};
+
+/// The direction in which a counter last counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountDirection {
+    /// Counting up
+    Forward,
+    /// Counting down
+    Backward,
+}
+
+impl FromStr for CountDirection {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "forward" => Ok(CountDirection::Forward),
+            "backward" => Ok(CountDirection::Backward),
+            _ => Err(Error::StringConversionError),
+        }
+    }
+}
+
+impl fmt::Display for CountDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CountDirection::Forward => write!(f, "forward"),
+            CountDirection::Backward => write!(f, "backward"),
+        }
+    }
+}
+
+/// A counter or index channel.
+///
+/// This dereferences to the underlying [`Channel`].
+#[derive(Debug, Clone)]
+pub struct Counter {
+    /// The count or index channel
+    chan: Channel,
+}
+
+impl Counter {
+    /// Wraps a count or index channel.
+    ///
+    /// This fails for any other type of channel.
+    pub fn new(chan: Channel) -> Result<Self> {
+        match chan.channel_type() {
+            ChannelType::Count | ChannelType::Index => Ok(Self { chan }),
+            _ => Err(Error::General(format!(
+                "'{}' is not a counter channel",
+                chan.id().unwrap_or_default()
+            ))),
+        }
+    }
+
+    /// Gets the underlying channel.
+    pub fn into_inner(self) -> Channel {
+        self.chan
+    }
+
+    /// Reads the current count.
+    pub fn read_count(&self) -> Result<i64> {
+        self.chan.attr_read_int("raw")
+    }
+
+    /// Sets the current count.
+    pub fn set_count(&self, count: i64) -> Result<()> {
+        self.chan.attr_write_int("raw", count)
+    }
+
+    /// Resets the count to zero.
+    pub fn reset(&self) -> Result<()> {
+        self.set_count(0)
+    }
+
+    /// Reads the preset value.
+    pub fn preset(&self) -> Result<i64> {
+        self.chan.attr_read_int("preset")
+    }
+
+    /// Sets the value loaded into the count when it's preset.
+    pub fn set_preset(&self, preset: i64) -> Result<()> {
+        self.chan.attr_write_int("preset", preset)
+    }
+
+    /// Determines if the preset is enabled.
+    pub fn is_preset_enabled(&self) -> Result<bool> {
+        self.chan.attr_read_bool("preset_enable")
+    }
+
+    /// Enables or disables the preset.
+    pub fn set_preset_enabled(&self, on: bool) -> Result<()> {
+        self.chan.attr_write_bool("preset_enable", on)
+    }
+
+    /// Determines if the counter is counting.
+    pub fn is_counting(&self) -> Result<bool> {
+        self.chan.attr_read_bool("enable")
+    }
+
+    /// Starts or stops the counter.
+    pub fn set_counting(&self, on: bool) -> Result<()> {
+        self.chan.attr_write_bool("enable", on)
+    }
+
+    /// Gets the direction in which the counter last counted.
+    pub fn direction(&self) -> Result<CountDirection> {
+        self.chan.attr_read_str("count_direction")?.parse()
+    }
+}
+
+impl Deref for Counter {
+    type Target = Channel;
+
+    fn deref(&self) -> &Channel {
+        &self.chan
+    }
+}
+
+impl TryFrom<Channel> for Counter {
+    type Error = Error;
+
+    fn try_from(chan: Channel) -> Result<Self> {
+        Self::new(chan)
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn direction() {
+        assert_eq!(
+            "forward\n".parse::<CountDirection>().unwrap(),
+            CountDirection::Forward
+        );
+        assert_eq!(
+            "backward".parse::<CountDirection>().unwrap(),
+            CountDirection::Backward
+        );
+        assert!("up".parse::<CountDirection>().is_err());
+        assert_eq!(CountDirection::Backward.to_string(), "backward");
+    }
+}
@@ -0,0 +1,256 @@
+// industrial-io/src/debugfs.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Low-level debug access to devices.
+//!
+//! Many IIO drivers expose a `direct_reg_access` debug (debugfs) attribute
+//! that gives raw access to the registers of the underlying chip, over
+//! whatever bus it's attached to (SPI, I2C, etc). A read is done by first
+//! writing the register address to the attribute, then reading it back,
+//! so it is inherently racy if more than one part of an application uses
+//! it at the same time.
+//!
+//! The [`DirectAccess`] type coordinates that. While it exists, it holds
+//! exclusive direct register access to its device within the process, and
+//! [`Device::reg_read()`] and [`Device::reg_write()`] fail for that device
+//! with `EBUSY`. A device is claimed by its [`DeviceIdentity`], from the
+//! URI of its context and its ID, so the claim covers every context in
+//! the process that was opened to the same devices, like two local
+//! contexts. Contexts reaching the
+//! same hardware by different URIs, like a local context and a network
+//! one to the same host, aren't recognized as the same. It also remembers the original value of every register
+//! that it writes, so that they can be put back with
+//! [`DirectAccess::restore()`], or automatically when it is dropped.
+//!
+//! This is intended for driver bring-up and debugging. Writing to the
+//! registers of a device behind the back of its driver can easily leave
+//! the driver and hardware out of sync.
+
+use crate::{Device, DeviceIdentity, Error, Operation, Result};
+use nix::errno::Errno;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Mutex, MutexGuard},
+};
+
+/// The name of the debug attribute for direct register access.
+pub const DIRECT_REG_ACCESS: &str = "direct_reg_access";
+
+/// The devices claimed by a [`DirectAccess`], by their identity.
+///
+/// Register operations hold this lock while they run, so that those from
+/// different threads can't interleave their address and data accesses.
+static CLAIMED: Mutex<BTreeSet<DeviceIdentity>> = Mutex::new(BTreeSet::new());
+
+/// Locks the set of claimed devices.
+///
+/// A panic in another holder can't leave the registers in any worse state
+/// than we'd find them, so this ignores poisoning.
+fn lock_claimed() -> MutexGuard<'static, BTreeSet<DeviceIdentity>> {
+    CLAIMED.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Claims exclusive direct register access to the device with the
+/// identity.
+///
+/// Fails with `EBUSY` if it's already claimed.
+fn claim(key: &DeviceIdentity) -> Result<()> {
+    if lock_claimed().insert(key.clone()) {
+        Ok(())
+    }
+    else {
+        Err(Error::Nix(Errno::EBUSY))
+    }
+}
+
+/// Releases a claim on direct register access to a device.
+fn release(key: &DeviceIdentity) {
+    lock_claimed().remove(key);
+}
+
+/// Runs a register operation on the device, unless a [`DirectAccess`]
+/// holds its registers, in which case this fails with `EBUSY`.
+pub(crate) fn with_reg_access<T, F>(dev: &Device, f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    let key = dev.identity();
+    let claimed = lock_claimed();
+    if claimed.contains(&key) {
+        return Err(Error::Nix(Errno::EBUSY));
+    }
+    f()
+}
+
+/// Guarded, direct access to the registers of a device.
+///
+/// See the [module-level documentation](crate::debugfs) for details.
+#[derive(Debug)]
+pub struct DirectAccess {
+    /// The device being accessed
+    dev: Device,
+    /// The identity under which the device is claimed
+    key: DeviceIdentity,
+    /// The original values of the registers that were written.
+    saved: BTreeMap<u32, u32>,
+    /// Whether to restore the original values when dropped.
+    restore_on_drop: bool,
+}
+
+impl DirectAccess {
+    /// Gets direct access to the registers of the device.
+    ///
+    /// This fails with `EBUSY` if another `DirectAccess` already exists
+    /// for the device, and with [`Error::NotSupported`] if the device
+    /// doesn't expose the `direct_reg_access` debug attribute.
+    pub fn new(dev: &Device) -> Result<Self> {
+        dev.check_supported(Operation::RegisterAccess)?;
+        let key = dev.identity();
+        claim(&key)?;
+
+        Ok(Self {
+            dev: dev.clone(),
+            key,
+            saved: BTreeMap::new(),
+            restore_on_drop: false,
+        })
+    }
+
+    /// Sets whether the original values of the registers that were
+    /// written should be restored when this object is dropped.
+    ///
+    /// This is off by default.
+    pub fn restore_on_drop(mut self, on: bool) -> Self {
+        self.restore_on_drop = on;
+        self
+    }
+
+    /// Gets the device being accessed.
+    pub fn device(&self) -> &Device {
+        &self.dev
+    }
+
+    /// Reads the value of the register at the specified address.
+    pub fn read(&self, addr: u32) -> Result<u32> {
+        let _claimed = lock_claimed();
+        self.dev
+            .debug_attr_write_unjournaled(DIRECT_REG_ACCESS, &format!("0x{:x}", addr))?;
+        let sval = self.dev.debug_attr_read_str(DIRECT_REG_ACCESS)?;
+        parse_reg_value(&sval)
+    }
+
+    /// Writes a value to the register at the specified address.
+    ///
+    /// The first time a register is written, its original value is read
+    /// and saved so that it can be restored later.
+    pub fn write(&mut self, addr: u32, val: u32) -> Result<()> {
+        if !self.saved.contains_key(&addr) {
+            let orig = self.read(addr)?;
+            self.saved.insert(addr, orig);
+        }
+        self.write_raw(addr, val)
+    }
+
+    /// Writes the register without saving the original value.
+    fn write_raw(&self, addr: u32, val: u32) -> Result<()> {
+        self.dev
+            .debug_attr_write_str(DIRECT_REG_ACCESS, &format!("0x{:x} 0x{:x}", addr, val))
+    }
+
+    /// Modifies some of the bits in a register.
+    ///
+    /// The bits set in `mask` are replaced by the corresponding bits in
+    /// `val`. Returns the new value of the register.
+    pub fn modify(&mut self, addr: u32, mask: u32, val: u32) -> Result<u32> {
+        let new = (self.read(addr)? & !mask) | (val & mask);
+        self.write(addr, new)?;
+        Ok(new)
+    }
+
+    /// Gets the original values of the registers that have been written,
+    /// as a map of address to value.
+    pub fn saved(&self) -> &BTreeMap<u32, u32> {
+        &self.saved
+    }
+
+    /// Restores the original values of all the registers that have been
+    /// written, in the reverse order of their addresses.
+    ///
+    /// On success, the saved values are cleared.
+    pub fn restore(&mut self) -> Result<()> {
+        for (addr, val) in self.saved.iter().rev() {
+            self.write_raw(*addr, *val)?;
+        }
+        self.saved.clear();
+        Ok(())
+    }
+}
+
+impl Drop for DirectAccess {
+    /// Restores the original register values, if requested, and releases
+    /// the device.
+    fn drop(&mut self) {
+        if self.restore_on_drop {
+            let _ = self.restore();
+        }
+        release(&self.key);
+    }
+}
+
+/// Parses a register value as reported by the driver.
+///
+/// Drivers report values in hex (with a "0x" prefix) or in decimal.
+fn parse_reg_value(s: &str) -> Result<u32> {
+    let s = s.trim();
+    let res = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse::<u32>(),
+    };
+    res.map_err(|_| Error::StringConversionError)
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reg_value() {
+        assert_eq!(parse_reg_value("0x1F\n").unwrap(), 0x1F);
+        assert_eq!(parse_reg_value("0X1f").unwrap(), 0x1F);
+        assert_eq!(parse_reg_value("31").unwrap(), 31);
+        assert!(parse_reg_value("xyz").is_err());
+    }
+
+    #[test]
+    fn claims() {
+        // Use keys that can't be those of a real device.
+        let key = |uri: &str, id: &str| DeviceIdentity {
+            uri: uri.to_string(),
+            id: id.to_string(),
+            name: None,
+            label: None,
+        };
+        let (a, b) = (key("test:a", "iio:device0"), key("test:a", "iio:device1"));
+
+        claim(&a).unwrap();
+        assert!(matches!(claim(&a), Err(Error::Nix(Errno::EBUSY))));
+        claim(&b).unwrap();
+        claim(&key("test:b", "iio:device0")).unwrap();
+        release(&a);
+        claim(&a).unwrap();
+        release(&a);
+        release(&b);
+        release(&key("test:b", "iio:device0"));
+    }
+}
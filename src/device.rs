@@ -11,13 +11,25 @@
 //!
 
 use super::*;
-use crate::{ffi, Direction, ATTR_BUF_SIZE};
+use crate::{
+    attr_reader::read_growing,
+    events::SYSFS_DEVICES,
+    ffi,
+    journal::{AttrKind, AttrTarget},
+    units::{self, Hertz},
+    Direction,
+};
 use nix::errno::Errno;
 use std::{
+    cell::Cell,
     collections::HashMap,
     ffi::CString,
+    fmt,
+    fs::File,
     os::raw::{c_char, c_longlong, c_uint},
+    path::Path,
     ptr,
+    time::Duration,
 };
 
 /// An Industrial I/O Device
@@ -31,6 +43,72 @@ pub struct Device {
     pub(crate) ctx: Context,
 }
 
+/// A stable key that identifies a device.
+///
+/// Two [`Device`] objects only compare equal if they refer to the same
+/// object in the underlying library, so a device from one context never
+/// matches the same physical device found through a cloned or reconnected
+/// context. The identity is made from the context URI and the device's ID,
+/// name, and label, and can be used as a key to aggregate devices across
+/// contexts, or to re-find a device with
+/// [`Context::find_device_by_identity()`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DeviceIdentity {
+    /// The URI of the context containing the device.
+    pub uri: String,
+    /// The device ID (e.g. <b><i>iio:device0</i></b>)
+    pub id: String,
+    /// The name of the device, if any.
+    pub name: Option<String>,
+    /// The label of the device, if any.
+    pub label: Option<String>,
+}
+
+impl fmt::Display for DeviceIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.uri, self.id)?;
+        if let Some(name) = &self.name {
+            write!(f, " ({})", name)?;
+        }
+        if let Some(label) = &self.label {
+            write!(f, " [{}]", label)?;
+        }
+        Ok(())
+    }
+}
+
+/// Device operations that are not supported by every backend.
+///
+/// Use [`Device::supports()`] to determine if an operation is available
+/// before trying it, so that portable applications can adapt to the
+/// backend in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Reading and writing hardware registers.
+    RegisterAccess,
+    /// Setting the number of kernel buffers.
+    KernelBuffers,
+    /// Reading and writing debug attributes.
+    DebugAttrs,
+    /// Configuring events.
+    Events,
+    /// Creating software triggers.
+    SoftwareTriggers,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Operation::RegisterAccess => "register access",
+            Operation::KernelBuffers => "kernel buffers",
+            Operation::DebugAttrs => "debug attributes",
+            Operation::Events => "events",
+            Operation::SoftwareTriggers => "software triggers",
+        };
+        write!(f, "{}", s)
+    }
+}
+
 impl Device {
     /// Gets the context to which the device belongs
     pub fn context(&self) -> Context {
@@ -56,6 +134,24 @@ impl Device {
         cstring_opt(pstr)
     }
 
+    /// Gets a stable identity key for the device.
+    ///
+    /// Unlike comparisons of `Device` objects, this can be used to match
+    /// the same device across different contexts.
+    pub fn identity(&self) -> DeviceIdentity {
+        #[cfg(not(any(feature = "libiio_v0_19", feature = "libiio_v0_21")))]
+        let label = self.label();
+        #[cfg(any(feature = "libiio_v0_19", feature = "libiio_v0_21"))]
+        let label = None;
+
+        DeviceIdentity {
+            uri: self.ctx.uri().unwrap_or_default(),
+            id: self.id().unwrap_or_default(),
+            name: self.name(),
+            label,
+        }
+    }
+
     /// Determines if the device is capable of buffered I/O.
     /// This is true if any of the channels are scan elements.
     pub fn is_buffer_capable(&self) -> bool {
@@ -74,9 +170,15 @@ impl Device {
     }
 
     /// Associate a trigger for this device.
-    /// `trigger` The device to be used as a trigger.
-    pub fn set_trigger(&self, trigger: &Self) -> Result<()> {
-        let ret = unsafe { ffi::iio_device_set_trigger(self.dev, trigger.dev) };
+    /// `trigger` The trigger to be used for the device.
+    ///
+    /// Only a [`Trigger`] is accepted, so a device that isn't a trigger
+    /// can't be assigned by mistake. Use
+    /// [`as_trigger()`](Device::as_trigger) or
+    /// [`Trigger::new()`](crate::Trigger::new) to get one from a plain
+    /// `Device`.
+    pub fn set_trigger(&self, trigger: &Trigger) -> Result<()> {
+        let ret = unsafe { ffi::iio_device_set_trigger(self.dev, trigger.device().dev) };
         sys_result(ret, ())
     }
 
@@ -88,8 +190,83 @@ impl Device {
 
     /// Set the number of kernel buffers for the device.
     pub fn set_num_kernel_buffers(&self, n: u32) -> Result<()> {
+        self.check_supported(Operation::KernelBuffers)?;
         let ret = unsafe { ffi::iio_device_set_kernel_buffers_count(self.dev, n as c_uint) };
-        sys_result(ret, ())
+        self.op_result(Operation::KernelBuffers, ret, ())
+    }
+
+    // ----- Backend Capabilities -----
+
+    /// Determines if the backend of the device's context supports the
+    /// specified operation for this device.
+    ///
+    /// This is a best guess from the type of backend and the attributes
+    /// that the device exposes. Even if this returns `true`, the operation
+    /// may still fail with [`Error::NotSupported`] if the backend rejects
+    /// it at runtime.
+    pub fn supports(&self, op: Operation) -> bool {
+        if self.ctx.name() == "xml" {
+            return false;
+        }
+        match op {
+            Operation::RegisterAccess => {
+                let attr = CString::new("direct_reg_access").unwrap();
+                let pstr = unsafe { ffi::iio_device_find_debug_attr(self.dev, attr.as_ptr()) };
+                !pstr.is_null()
+            }
+            // The backends that talk to the kernel, directly or through
+            // the IIO daemon, can change the number of buffers.
+            Operation::KernelBuffers => {
+                matches!(self.ctx.name().as_str(), "local" | "network" | "usb")
+            }
+            Operation::DebugAttrs => unsafe { ffi::iio_device_get_debug_attrs_count(self.dev) > 0 },
+            Operation::Events | Operation::SoftwareTriggers => {
+                cfg!(target_os = "linux") && self.ctx.name() == "local"
+            }
+        }
+    }
+
+    /// Creates an error for an operation that isn't supported.
+    fn not_supported(&self, op: Operation) -> Error {
+        Error::NotSupported {
+            backend: self.ctx.name(),
+            op,
+        }
+    }
+
+    /// Returns an error if the operation is known not to be supported.
+    pub(crate) fn check_supported(&self, op: Operation) -> Result<()> {
+        if self.supports(op) {
+            Ok(())
+        }
+        else {
+            Err(self.not_supported(op))
+        }
+    }
+
+    /// Converts the return value from the C library for a backend-dependent
+    /// operation, mapping "not implemented" errors to
+    /// [`Error::NotSupported`].
+    pub(crate) fn op_result<T>(&self, op: Operation, ret: i32, result: T) -> Result<T> {
+        if ret == -(Errno::ENOSYS as i32) || ret == -(Errno::EOPNOTSUPP as i32) {
+            Err(self.not_supported(op))
+        }
+        else {
+            sys_result(ret, result)
+        }
+    }
+
+    /// Adds the attribute and device to an error from the C library.
+    ///
+    /// See [`Error::Attr`].
+    pub(crate) fn attr_err(&self, attr: &str, err: Error) -> Error {
+        err.with_attr(attr, self.id().unwrap_or_default(), None)
+    }
+
+    /// Converts the return value of an attribute operation in the C
+    /// library to a result, with the attribute and device in any error.
+    pub(crate) fn attr_result<T>(&self, attr: &str, ret: i32, result: T) -> Result<T> {
+        sys_result(ret, result).map_err(|err| self.attr_err(attr, err))
     }
 
     // ----- Attributes -----
@@ -118,7 +295,8 @@ impl Device {
     }
 
     /// Determines if a buffer-specific attribute exists
-    pub fn has_attr(&self, name: &str) -> bool {
+    pub fn has_attr(&self, name: impl AsRef<str>) -> bool {
+        let name = name.as_ref();
         let cname = cstring_or_bail_false!(name);
         let pstr = unsafe { ffi::iio_device_find_attr(self.dev, cname.as_ptr()) };
         !pstr.is_null()
@@ -127,7 +305,8 @@ impl Device {
     /// Reads a device-specific attribute
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read<T: FromAttribute>(&self, attr: &str) -> Result<T> {
+    pub fn attr_read<T: FromAttribute>(&self, attr: impl AsRef<str>) -> Result<T> {
+        let attr = attr.as_ref();
         let sval = self.attr_read_str(attr)?;
         T::from_attr(&sval)
     }
@@ -135,13 +314,14 @@ impl Device {
     /// Reads a device-specific attribute as a string
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read_str(&self, attr: &str) -> Result<String> {
-        let mut buf = vec![0 as c_char; ATTR_BUF_SIZE];
-        let attr = CString::new(attr)?;
+    pub fn attr_read_str(&self, attr: impl AsRef<str>) -> Result<String> {
+        let attr = attr.as_ref();
+        let mut buf = vec![0 as c_char; self.ctx.attr_buf_size()];
+        let cattr = CString::new(attr)?;
         let ret = unsafe {
-            ffi::iio_device_attr_read(self.dev, attr.as_ptr(), buf.as_mut_ptr(), buf.len())
+            ffi::iio_device_attr_read(self.dev, cattr.as_ptr(), buf.as_mut_ptr(), buf.len())
         };
-        sys_result(ret as i32, ())?;
+        self.attr_result(attr, ret as i32, ())?;
         let s = unsafe {
             CStr::from_ptr(buf.as_ptr())
                 .to_str()
@@ -150,34 +330,96 @@ impl Device {
         Ok(s.into())
     }
 
+    /// Gets a reader for the value of a device-specific attribute.
+    ///
+    /// This is for attributes that may be too large to read as a string,
+    /// like calibration blobs. See the [`attr_reader`](crate::attr_reader)
+    /// module.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_reader(&self, attr: impl AsRef<str>) -> Result<AttrReader> {
+        let attr = attr.as_ref();
+        if !self.has_attr(attr) {
+            return Err(self.attr_err(attr, Errno::ENOENT.into()));
+        }
+        if let (true, Some(id)) = (self.ctx.name() == "local", self.id()) {
+            let path = Path::new(SYSFS_DEVICES).join(id).join(attr);
+            if let Ok(file) = File::open(path) {
+                return Ok(AttrReader::from_file(file));
+            }
+        }
+        let cattr = CString::new(attr)?;
+        let data = read_growing(self.ctx.attr_buf_size(), |buf| unsafe {
+            ffi::iio_device_attr_read(self.dev, cattr.as_ptr(), buf.as_mut_ptr(), buf.len())
+        })?;
+        Ok(AttrReader::from_vec(data))
+    }
+
     /// Reads a device-specific attribute as a boolean
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read_bool(&self, attr: &str) -> Result<bool> {
+    pub fn attr_read_bool(&self, attr: impl AsRef<str>) -> Result<bool> {
+        let attr = attr.as_ref();
         let mut val: bool = false;
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_device_attr_read_bool(self.dev, attr.as_ptr(), &mut val) };
-        sys_result(ret, val)
+        let cattr = CString::new(attr)?;
+        let ret = unsafe { ffi::iio_device_attr_read_bool(self.dev, cattr.as_ptr(), &mut val) };
+        self.attr_result(attr, ret, val)
     }
 
     /// Reads a device-specific attribute as an integer (i64)
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read_int(&self, attr: &str) -> Result<i64> {
+    pub fn attr_read_int(&self, attr: impl AsRef<str>) -> Result<i64> {
+        let attr = attr.as_ref();
         let mut val: c_longlong = 0;
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_device_attr_read_longlong(self.dev, attr.as_ptr(), &mut val) };
-        sys_result(ret, val as i64)
+        let cattr = CString::new(attr)?;
+        let ret = unsafe { ffi::iio_device_attr_read_longlong(self.dev, cattr.as_ptr(), &mut val) };
+        self.attr_result(attr, ret, val as i64)
     }
 
     /// Reads a device-specific attribute as a floating-point (f64) number
     ///
     /// `attr` The name of the attribute
-    pub fn attr_read_float(&self, attr: &str) -> Result<f64> {
+    pub fn attr_read_float(&self, attr: impl AsRef<str>) -> Result<f64> {
+        let attr = attr.as_ref();
         let mut val: f64 = 0.0;
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_device_attr_read_double(self.dev, attr.as_ptr(), &mut val) };
-        sys_result(ret, val)
+        let cattr = CString::new(attr)?;
+        let ret = unsafe { ffi::iio_device_attr_read_double(self.dev, cattr.as_ptr(), &mut val) };
+        self.attr_result(attr, ret, val)
+    }
+
+    /// Reads a time-like device-specific attribute as a duration
+    ///
+    /// The value is converted from the units of the attribute, found by
+    /// name with [`units::attr_unit()`]. This fails if the attribute
+    /// doesn't have known units of time.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_duration(&self, attr: impl AsRef<str>) -> Result<Duration> {
+        let attr = attr.as_ref();
+        units::duration_from_attr(attr, self.attr_read_float(attr)?)
+    }
+
+    /// Reads a frequency device-specific attribute
+    ///
+    /// This fails if the attribute doesn't have known units of frequency.
+    /// See [`units::attr_unit()`].
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_freq(&self, attr: impl AsRef<str>) -> Result<Hertz> {
+        let attr = attr.as_ref();
+        units::hertz_from_attr(attr, self.attr_read_float(attr)?)
+    }
+
+    /// Reads a device-specific attribute as a list of values
+    ///
+    /// The values can be separated by whitespace or commas. An error
+    /// reports the first value that couldn't be parsed.
+    ///
+    /// `attr` The name of the attribute
+    pub fn attr_read_vec<T: FromAttribute>(&self, attr: impl AsRef<str>) -> Result<Vec<T>> {
+        let attr = attr.as_ref();
+        parse_attr_list(&self.attr_read_str(attr)?)
     }
 
     /// Reads all the device-specific attributes.
@@ -190,11 +432,33 @@ impl Device {
         sys_result(ret, map)
     }
 
+    /// Gets an iterator over the names and values of the device-specific
+    /// attributes.
+    ///
+    /// With the network backend, the values are all fetched up front with
+    /// a single call to [`attr_read_all()`](Self::attr_read_all). Otherwise
+    /// each one is read as the iterator reaches it.
+    pub fn attrs_with_values(&self) -> impl Iterator<Item = (String, Result<String>)> + '_ {
+        let mut vals = if self.ctx.name() == "network" {
+            self.attr_read_all().unwrap_or_default()
+        }
+        else {
+            HashMap::new()
+        };
+        self.attributes().map(move |name| {
+            let val = vals
+                .remove(&name)
+                .map_or_else(|| self.attr_read_str(&name), Ok);
+            (name, val)
+        })
+    }
+
     /// Writes a device-specific attribute
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write<T: ToAttribute>(&self, attr: &str, val: T) -> Result<()> {
+    pub fn attr_write<T: ToAttribute>(&self, attr: impl AsRef<str>, val: T) -> Result<()> {
+        let attr = attr.as_ref();
         let sval = T::to_attr(&val)?;
         self.attr_write_str(attr, &sval)
     }
@@ -203,41 +467,141 @@ impl Device {
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_str(&self, attr: &str, val: &str) -> Result<()> {
-        let attr = CString::new(attr)?;
-        let val = CString::new(val)?;
-        let ret = unsafe { ffi::iio_device_attr_write(self.dev, attr.as_ptr(), val.as_ptr()) };
-        sys_result(ret as i32, ())
+    pub fn attr_write_str(&self, attr: impl AsRef<str>, val: &str) -> Result<()> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
+        let cval = CString::new(val)?;
+        self.journaled(AttrKind::Device, attr, val, || {
+            let ret =
+                unsafe { ffi::iio_device_attr_write(self.dev, cattr.as_ptr(), cval.as_ptr()) };
+            self.attr_result(attr, ret as i32, ())
+        })
+    }
+
+    /// Writes a set of device-specific attributes in a single operation.
+    ///
+    /// This is especially useful when using the network backend to
+    /// set all the attributes with a single call. It fails, without writing
+    /// anything, if any of the attributes doesn't exist.
+    pub fn attr_write_all(&self, vals: &HashMap<String, String>) -> Result<()> {
+        for (attr, val) in vals {
+            if !self.has_attr(attr) {
+                return Err(self.attr_err(attr, Errno::ENOENT.into()));
+            }
+            CString::new(val.as_str())?;
+        }
+        self.ctx.journaled_all(
+            || AttrTarget {
+                kind: AttrKind::Device,
+                device: self.id().unwrap_or_default(),
+                channel: None,
+            },
+            vals,
+            || self.attr_read_all().unwrap_or_default(),
+            |attr, val| self.check_write(attr, val),
+            || {
+                let pmap = (vals as *const HashMap<_, _>).cast_mut().cast();
+                let ret = unsafe {
+                    ffi::iio_device_attr_write_all(self.dev, Some(attr_write_all_cb), pmap)
+                };
+                sys_result(ret, ())
+            },
+        )
+    }
+
+    /// Gets the values available for a device-specific attribute, from its
+    /// `<attr>_available` companion attribute, if it has one.
+    pub fn attr_available(&self, attr: impl AsRef<str>) -> Result<Option<Available>> {
+        let attr = attr.as_ref();
+        let avail = Available::attr_name(attr);
+        if !self.has_attr(&avail) {
+            return Ok(None);
+        }
+        self.attr_read_str(&avail)?.parse().map(Some)
+    }
+
+    /// Writes a device-specific attribute, after checking the value against
+    /// the values available for it.
+    ///
+    /// If the attribute doesn't have an `<attr>_available` companion, the
+    /// value is written without a check.
+    pub fn validated_write<T: ToAttribute>(&self, attr: impl AsRef<str>, val: T) -> Result<()> {
+        let attr = attr.as_ref();
+        let sval = T::to_attr(&val)?;
+        if let Some(avail) = self.attr_available(attr)? {
+            avail.check(&sval)?;
+        }
+        self.attr_write_str(attr, &sval)
     }
 
     /// Writes a device-specific attribute as a boolean
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_bool(&self, attr: &str, val: bool) -> Result<()> {
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_device_attr_write_bool(self.dev, attr.as_ptr(), val) };
-        sys_result(ret, ())
+    pub fn attr_write_bool(&self, attr: impl AsRef<str>, val: bool) -> Result<()> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
+        self.journaled(AttrKind::Device, attr, if val { "1" } else { "0" }, || {
+            let ret = unsafe { ffi::iio_device_attr_write_bool(self.dev, cattr.as_ptr(), val) };
+            self.attr_result(attr, ret, ())
+        })
     }
 
     /// Writes a device-specific attribute as an integer (i64)
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_int(&self, attr: &str, val: i64) -> Result<()> {
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_device_attr_write_longlong(self.dev, attr.as_ptr(), val) };
-        sys_result(ret, ())
+    pub fn attr_write_int(&self, attr: impl AsRef<str>, val: i64) -> Result<()> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
+        self.journaled(AttrKind::Device, attr, &val.to_string(), || {
+            let ret = unsafe { ffi::iio_device_attr_write_longlong(self.dev, cattr.as_ptr(), val) };
+            self.attr_result(attr, ret, ())
+        })
     }
 
     /// Writes a device-specific attribute as a floating-point (f64) number
     ///
     /// `attr` The name of the attribute
     /// `val` The value to write
-    pub fn attr_write_float(&self, attr: &str, val: f64) -> Result<()> {
-        let attr = CString::new(attr)?;
-        let ret = unsafe { ffi::iio_device_attr_write_double(self.dev, attr.as_ptr(), val) };
-        sys_result(ret, ())
+    pub fn attr_write_float(&self, attr: impl AsRef<str>, val: f64) -> Result<()> {
+        let attr = attr.as_ref();
+        let cattr = CString::new(attr)?;
+        self.journaled(AttrKind::Device, attr, &val.to_string(), || {
+            let ret = unsafe { ffi::iio_device_attr_write_double(self.dev, cattr.as_ptr(), val) };
+            self.attr_result(attr, ret, ())
+        })
+    }
+
+    /// Writes a time-like device-specific attribute from a duration
+    ///
+    /// The value is converted to the units of the attribute. See
+    /// [`attr_read_duration()`](Self::attr_read_duration).
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_duration(&self, attr: impl AsRef<str>, val: Duration) -> Result<()> {
+        let attr = attr.as_ref();
+        self.attr_write_float(attr, units::duration_to_attr(attr, val)?)
+    }
+
+    /// Writes a frequency device-specific attribute
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn attr_write_freq(&self, attr: impl AsRef<str>, val: Hertz) -> Result<()> {
+        let attr = attr.as_ref();
+        self.attr_write_float(attr, units::hertz_to_attr(attr, val)?)
+    }
+
+    /// Writes a device-specific attribute as a list of values, separated by
+    /// spaces
+    ///
+    /// `attr` The name of the attribute
+    /// `vals` The values to write
+    pub fn attr_write_vec<T: ToAttribute>(&self, attr: impl AsRef<str>, vals: &[T]) -> Result<()> {
+        let attr = attr.as_ref();
+        self.attr_write_str(attr, &format_attr_list(vals)?)
     }
 
     /// Gets an iterator for the attributes in the device
@@ -245,6 +609,279 @@ impl Device {
         AttrIterator { dev: self, idx: 0 }
     }
 
+    /// Gets the names of the buffer attributes, without needing a buffer.
+    pub(crate) fn buffer_attr_names(&self) -> Vec<String> {
+        let n = unsafe { ffi::iio_device_get_buffer_attrs_count(self.dev) };
+        (0..n)
+            .filter_map(|i| {
+                let pstr = unsafe { ffi::iio_device_get_buffer_attr(self.dev, i) };
+                cstring_opt(pstr)
+            })
+            .collect()
+    }
+
+    /// Reads a buffer attribute as a string, without needing a buffer.
+    pub(crate) fn buffer_attr_read_str(&self, attr: &str) -> Result<String> {
+        let mut buf = vec![0 as c_char; self.ctx.attr_buf_size()];
+        let cattr = CString::new(attr)?;
+        let ret = unsafe {
+            ffi::iio_device_buffer_attr_read(self.dev, cattr.as_ptr(), buf.as_mut_ptr(), buf.len())
+        };
+        self.attr_result(attr, ret as i32, ())?;
+        let s = unsafe {
+            CStr::from_ptr(buf.as_ptr())
+                .to_str()
+                .map_err(|_| Error::StringConversionError)?
+        };
+        Ok(s.into())
+    }
+
+    // ----- Debug Attributes -----
+
+    /// Determines if the device has any debug attributes
+    pub fn has_debug_attrs(&self) -> bool {
+        unsafe { ffi::iio_device_get_debug_attrs_count(self.dev) > 0 }
+    }
+
+    /// Gets the number of debug attributes
+    pub fn num_debug_attrs(&self) -> usize {
+        unsafe { ffi::iio_device_get_debug_attrs_count(self.dev) as usize }
+    }
+
+    /// Gets the name of the debug attribute at the index
+    pub fn get_debug_attr(&self, idx: usize) -> Result<String> {
+        let pstr = unsafe { ffi::iio_device_get_debug_attr(self.dev, idx as c_uint) };
+        cstring_opt(pstr).ok_or(Error::InvalidIndex)
+    }
+
+    /// Try to find a debug attribute by its name
+    pub fn find_debug_attr(&self, name: &str) -> Option<String> {
+        let cname = cstring_or_bail!(name);
+        let pstr = unsafe { ffi::iio_device_find_debug_attr(self.dev, cname.as_ptr()) };
+        cstring_opt(pstr)
+    }
+
+    /// Determines if a debug attribute exists
+    pub fn has_debug_attr(&self, name: impl AsRef<str>) -> bool {
+        let name = name.as_ref();
+        let cname = cstring_or_bail_false!(name);
+        let pstr = unsafe { ffi::iio_device_find_debug_attr(self.dev, cname.as_ptr()) };
+        !pstr.is_null()
+    }
+
+    /// Reads a debug attribute
+    ///
+    /// `attr` The name of the attribute
+    pub fn debug_attr_read<T: FromAttribute>(&self, attr: impl AsRef<str>) -> Result<T> {
+        let attr = attr.as_ref();
+        let sval = self.debug_attr_read_str(attr)?;
+        T::from_attr(&sval)
+    }
+
+    /// Reads a debug attribute as a string
+    ///
+    /// `attr` The name of the attribute
+    pub fn debug_attr_read_str(&self, attr: impl AsRef<str>) -> Result<String> {
+        let attr = attr.as_ref();
+        self.check_supported(Operation::DebugAttrs)?;
+        let mut buf = vec![0 as c_char; self.ctx.attr_buf_size()];
+        let cattr = CString::new(attr)?;
+        let ret = unsafe {
+            ffi::iio_device_debug_attr_read(self.dev, cattr.as_ptr(), buf.as_mut_ptr(), buf.len())
+        };
+        self.op_result(Operation::DebugAttrs, ret as i32, ())
+            .map_err(|err| self.attr_err(attr, err))?;
+        let s = unsafe {
+            CStr::from_ptr(buf.as_ptr())
+                .to_str()
+                .map_err(|_| Error::StringConversionError)?
+        };
+        Ok(s.into())
+    }
+
+    /// Reads a debug attribute as a boolean
+    ///
+    /// `attr` The name of the attribute
+    pub fn debug_attr_read_bool(&self, attr: impl AsRef<str>) -> Result<bool> {
+        let attr = attr.as_ref();
+        self.check_supported(Operation::DebugAttrs)?;
+        let mut val: bool = false;
+        let cattr = CString::new(attr)?;
+        let ret =
+            unsafe { ffi::iio_device_debug_attr_read_bool(self.dev, cattr.as_ptr(), &mut val) };
+        self.op_result(Operation::DebugAttrs, ret, val)
+            .map_err(|err| self.attr_err(attr, err))
+    }
+
+    /// Reads a debug attribute as an integer (i64)
+    ///
+    /// `attr` The name of the attribute
+    pub fn debug_attr_read_int(&self, attr: impl AsRef<str>) -> Result<i64> {
+        let attr = attr.as_ref();
+        self.check_supported(Operation::DebugAttrs)?;
+        let mut val: c_longlong = 0;
+        let cattr = CString::new(attr)?;
+        let ret =
+            unsafe { ffi::iio_device_debug_attr_read_longlong(self.dev, cattr.as_ptr(), &mut val) };
+        self.op_result(Operation::DebugAttrs, ret, val as i64)
+            .map_err(|err| self.attr_err(attr, err))
+    }
+
+    /// Reads a debug attribute as a floating-point (f64) number
+    ///
+    /// `attr` The name of the attribute
+    pub fn debug_attr_read_float(&self, attr: impl AsRef<str>) -> Result<f64> {
+        let attr = attr.as_ref();
+        self.check_supported(Operation::DebugAttrs)?;
+        let mut val: f64 = 0.0;
+        let cattr = CString::new(attr)?;
+        let ret =
+            unsafe { ffi::iio_device_debug_attr_read_double(self.dev, cattr.as_ptr(), &mut val) };
+        self.op_result(Operation::DebugAttrs, ret, val)
+            .map_err(|err| self.attr_err(attr, err))
+    }
+
+    /// Writes a debug attribute
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn debug_attr_write<T: ToAttribute>(&self, attr: impl AsRef<str>, val: T) -> Result<()> {
+        let attr = attr.as_ref();
+        let sval = T::to_attr(&val)?;
+        self.debug_attr_write_str(attr, &sval)
+    }
+
+    /// Writes a debug attribute as a string
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn debug_attr_write_str(&self, attr: impl AsRef<str>, val: &str) -> Result<()> {
+        let attr = attr.as_ref();
+        self.check_supported(Operation::DebugAttrs)?;
+        self.journaled(AttrKind::Debug, attr, val, || {
+            self.debug_attr_write_unjournaled(attr, val)
+        })
+    }
+
+    /// Writes a debug attribute as a boolean
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn debug_attr_write_bool(&self, attr: impl AsRef<str>, val: bool) -> Result<()> {
+        let attr = attr.as_ref();
+        self.check_supported(Operation::DebugAttrs)?;
+        let cattr = CString::new(attr)?;
+        self.journaled(AttrKind::Debug, attr, if val { "1" } else { "0" }, || {
+            let ret =
+                unsafe { ffi::iio_device_debug_attr_write_bool(self.dev, cattr.as_ptr(), val) };
+            self.op_result(Operation::DebugAttrs, ret, ())
+                .map_err(|err| self.attr_err(attr, err))
+        })
+    }
+
+    /// Writes a debug attribute as an integer (i64)
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn debug_attr_write_int(&self, attr: impl AsRef<str>, val: i64) -> Result<()> {
+        let attr = attr.as_ref();
+        self.check_supported(Operation::DebugAttrs)?;
+        let cattr = CString::new(attr)?;
+        self.journaled(AttrKind::Debug, attr, &val.to_string(), || {
+            let ret =
+                unsafe { ffi::iio_device_debug_attr_write_longlong(self.dev, cattr.as_ptr(), val) };
+            self.op_result(Operation::DebugAttrs, ret, ())
+                .map_err(|err| self.attr_err(attr, err))
+        })
+    }
+
+    /// Writes a debug attribute as a floating-point (f64) number
+    ///
+    /// `attr` The name of the attribute
+    /// `val` The value to write
+    pub fn debug_attr_write_float(&self, attr: impl AsRef<str>, val: f64) -> Result<()> {
+        let attr = attr.as_ref();
+        self.check_supported(Operation::DebugAttrs)?;
+        let cattr = CString::new(attr)?;
+        self.journaled(AttrKind::Debug, attr, &val.to_string(), || {
+            let ret =
+                unsafe { ffi::iio_device_debug_attr_write_double(self.dev, cattr.as_ptr(), val) };
+            self.op_result(Operation::DebugAttrs, ret, ())
+                .map_err(|err| self.attr_err(attr, err))
+        })
+    }
+
+    /// Gets an iterator for the debug attributes in the device
+    pub fn debug_attributes(&self) -> DebugAttrIterator<'_> {
+        DebugAttrIterator { dev: self, idx: 0 }
+    }
+
+    /// Writes a debug attribute as a string, bypassing the journal and
+    /// dry-run mode.
+    ///
+    /// This is for writes that only select what a following read returns,
+    /// like the address of a register, and don't change the hardware.
+    pub(crate) fn debug_attr_write_unjournaled(&self, attr: &str, val: &str) -> Result<()> {
+        self.check_supported(Operation::DebugAttrs)?;
+        let cattr = CString::new(attr)?;
+        let val = CString::new(val)?;
+        let ret =
+            unsafe { ffi::iio_device_debug_attr_write(self.dev, cattr.as_ptr(), val.as_ptr()) };
+        self.op_result(Operation::DebugAttrs, ret as i32, ())
+            .map_err(|err| self.attr_err(attr, err))
+    }
+
+    /// Performs a write of a device or debug attribute, recording it in
+    /// the journal of the context, if enabled.
+    ///
+    /// The old values of debug attributes aren't read, since reading them
+    /// can have side effects on the hardware.
+    fn journaled<W>(&self, kind: AttrKind, attr: &str, val: &str, write: W) -> Result<()>
+    where
+        W: FnOnce() -> Result<()>,
+    {
+        self.ctx.journaled(
+            || AttrTarget {
+                kind,
+                device: self.id().unwrap_or_default(),
+                channel: None,
+            },
+            attr,
+            val,
+            || match kind {
+                AttrKind::Debug => None,
+                _ => self.attr_read_str(attr).ok(),
+            },
+            || match kind {
+                AttrKind::Debug => self.check_debug_attr(attr),
+                _ => self.check_write(attr, val),
+            },
+            write,
+        )
+    }
+
+    /// Checks that a write of a device attribute would be accepted, for a
+    /// dry run.
+    fn check_write(&self, attr: &str, val: &str) -> Result<()> {
+        if !self.has_attr(attr) {
+            return Err(self.attr_err(attr, Errno::ENOENT.into()));
+        }
+        if let Some(avail) = self.attr_available(attr)? {
+            avail.check(val)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the device has a debug attribute, for a dry run.
+    fn check_debug_attr(&self, attr: &str) -> Result<()> {
+        if self.has_debug_attr(attr) {
+            Ok(())
+        }
+        else {
+            Err(self.attr_err(attr, Errno::ENOENT.into()))
+        }
+    }
+
     // ----- Channels -----
 
     /// Gets the number of channels on the device
@@ -260,6 +897,7 @@ impl Device {
         }
         Ok(Channel {
             chan,
+            fmt: None,
             ctx: self.context(),
         })
     }
@@ -276,6 +914,7 @@ impl Device {
         else {
             Some(Channel {
                 chan,
+                fmt: None,
                 ctx: self.context(),
             })
         }
@@ -293,18 +932,67 @@ impl Device {
         self.find_channel(name, Direction::Output)
     }
 
+    /// Try to find a channel by its type, modifier, and direction
+    ///
+    /// This finds a channel like the X axis of an accelerometer input
+    /// without matching its ID. If `modifier` is `None`, a channel with
+    /// any modifier matches. If more than one channel matches, the first
+    /// one is returned.
+    pub fn find_channel_by_type(
+        &self,
+        chan_type: ChannelType,
+        modifier: Option<ChannelModifier>,
+        dir: Direction,
+    ) -> Option<Channel> {
+        self.channels_with_type(chan_type).find(|chan| {
+            chan.direction() == dir && modifier.map_or(true, |m| chan.channel_modifier() == m)
+        })
+    }
+
     /// Gets an iterator for the channels in the device
     pub fn channels(&self) -> ChannelIterator {
         ChannelIterator { dev: self, idx: 0 }
     }
 
+    /// Gets an iterator for the input channels in the device
+    pub fn input_channels(&self) -> impl Iterator<Item = Channel> + '_ {
+        self.channels().filter(|chan| chan.is_input())
+    }
+
+    /// Gets an iterator for the output channels in the device
+    pub fn output_channels(&self) -> impl Iterator<Item = Channel> + '_ {
+        self.channels().filter(|chan| chan.is_output())
+    }
+
+    /// Gets an iterator for the channels in the device that can be used
+    /// with a buffer, in order of their scan index.
+    pub fn scan_elements(&self) -> impl Iterator<Item = Channel> {
+        let mut chans: Vec<_> = self
+            .channels()
+            .filter(|chan| chan.is_scan_element())
+            .collect();
+        chans.sort_by_key(|chan| chan.index().unwrap_or(usize::MAX));
+        chans.into_iter()
+    }
+
+    /// Gets an iterator for the channels in the device of a given type
+    pub fn channels_with_type(&self, chan_type: ChannelType) -> impl Iterator<Item = Channel> + '_ {
+        self.channels()
+            .filter(move |chan| chan.channel_type() == chan_type)
+    }
+
     // ----- Buffer Functions -----
 
     /// Creates a buffer for the device.
     ///
     /// `sample_count` The number of samples the buffer should hold
     /// `cyclic` Whether to enable cyclic mode.
+    ///
+    /// This fails with [`Error::InvalidBufferSize`] if the sample count is
+    /// zero, or if the buffer would be larger than the maximum set for the
+    /// context with [`Context::set_max_buffer_size()`].
     pub fn create_buffer(&self, sample_count: usize, cyclic: bool) -> Result<Buffer> {
+        self.check_buffer_size(sample_count)?;
         let buf = unsafe { ffi::iio_device_create_buffer(self.dev, sample_count, cyclic) };
         if buf.is_null() {
             return Err(Errno::last().into());
@@ -313,9 +1001,74 @@ impl Device {
             buf,
             cap: sample_count,
             dev: self.clone(),
+            blocking: Cell::new(true),
         })
     }
 
+    /// Checks that a buffer of the number of samples is allowed, for the
+    /// channels that are currently enabled.
+    fn check_buffer_size(&self, sample_count: usize) -> Result<()> {
+        let sample_size = self.sample_size().unwrap_or(0);
+        let bytes = sample_count.saturating_mul(sample_size);
+        let max = self.ctx.max_buffer_size();
+        if sample_count == 0 || bytes > max {
+            return Err(Error::InvalidBufferSize {
+                samples: sample_count,
+                bytes,
+                max,
+            });
+        }
+        Ok(())
+    }
+
+    /// Captures a burst of samples from the enabled channels.
+    ///
+    /// This creates a buffer, which enables streaming in the device,
+    /// captures exactly `n` samples from each of the enabled scan elements,
+    /// then destroys the buffer to stop streaming again. This minimizes the
+    /// time that the device is streaming, which is useful for
+    /// power-constrained systems doing periodic captures.
+    ///
+    /// The samples are read in chunks of a default size. See
+    /// [`capture_burst_chunked()`](Device::capture_burst_chunked).
+    pub fn capture_burst(&self, n: usize) -> Result<Frame> {
+        const DFLT_CHUNK_SIZE: usize = 4096;
+        self.capture_burst_chunked(n, DFLT_CHUNK_SIZE)
+    }
+
+    /// Captures a burst of samples from the enabled channels, reading them
+    /// from the device in chunks of the specified size.
+    ///
+    /// This fails with [`Error::InvalidBufferSize`] if `n` is zero or no
+    /// scan elements are enabled.
+    pub fn capture_burst_chunked(&self, n: usize, chunk_size: usize) -> Result<Frame> {
+        let chans: Vec<_> = self
+            .channels()
+            .filter(|chan| chan.is_scan_element() && chan.is_enabled())
+            .collect();
+
+        if n == 0 || chans.is_empty() {
+            return Err(Error::InvalidBufferSize {
+                samples: n,
+                bytes: 0,
+                max: self.ctx.max_buffer_size(),
+            });
+        }
+
+        let mut buf = self.create_buffer(chunk_size.clamp(1, n), false)?;
+        buf.refill()?;
+        let mut frame = Frame::from_buffer(&buf, &chans)?;
+
+        while frame.len() < n {
+            buf.refill()?;
+            frame.extend(&Frame::from_buffer(&buf, &chans)?);
+        }
+        drop(buf);
+
+        frame.truncate(n);
+        Ok(frame)
+    }
+
     // ----- Low-level & Debug functions -----
 
     /// Gets the current sample size, in bytes.
@@ -327,22 +1080,142 @@ impl Device {
     }
 
     /// Gets the value of a hardware register
+    ///
+    /// This is not supported by all backends. See [`Device::supports()`].
+    /// It fails with `EBUSY` while a
+    /// [`DirectAccess`](crate::debugfs::DirectAccess) holds the registers
+    /// of the device.
     pub fn reg_read(&self, addr: u32) -> Result<u32> {
-        let mut val: u32 = 0;
-        let ret = unsafe { ffi::iio_device_reg_read(self.dev, addr, &mut val) };
-        sys_result(ret as i32, val)
+        self.check_supported(Operation::RegisterAccess)?;
+        debugfs::with_reg_access(self, || {
+            let mut val: u32 = 0;
+            let ret = unsafe { ffi::iio_device_reg_read(self.dev, addr, &mut val) };
+            self.op_result(Operation::RegisterAccess, ret as i32, val)
+        })
     }
 
     /// Sets the value of a hardware register
+    ///
+    /// This is not supported by all backends. See [`Device::supports()`].
+    /// It fails with `EBUSY` while a
+    /// [`DirectAccess`](crate::debugfs::DirectAccess) holds the registers
+    /// of the device.
     pub fn reg_write(&self, addr: u32, val: u32) -> Result<()> {
-        let ret = unsafe { ffi::iio_device_reg_write(self.dev, addr, val) };
-        sys_result(ret as i32, ())
+        self.check_supported(Operation::RegisterAccess)?;
+        self.ctx.journaled(
+            || AttrTarget {
+                kind: AttrKind::Register,
+                device: self.id().unwrap_or_default(),
+                channel: None,
+            },
+            &format!("0x{:x}", addr),
+            &format!("0x{:x}", val),
+            || None,
+            || Ok(()),
+            || {
+                debugfs::with_reg_access(self, || {
+                    let ret = unsafe { ffi::iio_device_reg_write(self.dev, addr, val) };
+                    self.op_result(Operation::RegisterAccess, ret as i32, ())
+                })
+            },
+        )
+    }
+
+    /// Reads a block of consecutive hardware registers.
+    ///
+    /// This reads `n` registers starting at `addr`, incrementing the
+    /// address by one for each register. It fails, without reading any
+    /// registers, if the block runs past the end of the address space.
+    pub fn reg_read_block(&self, addr: u32, n: usize) -> Result<Vec<u32>> {
+        reg_block(addr, n)?
+            .map(|addr| self.reg_read(addr))
+            .collect()
+    }
+
+    /// Writes a block of consecutive hardware registers.
+    ///
+    /// This writes the values to the registers starting at `addr`,
+    /// incrementing the address by one for each value. It fails, without
+    /// writing any registers, if the block runs past the end of the
+    /// address space.
+    pub fn reg_write_block(&self, addr: u32, vals: &[u32]) -> Result<()> {
+        for (addr, val) in reg_block(addr, vals.len())?.zip(vals) {
+            self.reg_write(addr, *val)?;
+        }
+        Ok(())
+    }
+
+    /// Modifies some of the bits in a hardware register.
+    ///
+    /// This does a read-modify-write of the register at `addr`, replacing
+    /// the bits set in `mask` with the corresponding bits from `val`.
+    /// It returns the new value of the register.
+    pub fn reg_modify(&self, addr: u32, mask: u32, val: u32) -> Result<u32> {
+        let old = self.reg_read(addr)?;
+        let new = (old & !mask) | (val & mask);
+        self.reg_write(addr, new)?;
+        Ok(new)
+    }
+}
+
+/// Saves which of a device's channels are enabled, restoring that state
+/// when dropped.
+#[derive(Debug)]
+pub(crate) struct ChannelStateGuard {
+    /// The channels and whether each was enabled.
+    state: Vec<(Channel, bool)>,
+}
+
+impl ChannelStateGuard {
+    /// Saves the enabled state of all the channels in the device.
+    pub(crate) fn new(dev: &Device) -> Self {
+        let state = dev
+            .channels()
+            .map(|chan| {
+                let enabled = chan.is_enabled();
+                (chan, enabled)
+            })
+            .collect();
+        Self { state }
+    }
+}
+
+impl Drop for ChannelStateGuard {
+    fn drop(&mut self) {
+        for (chan, enabled) in &self.state {
+            if *enabled {
+                chan.enable();
+            }
+            else {
+                chan.disable();
+            }
+        }
     }
 }
 
+/// Gets the addresses of a block of `n` consecutive registers starting
+/// at `addr`.
+///
+/// This fails if the count doesn't fit in a register address, or the
+/// block runs past the end of the address space.
+fn reg_block(addr: u32, n: usize) -> Result<impl Iterator<Item = u32>> {
+    let err = || Error::General(format!("Register block out of range: {} at {:#x}", n, addr));
+    let n = u32::try_from(n).map_err(|_| err())?;
+    if n > 0 {
+        addr.checked_add(n - 1).ok_or_else(err)?;
+    }
+    Ok((0..n).map(move |i| addr + i))
+}
+
 // The Device can be sent to another thread.
 unsafe impl Send for Device {}
 
+impl AsRef<Device> for Device {
+    fn as_ref(&self) -> &Device {
+        self
+    }
+}
+
 impl PartialEq for Device {
     /// Two devices are the same if they refer to the same underlying
     /// object in the library.
@@ -398,6 +1271,30 @@ impl Iterator for AttrIterator<'_> {
     }
 }
 
+/// Iterator over the debug attributes of a Device
+#[derive(Debug)]
+pub struct DebugAttrIterator<'a> {
+    /// Reference to the Device that we're scanning for debug attributes
+    dev: &'a Device,
+    /// Index for the next debug attribute from the Iterator.
+    idx: usize,
+}
+
+impl Iterator for DebugAttrIterator<'_> {
+    type Item = String;
+
+    /// Gets the next debug attribute from the iterator
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.dev.get_debug_attr(self.idx) {
+            Ok(name) => {
+                self.idx += 1;
+                Some(name)
+            }
+            Err(_) => None,
+        }
+    }
+}
+
 // --------------------------------------------------------------------------
 //                              Unit Tests
 // --------------------------------------------------------------------------
@@ -412,6 +1309,21 @@ mod tests {
     const DEV_ID: &str = "iio:device0";
     const DEV_NAME: &str = "dummydev";
 
+    // Register blocks can't overflow the address space.
+    #[test]
+    fn reg_block_range() {
+        let addrs: Vec<u32> = reg_block(0x10, 3).unwrap().collect();
+        assert_eq!(addrs, [0x10, 0x11, 0x12]);
+
+        let addrs: Vec<u32> = reg_block(u32::MAX - 1, 2).unwrap().collect();
+        assert_eq!(addrs, [u32::MAX - 1, u32::MAX]);
+
+        assert_eq!(reg_block(u32::MAX, 0).unwrap().count(), 0);
+        assert!(reg_block(u32::MAX, 2).is_err());
+        #[cfg(target_pointer_width = "64")]
+        assert!(reg_block(0, u32::MAX as usize + 1).is_err());
+    }
+
     // Make sure we get a device
     #[test]
     fn get_device() {
@@ -430,6 +1342,22 @@ mod tests {
         assert_eq!(name_dev, id_dev);
     }
 
+    // The identity should be the same for a device in a cloned context.
+    #[test]
+    fn identity() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device(DEV_ID).unwrap();
+
+        let ctx2 = ctx.try_deep_clone().unwrap();
+        let dev2 = ctx2.find_device(DEV_ID).unwrap();
+
+        assert!(dev != dev2);
+        assert_eq!(dev.identity(), dev2.identity());
+
+        let dev3 = ctx2.find_device_by_identity(&dev.identity()).unwrap();
+        assert_eq!(dev2, dev3);
+    }
+
     // See that attr iterator gets the correct number of attributes
     #[test]
     fn attr_iterator_count() {
@@ -441,6 +1369,116 @@ mod tests {
         assert!(dev.attributes().count() == n);
     }
 
+    // See that debug attr iterator gets the correct number of attributes
+    #[test]
+    fn debug_attr_iterator_count() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device(DEV_ID).unwrap();
+
+        let n = dev.num_debug_attrs();
+        assert_eq!(dev.debug_attributes().count(), n);
+        assert_eq!(dev.has_debug_attrs(), n != 0);
+    }
+
+    // Buffers with no samples, or over the maximum size, are rejected.
+    #[test]
+    fn invalid_buffer_size() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device(DEV_ID).unwrap();
+
+        let res = dev.create_buffer(0, false);
+        assert!(matches!(
+            res,
+            Err(Error::InvalidBufferSize { samples: 0, .. })
+        ));
+
+        let chan = dev.channels().find(|ch| ch.is_scan_element()).unwrap();
+        chan.enable();
+        ctx.set_max_buffer_size(0);
+        let res = dev.create_buffer(usize::MAX, false);
+        assert!(matches!(res, Err(Error::InvalidBufferSize { max: 0, .. })));
+    }
+
+    // Channels that aren't enabled, or are accessed with a type that
+    // doesn't fit the step, are rejected.
+    #[test]
+    fn buffer_layout_mismatch() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device(DEV_ID).unwrap();
+
+        let mut scan = dev.scan_elements();
+        let (chan, other) = (scan.next().unwrap(), scan.next().unwrap());
+        chan.enable();
+        other.disable();
+
+        let buf = dev.create_buffer(8, false).unwrap();
+        let step = buf.step();
+        assert!(buf.channel_iter::<u8>(&chan).is_ok());
+        assert!(matches!(
+            buf.channel_iter::<u8>(&other),
+            Err(Error::LayoutMismatch { step: s, sample_size: 1, .. }) if s == step
+        ));
+        assert!(matches!(
+            buf.check_layout::<[u8; 3]>(&chan),
+            Err(Error::LayoutMismatch { sample_size: 3, .. })
+        ));
+    }
+
+    // The dummy device has an accelerometer X axis input.
+    #[test]
+    fn find_channel_by_type() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device(DEV_ID).unwrap();
+
+        let chan = dev
+            .find_channel_by_type(
+                ChannelType::Accel,
+                Some(ChannelModifier::X),
+                Direction::Input,
+            )
+            .unwrap();
+        assert_eq!(chan.channel_modifier(), ChannelModifier::X);
+        assert!(dev.channels_with_type(ChannelType::Accel).count() >= 1);
+    }
+
+    // The scan elements should come in order of their index.
+    #[test]
+    fn scan_elements() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device(DEV_ID).unwrap();
+
+        let idx: Vec<_> = dev.scan_elements().map(|ch| ch.index().unwrap()).collect();
+        assert!(!idx.is_empty());
+        assert!(idx.windows(2).all(|w| w[0] <= w[1]));
+
+        let n = dev.input_channels().count() + dev.output_channels().count();
+        assert_eq!(n, dev.num_channels());
+    }
+
+    // The attribute values should match the ones read individually.
+    #[test]
+    fn attrs_with_values() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device(DEV_ID).unwrap();
+
+        let mut n = 0;
+        for (attr, val) in dev.attrs_with_values() {
+            if let Ok(val) = val {
+                assert_eq!(val, dev.attr_read_str(&attr).unwrap());
+            }
+            n += 1;
+        }
+        assert_eq!(n, dev.num_attrs());
+    }
+
+    // The local backend should be able to set the kernel buffers.
+    #[test]
+    fn supports() {
+        let ctx = Context::new().unwrap();
+        let dev = ctx.find_device(DEV_ID).unwrap();
+        assert!(dev.supports(Operation::KernelBuffers));
+    }
+
     // Just the fact that this compiles is probably sufficient.
     #[test]
     fn test_device_send() {
@@ -0,0 +1,263 @@
+// industrial-io/src/discover.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Discovery of the available backends.
+//!
+//! Desktop tools often don't know where the hardware will be: plugged in
+//! over USB, on the network, or on the local machine.
+//! [`Context::discover()`] scans the backends in order of preference and
+//! creates a context for the first device that works.
+//! [`Context::discover_with_report()`] also gives a [`DiscoveryReport`]
+//! of what was found, tried, and skipped along the way.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::discover::BackendKind;
+//!
+//! let prefs = [BackendKind::Usb, BackendKind::Network];
+//! let (ctx, report) = iio::Context::discover_with_report(&prefs);
+//! print!("{}", report);
+//! let ctx = ctx.unwrap();
+//! ```
+
+use crate::{Context, Error, Result, ScanContext};
+use nix::errno::Errno;
+use std::fmt;
+
+/// A kind of backend that can be scanned for contexts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BackendKind {
+    /// Devices attached over USB
+    Usb,
+    /// IIOD servers on the network
+    Network,
+    /// Devices on the local system
+    Local,
+}
+
+impl BackendKind {
+    /// The order in which backends are scanned, by default.
+    pub const DEFAULT_ORDER: [BackendKind; 3] =
+        [BackendKind::Usb, BackendKind::Network, BackendKind::Local];
+
+    /// Gets the name of the backend used by the C library for scans.
+    pub fn scan_name(&self) -> &'static str {
+        match self {
+            BackendKind::Usb => "usb",
+            BackendKind::Network => "ip",
+            BackendKind::Local => "local",
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.scan_name())
+    }
+}
+
+/// What happened with a backend, or a context found by a scan, during
+/// discovery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiscoveryOutcome {
+    /// The context was created and returned.
+    Selected,
+    /// Creating the context failed, with the error.
+    Failed(String),
+    /// The context was found, but not tried, since an earlier one was
+    /// selected.
+    Skipped,
+    /// The scan of the backend didn't find any contexts.
+    NotFound,
+    /// The scan of the backend failed, with the error.
+    ScanFailed(String),
+    /// The backend wasn't scanned, since a context was already selected.
+    NotScanned,
+}
+
+/// An entry in a discovery report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveryEntry {
+    /// The backend
+    pub backend: BackendKind,
+    /// The URI of the context, if one was found
+    pub uri: Option<String>,
+    /// The description of the context, if one was found
+    pub description: Option<String>,
+    /// What happened
+    pub outcome: DiscoveryOutcome,
+}
+
+impl DiscoveryEntry {
+    /// Creates an entry for a backend, without a context.
+    fn backend(backend: BackendKind, outcome: DiscoveryOutcome) -> Self {
+        Self {
+            backend,
+            uri: None,
+            description: None,
+            outcome,
+        }
+    }
+}
+
+/// A report of what was found, tried, and skipped during discovery.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiscoveryReport {
+    /// The entries, in the order they were made
+    pub entries: Vec<DiscoveryEntry>,
+}
+
+impl DiscoveryReport {
+    /// Gets the entry for the context that was selected, if any.
+    pub fn selected(&self) -> Option<&DiscoveryEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.outcome == DiscoveryOutcome::Selected)
+    }
+}
+
+impl fmt::Display for DiscoveryReport {
+    /// Displays the report, with one entry per line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DiscoveryOutcome::*;
+        for e in &self.entries {
+            write!(f, "{}: ", e.backend)?;
+            if let Some(uri) = &e.uri {
+                write!(f, "{} ", uri)?;
+            }
+            if let Some(descr) = &e.description {
+                write!(f, "[{}] ", descr)?;
+            }
+            match &e.outcome {
+                Selected => writeln!(f, "selected")?,
+                Failed(err) => writeln!(f, "failed: {}", err)?,
+                Skipped => writeln!(f, "skipped")?,
+                NotFound => writeln!(f, "nothing found")?,
+                ScanFailed(err) => writeln!(f, "scan failed: {}", err)?,
+                NotScanned => writeln!(f, "not scanned")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Context {
+    /// Creates a context for the first device found by scanning the
+    /// backends in order of preference.
+    ///
+    /// If `prefs` is empty, the backends are scanned in the
+    /// [default order](BackendKind::DEFAULT_ORDER). This fails with
+    /// `ENODEV` if no context could be created.
+    pub fn discover(prefs: &[BackendKind]) -> Result<Self> {
+        Self::discover_with_report(prefs).0
+    }
+
+    /// Creates a context for the first device found by scanning the
+    /// backends in order of preference, with a report of what was found.
+    ///
+    /// See [`Context::discover()`].
+    pub fn discover_with_report(prefs: &[BackendKind]) -> (Result<Self>, DiscoveryReport) {
+        let prefs = if prefs.is_empty() {
+            &BackendKind::DEFAULT_ORDER
+        }
+        else {
+            prefs
+        };
+
+        let mut ctx = None;
+        let mut report = DiscoveryReport::default();
+
+        for &backend in prefs {
+            if ctx.is_some() {
+                report.entries.push(DiscoveryEntry::backend(
+                    backend,
+                    DiscoveryOutcome::NotScanned,
+                ));
+                continue;
+            }
+
+            let scan = match ScanContext::new(backend.scan_name()) {
+                Ok(scan) => scan,
+                Err(err) => {
+                    let outcome = DiscoveryOutcome::ScanFailed(err.to_string());
+                    report
+                        .entries
+                        .push(DiscoveryEntry::backend(backend, outcome));
+                    continue;
+                }
+            };
+            if scan.is_empty() {
+                report
+                    .entries
+                    .push(DiscoveryEntry::backend(backend, DiscoveryOutcome::NotFound));
+                continue;
+            }
+
+            for res in scan.iter() {
+                let outcome = if ctx.is_some() {
+                    DiscoveryOutcome::Skipped
+                }
+                else {
+                    match Self::from_uri(&res.uri) {
+                        Ok(c) => {
+                            ctx = Some(c);
+                            DiscoveryOutcome::Selected
+                        }
+                        Err(err) => DiscoveryOutcome::Failed(err.to_string()),
+                    }
+                };
+                report.entries.push(DiscoveryEntry {
+                    backend,
+                    uri: Some(res.uri),
+                    description: Some(res.description),
+                    outcome,
+                });
+            }
+        }
+
+        (ctx.ok_or(Error::Nix(Errno::ENODEV)), report)
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report() {
+        let report = DiscoveryReport {
+            entries: vec![
+                DiscoveryEntry::backend(BackendKind::Usb, DiscoveryOutcome::NotFound),
+                DiscoveryEntry {
+                    backend: BackendKind::Network,
+                    uri: Some("ip:192.168.2.1".into()),
+                    description: Some("PlutoSDR".into()),
+                    outcome: DiscoveryOutcome::Selected,
+                },
+                DiscoveryEntry::backend(BackendKind::Local, DiscoveryOutcome::NotScanned),
+            ],
+        };
+
+        assert_eq!(
+            report.selected().unwrap().uri.as_deref(),
+            Some("ip:192.168.2.1")
+        );
+        assert_eq!(
+            report.to_string(),
+            "usb: nothing found\n\
+             ip: ip:192.168.2.1 [PlutoSDR] selected\n\
+             local: not scanned\n"
+        );
+    }
+}
@@ -10,13 +10,18 @@
 //!
 //! Error definitions for the Industrial I/O Library.
 
+use crate::{available::Available, Operation};
 use std::{ffi, io};
 use thiserror::Error;
 
 //type SysError = nix::Error::Sys;
 
 /// The Error type for the IIO library
+///
+/// New variants may be added in minor releases, so matches on it need a
+/// wildcard arm.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// A low-level I/O error
     #[error("{0}")]
@@ -27,6 +32,18 @@ pub enum Error {
     /// A low-level Unix-style error
     #[error("{0}")]
     Nix(#[from] nix::Error),
+    /// An attribute read or write failed in the C library.
+    #[error("Attribute '{attr}' of {}: {source}", attr_owner(.device, .channel))]
+    Attr {
+        /// The name of the attribute
+        attr: String,
+        /// The ID of the device
+        device: String,
+        /// The ID of the channel, for a channel attribute
+        channel: Option<String>,
+        /// The error from the C library
+        source: nix::Error,
+    },
     /// An error converting a value to/from a string representation.
     #[error("String conversion error")]
     StringConversionError,
@@ -36,13 +53,140 @@ pub enum Error {
     /// The size of a data or return value was different than expected.
     #[error("Bad return size")]
     BadReturnSize,
+    /// The samples of a channel can't be accessed with the layout of a
+    /// buffer.
+    ///
+    /// This happens if the channel isn't enabled in the buffer, or the
+    /// buffer step is not a multiple of the size of the sample type.
+    #[error(
+        "Buffer layout mismatch for channel '{channel}': step of {step} bytes, sample size of {sample_size} bytes"
+    )]
+    LayoutMismatch {
+        /// The distance between samples of the channel, in bytes
+        step: usize,
+        /// The size of a sample, in bytes
+        sample_size: usize,
+        /// The ID of the channel
+        channel: String,
+    },
+    /// A value in a list attribute could not be converted.
+    #[error("Invalid value '{token}' at position {index} of the list")]
+    InvalidListItem {
+        /// The position of the value in the list, starting at zero
+        index: usize,
+        /// The text of the value
+        token: String,
+    },
     /// A device or channel index did not find a requested object
     #[error("Invalid index")]
     InvalidIndex,
+    /// The operation is not supported by the backend of the context.
+    #[error("Operation '{op}' not supported by the '{backend}' backend")]
+    NotSupported {
+        /// The name of the context backend (i.e. "local", "network", etc)
+        backend: String,
+        /// The operation that was attempted
+        op: Operation,
+    },
+    /// A value is not one of the values available for an attribute.
+    #[error("{value} not in {available}")]
+    NotAvailable {
+        /// The value that was rejected
+        value: String,
+        /// The values that are available
+        available: Available,
+    },
+    /// A buffer was requested with no samples, or with more bytes than the
+    /// maximum set for the context.
+    #[error("Invalid buffer size: {samples} samples ({bytes} bytes, max {max})")]
+    InvalidBufferSize {
+        /// The number of samples requested
+        samples: usize,
+        /// The size of the buffer in bytes, for the enabled channels
+        bytes: usize,
+        /// The maximum size of a buffer in bytes
+        max: usize,
+    },
+    /// A value is malformed, or out of the range allowed for it.
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+    /// An operation was rejected by a safety policy.
+    #[error("Policy violation: {0}")]
+    PolicyViolation(String),
+    /// Frames were dropped because the consumer fell behind the capture.
+    #[error("Overrun: {0} frames dropped")]
+    Overrun(u64),
     /// A generic error with a string explaination
     #[error("{0}")]
     General(String),
 }
 
+impl Error {
+    /// Gets the underlying errno of the error, if it came from the C
+    /// library or the OS.
+    pub fn errno(&self) -> Option<nix::Error> {
+        match self {
+            Error::Nix(errno) | Error::Attr { source: errno, .. } => Some(*errno),
+            Error::Io(err) => err.raw_os_error().map(nix::Error::from_raw),
+            _ => None,
+        }
+    }
+
+    /// Adds the attribute, device, and channel to an error from the C
+    /// library.
+    ///
+    /// Other kinds of errors are returned as-is.
+    pub(crate) fn with_attr(self, attr: &str, device: String, channel: Option<String>) -> Self {
+        match self {
+            Error::Nix(source) => Error::Attr {
+                attr: attr.to_string(),
+                device,
+                channel,
+                source,
+            },
+            err => err,
+        }
+    }
+}
+
+/// Describes the device, and possibly the channel, that owns an
+/// attribute, for an error message.
+fn attr_owner(device: &str, channel: &Option<String>) -> String {
+    match channel {
+        Some(chan) => format!("channel '{}' of device '{}'", chan, device),
+        None => format!("device '{}'", device),
+    }
+}
+
 /// The default result type for the IIO library
 pub type Result<T> = std::result::Result<T, Error>;
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::errno::Errno;
+
+    #[test]
+    fn attr_error() {
+        let err = Error::Nix(Errno::EINVAL).with_attr("scale", "iio:device0".into(), None);
+        assert_eq!(err.errno(), Some(Errno::EINVAL));
+        assert_eq!(
+            err.to_string(),
+            "Attribute 'scale' of device 'iio:device0': EINVAL: Invalid argument"
+        );
+
+        let err =
+            Error::Nix(Errno::EIO).with_attr("raw", "iio:device0".into(), Some("voltage0".into()));
+        assert!(err
+            .to_string()
+            .starts_with("Attribute 'raw' of channel 'voltage0' of device 'iio:device0':"));
+
+        let err = Error::InvalidIndex.with_attr("raw", "iio:device0".into(), None);
+        assert!(matches!(err, Error::InvalidIndex));
+        assert_eq!(err.errno(), None);
+    }
+}
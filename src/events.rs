@@ -0,0 +1,616 @@
+// industrial-io/src/events.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! IIO events.
+//!
+//! Drivers configure the events that a device can generate, like a value
+//! crossing a threshold, with attributes in the `events/` directory of
+//! the device in sysfs. These are named like
+//! `in_<channel>_<type>[_<direction>]_<info>`, such as
+//! `in_activity_running_thresh_rising_en` or
+//! `in_voltage0_thresh_falling_value`.
+//!
+//! The C library doesn't expose these attributes, so they are accessed
+//! directly in sysfs. That only works with the local backend; check with
+//! [`Device::supports(Operation::Events)`](crate::Device::supports).
+//!
+//! Once enabled, the events themselves are read from an [`EventStream`],
+//! which decodes each one into an [`Event`] with the channel, type,
+//! direction, and timestamp.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::events::{EventAttr, EventConfig, EventDirection, EventType};
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ad7291").unwrap();
+//! let events = EventConfig::new(&dev).unwrap();
+//!
+//! for attr in events.attrs().unwrap() {
+//!     println!("{}: {}", attr, events.read(&attr).unwrap());
+//! }
+//!
+//! let attr = EventAttr::new("voltage0", EventType::Threshold, Some(EventDirection::Rising));
+//! events.write(&attr.with_info("value"), "1800").unwrap();
+//! events.set_enabled(&attr, true).unwrap();
+//!
+//! for ev in iio::events::EventStream::new(&dev).unwrap() {
+//!     println!("{:?}", ev.unwrap());
+//! }
+//! ```
+
+use crate::{abi, ChannelType, Device, Error, Operation, Result};
+use std::{fmt, fs, path::PathBuf, str::FromStr};
+
+#[cfg(target_os = "linux")]
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+#[cfg(target_os = "linux")]
+use std::{
+    io::Read,
+    os::{
+        fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd},
+        raw::c_int,
+    },
+    time::Duration,
+};
+
+/// The directory in sysfs with the IIO devices.
+pub(crate) const SYSFS_DEVICES: &str = "/sys/bus/iio/devices";
+
+/// The type of an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventType {
+    /// The value crossed a threshold.
+    Threshold,
+    /// The magnitude of the value crossed a threshold.
+    Magnitude,
+    /// The rate of change of the value crossed a threshold.
+    RateOfChange,
+    /// The value crossed a threshold that adapts to the signal.
+    ThresholdAdaptive,
+    /// The magnitude crossed a threshold that adapts to the signal.
+    MagnitudeAdaptive,
+    /// The value changed, like a step being detected.
+    Change,
+    /// The magnitude crossed a threshold relative to a reference.
+    MagnitudeReferenced,
+    /// A gesture, like a tap, was detected.
+    Gesture,
+}
+
+impl EventType {
+    /// All the event types, with those of more than one word first so
+    /// that they match before their prefixes.
+    const ALL: [EventType; 8] = [
+        EventType::ThresholdAdaptive,
+        EventType::MagnitudeAdaptive,
+        EventType::MagnitudeReferenced,
+        EventType::Threshold,
+        EventType::Magnitude,
+        EventType::RateOfChange,
+        EventType::Change,
+        EventType::Gesture,
+    ];
+
+    /// Gets the event type from its code in the kernel ABI.
+    fn from_code(code: u64) -> Option<Self> {
+        let typ = match code {
+            0 => EventType::Threshold,
+            1 => EventType::Magnitude,
+            2 => EventType::RateOfChange,
+            3 => EventType::ThresholdAdaptive,
+            4 => EventType::MagnitudeAdaptive,
+            5 => EventType::Change,
+            6 => EventType::MagnitudeReferenced,
+            7 => EventType::Gesture,
+            _ => return None,
+        };
+        Some(typ)
+    }
+
+    /// Gets the name of the event type used in the ABI.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventType::Threshold => "thresh",
+            EventType::Magnitude => "mag",
+            EventType::RateOfChange => "roc",
+            EventType::ThresholdAdaptive => "thresh_adaptive",
+            EventType::MagnitudeAdaptive => "mag_adaptive",
+            EventType::Change => "change",
+            EventType::MagnitudeReferenced => "mag_referenced",
+            EventType::Gesture => "gesture",
+        }
+    }
+}
+
+impl FromStr for EventType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|typ| typ.as_str() == s)
+            .ok_or(Error::StringConversionError)
+    }
+}
+
+impl fmt::Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// The direction of an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventDirection {
+    /// Either rising or falling
+    Either,
+    /// The value is rising
+    Rising,
+    /// The value is falling
+    Falling,
+    /// The event has no direction
+    None,
+    /// A single tap gesture
+    SingleTap,
+    /// A double tap gesture
+    DoubleTap,
+}
+
+impl EventDirection {
+    /// All the event directions.
+    const ALL: [EventDirection; 6] = [
+        EventDirection::Either,
+        EventDirection::Rising,
+        EventDirection::Falling,
+        EventDirection::None,
+        EventDirection::SingleTap,
+        EventDirection::DoubleTap,
+    ];
+
+    /// Gets the direction from its code in the kernel ABI.
+    ///
+    /// The directions are listed in the same order as the ABI.
+    fn from_code(code: u64) -> Option<Self> {
+        Self::ALL.get(code as usize).copied()
+    }
+
+    /// Gets the name of the direction used in the ABI.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventDirection::Either => "either",
+            EventDirection::Rising => "rising",
+            EventDirection::Falling => "falling",
+            EventDirection::None => "none",
+            EventDirection::SingleTap => "singletap",
+            EventDirection::DoubleTap => "doubletap",
+        }
+    }
+}
+
+impl FromStr for EventDirection {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|dir| dir.as_str() == s)
+            .ok_or(Error::StringConversionError)
+    }
+}
+
+impl fmt::Display for EventDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// The decoded name of an event configuration attribute.
+///
+/// This is like `in_<channel>_<type>[_<direction>]_<info>`, where the
+/// info is what the attribute configures, like "en" to enable the event,
+/// "value" for a threshold, or "hysteresis".
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EventAttr {
+    /// Whether the event is for an output channel
+    pub output: bool,
+    /// The channel, like "voltage0" or "activity_running"
+    pub channel: String,
+    /// The type of event
+    pub ev_type: EventType,
+    /// The direction of the event, if given
+    pub direction: Option<EventDirection>,
+    /// What the attribute configures, like "en" or "value"
+    pub info: String,
+}
+
+impl EventAttr {
+    /// Creates the "en" attribute for an event on an input channel.
+    pub fn new(channel: &str, ev_type: EventType, direction: Option<EventDirection>) -> Self {
+        Self {
+            output: false,
+            channel: channel.to_string(),
+            ev_type,
+            direction,
+            info: abi::event::EN.to_string(),
+        }
+    }
+
+    /// Gets another attribute of the same event.
+    pub fn with_info(&self, info: &str) -> Self {
+        Self {
+            info: info.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// Determines if this is the attribute that enables the event.
+    pub fn is_enable(&self) -> bool {
+        self.info == abi::event::EN
+    }
+}
+
+impl FromStr for EventAttr {
+    type Err = Error;
+
+    /// Decodes the name of an attribute in the `events/` directory.
+    fn from_str(s: &str) -> Result<Self> {
+        let (output, rest) = match (s.strip_prefix("in_"), s.strip_prefix("out_")) {
+            (Some(rest), _) => (false, rest),
+            (_, Some(rest)) => (true, rest),
+            _ => return Err(Error::StringConversionError),
+        };
+
+        // Find the first place where the event type appears after the
+        // start of a word, with something after it.
+        for (i, _) in rest.match_indices('_') {
+            let (channel, tail) = (&rest[..i], &rest[i + 1..]);
+            for ev_type in EventType::ALL {
+                let Some(tail) = tail
+                    .strip_prefix(ev_type.as_str())
+                    .and_then(|t| t.strip_prefix('_'))
+                else {
+                    continue;
+                };
+                let (direction, info) = match tail.split_once('_') {
+                    Some((dir, info)) => match dir.parse() {
+                        Ok(dir) => (Some(dir), info),
+                        Err(_) => (None, tail),
+                    },
+                    None => (None, tail),
+                };
+                if info.is_empty() {
+                    continue;
+                }
+                return Ok(Self {
+                    output,
+                    channel: channel.to_string(),
+                    ev_type,
+                    direction,
+                    info: info.to_string(),
+                });
+            }
+        }
+        Err(Error::StringConversionError)
+    }
+}
+
+impl fmt::Display for EventAttr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dir = if self.output { "out" } else { "in" };
+        write!(f, "{}_{}_{}", dir, self.channel, self.ev_type)?;
+        if let Some(direction) = self.direction {
+            write!(f, "_{}", direction)?;
+        }
+        write!(f, "_{}", self.info)
+    }
+}
+
+/// The event configuration of a device.
+#[derive(Debug, Clone)]
+pub struct EventConfig {
+    /// The `events/` directory of the device in sysfs
+    dir: PathBuf,
+}
+
+impl EventConfig {
+    /// Gets the event configuration of the device.
+    ///
+    /// This fails with [`Error::NotSupported`] if the device isn't in a
+    /// local context, and with an I/O error if it doesn't have any events.
+    pub fn new(dev: &Device) -> Result<Self> {
+        dev.check_supported(Operation::Events)?;
+        let id = dev
+            .id()
+            .ok_or_else(|| Error::General("The device has no ID".into()))?;
+        let dir = PathBuf::from(SYSFS_DEVICES).join(id).join("events");
+        fs::metadata(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Gets all the event configuration attributes of the device.
+    ///
+    /// Any attributes whose names can't be decoded are skipped.
+    pub fn attrs(&self) -> Result<Vec<EventAttr>> {
+        let mut attrs = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            if let Some(attr) = entry?
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse().ok())
+            {
+                attrs.push(attr);
+            }
+        }
+        attrs.sort_by_key(|attr: &EventAttr| attr.to_string());
+        Ok(attrs)
+    }
+
+    /// Determines if the device has the attribute.
+    pub fn has_attr(&self, attr: &EventAttr) -> bool {
+        self.dir.join(attr.to_string()).exists()
+    }
+
+    /// Reads an attribute.
+    pub fn read(&self, attr: &EventAttr) -> Result<String> {
+        let s = fs::read_to_string(self.dir.join(attr.to_string()))?;
+        Ok(s.trim_end().to_string())
+    }
+
+    /// Writes an attribute.
+    pub fn write(&self, attr: &EventAttr, val: &str) -> Result<()> {
+        fs::write(self.dir.join(attr.to_string()), val)?;
+        Ok(())
+    }
+
+    /// Determines if the event is enabled.
+    pub fn is_enabled(&self, attr: &EventAttr) -> Result<bool> {
+        Ok(self.read(&attr.with_info(abi::event::EN))? == "1")
+    }
+
+    /// Enables or disables the event.
+    pub fn set_enabled(&self, attr: &EventAttr, on: bool) -> Result<()> {
+        self.write(&attr.with_info(abi::event::EN), if on { "1" } else { "0" })
+    }
+
+    /// Gets the channels that can generate events, like "voltage0".
+    pub fn channels(&self) -> Result<Vec<String>> {
+        let mut chans: Vec<_> = self.attrs()?.into_iter().map(|attr| attr.channel).collect();
+        chans.sort();
+        chans.dedup();
+        Ok(chans)
+    }
+}
+
+/// An event generated by a device.
+///
+/// This is decoded from the event code and timestamp that the kernel
+/// reports for each event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    /// The type of the channel that generated the event
+    pub chan_type: ChannelType,
+    /// The channel number, or -1 if the channel isn't indexed
+    pub channel: i16,
+    /// The second channel number, for differential channels
+    pub channel2: i16,
+    /// The modifier of the channel, like the axis, as a kernel ABI code
+    pub modifier: u8,
+    /// Whether the channel is differential
+    pub differential: bool,
+    /// The type of event
+    pub ev_type: EventType,
+    /// The direction of the event
+    pub direction: EventDirection,
+    /// The time of the event, in nanoseconds, from the device clock
+    pub timestamp: i64,
+}
+
+impl Event {
+    /// Decodes an event from the kernel event code and timestamp.
+    pub fn from_code(id: u64, timestamp: i64) -> Result<Self> {
+        let ev_type = EventType::from_code((id >> 56) & 0xFF)
+            .ok_or_else(|| Error::General(format!("Unknown event type: {}", (id >> 56) & 0xFF)))?;
+        let direction = EventDirection::from_code((id >> 48) & 0x7F).ok_or_else(|| {
+            Error::General(format!("Unknown event direction: {}", (id >> 48) & 0x7F))
+        })?;
+        Ok(Self {
+            chan_type: ChannelType::from_raw(((id >> 32) & 0xFF) as u32),
+            channel: (id & 0xFFFF) as i16,
+            channel2: ((id >> 16) & 0xFFFF) as i16,
+            modifier: ((id >> 40) & 0xFF) as u8,
+            differential: (id >> 55) & 1 != 0,
+            ev_type,
+            direction,
+            timestamp,
+        })
+    }
+}
+
+/// The ioctl requests of the IIO character devices.
+#[cfg(target_os = "linux")]
+mod ioctl {
+    use std::os::raw::c_int;
+
+    nix::ioctl_read!(
+        /// Gets the event file of a device (`IIO_GET_EVENT_FD_IOCTL`).
+        iio_get_event_fd,
+        b'i',
+        0x90,
+        c_int
+    );
+}
+
+/// A stream of the events generated by a device.
+///
+/// The events to report are selected with the [`EventConfig`]. They can
+/// be read one at a time, blocking or not, or with the stream used as a
+/// blocking iterator.
+///
+/// Only one stream can be open for a device at a time. As with the event
+/// configuration, this requires the local backend.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct EventStream {
+    /// The event file of the device
+    file: fs::File,
+}
+
+#[cfg(target_os = "linux")]
+impl EventStream {
+    /// Opens the event stream of the device.
+    pub fn new(dev: &Device) -> Result<Self> {
+        dev.check_supported(Operation::Events)?;
+        let id = dev
+            .id()
+            .ok_or_else(|| Error::General("The device has no ID".into()))?;
+        let chrdev = fs::File::open(PathBuf::from("/dev").join(id))?;
+
+        let mut fd: c_int = -1;
+        unsafe { ioctl::iio_get_event_fd(chrdev.as_raw_fd(), &mut fd) }?;
+        let file = fs::File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        Ok(Self { file })
+    }
+
+    /// Blocks until the next event, and reads it.
+    pub fn read_event(&mut self) -> Result<Event> {
+        let mut data = [0u8; 16];
+        self.file.read_exact(&mut data)?;
+        let (id, timestamp) = data.split_at(8);
+        Event::from_code(
+            u64::from_ne_bytes(id.try_into().unwrap()),
+            i64::from_ne_bytes(timestamp.try_into().unwrap()),
+        )
+    }
+
+    /// Reads the next event, if there is one, without blocking.
+    pub fn try_read_event(&mut self) -> Result<Option<Event>> {
+        self.read_event_timeout(Duration::ZERO)
+    }
+
+    /// Waits up to the timeout for the next event, and reads it.
+    ///
+    /// This returns `None` if no event arrived in time.
+    pub fn read_event_timeout(&mut self, timeout: Duration) -> Result<Option<Event>> {
+        let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+        let mut fds = [PollFd::new(self.file.as_fd(), PollFlags::POLLIN)];
+        if poll(&mut fds, timeout)? == 0 {
+            return Ok(None);
+        }
+        self.read_event().map(Some)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Iterator for EventStream {
+    type Item = Result<Event>;
+
+    /// Blocks until the next event, and reads it.
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.read_event())
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl AsRawFd for EventStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_attr() {
+        let attr: EventAttr = "in_activity_running_thresh_rising_en".parse().unwrap();
+        assert_eq!(
+            attr,
+            EventAttr {
+                output: false,
+                channel: "activity_running".into(),
+                ev_type: EventType::Threshold,
+                direction: Some(EventDirection::Rising),
+                info: "en".into(),
+            }
+        );
+
+        let attr: EventAttr = "in_steps_change_value".parse().unwrap();
+        assert_eq!(attr.channel, "steps");
+        assert_eq!(attr.ev_type, EventType::Change);
+        assert_eq!(attr.direction, None);
+        assert_eq!(attr.info, "value");
+
+        let attr: EventAttr = "in_accel_x_thresh_adaptive_either_hysteresis"
+            .parse()
+            .unwrap();
+        assert_eq!(attr.channel, "accel_x");
+        assert_eq!(attr.ev_type, EventType::ThresholdAdaptive);
+        assert_eq!(attr.direction, Some(EventDirection::Either));
+        assert_eq!(attr.info, "hysteresis");
+
+        let attr: EventAttr = "in_magn_y_mag_falling_reset_timeout".parse().unwrap();
+        assert_eq!(attr.channel, "magn_y");
+        assert_eq!(attr.info, "reset_timeout");
+
+        assert!("in_voltage0_raw".parse::<EventAttr>().is_err());
+        assert!("voltage0_thresh_rising_en".parse::<EventAttr>().is_err());
+    }
+
+    #[test]
+    fn decode_event() {
+        // IIO_UNMOD_EVENT_CODE(IIO_VOLTAGE, 3, IIO_EV_TYPE_THRESH, IIO_EV_DIR_FALLING)
+        let id = (2u64 << 48) | 3;
+        let ev = Event::from_code(id, 1234).unwrap();
+        assert_eq!(ev.chan_type, ChannelType::Voltage);
+        assert_eq!(ev.channel, 3);
+        assert_eq!(ev.ev_type, EventType::Threshold);
+        assert_eq!(ev.direction, EventDirection::Falling);
+        assert!(!ev.differential);
+        assert_eq!(ev.timestamp, 1234);
+
+        // IIO_MOD_EVENT_CODE(IIO_ACCEL, 0, IIO_MOD_X, IIO_EV_TYPE_ROC, IIO_EV_DIR_RISING)
+        let id = (2u64 << 56) | (1u64 << 48) | (1u64 << 40) | (3u64 << 32);
+        let ev = Event::from_code(id, 0).unwrap();
+        assert_eq!(ev.chan_type, ChannelType::Accel);
+        assert_eq!(ev.modifier, 1);
+        assert_eq!(ev.ev_type, EventType::RateOfChange);
+        assert_eq!(ev.direction, EventDirection::Rising);
+
+        assert!(Event::from_code(99u64 << 56, 0).is_err());
+    }
+
+    #[test]
+    fn encode_attr() {
+        for name in [
+            "in_activity_running_thresh_rising_en",
+            "in_steps_change_value",
+            "out_voltage0_roc_falling_period",
+        ] {
+            assert_eq!(name.parse::<EventAttr>().unwrap().to_string(), name);
+        }
+
+        let attr = EventAttr::new(
+            "voltage0",
+            EventType::Threshold,
+            Some(EventDirection::Falling),
+        );
+        assert!(attr.is_enable());
+        assert_eq!(
+            attr.with_info("value").to_string(),
+            "in_voltage0_thresh_falling_value"
+        );
+    }
+}
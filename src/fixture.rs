@@ -0,0 +1,307 @@
+// industrial-io/src/fixture.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Test fixtures captured from live hardware.
+//!
+//! [`Context::export_test_fixture()`] produces the XML description of a
+//! context, like [`Context::xml()`], but with the current value of every
+//! readable device, channel, buffer, and debug attribute recorded in a
+//! `value` property of its element. This is a snapshot of the real
+//! hardware, with all its quirks, that can be checked in as a regression
+//! test fixture. The extra properties are ignored by the _libiio_ XML
+//! backend, so the fixture can still be loaded as an XML context.
+//!
+//! Serial numbers can optionally be anonymized, so that fixtures can be
+//! shared without identifying the particular unit they came from.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let xml = ctx.export_test_fixture(true).unwrap();
+//! std::fs::write("fixture.xml", xml).unwrap();
+//! ```
+
+use crate::{Context, Direction, Result};
+
+/// The element that an attribute belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttrScope<'a> {
+    /// A context attribute
+    Context,
+    /// A device attribute, by device ID
+    Device(&'a str),
+    /// A channel attribute, by device ID, channel ID, and whether the
+    /// channel is an output
+    Channel(&'a str, &'a str, bool),
+    /// A buffer attribute, by device ID
+    Buffer(&'a str),
+    /// A debug attribute, by device ID
+    Debug(&'a str),
+}
+
+impl Context {
+    /// Creates an XML description of the context with the current
+    /// attribute values baked in, for use as a test fixture.
+    ///
+    /// Attributes that can't be read, such as write-only ones, are left
+    /// without a value. If `anonymize` is set, the value of any attribute
+    /// with "serial" in its name has its letters and digits replaced with
+    /// zeros.
+    pub fn export_test_fixture(&self, anonymize: bool) -> Result<String> {
+        Ok(bake_values(
+            &self.xml(),
+            anonymize,
+            |scope, name| match scope {
+                AttrScope::Context => None,
+                AttrScope::Device(id) => self.find_device(id)?.attr_read_str(name).ok(),
+                AttrScope::Channel(id, chan, output) => {
+                    let dir = if output {
+                        Direction::Output
+                    }
+                    else {
+                        Direction::Input
+                    };
+                    self.find_device(id)?
+                        .find_channel(chan, dir)?
+                        .attr_read_str(name)
+                        .ok()
+                }
+                AttrScope::Buffer(id) => self.find_device(id)?.buffer_attr_read_str(name).ok(),
+                AttrScope::Debug(id) => self.find_device(id)?.debug_attr_read_str(name).ok(),
+            },
+        ))
+    }
+}
+
+/// Adds the values of the attributes to the XML description of a
+/// context.
+///
+/// `read` Reads the value of an attribute, by scope and name
+fn bake_values<F>(xml: &str, anonymize: bool, mut read: F) -> String
+where
+    F: FnMut(AttrScope, &str) -> Option<String>,
+{
+    let mut out = String::with_capacity(2 * xml.len());
+    let mut dev: Option<String> = None;
+    let mut chan: Option<(String, bool)> = None;
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let end = match rest[start..].find('>') {
+            Some(n) => start + n + 1,
+            None => break,
+        };
+        let tag = &rest[start..end];
+        rest = &rest[end..];
+
+        let tag_name = tag[1..]
+            .split(|c: char| c.is_whitespace() || c == '>')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches('/');
+        let closed = tag.ends_with("/>");
+
+        let scope = match (tag_name, &dev, &chan) {
+            ("device", _, _) => {
+                dev = if closed { None } else { prop(tag, "id") };
+                None
+            }
+            ("/device", _, _) => {
+                dev = None;
+                None
+            }
+            ("channel", _, _) => {
+                let output = prop(tag, "type").as_deref() == Some("output");
+                chan = if closed {
+                    None
+                }
+                else {
+                    prop(tag, "id").map(|id| (id, output))
+                };
+                None
+            }
+            ("/channel", _, _) => {
+                chan = None;
+                None
+            }
+            ("context-attribute", _, _) => Some(AttrScope::Context),
+            ("attribute", Some(dev), Some((chan, output))) => {
+                Some(AttrScope::Channel(dev, chan, *output))
+            }
+            ("attribute", Some(dev), None) => Some(AttrScope::Device(dev)),
+            ("buffer-attribute", Some(dev), _) => Some(AttrScope::Buffer(dev)),
+            ("debug-attribute", Some(dev), _) => Some(AttrScope::Debug(dev)),
+            _ => None,
+        };
+
+        let name = match (scope, prop(tag, "name")) {
+            (Some(scope), Some(name)) => Some((scope, name)),
+            _ => None,
+        };
+        match name {
+            Some((scope, name)) => {
+                let val = match prop(tag, "value") {
+                    Some(val) => Some(val),
+                    None => read(scope, &name),
+                };
+                let val = match val {
+                    Some(val) if anonymize && name.contains("serial") => Some(anonymized(&val)),
+                    val => val,
+                };
+                out.push_str(&with_value(tag, val.as_deref()));
+            }
+            None => out.push_str(tag),
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Gets the unescaped value of a property from an XML tag.
+fn prop(tag: &str, name: &str) -> Option<String> {
+    let pat = format!(" {}=\"", name);
+    let start = tag.find(&pat)? + pat.len();
+    let len = tag[start..].find('"')?;
+    Some(unescape(&tag[start..start + len]))
+}
+
+/// Sets the `value` property of an XML tag, replacing any existing one.
+fn with_value(tag: &str, val: Option<&str>) -> String {
+    let val = match val {
+        Some(val) => val,
+        None => return tag.to_string(),
+    };
+    let (head, tail) = match tag.find(" value=\"") {
+        Some(start) => {
+            let end = start + 8 + tag[start + 8..].find('"').unwrap_or(0) + 1;
+            (&tag[..start], &tag[end..])
+        }
+        None => {
+            let n = if tag.ends_with("/>") { 2 } else { 1 };
+            tag.split_at(tag.len() - n)
+        }
+    };
+    format!(
+        "{} value=\"{}\"{}{}",
+        head.trim_end(),
+        escape(val),
+        if tail.starts_with(' ') { "" } else { " " },
+        tail
+    )
+}
+
+/// Replaces the letters and digits of a value with zeros, keeping any
+/// separators.
+fn anonymized(val: &str) -> String {
+    val.chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                '0'
+            }
+            else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Escapes a string for use in an XML property.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\n' => out.push_str("&#10;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Unescapes a string from an XML property.
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#10;", "\n")
+        .replace("&amp;", "&")
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+        <context name=\"local\" >\
+        <context-attribute name=\"hw_serial\" value=\"AB12-34\" />\
+        <device id=\"iio:device0\" name=\"adc\" >\
+        <channel id=\"voltage0\" type=\"input\" >\
+        <scan-element index=\"0\" format=\"le:s12/16&gt;&gt;4\" scale=\"0.5\" />\
+        <attribute name=\"raw\" filename=\"in_voltage0_raw\" />\
+        </channel>\
+        <channel id=\"voltage0\" type=\"output\" >\
+        <attribute name=\"raw\" filename=\"out_voltage0_raw\" />\
+        </channel>\
+        <attribute name=\"label\" />\
+        <attribute name=\"serial_number\" />\
+        <buffer-attribute name=\"watermark\" />\
+        <debug-attribute name=\"direct_reg_access\" />\
+        </device>\
+        </context>";
+
+    fn read(scope: AttrScope, name: &str) -> Option<String> {
+        let val = match (scope, name) {
+            (AttrScope::Channel("iio:device0", "voltage0", false), "raw") => "1234",
+            (AttrScope::Channel("iio:device0", "voltage0", true), "raw") => "42",
+            (AttrScope::Device("iio:device0"), "label") => "a \"b\" & <c>",
+            (AttrScope::Device("iio:device0"), "serial_number") => "SN-9876",
+            (AttrScope::Buffer("iio:device0"), "watermark") => "1",
+            _ => return None,
+        };
+        Some(val.to_string())
+    }
+
+    #[test]
+    fn bake() {
+        let xml = bake_values(XML, false, read);
+        assert!(xml.contains("<context-attribute name=\"hw_serial\" value=\"AB12-34\" />"));
+        assert!(
+            xml.contains("<attribute name=\"raw\" filename=\"in_voltage0_raw\" value=\"1234\" />")
+        );
+        assert!(
+            xml.contains("<attribute name=\"raw\" filename=\"out_voltage0_raw\" value=\"42\" />")
+        );
+        assert!(
+            xml.contains("<attribute name=\"label\" value=\"a &quot;b&quot; &amp; &lt;c&gt;\" />")
+        );
+        assert!(xml.contains("<buffer-attribute name=\"watermark\" value=\"1\" />"));
+        assert!(xml.contains("<debug-attribute name=\"direct_reg_access\" />"));
+        assert!(xml.contains("format=\"le:s12/16&gt;&gt;4\""));
+    }
+
+    #[test]
+    fn anonymize() {
+        let xml = bake_values(XML, true, read);
+        assert!(xml.contains("<context-attribute name=\"hw_serial\" value=\"0000-00\" />"));
+        assert!(xml.contains("<attribute name=\"serial_number\" value=\"00-0000\" />"));
+        assert!(xml.contains("value=\"1234\""));
+    }
+}
@@ -0,0 +1,688 @@
+// industrial-io/src/frame.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Frames of captured samples.
+//!
+//! A [`Frame`] is a block of samples captured from a set of channels of a
+//! device at the same time, typically from a single refill of a
+//! [`Buffer`]. The samples for each channel are demultiplexed from the
+//! buffer and converted to floating-point values, so that the higher-level
+//! parts of the library can work with them without knowing the data format
+//! of each channel.
+//!
+//! Each channel of a frame records where its samples came from: the
+//! device and channel ID, the channel type and modifier, and any scale
+//! applied to them, so that the stages and sinks downstream don't need a
+//! separate description of the capture.
+//!
+//! A frame can also carry a snapshot of device, buffer, or channel
+//! attribute values taken when it was captured, such as the gain or LO
+//! frequency, so that recordings retain their acquisition parameters.
+//!
+//! If the frames have a timestamp channel, the samples in a range of
+//! absolute time can be pulled out of a [`FrameSet`], across the
+//! boundaries of the frames, with [`FrameSet::window()`]:
+//!
+//! ```
+//! use industrial_io::frame::{Frame, FrameChannel, FrameSet};
+//!
+//! let frame = |ts: &[f64], v: &[f64]| Frame {
+//!     channels: vec![
+//!         FrameChannel::new("voltage0", v.to_vec()),
+//!         FrameChannel::new("timestamp", ts.to_vec()),
+//!     ],
+//!     ..Frame::default()
+//! };
+//! let set: FrameSet = vec![
+//!     frame(&[100.0, 200.0], &[1.0, 2.0]),
+//!     frame(&[300.0, 400.0], &[3.0, 4.0]),
+//! ]
+//! .into_iter()
+//! .collect();
+//!
+//! let win = set.window(200, 400).unwrap();
+//! assert_eq!(win.samples("voltage0").unwrap(), &[2.0, 3.0]);
+//! ```
+
+use crate::{device::ChannelStateGuard, Buffer, Channel, ChannelType, Device, Error, Result};
+use std::{collections::BTreeMap, ops::Range};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The samples for one channel in a [`Frame`].
+///
+/// Along with the samples, this carries the identity of the channel that
+/// they came from, when known, so that the frame is self-describing.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FrameChannel {
+    /// The ID of the channel (e.g. <b><i>voltage0</i></b>)
+    pub id: String,
+    /// The index of the channel in the device, if known.
+    pub index: Option<usize>,
+    /// The samples for the channel
+    pub samples: Vec<f64>,
+    /// The ID of the device (e.g. <b><i>iio:device0</i></b>), if known.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub device: Option<String>,
+    /// The type of the channel, if known.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub chan_type: Option<ChannelType>,
+    /// The modifier of the channel, as a kernel ABI code, if known.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub modifier: Option<u32>,
+    /// The scale that was applied to the samples, if they were scaled.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub scale: Option<f64>,
+}
+
+impl FrameChannel {
+    /// Creates a frame channel with the ID and samples.
+    pub fn new(id: &str, samples: Vec<f64>) -> Self {
+        Self {
+            id: id.to_string(),
+            samples,
+            ..Self::default()
+        }
+    }
+
+    /// Creates a frame channel with samples from a device channel,
+    /// recording the identity of the channel.
+    pub fn from_channel(chan: &Channel, samples: Vec<f64>) -> Self {
+        Self {
+            id: chan.id().unwrap_or_default(),
+            index: chan.index().ok(),
+            samples,
+            device: chan.device_id(),
+            chan_type: Some(chan.channel_type()),
+            modifier: Some(chan.modifier()),
+            scale: None,
+        }
+    }
+
+    /// Multiplies the samples by the scale, and records it.
+    ///
+    /// If the samples were already scaled, the recorded scale is the
+    /// product of the two.
+    pub fn apply_scale(&mut self, scale: f64) {
+        for x in self.samples.iter_mut() {
+            *x *= scale;
+        }
+        self.scale = Some(self.scale.unwrap_or(1.0) * scale);
+    }
+}
+
+/// A block of samples captured from a set of channels at the same time.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Frame {
+    /// The sequence number of the frame in a capture.
+    pub seq: u64,
+    /// The sample rate, in Hz, if known.
+    pub sample_rate: Option<f64>,
+    /// The samples for each channel.
+    pub channels: Vec<FrameChannel>,
+    /// Attribute values captured with the frame, by name.
+    pub attrs: BTreeMap<String, String>,
+}
+
+impl Frame {
+    /// Creates a new, empty, frame.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a frame from the samples in a buffer.
+    ///
+    /// This demultiplexes and converts the samples for each of the
+    /// channels. It fails with [`Error::LayoutMismatch`] if a channel
+    /// isn't enabled in the buffer, and with [`Error::WrongDataType`] if
+    /// its samples repeat, or don't fit into a standard integer type.
+    pub fn from_buffer(buf: &Buffer, chans: &[Channel]) -> Result<Self> {
+        let mut channels = Vec::with_capacity(chans.len());
+        for chan in chans {
+            channels.push(FrameChannel::from_channel(chan, chan.read_f64(buf)?));
+        }
+        Ok(Self {
+            channels,
+            ..Self::default()
+        })
+    }
+
+    /// Captures the current values of attributes into the frame.
+    ///
+    /// Each name can be a buffer or device attribute, like "gain", or a
+    /// channel attribute, given as "channel/attr", like
+    /// "altvoltage0/frequency". Buffer attributes take precedence over
+    /// device attributes with the same name. A channel that isn't found
+    /// is reported as [`Error::InvalidIndex`].
+    pub fn snapshot_attrs<S: AsRef<str>>(&mut self, buf: &Buffer, names: &[S]) -> Result<()> {
+        let dev = buf.device();
+        for name in names {
+            let name = name.as_ref();
+            let val = match name.split_once('/') {
+                Some((chan, attr)) => dev
+                    .find_input_channel(chan)
+                    .or_else(|| dev.find_output_channel(chan))
+                    .ok_or(Error::InvalidIndex)?
+                    .attr_read_str(attr)?,
+                None if buf.has_attr(name) => buf.attr_read_str(name)?,
+                None => dev.attr_read_str(name)?,
+            };
+            self.attrs.insert(name.to_string(), val);
+        }
+        Ok(())
+    }
+
+    /// Gets the value of a captured attribute, by name.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).map(|s| s.as_str())
+    }
+
+    /// Gets the number of samples in the frame.
+    ///
+    /// This is the number of samples for each channel. If, for some
+    /// reason, the channels have different lengths, it's the shortest one.
+    pub fn len(&self) -> usize {
+        self.channels
+            .iter()
+            .map(|ch| ch.samples.len())
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Determines if the frame has no samples.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the number of channels in the frame.
+    pub fn num_channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Gets the data for a channel by its ID.
+    pub fn channel(&self, id: &str) -> Option<&FrameChannel> {
+        self.channels.iter().find(|ch| ch.id == id)
+    }
+
+    /// Gets the samples for a channel by its ID.
+    pub fn samples(&self, id: &str) -> Option<&[f64]> {
+        self.channel(id).map(|ch| ch.samples.as_slice())
+    }
+
+    /// Gets the samples interleaved by channel, like they would be in a
+    /// buffer: the first sample from each channel, then the second, etc.
+    pub fn interleaved(&self) -> Vec<f64> {
+        let n = self.len();
+        let mut data = Vec::with_capacity(n * self.num_channels());
+        for i in 0..n {
+            data.extend(self.channels.iter().map(|ch| ch.samples[i]));
+        }
+        data
+    }
+
+    /// Appends the samples from another frame with the same channels.
+    ///
+    /// Channels are matched by ID. Any channels in the other frame that
+    /// aren't in this one are ignored. The attributes of this frame are
+    /// kept.
+    pub fn extend(&mut self, other: &Frame) {
+        for ch in &mut self.channels {
+            if let Some(samples) = other.samples(&ch.id) {
+                ch.samples.extend_from_slice(samples);
+            }
+        }
+    }
+
+    /// Removes the first `n` samples from each channel.
+    pub fn drain_front(&mut self, n: usize) {
+        for ch in &mut self.channels {
+            let n = n.min(ch.samples.len());
+            ch.samples.drain(..n);
+        }
+    }
+
+    /// Shortens each channel to, at most, `n` samples.
+    pub fn truncate(&mut self, n: usize) {
+        for ch in &mut self.channels {
+            ch.samples.truncate(n);
+        }
+    }
+
+    /// Gets a copy of the frame with only the samples in the range of
+    /// indexes.
+    ///
+    /// The range is clamped to the samples of each channel.
+    pub fn slice(&self, range: Range<usize>) -> Frame {
+        let channels = self
+            .channels
+            .iter()
+            .map(|ch| {
+                let end = range.end.min(ch.samples.len());
+                let start = range.start.min(end);
+                FrameChannel {
+                    samples: ch.samples[start..end].to_vec(),
+                    ..ch.clone()
+                }
+            })
+            .collect();
+        Frame {
+            channels,
+            ..self.clone_meta()
+        }
+    }
+
+    /// Gets the timestamp channel of the frame, if it has one.
+    ///
+    /// This is the channel with the timestamp type, or, if the type isn't
+    /// known, the one with the ID "timestamp".
+    pub fn timestamp_channel(&self) -> Option<&FrameChannel> {
+        self.channels
+            .iter()
+            .find(|ch| ch.chan_type == Some(ChannelType::Timestamp))
+            .or_else(|| self.channel("timestamp"))
+    }
+
+    /// Gets the range of indexes of the samples with timestamps in the
+    /// range from `start` up to, but not including, `end`.
+    ///
+    /// The timestamps are assumed to be increasing. This fails if the
+    /// frame doesn't have a timestamp channel.
+    pub fn time_range(&self, start: u64, end: u64) -> Result<Range<usize>> {
+        let ts = &self
+            .timestamp_channel()
+            .ok_or_else(|| Error::General("The frame has no timestamp channel".into()))?
+            .samples[..self.len()];
+        let first = ts.partition_point(|&t| t < start as f64);
+        let last = ts.partition_point(|&t| t < end as f64);
+        Ok(first..last.max(first))
+    }
+
+    /// Gets a copy of the frame with the samples that have timestamps in
+    /// the range from `start` up to, but not including, `end`.
+    ///
+    /// See [`time_range()`](Frame::time_range).
+    pub fn window(&self, start: u64, end: u64) -> Result<Frame> {
+        Ok(self.slice(self.time_range(start, end)?))
+    }
+
+    /// Gets a copy of the frame without any samples.
+    fn clone_meta(&self) -> Frame {
+        Frame {
+            seq: self.seq,
+            sample_rate: self.sample_rate,
+            channels: Vec::new(),
+            attrs: self.attrs.clone(),
+        }
+    }
+}
+
+/// A sequence of frames, typically from consecutive captures of the same
+/// set of channels.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FrameSet {
+    /// The frames, in the order they were captured.
+    pub frames: Vec<Frame>,
+}
+
+impl FrameSet {
+    /// Creates a new, empty, set of frames.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a frame to the set.
+    pub fn push(&mut self, frame: Frame) {
+        self.frames.push(frame);
+    }
+
+    /// Gets the number of frames in the set.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Determines if there are no frames in the set.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Gets an iterator over the frames in the set.
+    pub fn iter(&self) -> std::slice::Iter<'_, Frame> {
+        self.frames.iter()
+    }
+
+    /// Gets the total number of samples, per channel, in all the frames.
+    pub fn num_samples(&self) -> usize {
+        self.frames.iter().map(Frame::len).sum()
+    }
+
+    /// Joins all the frames into a single frame.
+    ///
+    /// The channels, sequence number, sample rate, and attributes are
+    /// taken from the first frame.
+    pub fn concat(&self) -> Frame {
+        let mut iter = self.frames.iter();
+        let mut frame = iter.next().cloned().unwrap_or_default();
+        for f in iter {
+            frame.extend(f);
+        }
+        frame
+    }
+
+    /// Gets the samples with timestamps in the range from `start` up to,
+    /// but not including, `end`, as a single frame.
+    ///
+    /// The timestamps are in the units of the timestamp channel, usually
+    /// nanoseconds, and are assumed to increase through the frames. The
+    /// window can span any number of frames. The sequence number, sample
+    /// rate, and attributes are taken from the first frame with samples
+    /// in the window. Note that the timestamps are held as floating-point
+    /// values, so that times since the Unix epoch only have a resolution
+    /// of a few hundred nanoseconds.
+    ///
+    /// This fails if any of the frames doesn't have a timestamp channel.
+    pub fn window(&self, start: u64, end: u64) -> Result<Frame> {
+        let mut win: Option<Frame> = None;
+        for f in &self.frames {
+            let part = f.window(start, end)?;
+            match win.as_mut() {
+                Some(win) => win.extend(&part),
+                None if !part.is_empty() => win = Some(part),
+                None => {}
+            }
+        }
+        Ok(win.unwrap_or_else(|| {
+            self.frames
+                .first()
+                .map(|f| f.slice(0..0))
+                .unwrap_or_default()
+        }))
+    }
+
+    /// Splits the set into the samples before the time, `t`, and those at
+    /// or after it.
+    ///
+    /// The frame that contains the time is split in two, with a part in
+    /// each set. Any empty frames are dropped. This fails if any of the
+    /// frames doesn't have a timestamp channel.
+    pub fn split_at_time(&self, t: u64) -> Result<(FrameSet, FrameSet)> {
+        let (mut before, mut after) = (FrameSet::new(), FrameSet::new());
+        for f in &self.frames {
+            let n = f.time_range(0, t)?.end;
+            if n > 0 {
+                before.push(f.slice(0..n));
+            }
+            if n < f.len() {
+                after.push(f.slice(n..f.len()));
+            }
+        }
+        Ok((before, after))
+    }
+}
+
+impl FromIterator<Frame> for FrameSet {
+    fn from_iter<I: IntoIterator<Item = Frame>>(iter: I) -> Self {
+        Self {
+            frames: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for FrameSet {
+    type Item = Frame;
+    type IntoIter = std::vec::IntoIter<Frame>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.frames.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a FrameSet {
+    type Item = &'a Frame;
+    type IntoIter = std::slice::Iter<'a, Frame>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.frames.iter()
+    }
+}
+
+/// A blocking stream of frames from an input buffer.
+///
+/// Each call to `next()` refills the buffer and converts the samples of
+/// the selected channels into a [`Frame`], numbered in sequence. The
+/// stream never ends on its own; an error from the refill or conversion
+/// is returned as an item, and the caller decides whether to carry on.
+///
+/// ```no_run
+/// use industrial_io as iio;
+///
+/// let ctx = iio::Context::new().unwrap();
+/// let dev = ctx.find_device("ads1015").unwrap();
+/// let chan = dev.find_input_channel("voltage0").unwrap();
+///
+/// for frame in dev.stream(&[chan], 256).unwrap().take(10) {
+///     let frame = frame.unwrap();
+///     println!("{}: {:?}", frame.seq, frame.samples("voltage0"));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CaptureStream {
+    /// The input buffer
+    buf: Buffer,
+    /// The channels read into each frame
+    chans: Vec<Channel>,
+    /// The sample rate of the device, if known
+    sample_rate: Option<f64>,
+    /// The sequence number of the next frame
+    seq: u64,
+    #[allow(dead_code)]
+    /// The enabled state of the channels, to restore when the stream is
+    /// dropped, if the stream enabled them. This is dropped after the
+    /// buffer.
+    state: Option<ChannelStateGuard>,
+}
+
+impl CaptureStream {
+    /// Creates a stream of frames for the channels, from the buffer.
+    ///
+    /// The channels should all have been enabled when the buffer was
+    /// created.
+    pub fn new(buf: Buffer, chans: &[Channel]) -> Self {
+        let sample_rate = buf.device().sampling_frequency().ok();
+        Self {
+            buf,
+            chans: chans.to_vec(),
+            sample_rate,
+            seq: 0,
+            state: None,
+        }
+    }
+
+    /// Gets the underlying buffer.
+    pub fn buffer(&self) -> &Buffer {
+        &self.buf
+    }
+
+    /// Gets the channels read into each frame.
+    pub fn channels(&self) -> &[Channel] {
+        &self.chans
+    }
+
+    /// Gets the buffer back.
+    ///
+    /// If the stream came from [`Device::stream()`], the channels are
+    /// restored to their previous state, but the buffer keeps the set of
+    /// channels that it was created with.
+    pub fn into_inner(self) -> Buffer {
+        self.buf
+    }
+
+    /// Blocks until the next buffer of samples is read, and converts it
+    /// into a frame.
+    pub fn read_frame(&mut self) -> Result<Frame> {
+        self.buf.refill()?;
+        let mut frame = Frame::from_buffer(&self.buf, &self.chans)?;
+        frame.seq = self.seq;
+        frame.sample_rate = self.sample_rate;
+        self.seq += 1;
+        Ok(frame)
+    }
+}
+
+impl Iterator for CaptureStream {
+    type Item = Result<Frame>;
+
+    /// Blocks until the next buffer of samples is read.
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.read_frame())
+    }
+}
+
+impl Buffer {
+    /// Converts the buffer into a blocking stream of frames for the
+    /// channels.
+    ///
+    /// See [`CaptureStream`].
+    pub fn stream(self, chans: &[Channel]) -> CaptureStream {
+        CaptureStream::new(self, chans)
+    }
+}
+
+impl Device {
+    /// Enables the channels and creates a blocking stream of frames from
+    /// them, with `block_len` samples per frame.
+    ///
+    /// The channels are restored to their previous state when the stream
+    /// is dropped, or if it can't be created.
+    ///
+    /// See [`CaptureStream`].
+    pub fn stream(&self, chans: &[Channel], block_len: usize) -> Result<CaptureStream> {
+        let state = ChannelStateGuard::new(self);
+        for chan in chans {
+            chan.enable();
+        }
+        let buf = self.create_buffer(block_len, false)?;
+        Ok(CaptureStream {
+            state: Some(state),
+            ..buf.stream(chans)
+        })
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(a: &[f64], b: &[f64]) -> Frame {
+        Frame {
+            channels: vec![
+                FrameChannel::new("voltage0", a.to_vec()),
+                FrameChannel::new("voltage1", b.to_vec()),
+            ],
+            ..Frame::default()
+        }
+    }
+
+    #[test]
+    fn extend_and_trim() {
+        let mut f = frame(&[1.0, 2.0], &[10.0, 20.0]);
+        f.attrs.insert("hardwaregain".into(), "10".into());
+        assert_eq!(f.len(), 2);
+        assert_eq!(f.num_channels(), 2);
+        assert_eq!(f.interleaved(), vec![1.0, 10.0, 2.0, 20.0]);
+
+        f.extend(&frame(&[3.0], &[30.0]));
+        assert_eq!(f.samples("voltage0").unwrap(), &[1.0, 2.0, 3.0]);
+        assert_eq!(f.samples("voltage1").unwrap(), &[10.0, 20.0, 30.0]);
+
+        f.drain_front(1);
+        f.truncate(1);
+        assert_eq!(f.samples("voltage0").unwrap(), &[2.0]);
+        assert_eq!(f.samples("voltage1").unwrap(), &[20.0]);
+        assert!(f.samples("voltage2").is_none());
+        assert_eq!(f.attr("hardwaregain"), Some("10"));
+    }
+
+    #[test]
+    fn apply_scale() {
+        let mut ch = FrameChannel::new("voltage0", vec![100.0, -20.0]);
+        assert_eq!(ch.scale, None);
+        ch.apply_scale(0.5);
+        assert_eq!(ch.samples, [50.0, -10.0]);
+        ch.apply_scale(1e-3);
+        assert_eq!(ch.scale, Some(0.5e-3));
+    }
+
+    #[test]
+    fn frame_set() {
+        let set: FrameSet = vec![frame(&[1.0], &[10.0]), frame(&[2.0, 3.0], &[20.0, 30.0])]
+            .into_iter()
+            .collect();
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.num_samples(), 3);
+
+        let f = set.concat();
+        assert_eq!(f.samples("voltage0").unwrap(), &[1.0, 2.0, 3.0]);
+        assert!(FrameSet::new().concat().is_empty());
+    }
+
+    fn ts_frame(seq: u64, ts: &[f64], v: &[f64]) -> Frame {
+        let mut ts_chan = FrameChannel::new("timestamp", ts.to_vec());
+        ts_chan.chan_type = Some(ChannelType::Timestamp);
+        Frame {
+            seq,
+            channels: vec![FrameChannel::new("voltage0", v.to_vec()), ts_chan],
+            ..Frame::default()
+        }
+    }
+
+    #[test]
+    fn time_window() {
+        let set: FrameSet = vec![
+            ts_frame(0, &[100.0, 200.0, 300.0], &[1.0, 2.0, 3.0]),
+            ts_frame(1, &[400.0, 500.0, 600.0], &[4.0, 5.0, 6.0]),
+        ]
+        .into_iter()
+        .collect();
+
+        let win = set.window(250, 550).unwrap();
+        assert_eq!(win.seq, 0);
+        assert_eq!(win.samples("voltage0").unwrap(), &[3.0, 4.0, 5.0]);
+        assert_eq!(win.samples("timestamp").unwrap(), &[300.0, 400.0, 500.0]);
+
+        let win = set.window(450, 1000).unwrap();
+        assert_eq!(win.seq, 1);
+        assert_eq!(win.samples("voltage0").unwrap(), &[5.0, 6.0]);
+
+        let win = set.window(700, 800).unwrap();
+        assert!(win.is_empty());
+        assert_eq!(win.num_channels(), 2);
+
+        let (before, after) = set.split_at_time(200).unwrap();
+        assert_eq!(before.len(), 1);
+        assert_eq!(before.num_samples(), 1);
+        assert_eq!(after.len(), 2);
+        assert_eq!(
+            after.concat().samples("voltage0").unwrap(),
+            &[2.0, 3.0, 4.0, 5.0, 6.0]
+        );
+
+        let set: FrameSet = vec![frame(&[1.0], &[10.0])].into_iter().collect();
+        assert!(set.window(0, 100).is_err());
+        assert!(set.split_at_time(0).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+// industrial-io/src/frame_stream.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! An asynchronous stream of frames with _tokio_.
+//!
+//! A [`FrameStream`] runs a task that refills an [`AsyncBuffer`] and
+//! converts the samples of the selected channels into [`Frame`]s, queueing
+//! them for the consumer. It implements the `Stream` trait from
+//! _futures-core_, so the frames can be consumed with the combinators of
+//! `StreamExt` from _futures_ or _tokio-stream_.
+//!
+//! The queue has a fixed depth. If the consumer falls behind, the newest
+//! frames are dropped rather than stalling the capture, and the number of
+//! frames lost is reported in the stream as an [`Error::Overrun`] before
+//! the next frame that gets through. The sequence numbers of the frames
+//! count the dropped ones as well, so the gaps show where they were lost.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use industrial_io as iio;
+//! use iio::{frame_stream::FrameStream, AsyncBuffer};
+//!
+//! # async fn capture() -> iio::Result<()> {
+//! let ctx = iio::Context::new()?;
+//! let dev = ctx.find_device("ads1015").unwrap();
+//! let chan = dev.find_input_channel("voltage0").unwrap();
+//! chan.enable();
+//!
+//! let buf = AsyncBuffer::new(dev.create_buffer(256, false)?)?;
+//! let mut frames = FrameStream::new(buf, &[chan], 16);
+//!
+//! while let Some(res) = frames.next().await {
+//!     match res {
+//!         Ok(frame) => println!("{}: {:?}", frame.seq, frame.samples("voltage0")),
+//!         Err(iio::Error::Overrun(n)) => eprintln!("Dropped {} frames", n),
+//!         Err(err) => return Err(err),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{AsyncBuffer, Buffer, Channel, Direction, Error, Frame, Result};
+use futures_core::Stream;
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+use tokio::{
+    sync::mpsc::{self, error::TrySendError},
+    task::JoinHandle,
+};
+
+/// An asynchronous stream of frames captured from an input buffer.
+///
+/// The stream ends after the first error refilling the buffer or reading
+/// the samples, which is returned as its last item.
+#[derive(Debug)]
+pub struct FrameStream {
+    /// The queue of frames from the capture task
+    rx: mpsc::Receiver<Result<Frame>>,
+    /// The capture task, aborted when the stream is dropped
+    task: JoinHandle<()>,
+    /// The total number of frames dropped
+    dropped: Arc<AtomicU64>,
+}
+
+impl FrameStream {
+    /// Starts capturing frames of the channels from the buffer, queueing
+    /// up to `queue_depth` of them for the consumer.
+    ///
+    /// The channels should all have been enabled when the buffer was
+    /// created. This must be called from within a _tokio_ runtime.
+    pub fn new(buf: AsyncBuffer, chans: &[Channel], queue_depth: usize) -> Self {
+        // Channels can't be sent to another task, so the task finds them
+        // again by ID.
+        let ids = chans
+            .iter()
+            .map(|chan| (chan.id().unwrap_or_default(), chan.direction()))
+            .collect();
+
+        let (tx, rx) = mpsc::channel(queue_depth.max(1));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let task = tokio::spawn(capture(buf, ids, tx, Arc::clone(&dropped)));
+        Self { rx, task, dropped }
+    }
+
+    /// Gets the total number of frames that were dropped because the
+    /// queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Stream for FrameStream {
+    type Item = Result<Frame>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl Drop for FrameStream {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Reads a frame of the channels, by ID, from the buffer.
+fn read_frame(buf: &Buffer, ids: &[(String, Direction)]) -> Result<Frame> {
+    let dev = buf.device();
+    let chans = ids
+        .iter()
+        .map(|(id, dir)| {
+            dev.find_channel(id, *dir)
+                .ok_or_else(|| Error::General(format!("Channel not found: '{}'", id)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Frame::from_buffer(buf, &chans)
+}
+
+/// The capture task, which refills the buffer and queues the frames
+/// until the stream is dropped or there's an error.
+async fn capture(
+    mut buf: AsyncBuffer,
+    ids: Vec<(String, Direction)>,
+    tx: mpsc::Sender<Result<Frame>>,
+    dropped: Arc<AtomicU64>,
+) {
+    let sample_rate = buf.device().sampling_frequency().ok();
+    let mut seq = 0;
+    // The number of frames dropped, but not yet reported
+    let mut pending = 0;
+
+    loop {
+        let mut frame = match buf.refill().await.and_then(|_| read_frame(&buf, &ids)) {
+            Ok(frame) => frame,
+            Err(err) => {
+                let _ = tx.send(Err(err)).await;
+                return;
+            }
+        };
+        frame.seq = seq;
+        frame.sample_rate = sample_rate;
+        seq += 1;
+
+        if pending > 0 {
+            match tx.try_reserve() {
+                Ok(permit) => {
+                    permit.send(Err(Error::Overrun(pending)));
+                    pending = 0;
+                }
+                Err(TrySendError::Full(_)) => {}
+                Err(TrySendError::Closed(_)) => return,
+            }
+        }
+
+        match tx.try_send(Ok(frame)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                pending += 1;
+                dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Closed(_)) => return,
+        }
+    }
+}
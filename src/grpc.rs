@@ -0,0 +1,643 @@
+// industrial-io/src/grpc.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! A gRPC service for remote access to the devices in a context.
+//!
+//! The service is defined in `proto/iio.proto`, which can be used to
+//! generate typed clients in any language. It has calls to list the
+//! devices, read and write attributes, and a server-streaming capture
+//! call that sends frames from a [`Pipeline`] until the client cancels.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! use industrial_io as iio;
+//!
+//! let ctx = iio::Context::new()?;
+//! iio::grpc::serve(ctx, "0.0.0.0:50051".parse()?).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A Rust client is available as [`proto::iio_client::IioClient`].
+//!
+//! [`Pipeline`]: crate::sink::Pipeline
+
+use crate::{
+    auth::{AllowList, Auth, TlsFiles},
+    device::ChannelStateGuard,
+    sink::{FrameSink, Pipeline},
+    Channel, ChannelType, Context, Device, Direction, Error, Frame, Result,
+};
+use std::{collections::HashMap, net::SocketAddr, thread};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Server, Request, Response, Status};
+
+/// The messages and generated service stubs.
+///
+/// These mirror the definitions in `proto/iio.proto`.
+#[allow(missing_docs, missing_copy_implementations, unused_qualifications)]
+pub mod proto {
+    use std::collections::HashMap;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ListDevicesRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ChannelInfo {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, tag = "2")]
+        pub name: String,
+        #[prost(bool, tag = "3")]
+        pub output: bool,
+        #[prost(bool, tag = "4")]
+        pub scan_element: bool,
+        #[prost(string, repeated, tag = "5")]
+        pub attrs: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DeviceInfo {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, tag = "2")]
+        pub name: String,
+        #[prost(string, tag = "3")]
+        pub label: String,
+        #[prost(message, repeated, tag = "4")]
+        pub channels: Vec<ChannelInfo>,
+        #[prost(string, repeated, tag = "5")]
+        pub attrs: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ListDevicesResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub devices: Vec<DeviceInfo>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ReadAttributesRequest {
+        #[prost(string, tag = "1")]
+        pub device: String,
+        #[prost(string, tag = "2")]
+        pub channel: String,
+        #[prost(bool, tag = "3")]
+        pub output: bool,
+        #[prost(string, repeated, tag = "4")]
+        pub names: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ReadAttributesResponse {
+        #[prost(map = "string, string", tag = "1")]
+        pub values: HashMap<String, String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct WriteAttributeRequest {
+        #[prost(string, tag = "1")]
+        pub device: String,
+        #[prost(string, tag = "2")]
+        pub channel: String,
+        #[prost(bool, tag = "3")]
+        pub output: bool,
+        #[prost(string, tag = "4")]
+        pub name: String,
+        #[prost(string, tag = "5")]
+        pub value: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct WriteAttributeResponse {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct CaptureRequest {
+        #[prost(string, tag = "1")]
+        pub device: String,
+        #[prost(string, repeated, tag = "2")]
+        pub channels: Vec<String>,
+        #[prost(uint32, tag = "3")]
+        pub samples: u32,
+        #[prost(uint64, tag = "4")]
+        pub max_frames: u64,
+        #[prost(string, repeated, tag = "5")]
+        pub attrs: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FrameChannel {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(double, repeated, tag = "2")]
+        pub samples: Vec<f64>,
+        #[prost(uint32, optional, tag = "3")]
+        pub index: Option<u32>,
+        #[prost(string, optional, tag = "4")]
+        pub device: Option<String>,
+        #[prost(uint32, optional, tag = "5")]
+        pub chan_type: Option<u32>,
+        #[prost(uint32, optional, tag = "6")]
+        pub modifier: Option<u32>,
+        #[prost(double, optional, tag = "7")]
+        pub scale: Option<f64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Frame {
+        #[prost(uint64, tag = "1")]
+        pub seq: u64,
+        #[prost(double, optional, tag = "2")]
+        pub sample_rate: Option<f64>,
+        #[prost(message, repeated, tag = "3")]
+        pub channels: Vec<FrameChannel>,
+        #[prost(map = "string, string", tag = "4")]
+        pub attrs: HashMap<String, String>,
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/iio.Iio.rs"));
+}
+
+use proto::iio_server::{Iio, IioServer};
+
+impl From<&Frame> for proto::Frame {
+    fn from(frame: &Frame) -> Self {
+        Self {
+            seq: frame.seq,
+            sample_rate: frame.sample_rate,
+            channels: frame
+                .channels
+                .iter()
+                .map(|ch| proto::FrameChannel {
+                    id: ch.id.clone(),
+                    samples: ch.samples.clone(),
+                    index: ch.index.map(|i| i as u32),
+                    device: ch.device.clone(),
+                    chan_type: ch.chan_type.map(|typ| typ as u32),
+                    modifier: ch.modifier,
+                    scale: ch.scale,
+                })
+                .collect(),
+            attrs: frame
+                .attrs
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl From<proto::Frame> for Frame {
+    fn from(frame: proto::Frame) -> Self {
+        Self {
+            seq: frame.seq,
+            sample_rate: frame.sample_rate,
+            channels: frame
+                .channels
+                .into_iter()
+                .map(|ch| crate::FrameChannel {
+                    id: ch.id,
+                    index: ch.index.map(|i| i as usize),
+                    samples: ch.samples,
+                    device: ch.device,
+                    chan_type: ch.chan_type.map(ChannelType::from_raw),
+                    modifier: ch.modifier,
+                    scale: ch.scale,
+                })
+                .collect(),
+            attrs: frame.attrs.into_iter().collect(),
+        }
+    }
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::NotSupported { .. } => Status::unimplemented(err.to_string()),
+            Error::InvalidIndex => Status::not_found(err.to_string()),
+            Error::Attr {
+                source: nix::Error::ENOENT,
+                ..
+            } => Status::not_found(err.to_string()),
+            Error::Attr {
+                source: nix::Error::EINVAL,
+                ..
+            } => Status::invalid_argument(err.to_string()),
+            Error::PolicyViolation(_) => Status::permission_denied(err.to_string()),
+            Error::NulError(_) | Error::StringConversionError | Error::InvalidBufferSize { .. } => {
+                Status::invalid_argument(err.to_string())
+            }
+            _ => Status::internal(err.to_string()),
+        }
+    }
+}
+
+/// The implementation of the gRPC service for a context.
+#[derive(Debug, Clone)]
+pub struct IioService {
+    /// The context with the devices
+    ctx: Context,
+    /// The attributes that clients can write
+    writes: AllowList,
+}
+
+impl IioService {
+    /// The default number of samples per frame for a capture
+    pub const DFLT_SAMPLES: usize = 1024;
+
+    /// Creates a service for the devices in the context.
+    ///
+    /// By default, the service is read-only: clients can't write any
+    /// attributes until they're allowed with [`writes()`](Self::writes).
+    pub fn new(ctx: Context) -> Self {
+        Self {
+            ctx,
+            writes: AllowList::new(),
+        }
+    }
+
+    /// Sets the attributes that clients can write.
+    ///
+    /// Use [`AllowList::any()`] to let clients write any attribute.
+    pub fn writes(mut self, writes: AllowList) -> Self {
+        self.writes = writes;
+        self
+    }
+
+    /// Gets the service as a server that can be added to a tonic router.
+    pub fn into_server(self) -> IioServer<Self> {
+        IioServer::new(self)
+    }
+
+    /// Finds a device by name or ID.
+    #[allow(clippy::result_large_err)]
+    fn device(ctx: &Context, name: &str) -> std::result::Result<Device, Status> {
+        ctx.find_device(name)
+            .ok_or_else(|| Status::not_found(format!("Device not found: '{}'", name)))
+    }
+
+    /// Finds a channel of a device.
+    #[allow(clippy::result_large_err)]
+    fn channel(dev: &Device, name: &str, output: bool) -> std::result::Result<Channel, Status> {
+        let dir = if output {
+            Direction::Output
+        }
+        else {
+            Direction::Input
+        };
+        dev.find_channel(name, dir)
+            .ok_or_else(|| Status::not_found(format!("Channel not found: '{}'", name)))
+    }
+}
+
+/// Runs a blocking call into the library on the _tokio_ blocking thread
+/// pool, so that a slow backend doesn't stall the executor.
+async fn blocking<T, F>(f: F) -> std::result::Result<T, Status>
+where
+    T: Send + 'static,
+    F: FnOnce() -> std::result::Result<T, Status> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|err| Status::internal(err.to_string()))?
+}
+
+#[allow(clippy::result_large_err)]
+#[tonic::async_trait]
+impl Iio for IioService {
+    async fn list_devices(
+        &self,
+        _req: Request<proto::ListDevicesRequest>,
+    ) -> std::result::Result<Response<proto::ListDevicesResponse>, Status> {
+        let ctx = self.ctx.clone();
+        let devices = blocking(move || {
+            Ok(ctx
+                .devices()
+                .map(|dev| proto::DeviceInfo {
+                    id: dev.id().unwrap_or_default(),
+                    name: dev.name().unwrap_or_default(),
+                    label: dev.label().unwrap_or_default(),
+                    channels: dev
+                        .channels()
+                        .map(|ch| proto::ChannelInfo {
+                            id: ch.id().unwrap_or_default(),
+                            name: ch.name().unwrap_or_default(),
+                            output: ch.is_output(),
+                            scan_element: ch.is_scan_element(),
+                            attrs: ch.attrs().collect(),
+                        })
+                        .collect(),
+                    attrs: dev.attributes().collect(),
+                })
+                .collect())
+        })
+        .await?;
+        Ok(Response::new(proto::ListDevicesResponse { devices }))
+    }
+
+    async fn read_attributes(
+        &self,
+        req: Request<proto::ReadAttributesRequest>,
+    ) -> std::result::Result<Response<proto::ReadAttributesResponse>, Status> {
+        let req = req.into_inner();
+        let ctx = self.ctx.clone();
+
+        let values = blocking(move || {
+            let dev = Self::device(&ctx, &req.device)?;
+            let values = if req.channel.is_empty() {
+                if req.names.is_empty() {
+                    dev.attr_read_all()?
+                }
+                else {
+                    req.names
+                        .into_iter()
+                        .map(|name| dev.attr_read_str(&name).map(|val| (name, val)))
+                        .collect::<Result<HashMap<_, _>>>()?
+                }
+            }
+            else {
+                let chan = Self::channel(&dev, &req.channel, req.output)?;
+                if req.names.is_empty() {
+                    chan.attr_read_all()?
+                }
+                else {
+                    req.names
+                        .into_iter()
+                        .map(|name| chan.attr_read_str(&name).map(|val| (name, val)))
+                        .collect::<Result<HashMap<_, _>>>()?
+                }
+            };
+            Ok(values)
+        })
+        .await?;
+        Ok(Response::new(proto::ReadAttributesResponse { values }))
+    }
+
+    async fn write_attribute(
+        &self,
+        req: Request<proto::WriteAttributeRequest>,
+    ) -> std::result::Result<Response<proto::WriteAttributeResponse>, Status> {
+        let req = req.into_inner();
+        let ctx = self.ctx.clone();
+        let writes = self.writes.clone();
+
+        blocking(move || {
+            let dev = Self::device(&ctx, &req.device)?;
+
+            // The channel might be requested by name, so the rules are
+            // checked against the ID of the one that was found.
+            let chan = if req.channel.is_empty() {
+                None
+            }
+            else {
+                Some(Self::channel(&dev, &req.channel, req.output)?)
+            };
+            let chan_id = chan
+                .as_ref()
+                .map(|chan| (chan.id().unwrap_or_default(), chan.direction()));
+            let rule_chan = chan_id.as_ref().map(|(id, dir)| (id.as_str(), *dir));
+
+            if ![dev.name(), dev.id()]
+                .iter()
+                .flatten()
+                .any(|name| writes.is_allowed(name, rule_chan, &req.name))
+            {
+                return Err(Status::permission_denied(format!(
+                    "Writing '{}' is not allowed",
+                    req.name
+                )));
+            }
+
+            match chan {
+                Some(chan) => chan.attr_write_str(&req.name, &req.value)?,
+                None => dev.attr_write_str(&req.name, &req.value)?,
+            }
+            Ok(())
+        })
+        .await?;
+        Ok(Response::new(proto::WriteAttributeResponse {}))
+    }
+
+    type CaptureStream = ReceiverStream<std::result::Result<proto::Frame, Status>>;
+
+    async fn capture(
+        &self,
+        req: Request<proto::CaptureRequest>,
+    ) -> std::result::Result<Response<Self::CaptureStream>, Status> {
+        const QUEUE_LEN: usize = 8;
+
+        let mut req = req.into_inner();
+        let ctx = self.ctx.clone();
+        let (name, chans) = (req.device.clone(), std::mem::take(&mut req.channels));
+
+        // Channels can't be sent to the capture thread, so pass their ID's
+        let (dev, ids) = blocking(move || {
+            let dev = Self::device(&ctx, &name)?;
+            let ids: Vec<_> = if chans.is_empty() {
+                dev.scan_elements()
+                    .filter(|ch| ch.is_input())
+                    .filter_map(|ch| ch.id())
+                    .collect()
+            }
+            else {
+                for name in &chans {
+                    Self::channel(&dev, name, false)?;
+                }
+                chans
+            };
+            Ok((dev, ids))
+        })
+        .await?;
+        if ids.is_empty() {
+            return Err(Status::failed_precondition("No channels to capture"));
+        }
+
+        let (tx, rx) = mpsc::channel(QUEUE_LEN);
+        let n = match req.samples as usize {
+            0 => Self::DFLT_SAMPLES,
+            n => n,
+        };
+
+        thread::spawn(move || {
+            let err_tx = tx.clone();
+            if let Err(err) = run_capture(dev, &ids, n, req.max_frames, &req.attrs, tx) {
+                let _ = err_tx.blocking_send(Err(err.into()));
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// The sink that sends captured frames to a streaming call.
+#[derive(Debug)]
+struct StreamSink {
+    tx: mpsc::Sender<std::result::Result<proto::Frame, Status>>,
+}
+
+impl FrameSink for StreamSink {
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        self.tx
+            .blocking_send(Ok(frame.into()))
+            .map_err(|_| Error::General("Capture cancelled".into()))
+    }
+}
+
+/// Captures frames from a device into a pipeline feeding the stream,
+/// until the client cancels the call or the maximum number of frames
+/// have been sent.
+fn run_capture(
+    dev: Device,
+    ids: &[String],
+    n: usize,
+    max_frames: u64,
+    attrs: &[String],
+    tx: mpsc::Sender<std::result::Result<proto::Frame, Status>>,
+) -> Result<()> {
+    let chans: Vec<_> = ids
+        .iter()
+        .filter_map(|id| dev.find_channel(id, Direction::Input))
+        .collect();
+
+    // Put the channels back the way they were, however the capture ends.
+    let _state = ChannelStateGuard::new(&dev);
+    for chan in &chans {
+        chan.enable();
+    }
+    let mut buf = dev.create_buffer(n, false)?;
+
+    let mut pipeline = Pipeline::new();
+    pipeline.add_sink(Box::new(StreamSink { tx: tx.clone() }));
+
+    let mut seq = 0;
+    while !tx.is_closed() && (max_frames == 0 || seq < max_frames) {
+        buf.refill()?;
+        let mut frame = Frame::from_buffer(&buf, &chans)?;
+        frame.seq = seq;
+        if !attrs.is_empty() {
+            frame.snapshot_attrs(&buf, attrs)?;
+        }
+        pipeline.send(frame);
+        seq += 1;
+    }
+
+    match pipeline.close() {
+        Err(_) if tx.is_closed() => Ok(()),
+        res => res,
+    }
+}
+
+/// Serves the devices in the context over gRPC on the address, until the
+/// server fails.
+///
+/// This doesn't require a token from clients, and they can only read
+/// attributes and capture data. Use [`serve_with_auth()`] to allow writes,
+/// or to expose the devices on an untrusted network.
+pub async fn serve(ctx: Context, addr: SocketAddr) -> Result<()> {
+    Server::builder()
+        .add_service(IioService::new(ctx).into_server())
+        .serve(addr)
+        .await
+        .map_err(|err| Error::General(err.to_string()))
+}
+
+/// Serves the devices in the context over gRPC on the address, with the
+/// security settings, until the server fails.
+///
+/// Clients must present one of the tokens, if any, as a bearer token in
+/// the "authorization" metadata, and can only write the attributes in the
+/// allow list.
+pub async fn serve_with_auth(ctx: Context, addr: SocketAddr, auth: &Auth) -> Result<()> {
+    let svc = IioService::new(ctx).writes(auth.write_allow_list().clone());
+
+    let token_auth = auth.clone();
+    #[allow(clippy::result_large_err)]
+    let check = move |req: Request<()>| {
+        let token = req
+            .metadata()
+            .get("authorization")
+            .and_then(|val| val.to_str().ok());
+        if token_auth.check_token(token) {
+            Ok(req)
+        }
+        else {
+            Err(Status::unauthenticated("Invalid or missing token"))
+        }
+    };
+
+    let mut builder = Server::builder();
+    if let Some(tls) = auth.tls_files() {
+        builder = tls_config(builder, tls)?;
+    }
+    builder
+        .add_service(IioServer::with_interceptor(svc, check))
+        .serve(addr)
+        .await
+        .map_err(|err| Error::General(err.to_string()))
+}
+
+/// Applies the TLS settings to a server.
+#[cfg(feature = "tls")]
+fn tls_config(builder: Server, tls: &TlsFiles) -> Result<Server> {
+    use std::fs;
+    use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+
+    let identity = Identity::from_pem(fs::read(&tls.cert)?, fs::read(&tls.key)?);
+    let mut cfg = ServerTlsConfig::new().identity(identity);
+    if let Some(ca) = &tls.client_ca {
+        cfg = cfg.client_ca_root(Certificate::from_pem(fs::read(ca)?));
+    }
+    builder
+        .tls_config(cfg)
+        .map_err(|err| Error::General(err.to_string()))
+}
+
+/// Applies the TLS settings to a server.
+#[cfg(not(feature = "tls"))]
+fn tls_config(_builder: Server, _tls: &TlsFiles) -> Result<Server> {
+    Err(Error::General(
+        "Serving over TLS requires the 'tls' feature".into(),
+    ))
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameChannel;
+    use prost::Message;
+
+    #[test]
+    fn frame_round_trip() {
+        let mut frame = Frame {
+            seq: 7,
+            sample_rate: Some(1000.0),
+            channels: vec![FrameChannel {
+                index: Some(2),
+                device: Some("iio:device0".into()),
+                chan_type: Some(ChannelType::Voltage),
+                modifier: Some(0),
+                scale: Some(0.5),
+                ..FrameChannel::new("voltage2", vec![0.5, -0.25])
+            }],
+            ..Frame::default()
+        };
+        frame.attrs.insert("gain".into(), "3".into());
+
+        let bytes = proto::Frame::from(&frame).encode_to_vec();
+        let msg = proto::Frame::decode(bytes.as_slice()).unwrap();
+        assert_eq!(msg.channels[0].index, Some(2));
+        assert_eq!(Frame::from(msg), frame);
+    }
+}
@@ -0,0 +1,394 @@
+// industrial-io/src/guard.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! A safety interlock for attribute writes.
+//!
+//! A [`GuardedContext`] wraps a context so that the devices and channels
+//! found through it can only write the attributes allowed by a
+//! [`Policy`], and only with values inside any numeric bounds that the
+//! policy sets for them. Anything else fails with
+//! [`Error::PolicyViolation`] before reaching the driver.
+//!
+//! This is meant to protect shared lab hardware from buggy application
+//! code, rather than as a security boundary.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::guard::{GuardedContext, Policy};
+//!
+//! // The RX gain and TX attenuation have different ranges
+//! let policy = Policy::new()
+//!     .allow("ad9361-phy/*/hardwaregain").unwrap()
+//!     .bounds("ad9361-phy/in_voltage*/hardwaregain", -3.0, 71.0).unwrap()
+//!     .bounds("ad9361-phy/out_voltage*/hardwaregain", -89.75, 0.0).unwrap();
+//!
+//! let ctx = GuardedContext::new(iio::Context::new().unwrap(), policy);
+//! let dev = ctx.find_device("ad9361-phy").unwrap();
+//! let rx = dev.find_channel("voltage0", iio::Direction::Input).unwrap();
+//! let tx = dev.find_channel("voltage0", iio::Direction::Output).unwrap();
+//!
+//! rx.attr_write("hardwaregain", 30.0).unwrap();
+//! assert!(rx.attr_write("hardwaregain", 100.0).is_err());
+//! tx.attr_write("hardwaregain", -10.0).unwrap();
+//! assert!(tx.attr_write("hardwaregain", 30.0).is_err());
+//! assert!(dev.attr_write_str("calib_mode", "manual").is_err());
+//! ```
+
+use crate::{
+    auth::{AllowList, Rule},
+    Channel, Context, Device, Direction, Error, FromAttribute, Result, ToAttribute,
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// The rules for which attributes can be written, and with what values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Policy {
+    /// The attributes that can be written
+    writes: AllowList,
+    /// The numeric limits for matching attributes
+    bounds: Vec<(Rule, f64, f64)>,
+}
+
+impl Policy {
+    /// Creates a policy that doesn't allow any writes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows writing the attributes matching the rule.
+    ///
+    /// This has the same form as the rules in an [`AllowList`], like
+    /// "device/attr" or "device/channel/attr", with `*` wildcards, and
+    /// "in_" or "out_" channel prefixes to limit it to one direction.
+    pub fn allow(mut self, rule: &str) -> Result<Self> {
+        self.writes = self.writes.allow(rule)?;
+        Ok(self)
+    }
+
+    /// Limits the values written to the attributes matching the rule to
+    /// the range `min..=max`.
+    ///
+    /// Input and output channels often have different ranges for an
+    /// attribute of the same name, so use "in_" or "out_" channel
+    /// prefixes in the rule to set them separately.
+    ///
+    /// The attributes must still be allowed to be written. If more than
+    /// one set of bounds match an attribute, the value must be within all
+    /// of them.
+    pub fn bounds(mut self, rule: &str, min: f64, max: f64) -> Result<Self> {
+        self.bounds.push((rule.parse()?, min, max));
+        Ok(self)
+    }
+
+    /// Checks whether a value can be written to the attribute of the
+    /// device, known by any of the names, or one of its channels.
+    fn check(
+        &self,
+        names: &[String],
+        channel: Option<(&str, Direction)>,
+        attr: &str,
+        val: &str,
+    ) -> Result<()> {
+        let dev = names.first().map(|s| s.as_str()).unwrap_or_default();
+        let target = || match channel {
+            Some((chan, Direction::Input)) => format!("{}/in_{}/{}", dev, chan, attr),
+            Some((chan, Direction::Output)) => format!("{}/out_{}/{}", dev, chan, attr),
+            None => format!("{}/{}", dev, attr),
+        };
+
+        if !names
+            .iter()
+            .any(|name| self.writes.is_allowed(name, channel, attr))
+        {
+            return Err(Error::PolicyViolation(format!(
+                "Writing '{}' is not allowed",
+                target()
+            )));
+        }
+
+        for (rule, min, max) in &self.bounds {
+            if !names.iter().any(|name| rule.matches(name, channel, attr)) {
+                continue;
+            }
+            match val.trim().parse::<f64>() {
+                Ok(x) if (*min..=*max).contains(&x) => {}
+                Ok(_) => {
+                    return Err(Error::PolicyViolation(format!(
+                        "Value {} for '{}' is outside [{}, {}]",
+                        val.trim(),
+                        target(),
+                        min,
+                        max
+                    )))
+                }
+                Err(_) => {
+                    return Err(Error::PolicyViolation(format!(
+                        "Value '{}' for '{}' is not a number",
+                        val,
+                        target()
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<AllowList> for Policy {
+    fn from(writes: AllowList) -> Self {
+        Self {
+            writes,
+            bounds: Vec::new(),
+        }
+    }
+}
+
+/// A context whose devices can only write the attributes allowed by a
+/// policy.
+#[derive(Debug, Clone)]
+pub struct GuardedContext {
+    /// The underlying context
+    ctx: Context,
+    /// The write policy
+    policy: Arc<Policy>,
+}
+
+impl GuardedContext {
+    /// Wraps the context with the write policy.
+    pub fn new(ctx: Context, policy: Policy) -> Self {
+        Self {
+            ctx,
+            policy: Arc::new(policy),
+        }
+    }
+
+    /// Gets the write policy.
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Gets the number of devices in the context.
+    pub fn num_devices(&self) -> usize {
+        self.ctx.num_devices()
+    }
+
+    /// Tries to find a device by name or ID.
+    pub fn find_device(&self, name: &str) -> Option<GuardedDevice> {
+        self.ctx.find_device(name).map(|dev| self.guard(dev))
+    }
+
+    /// Gets an iterator over the devices in the context.
+    pub fn devices(&self) -> impl Iterator<Item = GuardedDevice> + '_ {
+        self.ctx.devices().map(|dev| self.guard(dev))
+    }
+
+    /// Wraps a device with the policy.
+    fn guard(&self, dev: Device) -> GuardedDevice {
+        let names = [dev.name(), dev.id()].into_iter().flatten().collect();
+        GuardedDevice {
+            dev,
+            names,
+            policy: Arc::clone(&self.policy),
+        }
+    }
+}
+
+/// A device that can only write the attributes allowed by a policy.
+#[derive(Debug, Clone)]
+pub struct GuardedDevice {
+    /// The underlying device
+    dev: Device,
+    /// The names by which the policy can refer to the device
+    names: Vec<String>,
+    /// The write policy
+    policy: Arc<Policy>,
+}
+
+impl GuardedDevice {
+    /// Gets the device ID (e.g. <b><i>iio:device0</i></b>)
+    pub fn id(&self) -> Option<String> {
+        self.dev.id()
+    }
+
+    /// Gets the descriptive name of the device.
+    pub fn name(&self) -> Option<String> {
+        self.dev.name()
+    }
+
+    /// Reads a device-specific attribute
+    pub fn attr_read<T: FromAttribute>(&self, attr: impl AsRef<str>) -> Result<T> {
+        self.dev.attr_read(attr)
+    }
+
+    /// Reads a device-specific attribute as a string
+    pub fn attr_read_str(&self, attr: impl AsRef<str>) -> Result<String> {
+        self.dev.attr_read_str(attr)
+    }
+
+    /// Reads all the device-specific attributes.
+    pub fn attr_read_all(&self) -> Result<HashMap<String, String>> {
+        self.dev.attr_read_all()
+    }
+
+    /// Writes a device-specific attribute, if the policy allows it.
+    pub fn attr_write<T: ToAttribute>(&self, attr: impl AsRef<str>, val: T) -> Result<()> {
+        self.attr_write_str(attr, &val.to_attr()?)
+    }
+
+    /// Writes a device-specific attribute as a string, if the policy
+    /// allows it.
+    pub fn attr_write_str(&self, attr: impl AsRef<str>, val: &str) -> Result<()> {
+        let attr = attr.as_ref();
+        self.policy.check(&self.names, None, attr, val)?;
+        self.dev.attr_write_str(attr, val)
+    }
+
+    /// Gets the number of channels on the device
+    pub fn num_channels(&self) -> usize {
+        self.dev.num_channels()
+    }
+
+    /// Try to find a channel by its name or ID
+    pub fn find_channel(&self, name: &str, dir: Direction) -> Option<GuardedChannel> {
+        self.dev
+            .find_channel(name, dir)
+            .map(|chan| self.guard(chan))
+    }
+
+    /// Gets an iterator over the channels of the device.
+    pub fn channels(&self) -> impl Iterator<Item = GuardedChannel> + '_ {
+        self.dev.channels().map(|chan| self.guard(chan))
+    }
+
+    /// Wraps a channel with the policy.
+    fn guard(&self, chan: Channel) -> GuardedChannel {
+        GuardedChannel {
+            id: chan.id().unwrap_or_default(),
+            dir: chan.direction(),
+            chan,
+            dev_names: self.names.clone(),
+            policy: Arc::clone(&self.policy),
+        }
+    }
+}
+
+/// A channel that can only write the attributes allowed by a policy.
+#[derive(Debug, Clone)]
+pub struct GuardedChannel {
+    /// The underlying channel
+    chan: Channel,
+    /// The channel ID
+    id: String,
+    /// The channel direction
+    dir: Direction,
+    /// The names by which the policy can refer to the device
+    dev_names: Vec<String>,
+    /// The write policy
+    policy: Arc<Policy>,
+}
+
+impl GuardedChannel {
+    /// Gets the channel ID (e.g. <b><i>voltage0</i></b>)
+    pub fn id(&self) -> Option<String> {
+        self.chan.id()
+    }
+
+    /// Gets the name of the channel (e.g. <b><i>vccint</i></b>)
+    pub fn name(&self) -> Option<String> {
+        self.chan.name()
+    }
+
+    /// Determines if this is an output channel.
+    pub fn is_output(&self) -> bool {
+        self.chan.is_output()
+    }
+
+    /// Reads a channel-specific attribute
+    pub fn attr_read<T: FromAttribute>(&self, attr: impl AsRef<str>) -> Result<T> {
+        self.chan.attr_read(attr)
+    }
+
+    /// Reads a channel-specific attribute as a string
+    pub fn attr_read_str(&self, attr: impl AsRef<str>) -> Result<String> {
+        self.chan.attr_read_str(attr)
+    }
+
+    /// Reads all the channel-specific attributes.
+    pub fn attr_read_all(&self) -> Result<HashMap<String, String>> {
+        self.chan.attr_read_all()
+    }
+
+    /// Writes a channel-specific attribute, if the policy allows it.
+    pub fn attr_write<T: ToAttribute>(&self, attr: impl AsRef<str>, val: T) -> Result<()> {
+        self.attr_write_str(attr, &val.to_attr()?)
+    }
+
+    /// Writes a channel-specific attribute as a string, if the policy
+    /// allows it.
+    pub fn attr_write_str(&self, attr: impl AsRef<str>, val: &str) -> Result<()> {
+        let attr = attr.as_ref();
+        self.policy
+            .check(&self.dev_names, Some((&self.id, self.dir)), attr, val)?;
+        self.chan.attr_write_str(attr, val)
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_check() {
+        use Direction::*;
+
+        let names = ["ad9361-phy".to_string(), "iio:device0".to_string()];
+        let policy = Policy::new()
+            .allow("ad9361-phy/voltage*/hardwaregain")
+            .unwrap()
+            .allow("iio:device0/ensm_mode")
+            .unwrap()
+            .bounds("*/in_*/hardwaregain", -3.0, 71.0)
+            .unwrap()
+            .bounds("*/out_*/hardwaregain", -89.75, 0.0)
+            .unwrap();
+
+        let rx = Some(("voltage0", Input));
+        let tx = Some(("voltage0", Output));
+
+        assert!(policy.check(&names, rx, "hardwaregain", "30").is_ok());
+        assert!(policy.check(&names, tx, "hardwaregain", "-10").is_ok());
+        assert!(policy.check(&names, None, "ensm_mode", "fdd").is_ok());
+
+        let err = policy
+            .check(&names, rx, "hardwaregain", "72.5")
+            .unwrap_err();
+        assert!(matches!(err, Error::PolicyViolation(_)));
+        assert_eq!(
+            err.to_string(),
+            "Policy violation: Value 72.5 for 'ad9361-phy/in_voltage0/hardwaregain' is outside [-3, 71]"
+        );
+
+        // The TX channel with the same ID has its own limits
+        assert!(policy.check(&names, tx, "hardwaregain", "30").is_err());
+
+        assert!(policy.check(&names, rx, "hardwaregain", "max").is_err());
+        assert!(policy
+            .check(&names, Some(("altvoltage0", Output)), "hardwaregain", "0")
+            .is_err());
+        assert!(policy.check(&names, None, "calib_mode", "auto").is_err());
+        assert!(Policy::new()
+            .check(&names, None, "ensm_mode", "fdd")
+            .is_err());
+    }
+}
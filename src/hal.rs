@@ -0,0 +1,198 @@
+// industrial-io/src/hal.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Adapters for the _embedded-hal_ traits.
+//!
+//! These let drivers and application code written against _embedded-hal_
+//! run on Linux IIO hardware. An input channel is wrapped as an
+//! [`AdcPin`], which is read by an [`Adc`] through the `adc::OneShot`
+//! trait, giving the raw value of the channel, like a microcontroller ADC.
+//!
+//! The ADC traits are only in the 0.2 releases of _embedded-hal_, under
+//! its "unproven" feature, so that is the version used here. There isn't
+//! a DAC trait in any version, so a [`DacPin`] gives the equivalent for an
+//! output channel.
+//!
+//! The values are read and written through the `raw` attributes of the
+//! channels, so a read never returns `WouldBlock`.
+//!
+//! This requires the `embedded-hal` feature.
+//!
+//! ```no_run
+//! use embedded_hal::adc::OneShot;
+//! use industrial_io as iio;
+//! use iio::hal::{Adc, AdcPin};
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ads1015").unwrap();
+//!
+//! let mut adc = Adc::new();
+//! let mut pin = AdcPin::new(dev.find_input_channel("voltage0").unwrap()).unwrap();
+//! let val: u16 = nb::block!(adc.read(&mut pin)).unwrap();
+//! println!("{}", val);
+//! ```
+
+use crate::{abi, ops::ChannelOps, Channel, Error, Result};
+use embedded_hal::adc;
+
+/// Checks that a channel has a raw value, in the expected direction.
+fn check_raw<C: ChannelOps>(chan: &C, output: bool) -> Result<()> {
+    let id = chan.id().unwrap_or_default();
+    if chan.is_output() != output {
+        let dir = if output { "an output" } else { "an input" };
+        return Err(Error::General(format!("'{}' is not {} channel", id, dir)));
+    }
+    if !chan.has_attr(abi::chan::RAW) {
+        return Err(Error::General(format!("'{}' has no raw value", id)));
+    }
+    Ok(())
+}
+
+/// An IIO input channel used as an ADC pin.
+#[derive(Debug, Clone)]
+pub struct AdcPin<C: ChannelOps = Channel> {
+    /// The channel
+    chan: C,
+}
+
+impl<C: ChannelOps> AdcPin<C> {
+    /// Wraps an input channel that has a raw value.
+    pub fn new(chan: C) -> Result<Self> {
+        check_raw(&chan, false)?;
+        Ok(Self { chan })
+    }
+
+    /// Gets the channel.
+    pub fn channel(&self) -> &C {
+        &self.chan
+    }
+
+    /// Gets the channel back.
+    pub fn into_inner(self) -> C {
+        self.chan
+    }
+}
+
+/// The pins are identified by their channels, at runtime, rather than by
+/// their type, so the ID is empty.
+impl<C: ChannelOps> adc::Channel<Adc> for AdcPin<C> {
+    type ID = ();
+
+    fn channel() -> Self::ID {}
+}
+
+/// The ADC that reads [`AdcPin`]s.
+///
+/// This doesn't hold any state. The pins can be from any devices.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Adc;
+
+impl Adc {
+    /// Creates an ADC.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Reads the raw value of a channel.
+///
+/// The error is [`Error::BadReturnSize`] if the value doesn't fit into the
+/// word type.
+impl<C, Word> adc::OneShot<Adc, Word, AdcPin<C>> for Adc
+where
+    C: ChannelOps,
+    Word: TryFrom<i64>,
+{
+    type Error = Error;
+
+    fn read(&mut self, pin: &mut AdcPin<C>) -> nb::Result<Word, Error> {
+        let raw = pin.chan.attr_read_int(abi::chan::RAW)?;
+        Word::try_from(raw).map_err(|_| nb::Error::Other(Error::BadReturnSize))
+    }
+}
+
+/// An IIO output channel used as a DAC pin.
+#[derive(Debug, Clone)]
+pub struct DacPin<C: ChannelOps = Channel> {
+    /// The channel
+    chan: C,
+}
+
+impl<C: ChannelOps> DacPin<C> {
+    /// Wraps an output channel that has a raw value.
+    pub fn new(chan: C) -> Result<Self> {
+        check_raw(&chan, true)?;
+        Ok(Self { chan })
+    }
+
+    /// Gets the channel.
+    pub fn channel(&self) -> &C {
+        &self.chan
+    }
+
+    /// Gets the channel back.
+    pub fn into_inner(self) -> C {
+        self.chan
+    }
+
+    /// Sets the raw output value.
+    pub fn set_raw<Word: Into<i64>>(&mut self, val: Word) -> Result<()> {
+        self.chan
+            .attr_write_str(abi::chan::RAW, &val.into().to_string())
+    }
+
+    /// Gets the current raw output value.
+    pub fn raw(&self) -> Result<i64> {
+        self.chan.attr_read_int(abi::chan::RAW)
+    }
+
+    /// Powers the output down, or back up, if the channel supports it.
+    pub fn set_powerdown(&mut self, on: bool) -> Result<()> {
+        let val = if on { "1" } else { "0" };
+        self.chan.attr_write_str(abi::chan::POWERDOWN, val)
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock::MockChannel, ChannelType};
+    use embedded_hal::adc::OneShot;
+
+    #[test]
+    fn adc_read() {
+        let chan = MockChannel::input("voltage0", ChannelType::Voltage).attr("raw", "1234");
+        let mut pin = AdcPin::new(chan).unwrap();
+        let mut adc = Adc::new();
+
+        let val: u16 = nb::block!(adc.read(&mut pin)).unwrap();
+        assert_eq!(val, 1234);
+        let val: Result<u8> = nb::block!(adc.read(&mut pin));
+        assert!(val.is_err());
+
+        let out = MockChannel::output("voltage0", ChannelType::Voltage).attr("raw", "0");
+        assert!(AdcPin::new(out).is_err());
+    }
+
+    #[test]
+    fn dac_write() {
+        let chan = MockChannel::output("voltage0", ChannelType::Voltage).attr("raw", "0");
+        let mut pin = DacPin::new(chan).unwrap();
+        pin.set_raw(2048u16).unwrap();
+        assert_eq!(pin.raw().unwrap(), 2048);
+
+        let input = MockChannel::input("voltage0", ChannelType::Voltage);
+        assert!(DacPin::new(input).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+// industrial-io/src/health.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Health reports for daemons.
+//!
+//! A [`Report`] summarizes the state of the devices in a context: whether
+//! each one can be reached, when it was last read successfully, how many
+//! samples were captured and dropped, and how many reads failed. It is a
+//! plain struct, serializable with the **serde** feature, so that a daemon
+//! can expose it however it likes, such as on an HTTP health endpoint.
+//!
+//! Reachability is checked when the report is collected. The rest comes
+//! from a [`Tracker`], which the acquisition loops of the application
+//! update as they run. Clones of a tracker share the same statistics, so
+//! one can be handed to each thread.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::health::{Report, Tracker};
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let tracker = Tracker::new();
+//!
+//! let dev = ctx.find_device("ads1015").unwrap();
+//! match dev.capture_burst(256) {
+//!     Ok(frame) => tracker.record_read("ads1015", frame.len()),
+//!     Err(err) => tracker.record_error("ads1015", &err),
+//! }
+//!
+//! let report = Report::collect_with(&ctx, &tracker);
+//! println!("Healthy: {}", report.is_healthy());
+//! ```
+
+use crate::{Context, Device, Error};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Gets the current time, in milliseconds since the Unix epoch.
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|dur| dur.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// The statistics kept for a device by a [`Tracker`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceStats {
+    /// The time of the last successful read, in ms since the Unix epoch
+    pub last_read_ms: Option<u64>,
+    /// The number of successful reads
+    pub reads: u64,
+    /// The number of samples captured
+    pub samples: u64,
+    /// The number of frames or samples dropped
+    pub dropped: u64,
+    /// The number of failed reads
+    pub errors: u64,
+    /// The time of the last failed read, in ms since the Unix epoch
+    pub last_error_ms: Option<u64>,
+    /// The most recent error, if any
+    pub last_error: Option<String>,
+}
+
+impl DeviceStats {
+    /// Determines if the most recent read failed.
+    pub fn is_failing(&self) -> bool {
+        match (self.last_error_ms, self.last_read_ms) {
+            (Some(err), Some(read)) => err > read,
+            (Some(_), None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Collects the statistics for the devices in a context.
+///
+/// Devices are identified by the name or ID that the application uses
+/// for them, which should match the device's name, or its ID if it
+/// doesn't have a name.
+#[derive(Debug, Clone, Default)]
+pub struct Tracker {
+    /// The statistics, by device
+    stats: Arc<Mutex<HashMap<String, DeviceStats>>>,
+}
+
+impl Tracker {
+    /// Creates an empty tracker.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the statistics for a device.
+    fn update<F: FnOnce(&mut DeviceStats)>(&self, dev: &str, f: F) {
+        let mut stats = self.stats.lock().unwrap_or_else(|err| err.into_inner());
+        f(stats.entry(dev.to_string()).or_default());
+    }
+
+    /// Records a successful read from the device, of the number of
+    /// samples.
+    pub fn record_read(&self, dev: &str, samples: usize) {
+        let now = now_ms();
+        self.update(dev, |st| {
+            st.last_read_ms = Some(now);
+            st.reads += 1;
+            st.samples += samples as u64;
+        });
+    }
+
+    /// Records that frames or samples from the device were dropped.
+    pub fn record_dropped(&self, dev: &str, n: u64) {
+        self.update(dev, |st| st.dropped += n);
+    }
+
+    /// Records a failed read from the device.
+    pub fn record_error(&self, dev: &str, err: &Error) {
+        let now = now_ms();
+        self.update(dev, |st| {
+            st.errors += 1;
+            st.last_error_ms = Some(now);
+            st.last_error = Some(err.to_string());
+        });
+    }
+
+    /// Gets the statistics for a device, if any were recorded.
+    pub fn stats(&self, dev: &str) -> Option<DeviceStats> {
+        let stats = self.stats.lock().unwrap_or_else(|err| err.into_inner());
+        stats.get(dev).cloned()
+    }
+}
+
+/// The health of a device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceHealth {
+    /// The device ID (e.g. <b><i>iio:device0</i></b>)
+    pub id: String,
+    /// The device name, if any
+    pub name: Option<String>,
+    /// Whether the device responded when the report was collected
+    pub reachable: bool,
+    /// The error if the device didn't respond
+    pub error: Option<String>,
+    /// The statistics recorded for the device
+    pub stats: DeviceStats,
+}
+
+impl DeviceHealth {
+    /// Determines if the device is reachable, and the most recent read
+    /// from it, if any, succeeded.
+    pub fn is_healthy(&self) -> bool {
+        self.reachable && !self.stats.is_failing()
+    }
+}
+
+/// The health of the devices in a context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Report {
+    /// The time the report was collected, in ms since the Unix epoch
+    pub timestamp_ms: u64,
+    /// The name of the context backend (i.e. "local", "network")
+    pub backend: String,
+    /// The URI of the context, if known
+    pub uri: Option<String>,
+    /// The health of each device
+    pub devices: Vec<DeviceHealth>,
+}
+
+impl Report {
+    /// Collects the reachability of the devices in the context.
+    pub fn collect(ctx: &Context) -> Self {
+        Self::collect_with(ctx, &Tracker::new())
+    }
+
+    /// Collects the reachability of the devices in the context, with the
+    /// statistics from the tracker.
+    pub fn collect_with(ctx: &Context, tracker: &Tracker) -> Self {
+        let devices = ctx
+            .devices()
+            .map(|dev| {
+                let id = dev.id().unwrap_or_default();
+                let name = dev.name();
+                let key = name.as_deref().unwrap_or(&id);
+                let stats = tracker
+                    .stats(key)
+                    .or_else(|| tracker.stats(&id))
+                    .unwrap_or_default();
+                let error = probe(&dev).err().map(|err| err.to_string());
+                DeviceHealth {
+                    reachable: error.is_none(),
+                    id,
+                    name,
+                    error,
+                    stats,
+                }
+            })
+            .collect();
+
+        Self {
+            timestamp_ms: now_ms(),
+            backend: ctx.name(),
+            uri: ctx.uri(),
+            devices,
+        }
+    }
+
+    /// Determines if all the devices are healthy.
+    pub fn is_healthy(&self) -> bool {
+        self.devices.iter().all(DeviceHealth::is_healthy)
+    }
+
+    /// Gets the health of a device by name or ID.
+    pub fn device(&self, name: &str) -> Option<&DeviceHealth> {
+        self.devices
+            .iter()
+            .find(|dev| dev.id == name || dev.name.as_deref() == Some(name))
+    }
+}
+
+/// Checks that a device responds, by reading one of its attributes.
+///
+/// Devices without attributes can't be checked, and are assumed to be
+/// reachable.
+fn probe(dev: &Device) -> crate::Result<()> {
+    match dev.attributes().next() {
+        Some(attr) => dev.attr_read_str(&attr).map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracker() {
+        let tracker = Tracker::new();
+        let shared = tracker.clone();
+
+        shared.record_read("adc", 256);
+        shared.record_read("adc", 256);
+        shared.record_dropped("adc", 3);
+        shared.record_error("adc", &Error::General("timeout".into()));
+
+        let st = tracker.stats("adc").unwrap();
+        assert_eq!(st.reads, 2);
+        assert_eq!(st.samples, 512);
+        assert_eq!(st.dropped, 3);
+        assert_eq!(st.errors, 1);
+        assert_eq!(st.last_error.as_deref(), Some("timeout"));
+        assert!(st.last_read_ms.is_some());
+        assert!(tracker.stats("dac").is_none());
+
+        let mut st = DeviceStats::default();
+        assert!(!st.is_failing());
+        st.last_error_ms = Some(1000);
+        assert!(st.is_failing());
+        st.last_read_ms = Some(1000);
+        assert!(!st.is_failing());
+        st.last_error_ms = Some(1001);
+        assert!(st.is_failing());
+    }
+}
@@ -0,0 +1,192 @@
+// industrial-io/src/hotplug.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Notifications of devices being added to, or removed from, a context.
+//!
+//! A _libiio_ context is a snapshot of the devices present when it was
+//! created, and the v0.x library doesn't report changes to it. So
+//! [`ContextEvents`] watches for changes by periodically re-creating the
+//! context from its URI and comparing the devices in it, by their
+//! [`DeviceIdentity`], against the previous snapshot.
+//!
+//! Each time a change is found, the context is replaced and the
+//! _generation_ count is incremented. Applications that cache [`Device`]
+//! handles should compare the generation and re-find their devices with
+//! [`Context::find_device_by_identity()`] when it changes.
+//!
+//! [`Device`]: crate::Device
+
+use crate::{Context, DeviceIdentity, Error, Result};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    fmt, thread,
+    time::Duration,
+};
+
+/// A change to the devices in a context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContextEvent {
+    /// A device appeared in the context.
+    DeviceAdded(DeviceIdentity),
+    /// A device disappeared from the context.
+    DeviceRemoved(DeviceIdentity),
+}
+
+impl fmt::Display for ContextEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextEvent::DeviceAdded(id) => write!(f, "added: {}", id),
+            ContextEvent::DeviceRemoved(id) => write!(f, "removed: {}", id),
+        }
+    }
+}
+
+/// A watcher for devices being added to, or removed from, a context.
+///
+/// This can be polled for changes, or used as a blocking iterator that
+/// yields each event as it is detected.
+#[derive(Debug)]
+pub struct ContextEvents {
+    /// The URI used to re-create the context
+    uri: String,
+    /// The latest snapshot of the context
+    ctx: Context,
+    /// The devices in the latest snapshot
+    devices: BTreeSet<DeviceIdentity>,
+    /// The number of times that the context was replaced
+    generation: u64,
+    /// The time between polls, when used as an iterator
+    interval: Duration,
+    /// Events detected, but not yet returned by the iterator
+    pending: VecDeque<ContextEvent>,
+}
+
+impl ContextEvents {
+    /// Creates a watcher for the context.
+    ///
+    /// The context must report its URI so that it can be re-created.
+    pub fn new(ctx: &Context) -> Result<Self> {
+        const DFLT_INTERVAL: Duration = Duration::from_secs(1);
+
+        let uri = ctx
+            .uri()
+            .ok_or_else(|| Error::General("The context does not report its URI".into()))?;
+
+        Ok(Self {
+            uri,
+            ctx: ctx.clone(),
+            devices: snapshot(ctx),
+            generation: 0,
+            interval: DFLT_INTERVAL,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Sets the time between polls when used as an iterator.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Gets the latest snapshot of the context.
+    pub fn context(&self) -> Context {
+        self.ctx.clone()
+    }
+
+    /// Gets the number of times that the context has changed.
+    ///
+    /// Device handles obtained from an earlier generation refer to a
+    /// stale context and should be re-found.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Re-creates the context and returns any changes to its devices.
+    ///
+    /// If there are changes, the new context replaces the previous one,
+    /// and the generation is incremented.
+    pub fn poll(&mut self) -> Result<Vec<ContextEvent>> {
+        let ctx = Context::from_uri(&self.uri)?;
+        let devices = snapshot(&ctx);
+        let events = diff(&self.devices, &devices);
+
+        if !events.is_empty() {
+            self.ctx = ctx;
+            self.devices = devices;
+            self.generation += 1;
+        }
+        Ok(events)
+    }
+}
+
+impl Iterator for ContextEvents {
+    type Item = Result<ContextEvent>;
+
+    /// Blocks until the next event is detected, polling at the interval.
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            thread::sleep(self.interval);
+            match self.poll() {
+                Ok(events) => self.pending.extend(events),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+/// Gets the identities of all the devices in a context.
+fn snapshot(ctx: &Context) -> BTreeSet<DeviceIdentity> {
+    ctx.devices().map(|dev| dev.identity()).collect()
+}
+
+/// Gets the events to go from the old set of devices to the new one.
+///
+/// Removals are reported before additions.
+fn diff(old: &BTreeSet<DeviceIdentity>, new: &BTreeSet<DeviceIdentity>) -> Vec<ContextEvent> {
+    old.difference(new)
+        .cloned()
+        .map(ContextEvent::DeviceRemoved)
+        .chain(new.difference(old).cloned().map(ContextEvent::DeviceAdded))
+        .collect()
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ident(id: &str, name: &str) -> DeviceIdentity {
+        DeviceIdentity {
+            uri: "ip:localhost".into(),
+            id: id.into(),
+            name: Some(name.into()),
+            label: None,
+        }
+    }
+
+    #[test]
+    fn device_diff() {
+        let old: BTreeSet<_> = [ident("iio:device0", "adc"), ident("iio:device1", "dac")].into();
+        let new: BTreeSet<_> = [ident("iio:device0", "adc"), ident("iio:device2", "imu")].into();
+
+        assert!(diff(&old, &old).is_empty());
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                ContextEvent::DeviceRemoved(ident("iio:device1", "dac")),
+                ContextEvent::DeviceAdded(ident("iio:device2", "imu")),
+            ]
+        );
+    }
+}
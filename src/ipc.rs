@@ -0,0 +1,557 @@
+// industrial-io/src/ipc.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Sharing captured frames with another process through shared memory.
+//!
+//! A [`ShmWriter`] creates an anonymous shared memory file (`memfd`) that
+//! holds a fixed-size ring of slots, and an `eventfd` that is signaled
+//! each time a frame is written. The two file descriptors can be passed
+//! to another process, such as a Python analysis script, by inheritance
+//! or over a Unix socket. That process maps the memory and reads the
+//! frames in place, without any further copies or system calls beyond
+//! the wakeup.
+//!
+//! The memory starts with a 64-byte header, with all values in the
+//! native byte order:
+//!
+//! | Offset | Type | Field |
+//! |--------|------|-------|
+//! | 0      | u32  | Magic number, [`SHM_MAGIC`] |
+//! | 4      | u32  | Layout version, [`SHM_VERSION`] |
+//! | 8      | u64  | The number of slots |
+//! | 16     | u64  | The size of each slot, in bytes |
+//! | 24     | u64  | The read count, updated by the reader |
+//! | 32     | u64  | The write count, updated by the writer |
+//!
+//! The slots follow the header. Slot `n % num_slots` holds frame `n`: a
+//! u64 length, followed by that many bytes of a [`StreamHeader`] and the
+//! interleaved samples, just like a network streamer block. The writer
+//! never overwrites a slot that hasn't been read; frames that arrive when
+//! the ring is full are dropped.
+//!
+//! [`StreamHeader`]: crate::sink::StreamHeader
+
+use crate::{
+    sink::{self, Encoding, FrameSink, StreamHeader},
+    Error, Frame, Result,
+};
+use nix::{
+    sys::{
+        eventfd::{EfdFlags, EventFd},
+        memfd::{memfd_create, MemFdCreateFlag},
+        mman::{mmap, munmap, MapFlags, ProtFlags},
+        stat::fstat,
+    },
+    unistd::{ftruncate, read},
+};
+use std::{
+    ffi::{c_void, CString},
+    fmt,
+    mem::size_of,
+    num::NonZeroUsize,
+    ops::Deref,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd},
+    ptr::NonNull,
+    slice,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// The magic number at the start of the shared memory, "IIOR"
+pub const SHM_MAGIC: u32 = 0x524f_4949;
+
+/// The version of the shared memory layout
+pub const SHM_VERSION: u32 = 1;
+
+/// The header at the start of the shared memory.
+#[repr(C)]
+struct ShmHeader {
+    magic: u32,
+    version: u32,
+    num_slots: u64,
+    slot_size: u64,
+    head: AtomicU64,
+    tail: AtomicU64,
+    _reserved: [u64; 3],
+}
+
+/// The size of the header, in bytes
+const HDR_SIZE: usize = size_of::<ShmHeader>();
+
+/// The size of the length that starts each slot
+const LEN_SIZE: usize = size_of::<u64>();
+
+/// A shared memory mapping, which is unmapped when dropped.
+struct Mapping {
+    ptr: NonNull<c_void>,
+    len: usize,
+}
+
+impl Mapping {
+    /// Maps the whole of a shared memory file, read/write.
+    fn new(fd: BorrowedFd<'_>, len: usize) -> Result<Self> {
+        let nz_len =
+            NonZeroUsize::new(len).ok_or_else(|| Error::General("Empty shared memory".into()))?;
+        let ptr = unsafe {
+            mmap(
+                None,
+                nz_len,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_SHARED,
+                fd,
+                0,
+            )?
+        };
+        Ok(Self { ptr, len })
+    }
+
+    /// Gets the header at the start of the memory.
+    fn header(&self) -> &ShmHeader {
+        unsafe { &*(self.ptr.as_ptr() as *const ShmHeader) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        let _ = unsafe { munmap(self.ptr, self.len) };
+    }
+}
+
+// The mapping is only a view of memory that outlives it, and all the
+// shared positions are atomic.
+unsafe impl Send for Mapping {}
+
+/// The layout of the slots in the ring.
+///
+/// This is copied from the shared header when the ring is created or
+/// opened, and checked against the size of the mapping. The header can be
+/// rewritten by the other process at any time, so only this copy is used
+/// to find the slots.
+#[derive(Debug, Clone, Copy)]
+struct Geometry {
+    /// The number of slots
+    num_slots: u64,
+    /// The size of each slot, in bytes
+    slot_size: usize,
+}
+
+impl Geometry {
+    /// Gets a pointer to the start of a slot.
+    fn slot(&self, map: &Mapping, pos: u64) -> *mut u8 {
+        let idx = (pos % self.num_slots) as usize;
+        unsafe { (map.ptr.as_ptr() as *mut u8).add(HDR_SIZE + idx * self.slot_size) }
+    }
+
+    /// Gets the maximum size of the data in a slot.
+    fn max_data(&self) -> usize {
+        self.slot_size - LEN_SIZE
+    }
+
+    /// Gets the number of slots in use between the read and write counts.
+    ///
+    /// The counts are updated by the other process, so this fails if they
+    /// don't make sense, rather than trusting them.
+    fn used(&self, head: u64, tail: u64) -> Result<u64> {
+        let n = tail.wrapping_sub(head);
+        if n > self.num_slots {
+            return Err(Error::General(format!(
+                "Corrupt shared memory ring positions: {} to {}",
+                head, tail
+            )));
+        }
+        Ok(n)
+    }
+}
+
+/// The writer end of a shared memory frame ring.
+pub struct ShmWriter {
+    /// The mapped memory
+    map: Mapping,
+    /// The layout of the slots
+    geom: Geometry,
+    /// The shared memory file
+    memfd: OwnedFd,
+    /// The notification counter
+    eventfd: EventFd,
+    /// The sample encoding
+    encoding: Encoding,
+    /// The number of frames dropped because the ring was full
+    dropped: u64,
+}
+
+impl ShmWriter {
+    /// Creates a ring in a new shared memory file.
+    ///
+    /// The `name` is only used for debugging, like in `/proc/<pid>/fd`.
+    /// Each of the `num_slots` slots is `slot_size` bytes, rounded up to a
+    /// multiple of eight, which must be big enough to hold the largest
+    /// frame that will be written.
+    pub fn create(name: &str, num_slots: usize, slot_size: usize) -> Result<Self> {
+        let len = slot_size
+            .checked_next_multiple_of(8)
+            .and_then(|size| num_slots.checked_mul(size))
+            .and_then(|len| len.checked_add(HDR_SIZE))
+            .ok_or_else(|| Error::General("Shared memory ring is too big".into()))?;
+        let slot_size = slot_size.next_multiple_of(8);
+        if num_slots == 0 || slot_size <= LEN_SIZE + StreamHeader::SIZE {
+            return Err(Error::General("Shared memory ring is too small".into()));
+        }
+
+        let name = CString::new(name)?;
+        let memfd = memfd_create(&name, MemFdCreateFlag::MFD_CLOEXEC)?;
+        ftruncate(&memfd, len as _)?;
+        let eventfd = EventFd::from_flags(EfdFlags::EFD_CLOEXEC)?;
+
+        let map = Mapping::new(memfd.as_fd(), len)?;
+        unsafe {
+            (map.ptr.as_ptr() as *mut ShmHeader).write(ShmHeader {
+                magic: SHM_MAGIC,
+                version: SHM_VERSION,
+                num_slots: num_slots as u64,
+                slot_size: slot_size as u64,
+                head: AtomicU64::new(0),
+                tail: AtomicU64::new(0),
+                _reserved: [0; 3],
+            });
+        }
+
+        Ok(Self {
+            map,
+            geom: Geometry {
+                num_slots: num_slots as u64,
+                slot_size,
+            },
+            memfd,
+            eventfd,
+            encoding: Encoding::default(),
+            dropped: 0,
+        })
+    }
+
+    /// Sets the encoding of the samples.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Gets the shared memory file descriptor, to pass to the reader.
+    pub fn memfd(&self) -> BorrowedFd<'_> {
+        self.memfd.as_fd()
+    }
+
+    /// Gets the notification file descriptor, to pass to the reader.
+    pub fn eventfd(&self) -> BorrowedFd<'_> {
+        self.eventfd.as_fd()
+    }
+
+    /// Gets the number of frames that were dropped because the ring was
+    /// full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Writes a frame into the next free slot and signals the reader.
+    ///
+    /// Returns `false` if the ring was full and the frame was dropped.
+    /// It is an error for the frame to be too big for a slot.
+    pub fn write_frame(&mut self, frame: &Frame) -> Result<bool> {
+        let n = frame.len();
+        let size = sink::block_size(frame, n, self.encoding);
+
+        let max = self.geom.max_data();
+        if size > max {
+            return Err(Error::General(format!(
+                "Frame of {} bytes doesn't fit in a {} byte slot",
+                size, max
+            )));
+        }
+
+        let hdr = self.map.header();
+        let tail = hdr.tail.load(Ordering::Relaxed);
+        if self.geom.used(hdr.head.load(Ordering::Acquire), tail)? == self.geom.num_slots {
+            self.dropped += 1;
+            return Ok(false);
+        }
+
+        // Encode the frame in place, in the free slot
+        unsafe {
+            let slot = self.geom.slot(&self.map, tail);
+            slot.cast::<u64>().write(size as u64);
+            let data = slice::from_raw_parts_mut(slot.add(LEN_SIZE), size);
+            sink::encode_block_into(frame, 0, n, self.encoding, data)?;
+        }
+        hdr.tail.store(tail.wrapping_add(1), Ordering::Release);
+
+        self.eventfd.write(1)?;
+        Ok(true)
+    }
+}
+
+impl FrameSink for ShmWriter {
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        self.write_frame(frame).map(|_| ())
+    }
+}
+
+impl fmt::Debug for ShmWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShmWriter")
+            .field("memfd", &self.memfd)
+            .field("eventfd", &self.eventfd)
+            .field("dropped", &self.dropped)
+            .finish()
+    }
+}
+
+/// The reader end of a shared memory frame ring.
+///
+/// This is usually in a different process than the writer, but can be
+/// used from Rust code on either side.
+pub struct ShmReader {
+    /// The mapped memory
+    map: Mapping,
+    /// The layout of the slots
+    geom: Geometry,
+    /// The notification counter
+    eventfd: OwnedFd,
+}
+
+impl ShmReader {
+    /// Opens the ring from the file descriptors created by a
+    /// [`ShmWriter`].
+    pub fn from_fds(memfd: OwnedFd, eventfd: OwnedFd) -> Result<Self> {
+        let len = fstat(memfd.as_raw_fd())?.st_size as usize;
+        if len < HDR_SIZE {
+            return Err(Error::General("Shared memory is too small".into()));
+        }
+        let map = Mapping::new(memfd.as_fd(), len)?;
+
+        let hdr = map.header();
+        if hdr.magic != SHM_MAGIC || hdr.version != SHM_VERSION {
+            return Err(Error::General("Not a frame ring".into()));
+        }
+        let (num_slots, slot_size) = (hdr.num_slots, hdr.slot_size);
+        let size = num_slots
+            .checked_mul(slot_size)
+            .and_then(|size| usize::try_from(size).ok())
+            .and_then(|size| size.checked_add(HDR_SIZE));
+        if num_slots == 0 || slot_size <= LEN_SIZE as u64 || size.map_or(true, |size| len < size) {
+            return Err(Error::General("Bad shared memory ring size".into()));
+        }
+        let geom = Geometry {
+            num_slots,
+            slot_size: slot_size as usize,
+        };
+        Ok(Self { map, geom, eventfd })
+    }
+
+    /// Gets the number of frames waiting to be read.
+    ///
+    /// This is zero if the positions in the shared header are corrupt.
+    pub fn len(&self) -> usize {
+        let hdr = self.map.header();
+        let head = hdr.head.load(Ordering::Relaxed);
+        let tail = hdr.tail.load(Ordering::Acquire);
+        self.geom.used(head, tail).unwrap_or(0) as usize
+    }
+
+    /// Determines if there are no frames waiting to be read.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the next frame, if there is one, without blocking.
+    ///
+    /// The slot is released back to the writer when the returned value is
+    /// dropped. This fails if the positions in the shared header are
+    /// corrupt.
+    pub fn try_next(&mut self) -> Result<Option<ShmSlot<'_>>> {
+        let hdr = self.map.header();
+        let head = hdr.head.load(Ordering::Relaxed);
+        if self.geom.used(head, hdr.tail.load(Ordering::Acquire))? == 0 {
+            return Ok(None);
+        }
+
+        let max = self.geom.max_data();
+        let data = unsafe {
+            let slot = self.geom.slot(&self.map, head);
+            let n = (slot.cast::<u64>().read() as usize).min(max);
+            slice::from_raw_parts(slot.add(LEN_SIZE), n)
+        };
+        Ok(Some(ShmSlot {
+            data,
+            head: &hdr.head,
+            pos: head,
+        }))
+    }
+
+    /// Blocks until the writer signals that at least one more frame was
+    /// written.
+    ///
+    /// Returns the number of frames written since the last wait.
+    pub fn wait(&self) -> Result<u64> {
+        let mut buf = [0u8; 8];
+        read(self.eventfd.as_raw_fd(), &mut buf)?;
+        Ok(u64::from_ne_bytes(buf))
+    }
+}
+
+impl fmt::Debug for ShmReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShmReader")
+            .field("eventfd", &self.eventfd)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// A frame in a slot of the shared memory ring.
+///
+/// This dereferences to the encoded bytes of the frame, in place in the
+/// shared memory.
+#[derive(Debug)]
+pub struct ShmSlot<'a> {
+    /// The encoded frame
+    data: &'a [u8],
+    /// The reader position in the shared header
+    head: &'a AtomicU64,
+    /// The position of this slot
+    pos: u64,
+}
+
+impl ShmSlot<'_> {
+    /// Gets the header of the frame.
+    pub fn header(&self) -> Option<StreamHeader> {
+        StreamHeader::parse(self.data)
+    }
+
+    /// Gets the interleaved sample bytes that follow the header.
+    pub fn samples(&self) -> &[u8] {
+        self.data.get(StreamHeader::SIZE..).unwrap_or_default()
+    }
+}
+
+impl Deref for ShmSlot<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.data
+    }
+}
+
+impl Drop for ShmSlot<'_> {
+    fn drop(&mut self) {
+        self.head.store(self.pos.wrapping_add(1), Ordering::Release);
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameChannel;
+
+    #[test]
+    fn shm_ring() {
+        let mut tx = ShmWriter::create("iio-test", 2, 256).unwrap();
+        let memfd = tx.memfd().try_clone_to_owned().unwrap();
+        let eventfd = tx.eventfd().try_clone_to_owned().unwrap();
+        let mut rx = ShmReader::from_fds(memfd, eventfd).unwrap();
+        assert!(rx.try_next().unwrap().is_none());
+
+        let mut frame = Frame {
+            channels: vec![
+                FrameChannel::new("voltage0", vec![1.0, 2.0]),
+                FrameChannel::new("voltage1", vec![-1.0, -2.0]),
+            ],
+            ..Frame::default()
+        };
+        for seq in 0..3 {
+            frame.seq = seq;
+            assert_eq!(tx.write_frame(&frame).unwrap(), seq < 2);
+        }
+        assert_eq!(tx.dropped(), 1);
+        assert_eq!(rx.wait().unwrap(), 2);
+        assert_eq!(rx.len(), 2);
+
+        for seq in 0..2 {
+            let slot = rx.try_next().unwrap().unwrap();
+            let hdr = slot.header().unwrap();
+            assert_eq!(hdr.seq, seq);
+            assert_eq!(hdr.num_channels, 2);
+            assert_eq!(hdr.num_samples, 2);
+
+            let samples: Vec<_> = slot
+                .samples()
+                .chunks_exact(8)
+                .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            assert_eq!(samples, [1.0, -1.0, 2.0, -2.0]);
+        }
+        assert!(rx.is_empty());
+
+        // The slots were released, so writing resumes.
+        assert!(tx.write_frame(&frame).unwrap());
+
+        // A frame too big for a slot is an error.
+        for ch in &mut frame.channels {
+            ch.samples = vec![0.0; 64];
+        }
+        assert!(tx.write_frame(&frame).is_err());
+    }
+
+    #[test]
+    fn shm_bad_size() {
+        assert!(ShmWriter::create("iio-test", usize::MAX, 256).is_err());
+        assert!(ShmWriter::create("iio-test", 2, usize::MAX).is_err());
+
+        // A header with a ring size that overflows is rejected
+        let tx = ShmWriter::create("iio-test", 2, 256).unwrap();
+        unsafe {
+            let hdr = tx.map.ptr.as_ptr() as *mut ShmHeader;
+            (*hdr).num_slots = u64::MAX / 2;
+            (*hdr).slot_size = 256;
+        }
+        let memfd = tx.memfd().try_clone_to_owned().unwrap();
+        let eventfd = tx.eventfd().try_clone_to_owned().unwrap();
+        assert!(ShmReader::from_fds(memfd, eventfd).is_err());
+    }
+
+    #[test]
+    fn shm_corrupt_positions() {
+        let mut tx = ShmWriter::create("iio-test", 2, 256).unwrap();
+        let memfd = tx.memfd().try_clone_to_owned().unwrap();
+        let eventfd = tx.eventfd().try_clone_to_owned().unwrap();
+        let mut rx = ShmReader::from_fds(memfd, eventfd).unwrap();
+
+        // The peer can't move the slots after the ring is opened
+        unsafe {
+            let hdr = tx.map.ptr.as_ptr() as *mut ShmHeader;
+            (*hdr).num_slots = u64::MAX;
+            (*hdr).slot_size = u64::MAX;
+        }
+        let frame = Frame {
+            channels: vec![FrameChannel::new("voltage0", vec![1.0])],
+            ..Frame::default()
+        };
+        assert!(tx.write_frame(&frame).unwrap());
+        let slot = rx.try_next().unwrap().unwrap();
+        assert_eq!(slot.header().unwrap().num_samples, 1);
+        drop(slot);
+
+        // A read position past the write position is an error
+        tx.map.header().head.store(5, Ordering::Release);
+        assert!(tx.write_frame(&frame).is_err());
+        assert!(rx.try_next().is_err());
+        assert_eq!(rx.len(), 0);
+    }
+}
@@ -0,0 +1,187 @@
+// industrial-io/src/iq.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Complex I/Q samples from pairs of channels.
+//!
+//! SDR transceivers, like the AD9361, expose the in-phase (I) and
+//! quadrature (Q) parts of each signal as a pair of channels, such as
+//! `voltage0` and `voltage1`. An [`IqPair`] keeps the two together and
+//! reads or writes them as [`Complex`] samples in one call, taking care
+//! of the demultiplexing and conversion of each part.
+//!
+//! This requires the `num-complex` feature.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::iq::IqPair;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("cf-ad9361-lpc").unwrap();
+//!
+//! let iq = IqPair::find(&dev, "voltage0", "voltage1", iio::Direction::Input).unwrap();
+//! iq.enable();
+//!
+//! let mut buf = dev.create_buffer(4096, false).unwrap();
+//! buf.refill().unwrap();
+//!
+//! let samples = iq.read::<i16>(&buf).unwrap();
+//! println!("First sample: {}", samples[0]);
+//! ```
+
+use crate::{Buffer, Channel, Device, Direction, Error, Result};
+pub use num_complex::Complex;
+
+/// A pair of channels holding the in-phase (I) and quadrature (Q) parts
+/// of a complex signal.
+#[derive(Debug, Clone)]
+pub struct IqPair {
+    /// The in-phase channel
+    i: Channel,
+    /// The quadrature channel
+    q: Channel,
+}
+
+impl IqPair {
+    /// Creates a pair from the I and Q channels.
+    ///
+    /// The channels must have the same direction and data format.
+    pub fn new(i: Channel, q: Channel) -> Result<Self> {
+        if i.direction() != q.direction() {
+            return Err(Error::General(
+                "The I and Q channels have different directions".into(),
+            ));
+        }
+        let (ifmt, qfmt) = (i.data_format(), q.data_format());
+        if ifmt.bits() != qfmt.bits()
+            || ifmt.byte_length() != qfmt.byte_length()
+            || ifmt.is_signed() != qfmt.is_signed()
+        {
+            return Err(Error::General(
+                "The I and Q channels have different data formats".into(),
+            ));
+        }
+        Ok(Self { i, q })
+    }
+
+    /// Finds the I and Q channels of a device by ID or name, and pairs
+    /// them.
+    pub fn find(dev: &Device, i_name: &str, q_name: &str, dir: Direction) -> Result<Self> {
+        let find = |name: &str| {
+            dev.find_channel(name, dir)
+                .ok_or_else(|| Error::General(format!("Channel not found: '{}'", name)))
+        };
+        Self::new(find(i_name)?, find(q_name)?)
+    }
+
+    /// Gets the in-phase channel.
+    pub fn i(&self) -> &Channel {
+        &self.i
+    }
+
+    /// Gets the quadrature channel.
+    pub fn q(&self) -> &Channel {
+        &self.q
+    }
+
+    /// Enables both channels for buffered I/O.
+    pub fn enable(&self) {
+        self.i.enable();
+        self.q.enable();
+    }
+
+    /// Disables both channels for buffered I/O.
+    pub fn disable(&self) {
+        self.i.disable();
+        self.q.disable();
+    }
+
+    /// Determines if both channels are enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.i.is_enabled() && self.q.is_enabled()
+    }
+
+    /// Demultiplexes and converts the samples of both channels, and
+    /// combines them into complex samples.
+    ///
+    /// The type must match the raw type of the channels, like `i16` for
+    /// most SDR converters.
+    pub fn read<T>(&self, buf: &Buffer) -> Result<Vec<Complex<T>>>
+    where
+        T: Default + Copy + 'static,
+    {
+        Ok(combine(self.i.read::<T>(buf)?, self.q.read::<T>(buf)?))
+    }
+
+    /// Demultiplexes and converts the samples of both channels into
+    /// complex floating-point samples, regardless of the raw type.
+    ///
+    /// The values are converted to the host format, but not scaled.
+    pub fn read_f32(&self, buf: &Buffer) -> Result<Vec<Complex<f32>>> {
+        let to_f32 = |v: Vec<f64>| v.into_iter().map(|x| x as f32).collect();
+        Ok(combine(
+            to_f32(self.i.read_f64(buf)?),
+            to_f32(self.q.read_f64(buf)?),
+        ))
+    }
+
+    /// Splits complex samples into their I and Q parts, converts them to
+    /// the hardware format, and multiplexes them into the buffer.
+    ///
+    /// Returns the number of samples written.
+    pub fn write<T>(&self, buf: &Buffer, data: &[Complex<T>]) -> Result<usize>
+    where
+        T: Default + Copy + 'static,
+    {
+        let (i, q) = split(data);
+        let n = self.i.write(buf, &i)?;
+        Ok(n.min(self.q.write(buf, &q)?))
+    }
+}
+
+/// Combines separate I and Q parts into complex samples.
+///
+/// If one part is longer, its extra values are dropped.
+pub fn combine<T>(i: Vec<T>, q: Vec<T>) -> Vec<Complex<T>> {
+    i.into_iter()
+        .zip(q)
+        .map(|(re, im)| Complex::new(re, im))
+        .collect()
+}
+
+/// Splits complex samples into separate I and Q parts.
+pub fn split<T: Copy>(data: &[Complex<T>]) -> (Vec<T>, Vec<T>) {
+    data.iter().map(|c| (c.re, c.im)).unzip()
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combine_split() {
+        let v = combine(vec![1i16, 2, 3], vec![-1, -2, -3, -4]);
+        assert_eq!(
+            v,
+            [
+                Complex::new(1, -1),
+                Complex::new(2, -2),
+                Complex::new(3, -3)
+            ]
+        );
+
+        let (i, q) = split(&v);
+        assert_eq!(i, [1, 2, 3]);
+        assert_eq!(q, [-1, -2, -3]);
+    }
+}
@@ -0,0 +1,493 @@
+// industrial-io/src/journal.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! A journal of attribute writes.
+//!
+//! When enabled on a context, with [`Context::set_journal_enabled()`],
+//! every attribute write made through the context and its devices,
+//! channels, and buffers is recorded: when it was made, which attribute
+//! was written, the value before the write (if it could be read), the
+//! new value, and whether the write succeeded. This is useful as an audit
+//! trail of the configuration changes made by an application.
+//!
+//! The journal is retrieved with [`Context::journal()`], and can be
+//! exported as CSV, or serialized with the **serde** feature.
+//!
+//! In dry-run mode, set with [`Context::set_dry_run()`], the writes are
+//! checked and journaled, but not sent to the hardware. This can be used
+//! to preview the changes that applying a configuration would make.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! ctx.set_journal_enabled(true);
+//!
+//! let dev = ctx.find_device("ad9361-phy").unwrap();
+//! dev.attr_write_str("ensm_mode", "fdd").unwrap();
+//!
+//! ctx.journal().write_csv(std::io::stdout()).unwrap();
+//! ```
+
+use crate::{health::now_ms, Context, Result};
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The kind of attribute that was written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AttrKind {
+    /// A device attribute
+    Device,
+    /// A channel attribute
+    Channel,
+    /// A buffer attribute
+    Buffer,
+    /// A debug attribute
+    Debug,
+    /// A hardware register, by its address
+    Register,
+}
+
+impl AttrKind {
+    /// Gets the name of the kind of attribute.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttrKind::Device => "device",
+            AttrKind::Channel => "channel",
+            AttrKind::Buffer => "buffer",
+            AttrKind::Debug => "debug",
+            AttrKind::Register => "register",
+        }
+    }
+}
+
+/// A record of an attribute write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct JournalEntry {
+    /// The time of the write, in ms since the Unix epoch
+    pub timestamp_ms: u64,
+    /// The kind of attribute
+    pub kind: AttrKind,
+    /// The ID of the device
+    pub device: String,
+    /// The ID of the channel, for a channel attribute
+    pub channel: Option<String>,
+    /// The name of the attribute
+    pub attr: String,
+    /// The value before the write, if it could be read
+    pub old: Option<String>,
+    /// The value written
+    pub new: String,
+    /// The error, if the write failed
+    pub error: Option<String>,
+    /// Whether the write was only validated, in dry-run mode
+    pub dry_run: bool,
+}
+
+impl JournalEntry {
+    /// Determines if the write succeeded.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// The attribute writes recorded for a context.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Journal {
+    /// The writes, in the order they were made
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Gets the number of writes in the journal.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Determines if the journal is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Gets an iterator over the writes in the journal.
+    pub fn iter(&self) -> std::slice::Iter<'_, JournalEntry> {
+        self.entries.iter()
+    }
+
+    /// Writes the journal as CSV, with a header line.
+    pub fn write_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(
+            out,
+            "timestamp_ms,kind,device,channel,attr,old,new,error,dry_run"
+        )?;
+        for ent in &self.entries {
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{}",
+                ent.timestamp_ms,
+                ent.kind.as_str(),
+                csv_field(&ent.device),
+                csv_field(ent.channel.as_deref().unwrap_or_default()),
+                csv_field(&ent.attr),
+                csv_field(ent.old.as_deref().unwrap_or_default()),
+                csv_field(&ent.new),
+                csv_field(ent.error.as_deref().unwrap_or_default()),
+                ent.dry_run,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> IntoIterator for &'a Journal {
+    type Item = &'a JournalEntry;
+    type IntoIter = std::slice::Iter<'a, JournalEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Quotes a CSV field, if needed.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    }
+    else {
+        s.to_string()
+    }
+}
+
+/// The attribute being written.
+#[derive(Debug)]
+pub(crate) struct AttrTarget {
+    /// The kind of attribute
+    pub kind: AttrKind,
+    /// The ID of the device
+    pub device: String,
+    /// The ID of the channel, for a channel attribute
+    pub channel: Option<String>,
+}
+
+/// The journal kept in a context.
+#[derive(Debug, Default)]
+pub(crate) struct Recorder {
+    /// The entries, or `None` when journaling is disabled
+    entries: Mutex<Option<Vec<JournalEntry>>>,
+    /// Whether writes are only validated and journaled
+    dry_run: AtomicBool,
+}
+
+impl Recorder {
+    /// Locks the journal.
+    pub fn lock(&self) -> MutexGuard<'_, Option<Vec<JournalEntry>>> {
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Determines if journaling is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.lock().is_some()
+    }
+
+    /// Creates an empty journal with the same settings.
+    pub fn clone_settings(&self) -> Self {
+        Self {
+            entries: Mutex::new(self.is_enabled().then(Vec::new)),
+            dry_run: AtomicBool::new(self.dry_run.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Context {
+    /// Enables or disables dry-run mode.
+    ///
+    /// In dry-run mode, attribute and register writes made through the
+    /// context are checked, but not sent to the hardware. Each write
+    /// fails if the attribute doesn't exist, or if the value isn't one of
+    /// those available for it, as reported by its `<attr>_available`
+    /// companion. The writes are recorded in the journal, which is
+    /// enabled along with dry-run mode, so that the changes can be
+    /// reviewed before they are made for real.
+    ///
+    /// Reads are still made from the hardware, so they don't reflect the
+    /// writes that were skipped.
+    pub fn set_dry_run(&self, on: bool) {
+        if on {
+            self.set_journal_enabled(true);
+        }
+        self.inner.journal.dry_run.store(on, Ordering::Relaxed);
+    }
+
+    /// Determines if the context is in dry-run mode.
+    pub fn is_dry_run(&self) -> bool {
+        self.inner.journal.dry_run.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables the journal of attribute writes.
+    ///
+    /// Disabling the journal discards the writes recorded so far.
+    pub fn set_journal_enabled(&self, on: bool) {
+        let mut journal = self.inner.journal.lock();
+        match (on, journal.is_some()) {
+            (true, false) => *journal = Some(Vec::new()),
+            (false, true) => *journal = None,
+            _ => {}
+        }
+    }
+
+    /// Determines if the journal of attribute writes is enabled.
+    pub fn is_journal_enabled(&self) -> bool {
+        self.inner.journal.is_enabled()
+    }
+
+    /// Gets a copy of the attribute writes recorded so far.
+    ///
+    /// This is empty if the journal is not enabled.
+    pub fn journal(&self) -> Journal {
+        Journal {
+            entries: self.inner.journal.lock().clone().unwrap_or_default(),
+        }
+    }
+
+    /// Removes the attribute writes recorded so far, returning them.
+    pub fn take_journal(&self) -> Journal {
+        Journal {
+            entries: self
+                .inner
+                .journal
+                .lock()
+                .as_mut()
+                .map(std::mem::take)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Performs an attribute write, recording it in the journal if it's
+    /// enabled.
+    ///
+    /// In dry-run mode, the write is validated instead of performed.
+    ///
+    /// `target` Gets the attribute being written
+    /// `read_old` Reads the value of the attribute before the write
+    /// `validate` Checks the write, in dry-run mode
+    /// `write` Performs the write
+    pub(crate) fn journaled<T, R, V, W>(
+        &self,
+        target: T,
+        attr: &str,
+        val: &str,
+        read_old: R,
+        validate: V,
+        write: W,
+    ) -> Result<()>
+    where
+        T: FnOnce() -> AttrTarget,
+        R: FnOnce() -> Option<String>,
+        V: FnOnce() -> Result<()>,
+        W: FnOnce() -> Result<()>,
+    {
+        // Any write could change the scale of a channel, like a new gain
+        // or range, so the cached values are dropped after each one.
+        let write = || {
+            let res = write();
+            self.clear_scale_cache();
+            res
+        };
+
+        let dry_run = self.is_dry_run();
+        if !dry_run && !self.inner.journal.is_enabled() {
+            return write();
+        }
+
+        let target = target();
+        let old = read_old();
+        let res = if dry_run { validate() } else { write() };
+
+        if let Some(journal) = self.inner.journal.lock().as_mut() {
+            journal.push(JournalEntry {
+                timestamp_ms: now_ms(),
+                kind: target.kind,
+                device: target.device,
+                channel: target.channel,
+                attr: attr.to_string(),
+                old,
+                new: val.to_string(),
+                error: res.as_ref().err().map(|err| err.to_string()),
+                dry_run,
+            });
+        }
+        res
+    }
+
+    /// Performs a write of a set of attributes in a single operation,
+    /// recording each of them in the journal if it's enabled.
+    ///
+    /// In dry-run mode, each value is validated instead of written. The
+    /// attributes are journaled in order of their names.
+    ///
+    /// `target` Gets the attributes being written
+    /// `vals` The attribute names and values
+    /// `read_old` Reads the values of the attributes before the write
+    /// `validate` Checks the write of a single attribute, in dry-run mode
+    /// `write` Performs the write
+    pub(crate) fn journaled_all<T, R, V, W>(
+        &self,
+        target: T,
+        vals: &HashMap<String, String>,
+        read_old: R,
+        validate: V,
+        write: W,
+    ) -> Result<()>
+    where
+        T: FnOnce() -> AttrTarget,
+        R: FnOnce() -> HashMap<String, String>,
+        V: Fn(&str, &str) -> Result<()>,
+        W: FnOnce() -> Result<()>,
+    {
+        let write = || {
+            let res = write();
+            self.clear_scale_cache();
+            res
+        };
+
+        let dry_run = self.is_dry_run();
+        if !dry_run && !self.inner.journal.is_enabled() {
+            return write();
+        }
+
+        let target = target();
+        let mut old = read_old();
+        let mut attrs: Vec<_> = vals.iter().collect();
+        attrs.sort();
+
+        let (res, errs): (Result<()>, Vec<_>) = if dry_run {
+            let results: Vec<_> = attrs
+                .iter()
+                .map(|(attr, val)| validate(attr, val))
+                .collect();
+            let errs = results
+                .iter()
+                .map(|res| res.as_ref().err().map(|err| err.to_string()))
+                .collect();
+            (results.into_iter().collect(), errs)
+        }
+        else {
+            let res = write();
+            let err = res.as_ref().err().map(|err| err.to_string());
+            (res, vec![err; attrs.len()])
+        };
+
+        if let Some(journal) = self.inner.journal.lock().as_mut() {
+            let timestamp_ms = now_ms();
+            for ((attr, val), error) in attrs.into_iter().zip(errs) {
+                journal.push(JournalEntry {
+                    timestamp_ms,
+                    kind: target.kind,
+                    device: target.device.clone(),
+                    channel: target.channel.clone(),
+                    attr: attr.clone(),
+                    old: old.remove(attr),
+                    new: val.clone(),
+                    error,
+                    dry_run,
+                });
+            }
+        }
+        res
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv() {
+        let journal = Journal {
+            entries: vec![
+                JournalEntry {
+                    timestamp_ms: 1000,
+                    kind: AttrKind::Channel,
+                    device: "iio:device0".into(),
+                    channel: Some("voltage0".into()),
+                    attr: "scale".into(),
+                    old: Some("0.5".into()),
+                    new: "0.25".into(),
+                    error: None,
+                    dry_run: false,
+                },
+                JournalEntry {
+                    timestamp_ms: 1001,
+                    kind: AttrKind::Device,
+                    device: "iio:device0".into(),
+                    channel: None,
+                    attr: "label".into(),
+                    old: None,
+                    new: "a, \"b\"".into(),
+                    error: Some("Permission denied".into()),
+                    dry_run: true,
+                },
+            ],
+        };
+        assert!(journal.entries[0].is_ok());
+        assert!(!journal.entries[1].is_ok());
+
+        let mut out = Vec::new();
+        journal.write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "timestamp_ms,kind,device,channel,attr,old,new,error,dry_run\n\
+             1000,channel,iio:device0,voltage0,scale,0.5,0.25,,false\n\
+             1001,device,iio:device0,,label,,\"a, \"\"b\"\"\",Permission denied,true\n"
+        );
+    }
+
+    #[test]
+    fn clone_settings() {
+        let rec = Recorder::default();
+        *rec.lock() = Some(vec![]);
+        rec.dry_run.store(true, Ordering::Relaxed);
+        rec.lock().as_mut().unwrap().push(JournalEntry {
+            timestamp_ms: 1000,
+            kind: AttrKind::Device,
+            device: "iio:device0".into(),
+            channel: None,
+            attr: "sampling_frequency".into(),
+            old: None,
+            new: "100".into(),
+            error: None,
+            dry_run: true,
+        });
+
+        let copy = rec.clone_settings();
+        assert!(copy.dry_run.load(Ordering::Relaxed));
+        assert_eq!(*copy.lock(), Some(vec![]));
+
+        let copy = Recorder::default().clone_settings();
+        assert!(!copy.dry_run.load(Ordering::Relaxed));
+        assert!(!copy.is_enabled());
+    }
+}
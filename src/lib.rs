@@ -37,6 +37,20 @@
 //! * **libiio_v0_23** - Use the bindings for _libiio_ v0.23
 //! * **libiio_v0_21** - Use the bindings for _libiio_ v0.21
 //! * **libiio_v0_19** - Use the bindings for _libiio_ v0.19
+//! * **serde** - Serialization of frames with _serde_
+//! * **bincode** - Binary encoding of frames with _bincode_
+//! * **cbor** - Binary encoding of frames as CBOR
+//! * **regmap** - Load device register maps from TOML descriptions
+//! * **zmq** - A ZeroMQ publisher sink for captured frames
+//! * **ipc** - Share captured frames with other processes through a
+//!   shared memory ring (Linux only)
+//! * **grpc** - A gRPC service for remote access to devices, with the
+//!   _riio_grpcd_ server
+//! * **tls** - Serve gRPC over TLS, with _rustls_
+//! * **async** - Asynchronous and batch attribute I/O, and buffer I/O and
+//!   frame streams (Unix only), with _tokio_
+//! * **script** - Virtual channels derived from _Rhai_ script expressions
+//! * **rayon** - Parallel demultiplexing and conversion of samples
 //!
 
 // Lints
@@ -57,41 +71,126 @@ use std::{
     ffi::{CStr, CString},
     fmt,
     os::raw::{c_char, c_int, c_uint, c_void},
-    slice, str,
+    ptr, slice, str,
     str::FromStr,
 };
 
 use libiio_sys::{self as ffi};
 use nix::errno::Errno;
 
+#[cfg(all(feature = "async", unix))]
+pub use crate::async_buffer::AsyncBuffer;
+#[cfg(all(feature = "async-io", unix))]
+pub use crate::async_io::{AsyncBufferExt, AsyncIoBuffer};
+pub use crate::attr_reader::AttrReader;
+pub use crate::available::Available;
 pub use crate::buffer::{AttrIterator as BufferAttrIterator, Buffer};
 pub use crate::channel::{
-    AttrIterator as ChannelAttrIterator, Channel, ChannelType, DataFormat, Direction,
+    AttrIterator as ChannelAttrIterator, Channel, ChannelModifier, ChannelType, DataFormat,
+    Direction,
 };
+pub use crate::compat::compatibility_report;
 pub use crate::context::{
-    AttrIterator as ContextAttrIterator, Backend, Context, DeviceIterator, InnerContext,
+    AttrIterator as ContextAttrIterator, Backend, Context, ContextBuilder, DeviceIterator,
+    InnerContext, OwnedBackend, WeakContext,
+};
+pub use crate::context_pool::{ContextPool, PooledContext};
+pub use crate::device::{
+    AttrIterator as DeviceAttrIterator, ChannelIterator, DebugAttrIterator, Device, DeviceIdentity,
+    Operation,
 };
-pub use crate::device::{AttrIterator as DeviceAttrIterator, ChannelIterator, Device};
 pub use crate::errors::{Error, Result};
+pub use crate::frame::{CaptureStream, Frame, FrameChannel, FrameSet};
+pub use crate::guard::GuardedContext;
+pub use crate::hotplug::{ContextEvent, ContextEvents};
+pub use crate::regmap::RegisterMap;
+pub use crate::trigger::{DeviceKindRef, Trigger, TriggerKind};
+pub use crate::typed_channel::TypedChannel;
 
 #[cfg(not(feature = "libiio_v0_19"))]
-pub use crate::scan_context::{ScanContext, ScanContextIterator};
+pub use crate::scan_context::{ScanContext, ScanContextIterator, ScanResult};
 
 mod macros;
 
+pub mod abi;
+#[cfg(feature = "async")]
+pub mod async_attr;
+#[cfg(all(feature = "async", unix))]
+pub mod async_buffer;
+#[cfg(all(feature = "async-io", unix))]
+pub mod async_io;
+pub mod attr_reader;
+pub mod auth;
+pub mod available;
 pub mod buffer;
+pub mod calib;
+pub mod capture;
 pub mod channel;
+#[cfg(any(feature = "bincode", feature = "cbor"))]
+pub mod codec;
+pub mod compat;
 pub mod context;
+pub mod context_pool;
+pub mod cookbook;
+pub mod counter;
+pub mod debugfs;
 pub mod device;
+#[cfg(not(feature = "libiio_v0_19"))]
+pub mod discover;
 pub mod errors;
+pub mod events;
+pub mod fixture;
+pub mod frame;
+#[cfg(all(feature = "async", unix))]
+pub mod frame_stream;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod guard;
+#[cfg(feature = "embedded-hal")]
+pub mod hal;
+pub mod health;
+pub mod hotplug;
+#[cfg(all(feature = "ipc", target_os = "linux"))]
+pub mod ipc;
+#[cfg(feature = "num-complex")]
+pub mod iq;
+pub mod journal;
+pub mod meta;
+pub mod mock;
+#[cfg(not(feature = "libiio_v0_19"))]
+pub mod net;
+pub mod ops;
+pub mod output;
+#[cfg(feature = "rayon")]
+pub mod par;
+#[cfg(feature = "async")]
+pub mod poll;
+pub mod pool;
+pub mod power;
+pub mod process;
+pub mod profile;
+pub mod regmap;
+pub mod replay;
+pub mod ring;
+pub mod scope;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod sensors;
+pub mod sigmf;
+pub mod sink;
+#[cfg(target_os = "linux")]
+pub mod sw_trigger;
+pub mod trigger;
+pub mod typed_channel;
+pub mod units;
+pub mod view;
+#[cfg(feature = "wav")]
+pub mod wav;
+pub mod waveform;
 
 #[cfg(not(feature = "libiio_v0_19"))]
 pub mod scan_context;
 
-/// According to the IIO samples, internal buffers need to be big enough
-/// for attributes coming back from the kernel.
-const ATTR_BUF_SIZE: usize = 16384;
-
 // --------------------------------------------------------------------------
 
 /// Gets an optional string value from a C const char pointer.
@@ -137,6 +236,31 @@ pub trait FromAttribute: FromStr {
     }
 }
 
+/// Parses an attribute string with a list of values, separated by
+/// whitespace or commas.
+pub(crate) fn parse_attr_list<T: FromAttribute>(s: &str) -> Result<Vec<T>> {
+    s.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|tok| !tok.is_empty())
+        .enumerate()
+        .map(|(index, tok)| {
+            T::from_attr(tok).map_err(|_| Error::InvalidListItem {
+                index,
+                token: tok.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Creates an attribute string from a list of values, separated by
+/// spaces.
+pub(crate) fn format_attr_list<T: ToAttribute>(vals: &[T]) -> Result<String> {
+    let vals = vals
+        .iter()
+        .map(|v| v.to_attr())
+        .collect::<Result<Vec<_>>>()?;
+    Ok(vals.join(" "))
+}
+
 /// Attribute conversion for the bool type.
 ///
 /// The bool type needs a special implementation of the attribute conversion
@@ -197,10 +321,55 @@ pub(crate) unsafe extern "C" fn attr_read_all_cb(
     0
 }
 
+// Callback from the C lib to get the values for a collection of
+// device-specific attributes. See attr_write_all().
+pub(crate) unsafe extern "C" fn attr_write_all_cb(
+    _dev: *mut ffi::iio_device,
+    attr: *const c_char,
+    buf: *mut c_void,
+    len: usize,
+    pmap: *mut c_void,
+) -> isize {
+    attr_write_all_value(attr, buf, len, pmap)
+}
+
+/// Copies the value of an attribute from a map into a buffer from the C
+/// lib, for one of the `attr_write_all()` functions.
+///
+/// The value is written as a NUL-terminated string, and the number of
+/// bytes is returned. Attributes that aren't in the map are skipped by
+/// returning zero.
+pub(crate) unsafe fn attr_write_all_value(
+    attr: *const c_char,
+    buf: *mut c_void,
+    len: usize,
+    pmap: *mut c_void,
+) -> isize {
+    if attr.is_null() || buf.is_null() || pmap.is_null() {
+        return -1;
+    }
+
+    let attr = CStr::from_ptr(attr).to_string_lossy();
+    let map: &HashMap<String, String> = &*pmap.cast();
+    match map.get(attr.as_ref()) {
+        Some(val) if val.len() < len => {
+            let buf: *mut u8 = buf.cast();
+            ptr::copy_nonoverlapping(val.as_ptr(), buf, val.len());
+            *buf.add(val.len()) = 0;
+            (val.len() + 1) as isize
+        }
+        Some(_) => -(Errno::ENOSPC as isize),
+        None => 0,
+    }
+}
+
 // --------------------------------------------------------------------------
 
 /// A struct to hold version numbers
-#[derive(Debug, PartialEq, Eq)]
+///
+/// Versions are ordered by their major, then minor, numbers. The git tag
+/// is only used to break ties.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Version {
     /// The Major version number
     pub major: u32,
@@ -210,12 +379,73 @@ pub struct Version {
     pub git_tag: String,
 }
 
+impl Version {
+    /// Creates a version with the major and minor numbers, and no git tag.
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self {
+            major,
+            minor,
+            git_tag: String::new(),
+        }
+    }
+
+    /// Determines if this version of _libiio_ supports the feature.
+    pub fn supports(&self, feature: Feature) -> bool {
+        let min = feature.min_version();
+        (self.major, self.minor) >= (min.major, min.minor)
+    }
+}
+
 impl fmt::Display for Version {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}.{} tag: {}", self.major, self.minor, self.git_tag)
     }
 }
 
+/// The first version of _libiio_ that supports device labels.
+pub const MIN_VERSION_LABELS: Version = Version::new(0, 23);
+
+/// The first version of _libiio_ that supports cancelling a buffer.
+pub const MIN_VERSION_BUFFER_CANCEL: Version = Version::new(0, 8);
+
+/// The first version of _libiio_ that supports scan blocks.
+pub const MIN_VERSION_SCAN_BLOCKS: Version = Version::new(0, 21);
+
+/// Notable features of _libiio_ that depend on its version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// Device labels
+    Labels,
+    /// Cancelling blocking buffer operations
+    BufferCancel,
+    /// Scanning for contexts with scan blocks
+    ScanBlocks,
+}
+
+impl Feature {
+    /// Gets the first version of _libiio_ that supports the feature.
+    pub fn min_version(&self) -> Version {
+        use Feature::*;
+        match self {
+            Labels => MIN_VERSION_LABELS,
+            BufferCancel => MIN_VERSION_BUFFER_CANCEL,
+            ScanBlocks => MIN_VERSION_SCAN_BLOCKS,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Feature::*;
+        let s = match self {
+            Labels => "device labels",
+            BufferCancel => "buffer cancel",
+            ScanBlocks => "scan blocks",
+        };
+        write!(f, "{}", s)
+    }
+}
+
 // --------------------------------------------------------------------------
 
 /// Gets the library version as (Major, Minor, Git Tag)
@@ -259,6 +489,55 @@ mod tests {
         assert!(v1 == v2);
     }
 
+    #[test]
+    fn version_order() {
+        assert!(Version::new(0, 9) < Version::new(0, 23));
+        assert!(Version::new(1, 0) > Version::new(0, 25));
+        assert!(Version::new(0, 25).supports(Feature::Labels));
+        assert!(!Version::new(0, 21).supports(Feature::Labels));
+        assert!(Version::new(0, 21).supports(Feature::ScanBlocks));
+
+        let tagged = Version {
+            git_tag: "b6028fde".into(),
+            ..Version::new(0, 23)
+        };
+        assert!(tagged.supports(Feature::Labels));
+    }
+
+    #[test]
+    fn attr_lists() {
+        let v: Vec<f64> = parse_attr_list("0.000152 0.000305\n").unwrap();
+        assert_eq!(v, [0.000152, 0.000305]);
+
+        let v: Vec<i32> = parse_attr_list("1, -2,3").unwrap();
+        assert_eq!(v, [1, -2, 3]);
+
+        let err = parse_attr_list::<i32>("1 2 x3 4").unwrap_err();
+        assert!(matches!(err, Error::InvalidListItem { index: 2, ref token } if token == "x3"));
+
+        assert_eq!(format_attr_list(&[1, -2, 3]).unwrap(), "1 -2 3");
+        assert!(parse_attr_list::<i32>("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn write_all_value() {
+        let map: HashMap<String, String> = [("raw".to_string(), "1234".to_string())].into();
+        let pmap = (&map as *const HashMap<_, _>).cast_mut().cast();
+        let mut buf = [0xffu8; 8];
+
+        let attr = CString::new("raw").unwrap();
+        let n = unsafe { attr_write_all_value(attr.as_ptr(), buf.as_mut_ptr().cast(), 8, pmap) };
+        assert_eq!(n, 5);
+        assert_eq!(&buf[..5], b"1234\0");
+
+        let n = unsafe { attr_write_all_value(attr.as_ptr(), buf.as_mut_ptr().cast(), 4, pmap) };
+        assert!(n < 0);
+
+        let attr = CString::new("scale").unwrap();
+        let n = unsafe { attr_write_all_value(attr.as_ptr(), buf.as_mut_ptr().cast(), 8, pmap) };
+        assert_eq!(n, 0);
+    }
+
     #[test]
     fn val_from_attr_str() {
         let val: i32 = i32::from_attr("123").unwrap();
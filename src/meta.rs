@@ -0,0 +1,189 @@
+// industrial-io/src/meta.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! A metadata view of the IIO tree.
+//!
+//! The objects of a [`Context`] are handles into the C library, so they
+//! can't be serialized themselves. The types in this module are a plain
+//! snapshot of a context, its devices, and their channels, with the
+//! current attribute values, that can be serialized with the **serde**
+//! feature. Tools can dump the whole tree to JSON or YAML for dashboards,
+//! or to diff the configuration of two systems.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let meta = ctx.meta();
+//!
+//! for dev in &meta.devices {
+//!     println!("{}: {} channels", dev.id, dev.channels.len());
+//! }
+//! ```
+
+use crate::{Channel, ChannelType, Context, DataFormat, Device, Direction};
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Attribute values, by name.
+///
+/// The value is `None` if the attribute couldn't be read, such as for a
+/// write-only attribute.
+pub type AttrValues = BTreeMap<String, Option<String>>;
+
+/// The metadata of a context.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ContextMeta {
+    /// The name of the context backend (i.e. "local", "network")
+    pub name: String,
+    /// The description of the context
+    pub description: String,
+    /// The version of the library or remote server, like "0.25 (b6028fd)"
+    pub version: String,
+    /// The context attributes
+    pub attrs: BTreeMap<String, String>,
+    /// The devices in the context
+    pub devices: Vec<DeviceMeta>,
+}
+
+/// The metadata of a device.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceMeta {
+    /// The device ID (e.g. <b><i>iio:device0</i></b>)
+    pub id: String,
+    /// The name of the device, if any
+    pub name: Option<String>,
+    /// The label of the device, if any
+    pub label: Option<String>,
+    /// Whether the device is a trigger
+    pub is_trigger: bool,
+    /// The device attributes
+    pub attrs: AttrValues,
+    /// The buffer attributes
+    pub buffer_attrs: AttrValues,
+    /// The channels of the device
+    pub channels: Vec<ChannelMeta>,
+}
+
+/// The metadata of a channel.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChannelMeta {
+    /// The channel ID (e.g. <b><i>voltage0</i></b>)
+    pub id: String,
+    /// The name of the channel, if any
+    pub name: Option<String>,
+    /// The direction of the channel
+    pub direction: Direction,
+    /// The type of the channel
+    pub chan_type: ChannelType,
+    /// The modifier of the channel, as a kernel ABI code
+    pub modifier: u32,
+    /// The scan index, for a scan element
+    pub index: Option<usize>,
+    /// The data format, for a scan element
+    pub format: Option<DataFormat>,
+    /// The channel attributes
+    pub attrs: AttrValues,
+}
+
+/// Collects the values of a set of attributes.
+fn attr_values<I: Iterator<Item = (String, crate::Result<String>)>>(iter: I) -> AttrValues {
+    iter.map(|(name, val)| (name, val.ok())).collect()
+}
+
+impl Context {
+    /// Gets a snapshot of the metadata of the context, and all its
+    /// devices and channels, with the current attribute values.
+    pub fn meta(&self) -> ContextMeta {
+        ContextMeta {
+            name: self.name(),
+            description: self.description(),
+            version: self.version().to_string(),
+            attrs: self.attributes().collect(),
+            devices: self.devices().map(|dev| dev.meta()).collect(),
+        }
+    }
+}
+
+impl Device {
+    /// Gets a snapshot of the metadata of the device and its channels,
+    /// with the current attribute values.
+    pub fn meta(&self) -> DeviceMeta {
+        #[cfg(not(any(feature = "libiio_v0_19", feature = "libiio_v0_21")))]
+        let label = self.label();
+        #[cfg(any(feature = "libiio_v0_19", feature = "libiio_v0_21"))]
+        let label = None;
+
+        DeviceMeta {
+            id: self.id().unwrap_or_default(),
+            name: self.name(),
+            label,
+            is_trigger: self.is_trigger(),
+            attrs: attr_values(self.attrs_with_values()),
+            buffer_attrs: attr_values(self.buffer_attr_names().into_iter().map(|name| {
+                let val = self.buffer_attr_read_str(&name);
+                (name, val)
+            })),
+            channels: self.channels().map(|chan| chan.meta()).collect(),
+        }
+    }
+}
+
+impl Channel {
+    /// Gets a snapshot of the metadata of the channel, with the current
+    /// attribute values.
+    pub fn meta(&self) -> ChannelMeta {
+        let scan = self.is_scan_element();
+        ChannelMeta {
+            id: self.id().unwrap_or_default(),
+            name: self.name(),
+            direction: self.direction(),
+            chan_type: self.channel_type(),
+            modifier: self.modifier(),
+            index: self.index().ok().filter(|_| scan),
+            format: scan.then(|| self.data_format()),
+            attrs: attr_values(self.attrs_with_values()),
+        }
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "cbor"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_meta_serde() {
+        let meta = ChannelMeta {
+            id: "voltage0".into(),
+            name: None,
+            direction: Direction::Input,
+            chan_type: ChannelType::Voltage,
+            modifier: 0,
+            index: Some(0),
+            format: Some(DataFormat::parse("le:s12/16>>4").unwrap()),
+            attrs: [("raw".to_string(), Some("1234".to_string()))].into(),
+        };
+
+        // Round-trip through a self-describing format
+        let mut buf = Vec::new();
+        ciborium::into_writer(&meta, &mut buf).unwrap();
+        let meta2: ChannelMeta = ciborium::from_reader(buf.as_slice()).unwrap();
+        assert_eq!(meta2, meta);
+    }
+}
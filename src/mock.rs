@@ -0,0 +1,605 @@
+// industrial-io/src/mock.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! A pure-Rust mock backend for unit tests.
+//!
+//! The types here implement the traits in the [`ops`](crate::ops) module
+//! with in-memory attributes and pre-loaded frames of samples, so that
+//! code written against those traits can be tested deterministically
+//! without any hardware or libiio backend.
+//!
+//! Like the handles from the C library, clones of a mock device or
+//! channel refer to the same object, so writes through one are seen by
+//! the others, and by the test. Reading or writing a missing attribute
+//! fails with an [`Error::Attr`] for `ENOENT`, naming the attribute and
+//! its device and channel, like the errors from the C library.
+//!
+//! A [`MockBuffer`] from a device is refilled from the same queued frames
+//! as its captures, and the samples pushed from it are queued on the
+//! device, for the test to take with [`MockDevice::take_pushed()`].
+//!
+//! ```
+//! use industrial_io::{
+//!     mock::{MockChannel, MockContext, MockDevice},
+//!     ops::{AttrOps, ContextOps, DeviceOps},
+//!     ChannelType, Direction, Frame, FrameChannel,
+//! };
+//!
+//! let mut frame = Frame::new();
+//! frame.channels.push(FrameChannel::new("voltage0", vec![1.0, 2.0, 3.0]));
+//!
+//! let ctx = MockContext::new().device(
+//!     MockDevice::new("iio:device0", "adc")
+//!         .attr("sampling_frequency", "1000")
+//!         .channel(MockChannel::input("voltage0", ChannelType::Voltage).attr("scale", "0.5"))
+//!         .frame(frame),
+//! );
+//!
+//! let dev = ctx.find_device("adc").unwrap();
+//! dev.attr_write("sampling_frequency", 2000).unwrap();
+//! assert_eq!(dev.attr_read_int("sampling_frequency").unwrap(), 2000);
+//!
+//! let chan = dev.find_channel("voltage0", Direction::Input).unwrap();
+//! assert_eq!(chan.attr_read_float("scale").unwrap(), 0.5);
+//!
+//! assert_eq!(dev.capture(2).unwrap().samples("voltage0").unwrap(), [1.0, 2.0]);
+//! ```
+
+use crate::{
+    context::DEFAULT_MAX_BUFFER_SIZE,
+    ops::{AttrOps, BufferOps, ChannelOps, ContextOps, DeviceOps},
+    ChannelType, Error, Frame, FrameChannel, Result,
+};
+use nix::errno::Errno;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    mem::size_of,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// A set of attributes shared by the clones of a mock object.
+#[derive(Debug, Clone, Default)]
+struct Attrs(Arc<Mutex<BTreeMap<String, String>>>);
+
+impl Attrs {
+    /// Locks the attributes, ignoring poisoning from a failed test.
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, String>> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Sets the value of an attribute.
+    fn set(&self, attr: &str, val: &str) {
+        self.lock().insert(attr.to_string(), val.to_string());
+    }
+}
+
+impl AttrOps for Attrs {
+    fn has_attr(&self, attr: &str) -> bool {
+        self.lock().contains_key(attr)
+    }
+
+    fn attr_names(&self) -> Vec<String> {
+        self.lock().keys().cloned().collect()
+    }
+
+    fn attr_read_str(&self, attr: &str) -> Result<String> {
+        self.lock()
+            .get(attr)
+            .cloned()
+            .ok_or(Error::Nix(Errno::ENOENT))
+    }
+
+    fn attr_write_str(&self, attr: &str, val: &str) -> Result<()> {
+        match self.lock().get_mut(attr) {
+            Some(v) => {
+                *v = val.trim_end().to_string();
+                Ok(())
+            }
+            None => Err(Error::Nix(Errno::ENOENT)),
+        }
+    }
+}
+
+/// A mock context.
+#[derive(Debug, Clone, Default)]
+pub struct MockContext {
+    /// The devices in the context
+    devices: Vec<MockDevice>,
+}
+
+impl MockContext {
+    /// Creates an empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a device to the context.
+    pub fn device(mut self, dev: MockDevice) -> Self {
+        self.devices.push(dev);
+        self
+    }
+}
+
+impl ContextOps for MockContext {
+    type Device = MockDevice;
+
+    fn name(&self) -> String {
+        "mock".into()
+    }
+
+    fn devices(&self) -> Vec<MockDevice> {
+        self.devices.clone()
+    }
+}
+
+/// A mock device.
+#[derive(Debug, Clone)]
+pub struct MockDevice {
+    /// The device ID
+    id: String,
+    /// The device name
+    name: Option<String>,
+    /// The device attributes
+    attrs: Attrs,
+    /// The channels of the device
+    channels: Vec<MockChannel>,
+    /// The samples to return from captures
+    frames: Arc<Mutex<VecDeque<Frame>>>,
+    /// The samples pushed from output buffers
+    pushed: Arc<Mutex<VecDeque<Frame>>>,
+}
+
+impl MockDevice {
+    /// Creates a device with the ID and name.
+    pub fn new(id: &str, name: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: Some(name.to_string()),
+            attrs: Attrs::default(),
+            channels: Vec::new(),
+            frames: Arc::default(),
+            pushed: Arc::default(),
+        }
+    }
+
+    /// Adds an attribute to the device.
+    pub fn attr(self, attr: &str, val: &str) -> Self {
+        self.attrs.set(attr, val);
+        self
+    }
+
+    /// Adds a channel to the device.
+    pub fn channel(mut self, mut chan: MockChannel) -> Self {
+        chan.device = self.id.clone();
+        self.channels.push(chan);
+        self
+    }
+
+    /// Queues a frame of samples to be returned by captures.
+    pub fn frame(self, frame: Frame) -> Self {
+        self.push_frame(frame);
+        self
+    }
+
+    /// Queues a frame of samples to be returned by captures.
+    pub fn push_frame(&self, frame: Frame) {
+        self.frames
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push_back(frame);
+    }
+
+    /// Takes the frames of samples pushed from the output buffers of the
+    /// device, in the order they were pushed.
+    pub fn take_pushed(&self) -> Vec<Frame> {
+        self.pushed
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .drain(..)
+            .collect()
+    }
+}
+
+impl AttrOps for MockDevice {
+    fn has_attr(&self, attr: &str) -> bool {
+        self.attrs.has_attr(attr)
+    }
+
+    fn attr_names(&self) -> Vec<String> {
+        self.attrs.attr_names()
+    }
+
+    fn attr_read_str(&self, attr: &str) -> Result<String> {
+        self.attrs
+            .attr_read_str(attr)
+            .map_err(|err| err.with_attr(attr, self.id.clone(), None))
+    }
+
+    fn attr_write_str(&self, attr: &str, val: &str) -> Result<()> {
+        self.attrs
+            .attr_write_str(attr, val)
+            .map_err(|err| err.with_attr(attr, self.id.clone(), None))
+    }
+}
+
+impl DeviceOps for MockDevice {
+    type Channel = MockChannel;
+    type Buffer = MockBuffer;
+
+    fn id(&self) -> Option<String> {
+        Some(self.id.clone())
+    }
+
+    fn name(&self) -> Option<String> {
+        self.name.clone()
+    }
+
+    fn channels(&self) -> Vec<MockChannel> {
+        self.channels.clone()
+    }
+
+    /// Captures `n` samples from the queued frames.
+    ///
+    /// Frames are consumed as their samples are used. If there aren't
+    /// enough samples queued, this fails with `ETIMEDOUT`, like a refill
+    /// that gets no data, and leaves the samples in the queue.
+    fn capture(&self, n: usize) -> Result<Frame> {
+        if n == 0 {
+            return Err(Error::General("Nothing to capture".into()));
+        }
+        let mut frames = self.frames.lock().unwrap_or_else(|err| err.into_inner());
+        let mut out: Option<Frame> = None;
+
+        while out.as_ref().map_or(0, |f| f.len()) < n {
+            let Some(frame) = frames.pop_front()
+            else {
+                // Leave the partial capture for the next try
+                if let Some(out) = out {
+                    frames.push_front(out);
+                }
+                return Err(Error::Nix(Errno::ETIMEDOUT));
+            };
+            match &mut out {
+                Some(out) => out.extend(&frame),
+                None => out = Some(frame),
+            }
+        }
+
+        // Put back any samples that weren't used
+        let mut out = out.unwrap();
+        if out.len() > n {
+            let mut rest = out.clone();
+            rest.drain_front(n);
+            frames.push_front(rest);
+            out.truncate(n);
+        }
+        Ok(out)
+    }
+
+    /// Creates a buffer that's refilled from the queued frames.
+    fn create_buffer(&self, n: usize, _cyclic: bool) -> Result<MockBuffer> {
+        let dev = self.clone();
+        MockBuffer::new(self.clone(), n, move |n| dev.capture(n))
+    }
+}
+
+/// Captures the samples to refill a [`MockBuffer`].
+type Source = Box<dyn Fn(usize) -> Result<Frame> + Send + Sync>;
+
+/// A mock buffer.
+///
+/// Each refill captures a block of samples from its source, like the
+/// frames queued on its device. The samples are kept by channel ID, and
+/// those that are written and pushed are queued on the device.
+pub struct MockBuffer {
+    /// The device that samples are pushed to
+    dev: MockDevice,
+    /// The source of the samples for each refill
+    source: Source,
+    /// The number of samples per channel
+    capacity: usize,
+    /// The current samples, by channel ID
+    block: Mutex<BTreeMap<String, Vec<f64>>>,
+}
+
+impl MockBuffer {
+    /// Creates a buffer for `n` samples per channel, refilled from the
+    /// source.
+    pub(crate) fn new<F>(dev: MockDevice, n: usize, source: F) -> Result<Self>
+    where
+        F: Fn(usize) -> Result<Frame> + Send + Sync + 'static,
+    {
+        if n == 0 {
+            return Err(Error::InvalidBufferSize {
+                samples: 0,
+                bytes: 0,
+                max: DEFAULT_MAX_BUFFER_SIZE,
+            });
+        }
+        Ok(Self {
+            dev,
+            source: Box::new(source),
+            capacity: n,
+            block: Mutex::default(),
+        })
+    }
+
+    /// Locks the current samples, ignoring poisoning from a failed test.
+    fn block(&self) -> MutexGuard<'_, BTreeMap<String, Vec<f64>>> {
+        self.block.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Gets the size of the samples in the buffer, in bytes.
+    fn nbytes(&self, nchan: usize) -> usize {
+        self.capacity * nchan * size_of::<f64>()
+    }
+}
+
+impl BufferOps for MockBuffer {
+    type Channel = MockChannel;
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Captures the next block of samples from the source.
+    ///
+    /// This fails like the source, such as with `ETIMEDOUT` if there
+    /// aren't enough samples queued on a mock device.
+    fn refill(&mut self) -> Result<usize> {
+        let frame = (self.source)(self.capacity)?;
+        let nchan = frame.channels.len();
+        *self.block() = frame
+            .channels
+            .into_iter()
+            .map(|ch| (ch.id, ch.samples))
+            .collect();
+        Ok(self.nbytes(nchan))
+    }
+
+    /// Captures the next block of samples, without waiting.
+    fn refill_timeout(&mut self, _timeout: Duration) -> Result<usize> {
+        self.refill()
+    }
+
+    /// Queues the samples of the output channels on the device.
+    fn push(&self) -> Result<usize> {
+        let block = self.block();
+        let mut frame = Frame::new();
+        for chan in self.dev.channels.iter().filter(|ch| ch.output) {
+            if let Some(samples) = block.get(&chan.id) {
+                frame
+                    .channels
+                    .push(FrameChannel::new(&chan.id, samples.clone()));
+            }
+        }
+        let nbytes = self.nbytes(frame.channels.len());
+        self.dev
+            .pushed
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push_back(frame);
+        Ok(nbytes)
+    }
+
+    fn read_channel(&self, chan: &MockChannel) -> Result<Vec<f64>> {
+        self.block()
+            .get(&chan.id)
+            .cloned()
+            .ok_or_else(|| Error::General(format!("Channel not in buffer: '{}'", chan.id)))
+    }
+
+    fn write_channel(&self, chan: &MockChannel, data: &[f64]) -> Result<usize> {
+        let mut block = self.block();
+        let samples = block
+            .entry(chan.id.clone())
+            .or_insert_with(|| vec![0.0; self.capacity]);
+        let n = data.len().min(self.capacity);
+        samples[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+}
+
+impl fmt::Debug for MockBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockBuffer")
+            .field("dev", &self.dev.id)
+            .field("capacity", &self.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A mock channel.
+#[derive(Debug, Clone)]
+pub struct MockChannel {
+    /// The channel ID
+    id: String,
+    /// The ID of the device the channel was added to
+    device: String,
+    /// The channel name, if any
+    name: Option<String>,
+    /// Whether this is an output channel
+    output: bool,
+    /// The type of channel
+    typ: ChannelType,
+    /// The channel attributes
+    attrs: Attrs,
+}
+
+impl MockChannel {
+    /// Creates an input channel.
+    pub fn input(id: &str, typ: ChannelType) -> Self {
+        Self {
+            id: id.to_string(),
+            device: String::new(),
+            name: None,
+            output: false,
+            typ,
+            attrs: Attrs::default(),
+        }
+    }
+
+    /// Creates an output channel.
+    pub fn output(id: &str, typ: ChannelType) -> Self {
+        Self {
+            output: true,
+            ..Self::input(id, typ)
+        }
+    }
+
+    /// Sets the name of the channel.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Adds an attribute to the channel.
+    pub fn attr(self, attr: &str, val: &str) -> Self {
+        self.attrs.set(attr, val);
+        self
+    }
+}
+
+impl AttrOps for MockChannel {
+    fn has_attr(&self, attr: &str) -> bool {
+        self.attrs.has_attr(attr)
+    }
+
+    fn attr_names(&self) -> Vec<String> {
+        self.attrs.attr_names()
+    }
+
+    fn attr_read_str(&self, attr: &str) -> Result<String> {
+        self.attrs
+            .attr_read_str(attr)
+            .map_err(|err| err.with_attr(attr, self.device.clone(), Some(self.id.clone())))
+    }
+
+    fn attr_write_str(&self, attr: &str, val: &str) -> Result<()> {
+        self.attrs
+            .attr_write_str(attr, val)
+            .map_err(|err| err.with_attr(attr, self.device.clone(), Some(self.id.clone())))
+    }
+}
+
+impl ChannelOps for MockChannel {
+    fn id(&self) -> Option<String> {
+        Some(self.id.clone())
+    }
+
+    fn name(&self) -> Option<String> {
+        self.name.clone()
+    }
+
+    fn is_output(&self) -> bool {
+        self.output
+    }
+
+    fn channel_type(&self) -> ChannelType {
+        self.typ
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Direction, FrameChannel};
+
+    fn frame(vals: &[f64]) -> Frame {
+        let mut frame = Frame::new();
+        frame
+            .channels
+            .push(FrameChannel::new("voltage0", vals.to_vec()));
+        frame
+    }
+
+    #[test]
+    fn attrs() {
+        let dev = MockDevice::new("iio:device0", "dac")
+            .attr("powerdown", "0")
+            .channel(MockChannel::output("voltage0", ChannelType::Voltage).attr("raw", "0"));
+
+        let chan = dev.find_channel("voltage0", Direction::Output).unwrap();
+        assert!(dev.find_channel("voltage0", Direction::Input).is_none());
+
+        // Clones share the attributes
+        chan.attr_write("raw", 2048).unwrap();
+        assert_eq!(dev.channels()[0].attr_read_int("raw").unwrap(), 2048);
+
+        dev.attr_write("powerdown", true).unwrap();
+        assert!(dev.attr_read_bool("powerdown").unwrap());
+
+        assert!(matches!(
+            dev.attr_read_str("missing"),
+            Err(Error::Attr { ref attr, ref device, channel: None, source: Errno::ENOENT })
+                if attr == "missing" && device == "iio:device0"
+        ));
+        assert!(matches!(
+            chan.attr_write_str("missing", "1"),
+            Err(Error::Attr { channel: Some(ref id), source: Errno::ENOENT, .. })
+                if id == "voltage0"
+        ));
+    }
+
+    #[test]
+    fn capture() {
+        let dev = MockDevice::new("iio:device0", "adc")
+            .frame(frame(&[1.0, 2.0, 3.0]))
+            .frame(frame(&[4.0, 5.0]));
+
+        assert_eq!(
+            dev.capture(2).unwrap().samples("voltage0").unwrap(),
+            [1.0, 2.0]
+        );
+        assert_eq!(
+            dev.capture(3).unwrap().samples("voltage0").unwrap(),
+            [3.0, 4.0, 5.0]
+        );
+        assert!(matches!(dev.capture(1), Err(Error::Nix(Errno::ETIMEDOUT))));
+
+        dev.push_frame(frame(&[6.0]));
+        assert!(dev.capture(2).is_err());
+        dev.push_frame(frame(&[7.0]));
+        assert_eq!(
+            dev.capture(2).unwrap().samples("voltage0").unwrap(),
+            [6.0, 7.0]
+        );
+    }
+
+    #[test]
+    fn buffer() {
+        let dev = MockDevice::new("iio:device0", "adc")
+            .channel(MockChannel::input("voltage0", ChannelType::Voltage))
+            .channel(MockChannel::output("voltage1", ChannelType::Voltage))
+            .frame(frame(&[1.0, 2.0, 3.0]));
+        let input = dev.find_channel("voltage0", Direction::Input).unwrap();
+        let output = dev.find_channel("voltage1", Direction::Output).unwrap();
+
+        assert!(dev.create_buffer(0, false).is_err());
+        let mut buf = dev.create_buffer(2, false).unwrap();
+        assert_eq!(buf.refill().unwrap(), 2 * size_of::<f64>());
+        assert_eq!(buf.read_channel(&input).unwrap(), [1.0, 2.0]);
+        assert!(buf.read_channel(&output).is_err());
+        assert!(matches!(buf.refill(), Err(Error::Nix(Errno::ETIMEDOUT))));
+
+        assert_eq!(buf.write_channel(&output, &[5.0, 6.0, 7.0]).unwrap(), 2);
+        buf.push().unwrap();
+        let pushed = dev.take_pushed();
+        assert_eq!(pushed.len(), 1);
+        assert_eq!(pushed[0].samples("voltage1").unwrap(), [5.0, 6.0]);
+        assert!(dev.take_pushed().is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+// industrial-io/src/net.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Discovery of IIOD servers on the network.
+//!
+//! The C library finds IIOD servers that announce themselves with
+//! mDNS/ZeroConf (DNS-SD) when a network context is created with an empty
+//! host name. That picks one server without saying which. The
+//! [`discover_iiod()`] function gives the whole list instead, so that an
+//! application can present a picker to the user, then connect to the
+//! chosen server with its [`uri()`](IiodServer::uri).
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use std::time::Duration;
+//!
+//! let servers = iio::net::discover_iiod(Duration::from_secs(2)).unwrap();
+//! for srv in &servers {
+//!     println!("{} {:?}:{}", srv.hostname, srv.addr, srv.port);
+//! }
+//! let ctx = iio::Context::from_uri(&servers[0].uri()).unwrap();
+//! ```
+
+use crate::{Error, Result, ScanContext};
+use nix::errno::Errno;
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+/// The default TCP port for IIOD servers.
+pub const DEFAULT_IIOD_PORT: u16 = 30431;
+
+/// An IIOD server found on the network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IiodServer {
+    /// The host name of the server, or its address if the name is not
+    /// known.
+    pub hostname: String,
+    /// The IP address of the server, if it could be determined.
+    pub addr: Option<IpAddr>,
+    /// The TCP port of the server
+    pub port: u16,
+    /// The description of the context
+    pub description: String,
+}
+
+impl IiodServer {
+    /// Creates the server info from a URI and description from a scan.
+    ///
+    /// The URI is of the form "ip:host", "ip:host:port", or
+    /// "ip:[ipv6]:port". This fails if it is not a network URI.
+    fn from_scan(uri: &str, descr: &str) -> Option<Self> {
+        let host = uri.strip_prefix("ip:")?;
+        if host.is_empty() {
+            return None;
+        }
+
+        let (hostname, port) = if let Some(rest) = host.strip_prefix('[') {
+            let (addr, rest) = rest.split_once(']')?;
+            let port = rest.strip_prefix(':').and_then(|p| p.parse().ok());
+            (addr, port)
+        }
+        else {
+            match host.rsplit_once(':') {
+                Some((name, port)) if !name.contains(':') => (name, port.parse().ok()),
+                _ => (host, None),
+            }
+        };
+
+        Some(Self {
+            hostname: hostname.to_string(),
+            addr: hostname.parse().ok(),
+            port: port.unwrap_or(DEFAULT_IIOD_PORT),
+            description: descr.to_string(),
+        })
+    }
+
+    /// Looks up the address of the server by its host name, if it's not
+    /// already known.
+    fn resolve(&mut self) {
+        if self.addr.is_none() {
+            self.addr = (self.hostname.as_str(), self.port)
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .map(|sa| sa.ip());
+        }
+    }
+
+    /// Gets the URI to create a context for the server.
+    pub fn uri(&self) -> String {
+        let host = match self.addr {
+            Some(IpAddr::V6(_)) if self.hostname.contains(':') => format!("[{}]", self.hostname),
+            _ => self.hostname.clone(),
+        };
+        if self.port == DEFAULT_IIOD_PORT {
+            format!("ip:{}", host)
+        }
+        else {
+            format!("ip:{}:{}", host, self.port)
+        }
+    }
+
+    /// Gets the socket address of the server, if the IP address is known.
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        self.addr.map(|addr| SocketAddr::new(addr, self.port))
+    }
+}
+
+impl fmt::Display for IiodServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}]", self.uri(), self.description)
+    }
+}
+
+/// Finds the IIOD servers that announce themselves on the network.
+///
+/// This uses the network scan of the C library, which browses for
+/// servers with mDNS/ZeroConf, and looks up the address of any server
+/// found by name. If the scan doesn't complete within the `timeout`, this
+/// fails with `ETIMEDOUT`. The scan is left to finish in the background.
+pub fn discover_iiod(timeout: Duration) -> Result<Vec<IiodServer>> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let res = ScanContext::new_network().map(|scan| {
+            scan.iter()
+                .filter_map(|res| IiodServer::from_scan(&res.uri, &res.description))
+                .map(|mut srv| {
+                    srv.resolve();
+                    srv
+                })
+                .collect::<Vec<_>>()
+        });
+        let _ = tx.send(res);
+    });
+
+    rx.recv_timeout(timeout)
+        .map_err(|_| Error::Nix(Errno::ETIMEDOUT))?
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn from_scan() {
+        let srv = IiodServer::from_scan("ip:192.168.2.1", "PlutoSDR").unwrap();
+        assert_eq!(srv.hostname, "192.168.2.1");
+        assert_eq!(srv.addr, Some(IpAddr::V4(Ipv4Addr::new(192, 168, 2, 1))));
+        assert_eq!(srv.port, DEFAULT_IIOD_PORT);
+        assert_eq!(srv.uri(), "ip:192.168.2.1");
+        assert_eq!(srv.to_string(), "ip:192.168.2.1 [PlutoSDR]");
+
+        let srv = IiodServer::from_scan("ip:pluto.local:1234", "").unwrap();
+        assert_eq!(srv.hostname, "pluto.local");
+        assert_eq!(srv.addr, None);
+        assert_eq!(srv.port, 1234);
+        assert_eq!(srv.uri(), "ip:pluto.local:1234");
+
+        let srv = IiodServer::from_scan("ip:[fe80::1]:30431", "").unwrap();
+        assert_eq!(srv.hostname, "fe80::1");
+        assert!(matches!(srv.addr, Some(IpAddr::V6(_))));
+        assert_eq!(srv.uri(), "ip:[fe80::1]");
+
+        assert!(IiodServer::from_scan("usb:1.2.5", "").is_none());
+        assert!(IiodServer::from_scan("ip:", "").is_none());
+    }
+}
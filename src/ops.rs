@@ -0,0 +1,342 @@
+// industrial-io/src/ops.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Traits for the operations on contexts, devices, channels, and buffers.
+//!
+//! These capture the attribute and capture operations that application
+//! logic typically needs, so that it can be written against the traits
+//! rather than the concrete types. The [`Context`], [`Device`],
+//! [`Channel`], and [`Buffer`] types implement them with the C library,
+//! and the types in the [`mock`](crate::mock) module implement them in
+//! pure Rust, so that the same logic can be driven deterministically in
+//! unit tests, without hardware, the `iio_dummy` kernel module, or a
+//! libiio backend.
+//!
+//! ```
+//! use industrial_io::{ops::ChannelOps, Result};
+//!
+//! // Works with a real Channel or a MockChannel
+//! fn read_millivolts<C: ChannelOps>(chan: &C) -> Result<f64> {
+//!     Ok(chan.attr_read_float("raw")? * chan.attr_read_float("scale")?)
+//! }
+//! ```
+
+use crate::{
+    Buffer, Channel, ChannelType, Context, Device, Direction, Frame, FrameChannel, FromAttribute,
+    Result, ToAttribute,
+};
+use std::time::Duration;
+
+/// Operations on the attributes of an object.
+pub trait AttrOps {
+    /// Determines if the object has the attribute.
+    fn has_attr(&self, attr: &str) -> bool;
+
+    /// Gets the names of all the attributes of the object.
+    fn attr_names(&self) -> Vec<String>;
+
+    /// Reads an attribute as a string.
+    fn attr_read_str(&self, attr: &str) -> Result<String>;
+
+    /// Writes an attribute as a string.
+    fn attr_write_str(&self, attr: &str, val: &str) -> Result<()>;
+
+    /// Reads an attribute, converting it to the value type.
+    fn attr_read<T: FromAttribute>(&self, attr: &str) -> Result<T>
+    where
+        Self: Sized,
+    {
+        T::from_attr(&self.attr_read_str(attr)?)
+    }
+
+    /// Writes an attribute from the value type.
+    fn attr_write<T: ToAttribute>(&self, attr: &str, val: T) -> Result<()>
+    where
+        Self: Sized,
+    {
+        self.attr_write_str(attr, &val.to_attr()?)
+    }
+
+    /// Reads an attribute as a boolean.
+    fn attr_read_bool(&self, attr: &str) -> Result<bool> {
+        bool::from_attr(&self.attr_read_str(attr)?)
+    }
+
+    /// Reads an attribute as an integer.
+    fn attr_read_int(&self, attr: &str) -> Result<i64> {
+        i64::from_attr(&self.attr_read_str(attr)?)
+    }
+
+    /// Reads an attribute as a floating-point value.
+    fn attr_read_float(&self, attr: &str) -> Result<f64> {
+        f64::from_attr(&self.attr_read_str(attr)?)
+    }
+}
+
+/// Operations on a channel.
+pub trait ChannelOps: AttrOps {
+    /// Gets the channel ID (e.g. <b><i>voltage0</i></b>)
+    fn id(&self) -> Option<String>;
+
+    /// Gets the name of the channel, if any.
+    fn name(&self) -> Option<String>;
+
+    /// Determines if this is an output channel.
+    fn is_output(&self) -> bool;
+
+    /// Gets the type of data associated with the channel.
+    fn channel_type(&self) -> ChannelType;
+
+    /// Determines if this is an input channel.
+    fn is_input(&self) -> bool {
+        !self.is_output()
+    }
+}
+
+/// Operations on a buffer of samples.
+///
+/// The samples are read and written as floating-point values in the host
+/// format, but not scaled, whatever the sample type of the channel.
+pub trait BufferOps {
+    /// The type of the channels with samples in the buffer.
+    type Channel: ChannelOps;
+
+    /// Gets the capacity of the buffer, in samples per channel.
+    fn capacity(&self) -> usize;
+
+    /// Fetches more samples from the device into the buffer.
+    ///
+    /// This is only valid for input buffers. Returns the size of the
+    /// samples, in bytes.
+    fn refill(&mut self) -> Result<usize>;
+
+    /// Fetches more samples, waiting no longer than the timeout.
+    ///
+    /// This fails with `ETIMEDOUT` if the samples aren't ready in time.
+    /// A buffer that can't be polled waits for as long as the timeout of
+    /// its context instead.
+    fn refill_timeout(&mut self, timeout: Duration) -> Result<usize>;
+
+    /// Sends the samples in the buffer to the device.
+    ///
+    /// This is only valid for output buffers. Returns the size of the
+    /// samples, in bytes.
+    fn push(&self) -> Result<usize>;
+
+    /// Reads the samples of a channel.
+    fn read_channel(&self, chan: &Self::Channel) -> Result<Vec<f64>>;
+
+    /// Writes the samples of a channel, from the start of the buffer.
+    ///
+    /// Returns the number of samples written.
+    fn write_channel(&self, chan: &Self::Channel, data: &[f64]) -> Result<usize>;
+
+    /// Reads the samples of the channels into a frame.
+    fn read_frame(&self, chans: &[Self::Channel]) -> Result<Frame> {
+        let mut frame = Frame::new();
+        for chan in chans {
+            let id = chan.id().unwrap_or_default();
+            frame
+                .channels
+                .push(FrameChannel::new(&id, self.read_channel(chan)?));
+        }
+        Ok(frame)
+    }
+}
+
+/// Operations on a device.
+pub trait DeviceOps: AttrOps {
+    /// The type of the channels of the device.
+    type Channel: ChannelOps;
+
+    /// The type of the buffers of the device.
+    type Buffer: BufferOps<Channel = Self::Channel>;
+
+    /// Gets the device ID (e.g. <b><i>iio:device0</i></b>)
+    fn id(&self) -> Option<String>;
+
+    /// Gets the descriptive name of the device, if any.
+    fn name(&self) -> Option<String>;
+
+    /// Gets the channels of the device.
+    fn channels(&self) -> Vec<Self::Channel>;
+
+    /// Captures a burst of `n` samples from the enabled channels.
+    fn capture(&self, n: usize) -> Result<Frame>;
+
+    /// Creates a buffer for `n` samples of each of the enabled channels.
+    fn create_buffer(&self, n: usize, cyclic: bool) -> Result<Self::Buffer>;
+
+    /// Tries to find a channel by its ID or name, and direction.
+    fn find_channel(&self, name: &str, dir: Direction) -> Option<Self::Channel> {
+        let is_output = dir == Direction::Output;
+        self.channels().into_iter().find(|chan| {
+            chan.is_output() == is_output
+                && (chan.id().as_deref() == Some(name) || chan.name().as_deref() == Some(name))
+        })
+    }
+}
+
+/// Operations on a context.
+pub trait ContextOps {
+    /// The type of the devices in the context.
+    type Device: DeviceOps;
+
+    /// Gets the name of the context backend (i.e. "local", "network").
+    fn name(&self) -> String;
+
+    /// Gets the devices in the context.
+    fn devices(&self) -> Vec<Self::Device>;
+
+    /// Tries to find a device by its ID or name.
+    fn find_device(&self, name: &str) -> Option<Self::Device> {
+        self.devices()
+            .into_iter()
+            .find(|dev| dev.id().as_deref() == Some(name) || dev.name().as_deref() == Some(name))
+    }
+}
+
+// ----- Implementations with the C library -----
+
+impl AttrOps for Channel {
+    fn has_attr(&self, attr: &str) -> bool {
+        Channel::has_attr(self, attr)
+    }
+
+    fn attr_names(&self) -> Vec<String> {
+        self.attrs().collect()
+    }
+
+    fn attr_read_str(&self, attr: &str) -> Result<String> {
+        Channel::attr_read_str(self, attr)
+    }
+
+    fn attr_write_str(&self, attr: &str, val: &str) -> Result<()> {
+        Channel::attr_write_str(self, attr, val)
+    }
+}
+
+impl ChannelOps for Channel {
+    fn id(&self) -> Option<String> {
+        Channel::id(self)
+    }
+
+    fn name(&self) -> Option<String> {
+        Channel::name(self)
+    }
+
+    fn is_output(&self) -> bool {
+        Channel::is_output(self)
+    }
+
+    fn channel_type(&self) -> ChannelType {
+        Channel::channel_type(self)
+    }
+}
+
+impl AttrOps for Device {
+    fn has_attr(&self, attr: &str) -> bool {
+        Device::has_attr(self, attr)
+    }
+
+    fn attr_names(&self) -> Vec<String> {
+        self.attributes().collect()
+    }
+
+    fn attr_read_str(&self, attr: &str) -> Result<String> {
+        Device::attr_read_str(self, attr)
+    }
+
+    fn attr_write_str(&self, attr: &str, val: &str) -> Result<()> {
+        Device::attr_write_str(self, attr, val)
+    }
+}
+
+impl DeviceOps for Device {
+    type Channel = Channel;
+    type Buffer = Buffer;
+
+    fn id(&self) -> Option<String> {
+        Device::id(self)
+    }
+
+    fn name(&self) -> Option<String> {
+        Device::name(self)
+    }
+
+    fn channels(&self) -> Vec<Channel> {
+        Device::channels(self).collect()
+    }
+
+    fn capture(&self, n: usize) -> Result<Frame> {
+        self.capture_burst(n)
+    }
+
+    fn create_buffer(&self, n: usize, cyclic: bool) -> Result<Buffer> {
+        Device::create_buffer(self, n, cyclic)
+    }
+
+    fn find_channel(&self, name: &str, dir: Direction) -> Option<Channel> {
+        Device::find_channel(self, name, dir)
+    }
+}
+
+impl BufferOps for Buffer {
+    type Channel = Channel;
+
+    fn capacity(&self) -> usize {
+        Buffer::capacity(self)
+    }
+
+    fn refill(&mut self) -> Result<usize> {
+        Buffer::refill(self)
+    }
+
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn refill_timeout(&mut self, timeout: Duration) -> Result<usize> {
+        #[cfg(unix)]
+        if self.poll_fd_raw().is_ok() {
+            return Buffer::refill_timeout(self, timeout);
+        }
+        Buffer::refill(self)
+    }
+
+    fn push(&self) -> Result<usize> {
+        Buffer::push(self)
+    }
+
+    fn read_channel(&self, chan: &Channel) -> Result<Vec<f64>> {
+        chan.read_f64(self)
+    }
+
+    fn write_channel(&self, chan: &Channel, data: &[f64]) -> Result<usize> {
+        chan.write_f64(self, data)
+    }
+
+    fn read_frame(&self, chans: &[Channel]) -> Result<Frame> {
+        Frame::from_buffer(self, chans)
+    }
+}
+
+impl ContextOps for Context {
+    type Device = Device;
+
+    fn name(&self) -> String {
+        Context::name(self)
+    }
+
+    fn devices(&self) -> Vec<Device> {
+        Context::devices(self).collect()
+    }
+
+    fn find_device(&self, name: &str) -> Option<Device> {
+        Context::find_device(self, name)
+    }
+}
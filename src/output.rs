@@ -0,0 +1,240 @@
+// industrial-io/src/output.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Typed controls for output (DAC) channels.
+//!
+//! An [`OutputChannel`] wraps an output channel, and maps the standard
+//! `out_*` ABI attributes that control it:
+//!
+//! * `powerdown` - Whether the output is powered down.
+//! * `powerdown_mode` and `powerdown_mode_available` - How the output
+//!   pin is terminated while powered down, like "1kohm_to_gnd" or
+//!   "three_state".
+//! * `raw_available`, `scale`, `scale_available`, and `offset` - The
+//!   range of the output, in the units of the channel (millivolts for a
+//!   voltage, milliamps for a current). DACs with selectable output
+//!   ranges present them as the available scales.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::output::OutputChannel;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ad5592r").unwrap();
+//! let dac = OutputChannel::new(dev.find_output_channel("voltage0").unwrap()).unwrap();
+//!
+//! dac.set_range(0.0, 5000.0).unwrap();
+//! println!("Range: {:?} mV", dac.range().unwrap());
+//! dac.set_powerdown(false).unwrap();
+//! ```
+
+use crate::{abi, Available, Channel, Error, Result};
+use std::ops::Deref;
+
+/// An output channel, such as a DAC output.
+///
+/// This dereferences to the underlying [`Channel`].
+#[derive(Debug, Clone)]
+pub struct OutputChannel {
+    /// The output channel
+    chan: Channel,
+}
+
+impl OutputChannel {
+    /// Wraps an output channel.
+    ///
+    /// This fails if the channel is an input.
+    pub fn new(chan: Channel) -> Result<Self> {
+        if !chan.is_output() {
+            return Err(Error::General(format!(
+                "'{}' is not an output channel",
+                chan.id().unwrap_or_default()
+            )));
+        }
+        Ok(Self { chan })
+    }
+
+    /// Gets the underlying channel.
+    pub fn into_inner(self) -> Channel {
+        self.chan
+    }
+
+    /// Determines if the output is powered down.
+    pub fn is_powered_down(&self) -> Result<bool> {
+        self.chan.attr_read_bool("powerdown")
+    }
+
+    /// Powers the output down, or back up.
+    pub fn set_powerdown(&self, on: bool) -> Result<()> {
+        self.chan.attr_write_bool("powerdown", on)
+    }
+
+    /// Gets how the output is terminated while powered down.
+    pub fn powerdown_mode(&self) -> Result<String> {
+        self.chan.attr_read_str("powerdown_mode")
+    }
+
+    /// Gets the powerdown modes supported by the output.
+    pub fn powerdown_modes(&self) -> Result<Vec<String>> {
+        match self.chan.attr_available("powerdown_mode")? {
+            Some(Available::List(modes)) => Ok(modes),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Sets how the output is terminated while powered down.
+    ///
+    /// The mode is checked against the available modes.
+    pub fn set_powerdown_mode(&self, mode: &str) -> Result<()> {
+        self.chan.validated_write("powerdown_mode", mode)
+    }
+
+    /// Gets the lowest and highest values that the output can produce,
+    /// in the units of the channel.
+    ///
+    /// This is computed from the range of raw values, and the current
+    /// scale and offset.
+    pub fn range(&self) -> Result<(f64, f64)> {
+        let (lo, hi) = self.raw_range()?;
+        let scale = self.scale()?;
+        let offset = self.offset();
+        Ok(((lo + offset) * scale, (hi + offset) * scale))
+    }
+
+    /// Selects the output range that covers `min..=max` with the best
+    /// resolution.
+    ///
+    /// This picks the smallest of the available scales that can still
+    /// reach both values, and fails if none of them can.
+    pub fn set_range(&self, min: f64, max: f64) -> Result<()> {
+        let raw = self.raw_range()?;
+        let offset = self.offset();
+        let scales = match self.chan.attr_available(abi::chan::SCALE)? {
+            Some(Available::List(items)) => items,
+            _ => {
+                return Err(Error::General(
+                    "The output doesn't have selectable ranges".into(),
+                ))
+            }
+        };
+
+        let scale = best_scale(&scales, raw, offset, min, max).ok_or_else(|| {
+            Error::General(format!(
+                "No available range covers [{}, {}]: {{{}}}",
+                min,
+                max,
+                scales.join(", ")
+            ))
+        })?;
+        self.chan.attr_write_str(abi::chan::SCALE, scale)
+    }
+
+    /// Gets the range of raw values the output accepts.
+    fn raw_range(&self) -> Result<(f64, f64)> {
+        match self.chan.attr_available(abi::chan::RAW)? {
+            Some(Available::Range { min, max, .. }) => Ok((min, max)),
+            Some(Available::List(items)) => {
+                let vals: Vec<f64> = items.iter().filter_map(|s| s.parse().ok()).collect();
+                let lo = vals.iter().copied().fold(f64::INFINITY, f64::min);
+                let hi = vals.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                if lo <= hi {
+                    Ok((lo, hi))
+                }
+                else {
+                    Err(Error::StringConversionError)
+                }
+            }
+            None => Err(Error::General(
+                "The output doesn't report its raw range".into(),
+            )),
+        }
+    }
+
+    /// Gets the scale of the output, which defaults to one.
+    fn scale(&self) -> Result<f64> {
+        if self.chan.has_attr(abi::chan::SCALE) {
+            self.chan.attr_read_float(abi::chan::SCALE)
+        }
+        else {
+            Ok(1.0)
+        }
+    }
+
+    /// Gets the offset of the output, which defaults to zero.
+    fn offset(&self) -> f64 {
+        self.chan.attr_read_float(abi::chan::OFFSET).unwrap_or(0.0)
+    }
+}
+
+impl Deref for OutputChannel {
+    type Target = Channel;
+
+    fn deref(&self) -> &Channel {
+        &self.chan
+    }
+}
+
+impl TryFrom<Channel> for OutputChannel {
+    type Error = Error;
+
+    fn try_from(chan: Channel) -> Result<Self> {
+        Self::new(chan)
+    }
+}
+
+/// Picks the smallest scale for which the raw range, with the offset,
+/// covers `min..=max`.
+fn best_scale(
+    scales: &[String],
+    (lo, hi): (f64, f64),
+    offset: f64,
+    min: f64,
+    max: f64,
+) -> Option<&str> {
+    scales
+        .iter()
+        .filter_map(|s| s.parse::<f64>().ok().map(|x| (s.as_str(), x)))
+        .filter(|&(_, x)| x > 0.0 && (lo + offset) * x <= min && (hi + offset) * x >= max)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(s, _)| s)
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_selection() {
+        // A 12-bit DAC with 0-2.5V and 0-5V ranges
+        let scales = vec!["0.610351562".to_string(), "1.220703125".to_string()];
+        let raw = (0.0, 4095.0);
+
+        assert_eq!(
+            best_scale(&scales, raw, 0.0, 0.0, 2000.0),
+            Some("0.610351562")
+        );
+        assert_eq!(
+            best_scale(&scales, raw, 0.0, 0.0, 4000.0),
+            Some("1.220703125")
+        );
+        assert_eq!(best_scale(&scales, raw, 0.0, 0.0, 6000.0), None);
+
+        // A bipolar range needs a negative offset
+        assert_eq!(best_scale(&scales, raw, 0.0, -100.0, 1000.0), None);
+        assert_eq!(
+            best_scale(&scales, raw, -2048.0, -1000.0, 1000.0),
+            Some("0.610351562")
+        );
+    }
+}
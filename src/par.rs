@@ -0,0 +1,235 @@
+// industrial-io/src/par.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Parallel demultiplexing and conversion of samples with _rayon_.
+//!
+//! Converting the samples of a buffer normally goes through the C
+//! library, one channel at a time, on the thread that did the refill. For
+//! a device with many channels at a high sample rate, that can become the
+//! bottleneck of a capture.
+//!
+//! The functions here find the layout and data format of each channel up
+//! front, then demultiplex and convert the channels in parallel on the
+//! _rayon_ thread pool, from the raw bytes of the buffer. The results are
+//! the same as [`Frame::from_buffer()`].
+//!
+//! This requires the `rayon` feature.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::Frame;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ad7606").unwrap();
+//! let chans: Vec<_> = dev.channels().filter(|ch| ch.is_scan_element()).collect();
+//! chans.iter().for_each(|ch| ch.enable());
+//!
+//! let mut buf = dev.create_buffer(4096, false).unwrap();
+//! buf.refill().unwrap();
+//! let frame = Frame::from_buffer_par(&buf, &chans).unwrap();
+//! ```
+
+use crate::{ffi, Buffer, Channel, Error, Frame, FrameChannel, Result};
+use rayon::prelude::*;
+
+/// The format of the samples of a channel, for converting them without
+/// the C library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Format {
+    /// The number of bytes in a sample: 1, 2, 4, or 8
+    nbytes: usize,
+    /// The number of valid bits in the sample
+    bits: u32,
+    /// The right-shift to apply to the sample
+    shift: u32,
+    /// Whether the sample is signed
+    signed: bool,
+    /// Whether the sample is big-endian
+    big_endian: bool,
+}
+
+impl Format {
+    /// Converts a sample in the hardware format into a floating-point
+    /// value, in the same way as the C library.
+    ///
+    /// The sample must be `nbytes` long.
+    fn convert(&self, sample: &[u8]) -> f64 {
+        let mut val = if self.big_endian {
+            sample
+                .iter()
+                .fold(0u64, |acc, &b| (acc << 8) | u64::from(b))
+        }
+        else {
+            sample
+                .iter()
+                .rev()
+                .fold(0u64, |acc, &b| (acc << 8) | u64::from(b))
+        };
+        val >>= self.shift;
+
+        let bits = self.bits.min(8 * self.nbytes as u32);
+        if bits == 0 || bits >= 64 {
+            return if self.signed {
+                val as i64 as f64
+            }
+            else {
+                val as f64
+            };
+        }
+        let unused = 64 - bits;
+        if self.signed {
+            (((val << unused) as i64) >> unused) as f64
+        }
+        else {
+            ((val << unused) >> unused) as f64
+        }
+    }
+}
+
+/// Where and how to convert the samples of one channel in a buffer.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    /// The offset of the channel in each scan, in bytes
+    off: usize,
+    /// The format of the samples
+    fmt: Format,
+}
+
+impl Layout {
+    /// Finds the layout of an enabled channel in the buffer.
+    fn new(buf: &Buffer, chan: &Channel) -> Result<Self> {
+        let data_fmt = chan.data_format();
+        let nbytes = data_fmt.byte_length();
+        if !chan.is_enabled() {
+            return Err(Error::LayoutMismatch {
+                step: buf.step(),
+                sample_size: nbytes,
+                channel: chan.id().unwrap_or_default(),
+            });
+        }
+        if data_fmt.repeat() != 1 || !matches!(nbytes, 1 | 2 | 4 | 8) {
+            return Err(Error::WrongDataType);
+        }
+        let off = unsafe {
+            let first = ffi::iio_buffer_first(buf.buf, chan.chan) as usize;
+            let start = ffi::iio_buffer_start(buf.buf) as usize;
+            first.checked_sub(start).ok_or(Error::BadReturnSize)?
+        };
+        let fmt = Format {
+            nbytes,
+            bits: data_fmt.bits(),
+            shift: data_fmt.shift(),
+            signed: data_fmt.is_signed(),
+            big_endian: data_fmt.is_big_endian(),
+        };
+        Ok(Self { off, fmt })
+    }
+
+    /// Demultiplexes and converts the samples of the channel from the raw
+    /// bytes of the buffer.
+    fn read(&self, data: &[u8], step: usize) -> Vec<f64> {
+        data.chunks_exact(step)
+            .filter_map(|scan| scan.get(self.off..self.off + self.fmt.nbytes))
+            .map(|sample| self.fmt.convert(sample))
+            .collect()
+    }
+}
+
+/// Demultiplexes and converts the samples of the channels in the buffer
+/// into floating-point values, with the channels in parallel.
+///
+/// The channels must all be enabled in the buffer, or this fails with
+/// [`Error::LayoutMismatch`]. The values are converted to the host format,
+/// but not scaled. This fails with [`Error::WrongDataType`] for channels
+/// with samples that don't fit into a standard integer type, or that
+/// repeat.
+pub fn read_f64_par(buf: &Buffer, chans: &[Channel]) -> Result<Vec<Vec<f64>>> {
+    let step = buf.step();
+    if step == 0 {
+        return Err(Error::BadReturnSize);
+    }
+    let layouts = chans
+        .iter()
+        .map(|chan| Layout::new(buf, chan))
+        .collect::<Result<Vec<_>>>()?;
+
+    let data = buf.as_bytes();
+    Ok(layouts
+        .par_iter()
+        .map(|layout| layout.read(data, step))
+        .collect())
+}
+
+impl Frame {
+    /// Creates a frame from the samples in a buffer, demultiplexing and
+    /// converting the channels in parallel.
+    ///
+    /// This is the same as [`from_buffer()`](Frame::from_buffer), but
+    /// uses the _rayon_ thread pool. See [`read_f64_par()`].
+    pub fn from_buffer_par(buf: &Buffer, chans: &[Channel]) -> Result<Self> {
+        let channels = chans
+            .iter()
+            .zip(read_f64_par(buf, chans)?)
+            .map(|(chan, samples)| FrameChannel::from_channel(chan, samples))
+            .collect();
+        Ok(Self {
+            channels,
+            ..Self::default()
+        })
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(nbytes: usize, bits: u32, shift: u32, signed: bool, big_endian: bool) -> Format {
+        Format {
+            nbytes,
+            bits,
+            shift,
+            signed,
+            big_endian,
+        }
+    }
+
+    #[test]
+    fn convert() {
+        // 12-bit signed, little-endian, in 16 bits
+        let fmt = format(2, 12, 0, true, false);
+        assert_eq!(fmt.convert(&[0xFF, 0x0F]), -1.0);
+        assert_eq!(fmt.convert(&[0xFF, 0x07]), 2047.0);
+
+        // 12-bit unsigned, big-endian, shifted up 4 bits
+        let fmt = format(2, 12, 4, false, true);
+        assert_eq!(fmt.convert(&[0xFF, 0xF0]), 4095.0);
+        assert_eq!(fmt.convert(&[0x00, 0x10]), 1.0);
+
+        // Full 32-bit signed
+        let fmt = format(4, 32, 0, true, false);
+        assert_eq!(fmt.convert(&(-5i32).to_le_bytes()), -5.0);
+    }
+
+    #[test]
+    fn demux() {
+        // Two 16-bit channels, with a 4-byte scan
+        let data: Vec<u8> = [1u16, 2, 3, 4, 5, 6]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let fmt = format(2, 16, 0, false, false);
+        let ch1 = Layout { off: 2, fmt };
+        assert_eq!(ch1.read(&data, 4), vec![2.0, 4.0, 6.0]);
+    }
+}
@@ -0,0 +1,269 @@
+// industrial-io/src/poll.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Batch polling of attributes with _tokio_.
+//!
+//! A dashboard showing hundreds of attributes of a remote context spends
+//! most of each refresh waiting on network round trips if it reads them
+//! one at a time. [`poll_all_async()`] reads a whole list of attributes
+//! concurrently, on the _tokio_ blocking thread pool, with a cap on the
+//! number of reads in flight, and returns a map of the results.
+//!
+//! A single network context sends its requests to the daemon one at a
+//! time, so the reads can only really overlap across connections. The
+//! [`poll_pool_async()`] variant spreads them over the contexts of a
+//! [`ContextPool`].
+//!
+//! Each attribute is given as an [`AttrSpec`], which can be parsed from
+//! "device/attr" or "device/channel/attr".
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::poll::{poll_all_async, AttrSpec};
+//!
+//! # async fn refresh() -> iio::Result<()> {
+//! let ctx = iio::Context::from_uri("ip:192.168.2.1")?;
+//! let specs = ["ad9361-phy/ensm_mode", "ad9361-phy/altvoltage0/frequency"]
+//!     .iter()
+//!     .map(|s| s.parse::<AttrSpec>())
+//!     .collect::<iio::Result<Vec<_>>>()?;
+//!
+//! for (spec, val) in poll_all_async(&ctx, specs, 8).await {
+//!     println!("{}: {:?}", spec, val);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{async_attr::blocking, Context, ContextPool, Error, Result};
+use std::{collections::HashMap, fmt, future::Future, panic, str::FromStr};
+use tokio::task::JoinSet;
+
+/// An attribute of a device or channel, to be read by name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AttrSpec {
+    /// The name or ID of the device
+    pub device: String,
+    /// The ID of the channel, for a channel attribute
+    pub channel: Option<String>,
+    /// The name of the attribute
+    pub attr: String,
+}
+
+impl AttrSpec {
+    /// Creates the spec for a device attribute.
+    pub fn device(device: &str, attr: &str) -> Self {
+        Self {
+            device: device.into(),
+            channel: None,
+            attr: attr.into(),
+        }
+    }
+
+    /// Creates the spec for a channel attribute.
+    ///
+    /// The channel can be an input or output.
+    pub fn channel(device: &str, channel: &str, attr: &str) -> Self {
+        Self {
+            device: device.into(),
+            channel: Some(channel.into()),
+            attr: attr.into(),
+        }
+    }
+
+    /// Reads the attribute, as a string, from the context.
+    pub fn read(&self, ctx: &Context) -> Result<String> {
+        let dev = ctx
+            .find_device(&self.device)
+            .ok_or_else(|| Error::General(format!("Device not found: '{}'", self.device)))?;
+
+        match &self.channel {
+            Some(id) => dev
+                .find_input_channel(id)
+                .or_else(|| dev.find_output_channel(id))
+                .ok_or_else(|| Error::General(format!("Channel not found: '{}'", id)))?
+                .attr_read_str(&self.attr),
+            None => dev.attr_read_str(&self.attr),
+        }
+    }
+}
+
+impl FromStr for AttrSpec {
+    type Err = Error;
+
+    /// Parses a spec from "device/attr" or "device/channel/attr".
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<_> = s.trim().split('/').collect();
+        if parts.iter().any(|p| p.is_empty()) {
+            return Err(Error::General(format!("Invalid attribute: '{}'", s)));
+        }
+        match parts[..] {
+            [dev, attr] => Ok(Self::device(dev, attr)),
+            [dev, chan, attr] => Ok(Self::channel(dev, chan, attr)),
+            _ => Err(Error::General(format!("Invalid attribute: '{}'", s))),
+        }
+    }
+}
+
+impl fmt::Display for AttrSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.channel {
+            Some(chan) => write!(f, "{}/{}/{}", self.device, chan, self.attr),
+            None => write!(f, "{}/{}", self.device, self.attr),
+        }
+    }
+}
+
+/// Reads the attributes concurrently from the context, with at most
+/// `max_in_flight` reads at a time.
+///
+/// This returns the result of each read, by spec. It must be called from
+/// within a _tokio_ runtime.
+pub async fn poll_all_async<I>(
+    ctx: &Context,
+    specs: I,
+    max_in_flight: usize,
+) -> HashMap<AttrSpec, Result<String>>
+where
+    I: IntoIterator<Item = AttrSpec>,
+{
+    let ctx = ctx.clone();
+    poll_with(specs, max_in_flight, move |spec| {
+        let ctx = ctx.clone();
+        blocking(move || spec.read(&ctx))
+    })
+    .await
+}
+
+/// Reads the attributes concurrently over the contexts of a pool, with
+/// at most `max_in_flight` reads at a time.
+///
+/// Each read waits for a context from the pool, so there are never more
+/// reads in flight than there are contexts. This returns the result of
+/// each read, by spec. It must be called from within a _tokio_ runtime.
+pub async fn poll_pool_async<I>(
+    pool: &ContextPool,
+    specs: I,
+    max_in_flight: usize,
+) -> HashMap<AttrSpec, Result<String>>
+where
+    I: IntoIterator<Item = AttrSpec>,
+{
+    let pool = pool.clone();
+    poll_with(specs, max_in_flight, move |spec| {
+        let pool = pool.clone();
+        async move {
+            let ctx = pool.get_async().await;
+            blocking(move || spec.read(&ctx)).await
+        }
+    })
+    .await
+}
+
+/// Runs the reads as tasks, keeping at most `max_in_flight` of them
+/// running, and collects the results.
+async fn poll_with<I, F, Fut>(
+    specs: I,
+    max_in_flight: usize,
+    mut read: F,
+) -> HashMap<AttrSpec, Result<String>>
+where
+    I: IntoIterator<Item = AttrSpec>,
+    F: FnMut(AttrSpec) -> Fut,
+    Fut: Future<Output = Result<String>> + Send + 'static,
+{
+    let max_in_flight = max_in_flight.max(1);
+    let mut results = HashMap::new();
+    let mut tasks = JoinSet::new();
+
+    for spec in specs {
+        if tasks.len() >= max_in_flight {
+            join_next(&mut tasks, &mut results).await;
+        }
+        let fut = read(spec.clone());
+        tasks.spawn(async move { (spec, fut.await) });
+    }
+    while join_next(&mut tasks, &mut results).await {}
+    results
+}
+
+/// Waits for the next read to finish and records its result.
+///
+/// This returns false if there were no reads left.
+async fn join_next(
+    tasks: &mut JoinSet<(AttrSpec, Result<String>)>,
+    results: &mut HashMap<AttrSpec, Result<String>>,
+) -> bool {
+    match tasks.join_next().await {
+        Some(Ok((spec, res))) => {
+            results.insert(spec, res);
+            true
+        }
+        Some(Err(err)) if err.is_panic() => panic::resume_unwind(err.into_panic()),
+        Some(Err(_)) => true,
+        None => false,
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_spec() {
+        let spec: AttrSpec = "ad9361-phy/ensm_mode".parse().unwrap();
+        assert_eq!(spec, AttrSpec::device("ad9361-phy", "ensm_mode"));
+        assert_eq!(spec.to_string(), "ad9361-phy/ensm_mode");
+
+        let spec: AttrSpec = "ad9361-phy/altvoltage0/frequency".parse().unwrap();
+        assert_eq!(spec.channel.as_deref(), Some("altvoltage0"));
+        assert_eq!(spec.to_string(), "ad9361-phy/altvoltage0/frequency");
+
+        assert!("ad9361-phy".parse::<AttrSpec>().is_err());
+        assert!("ad9361-phy//frequency".parse::<AttrSpec>().is_err());
+    }
+
+    #[tokio::test]
+    async fn concurrency_limit() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let specs = (0..20).map(|i| AttrSpec::device("dev", &format!("attr{}", i)));
+
+        let results = poll_with(specs, 4, |spec| {
+            let (running, peak) = (running.clone(), peak.clone());
+            async move {
+                let n = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(n, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(spec.attr)
+            }
+        })
+        .await;
+
+        assert_eq!(results.len(), 20);
+        assert_eq!(
+            results[&AttrSpec::device("dev", "attr7")]
+                .as_deref()
+                .unwrap(),
+            "attr7"
+        );
+        assert!(peak.load(Ordering::SeqCst) <= 4);
+    }
+}
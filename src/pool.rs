@@ -0,0 +1,430 @@
+// industrial-io/src/pool.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Recycling of sample memory.
+//!
+//! Capturing at a high rate creates, and then drops, a large sample vector
+//! for every channel on every refill. A [`FramePool`] keeps the vectors
+//! from dropped frames and hands them out again, so that a steady-state
+//! capture stops allocating.
+//!
+//! Frames built from a pool are returned as a [`PooledFrame`], which
+//! dereferences to a [`Frame`] and gives its sample vectors back to the
+//! pool when it is dropped. A [`Pipeline`] accepts pooled frames, in which
+//! case the memory is recycled once the last sink is done with it.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::pool::FramePool;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ads1015").unwrap();
+//! let chans: Vec<_> = dev.scan_elements().collect();
+//! for ch in &chans {
+//!     ch.enable();
+//! }
+//!
+//! let pool = FramePool::new(16);
+//! let mut buf = dev.create_buffer(1024, false).unwrap();
+//! loop {
+//!     buf.refill().unwrap();
+//!     let frame = pool.frame_from_buffer(&buf, &chans).unwrap();
+//!     println!("{} samples", frame.len());
+//! }
+//! ```
+//!
+//! For code that reads the channels directly, a [`SamplePool`] does the
+//! same for vectors of any sample type, through
+//! [`Channel::read_pooled()`].
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::pool::SamplePool;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ads1015").unwrap();
+//! let chan = dev.find_input_channel("voltage0").unwrap();
+//! chan.enable();
+//!
+//! let pool = SamplePool::<i16>::new(4);
+//! let mut buf = dev.create_buffer(1024, false).unwrap();
+//! loop {
+//!     buf.refill().unwrap();
+//!     let data = chan.read_pooled(&buf, &pool).unwrap();
+//!     println!("{} samples", data.len());
+//! }
+//! ```
+//!
+//! [`Pipeline`]: crate::sink::Pipeline
+
+use crate::{Buffer, Channel, Frame, FrameChannel, Result};
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+/// A pool of reusable vectors for the samples of a channel.
+///
+/// Vectors are taken from the pool as a [`PooledVec`], which dereferences
+/// to a `Vec<T>` and is given back to the pool when it is dropped. Clones
+/// of the pool share the same vectors, so it can be used from multiple
+/// threads.
+#[derive(Debug)]
+pub struct SamplePool<T> {
+    inner: Arc<PoolInner<T>>,
+}
+
+/// The shared state of a pool.
+#[derive(Debug)]
+struct PoolInner<T> {
+    /// The idle vectors
+    bufs: Mutex<Vec<Vec<T>>>,
+    /// The maximum number of idle vectors to keep
+    max_idle: usize,
+}
+
+impl<T> SamplePool<T> {
+    /// Creates a pool that keeps up to `max_idle` vectors for reuse.
+    ///
+    /// This should be at least the number of vectors expected to be in
+    /// use at once.
+    pub fn new(max_idle: usize) -> Self {
+        Self {
+            inner: Arc::new(PoolInner {
+                bufs: Mutex::new(Vec::with_capacity(max_idle)),
+                max_idle,
+            }),
+        }
+    }
+
+    /// Takes an empty vector from the pool, or allocates a new one if the
+    /// pool is empty.
+    ///
+    /// The vector is returned to the pool when it is dropped.
+    pub fn get(&self, capacity: usize) -> PooledVec<T> {
+        PooledVec {
+            v: self.take(capacity),
+            pool: Some(self.clone()),
+        }
+    }
+
+    /// Takes an empty vector from the pool, or allocates a new one if the
+    /// pool is empty.
+    ///
+    /// The vector is not associated with the pool, but can be given back
+    /// with [`give()`](SamplePool::give).
+    pub fn take(&self, capacity: usize) -> Vec<T> {
+        let v = self
+            .inner
+            .bufs
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .pop();
+        match v {
+            Some(mut v) => {
+                v.reserve(capacity);
+                v
+            }
+            None => Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Gives a vector back to the pool.
+    ///
+    /// The vector is dropped if the pool already holds its maximum number
+    /// of idle vectors.
+    pub fn give(&self, mut v: Vec<T>) {
+        let mut bufs = self
+            .inner
+            .bufs
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if bufs.len() < self.inner.max_idle && v.capacity() > 0 {
+            v.clear();
+            bufs.push(v);
+        }
+    }
+
+    /// Gets the number of idle vectors in the pool.
+    pub fn available(&self) -> usize {
+        self.inner
+            .bufs
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .len()
+    }
+}
+
+impl<T> Clone for SamplePool<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> PartialEq for SamplePool<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+/// A vector that is returned to a [`SamplePool`] when it is dropped.
+pub struct PooledVec<T> {
+    /// The vector
+    v: Vec<T>,
+    /// The pool that owns the vector, if any
+    pool: Option<SamplePool<T>>,
+}
+
+impl<T> PooledVec<T> {
+    /// Detaches the vector from the pool, returning it.
+    ///
+    /// The vector is then dropped normally.
+    pub fn into_vec(mut self) -> Vec<T> {
+        self.pool = None;
+        std::mem::take(&mut self.v)
+    }
+}
+
+impl<T> Deref for PooledVec<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.v
+    }
+}
+
+impl<T> DerefMut for PooledVec<T> {
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        &mut self.v
+    }
+}
+
+impl<T> From<Vec<T>> for PooledVec<T> {
+    fn from(v: Vec<T>) -> Self {
+        Self { v, pool: None }
+    }
+}
+
+impl<T> Drop for PooledVec<T> {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            pool.give(std::mem::take(&mut self.v));
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PooledVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.v.fmt(f)
+    }
+}
+
+// --------------------------------------------------------------------------
+
+/// A pool of sample vectors for building frames.
+///
+/// Clones of the pool share the same vectors, so it can be used from
+/// multiple threads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FramePool {
+    /// The sample vectors
+    samples: SamplePool<f64>,
+}
+
+impl FramePool {
+    /// Creates a pool that keeps up to `max_idle` sample vectors for
+    /// reuse.
+    ///
+    /// This should be at least the number of channels times the number of
+    /// frames expected to be in flight at once.
+    pub fn new(max_idle: usize) -> Self {
+        Self {
+            samples: SamplePool::new(max_idle),
+        }
+    }
+
+    /// Takes an empty vector from the pool, or allocates a new one if the
+    /// pool is empty.
+    pub fn take(&self, capacity: usize) -> Vec<f64> {
+        self.samples.take(capacity)
+    }
+
+    /// Gives a vector back to the pool.
+    ///
+    /// The vector is dropped if the pool already holds its maximum number
+    /// of idle vectors.
+    pub fn give(&self, v: Vec<f64>) {
+        self.samples.give(v)
+    }
+
+    /// Gets the number of idle vectors in the pool.
+    pub fn available(&self) -> usize {
+        self.samples.available()
+    }
+
+    /// Wraps a frame so that its sample vectors are returned to this pool
+    /// when it is dropped.
+    pub fn lease(&self, frame: Frame) -> PooledFrame {
+        PooledFrame {
+            frame,
+            pool: Some(self.clone()),
+        }
+    }
+
+    /// Creates a frame from the samples in a buffer, using vectors from
+    /// the pool.
+    ///
+    /// This is the pooled equivalent of [`Frame::from_buffer()`].
+    pub fn frame_from_buffer(&self, buf: &Buffer, chans: &[Channel]) -> Result<PooledFrame> {
+        let mut frame = self.lease(Frame::new());
+        for chan in chans {
+            let mut samples = self.take(buf.capacity());
+            chan.read_f64_into(buf, &mut samples)?;
+            frame
+                .channels
+                .push(FrameChannel::from_channel(chan, samples));
+        }
+        Ok(frame)
+    }
+}
+
+/// A frame whose sample vectors are returned to a [`FramePool`] when it is
+/// dropped.
+///
+/// A plain [`Frame`] can be converted into one that isn't associated with
+/// any pool, in which case it is dropped normally.
+#[derive(Clone, Default, PartialEq)]
+pub struct PooledFrame {
+    /// The frame
+    frame: Frame,
+    /// The pool that owns the sample vectors, if any
+    pool: Option<FramePool>,
+}
+
+impl PooledFrame {
+    /// Detaches the frame from the pool, returning it.
+    ///
+    /// The sample vectors are then dropped normally.
+    pub fn into_frame(mut self) -> Frame {
+        self.pool = None;
+        std::mem::take(&mut self.frame)
+    }
+}
+
+impl Deref for PooledFrame {
+    type Target = Frame;
+
+    fn deref(&self) -> &Frame {
+        &self.frame
+    }
+}
+
+impl DerefMut for PooledFrame {
+    fn deref_mut(&mut self) -> &mut Frame {
+        &mut self.frame
+    }
+}
+
+impl From<Frame> for PooledFrame {
+    fn from(frame: Frame) -> Self {
+        Self { frame, pool: None }
+    }
+}
+
+impl Drop for PooledFrame {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            for ch in self.frame.channels.drain(..) {
+                pool.give(ch.samples);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for PooledFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.frame.fmt(f)
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recycle() {
+        let pool = FramePool::new(2);
+        assert_eq!(pool.available(), 0);
+
+        let mut v = pool.take(100);
+        v.extend_from_slice(&[1.0, 2.0]);
+        let ptr = v.as_ptr();
+
+        let mut frame = pool.lease(Frame::new());
+        frame.channels.push(FrameChannel::new("voltage0", v));
+        frame
+            .channels
+            .push(FrameChannel::new("voltage1", vec![3.0]));
+        frame
+            .channels
+            .push(FrameChannel::new("voltage2", vec![4.0]));
+        drop(frame);
+
+        // Only up to the maximum are kept.
+        assert_eq!(pool.available(), 2);
+
+        let v = pool.take(10);
+        assert!(v.is_empty());
+        let v2 = pool.take(10);
+        assert!(v.as_ptr() == ptr || v2.as_ptr() == ptr);
+    }
+
+    #[test]
+    fn pooled_vec() {
+        let pool = SamplePool::<i16>::new(2);
+        let mut v = pool.get(100);
+        v.extend_from_slice(&[1, 2, 3]);
+        let ptr = v.as_ptr();
+        drop(v);
+        assert_eq!(pool.available(), 1);
+
+        let v = pool.get(10);
+        assert!(v.is_empty());
+        assert_eq!(v.as_ptr(), ptr);
+        assert_eq!(pool.available(), 0);
+
+        let v = v.into_vec();
+        drop(v);
+        assert_eq!(pool.available(), 0);
+    }
+
+    #[test]
+    fn detach() {
+        let pool = FramePool::new(4);
+        let mut frame = pool.lease(Frame::new());
+        frame
+            .channels
+            .push(FrameChannel::new("voltage0", vec![1.0]));
+
+        let frame = frame.into_frame();
+        assert_eq!(frame.samples("voltage0").unwrap(), &[1.0]);
+        assert_eq!(pool.available(), 0);
+
+        drop(PooledFrame::from(frame));
+        assert_eq!(pool.available(), 0);
+    }
+}
@@ -0,0 +1,296 @@
+// industrial-io/src/power.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Low-power, duty-cycled acquisition.
+//!
+//! Battery-powered loggers typically wake a sensor up, capture for a short
+//! time, then power everything back down until the next period. The
+//! [`DutyCycle`] scheduler orchestrates that across the device, its
+//! trigger, and the buffered capture:
+//!
+//! 1. Power up the device (if a power attribute is configured).
+//! 2. Attach the trigger (if one is configured).
+//! 3. Capture bursts of samples for the active window.
+//! 4. Detach the trigger and power the device down.
+//! 5. Sleep until the start of the next period.
+//!
+//! Frames are only produced during the active windows, and are handed to
+//! the caller as each burst is captured.
+
+use crate::{Device, Error, Frame, Result, Trigger};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// An attribute used to switch the power of a device on and off.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PowerAttr {
+    /// The name of the device attribute
+    name: String,
+    /// The value that powers the device up
+    on: String,
+    /// The value that powers the device down
+    off: String,
+}
+
+/// A duty-cycled acquisition scheduler.
+#[derive(Debug, Clone)]
+pub struct DutyCycle {
+    /// The length of each cycle
+    period: Duration,
+    /// The time at the start of each cycle during which we capture
+    active_window: Duration,
+    /// The number of samples per burst capture.
+    burst_size: usize,
+    /// The attribute to power the device up and down, if any.
+    power_attr: Option<PowerAttr>,
+    /// The trigger to attach during the active window, if any.
+    trigger: Option<Trigger>,
+}
+
+impl DutyCycle {
+    /// Creates a scheduler that captures during the first `active_window`
+    /// of every `period`.
+    ///
+    /// If the active window is longer than the period, it is limited to
+    /// the period (i.e. the capture is continuous).
+    pub fn new(period: Duration, active_window: Duration) -> Self {
+        const DFLT_BURST_SIZE: usize = 256;
+
+        Self {
+            period,
+            active_window: active_window.min(period),
+            burst_size: DFLT_BURST_SIZE,
+            power_attr: None,
+            trigger: None,
+        }
+    }
+
+    /// Sets the number of samples captured in each burst during the
+    /// active window.
+    pub fn burst_size(mut self, n: usize) -> Self {
+        self.burst_size = n.max(1);
+        self
+    }
+
+    /// Sets a device attribute, and the values to write to it, to power
+    /// the device up at the start of each active window, and down at the
+    /// end.
+    pub fn power_attr(mut self, name: &str, on: &str, off: &str) -> Self {
+        self.power_attr = Some(PowerAttr {
+            name: name.into(),
+            on: on.into(),
+            off: off.into(),
+        });
+        self
+    }
+
+    /// Sets a trigger to attach to the device for each active window, and
+    /// to detach from it afterward.
+    pub fn trigger(mut self, trig: &Trigger) -> Self {
+        self.trigger = Some(trig.clone());
+        self
+    }
+
+    /// Gets the period of the cycle.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Gets the active window at the start of each cycle.
+    pub fn active_window(&self) -> Duration {
+        self.active_window
+    }
+
+    /// Gets the fraction of time that the device is active, in the
+    /// range 0.0 to 1.0.
+    pub fn duty(&self) -> f64 {
+        if self.period.is_zero() {
+            1.0
+        }
+        else {
+            self.active_window.as_secs_f64() / self.period.as_secs_f64()
+        }
+    }
+
+    /// Runs a single active window, returning the frames captured.
+    ///
+    /// This powers up the device and attaches the trigger, captures bursts
+    /// until the active window has elapsed, then detaches the trigger and
+    /// powers down the device. At least one burst is always captured.
+    pub fn run_window(&self, dev: &Device) -> Result<Vec<Frame>> {
+        let mut frames = Vec::new();
+        self.run_window_with(dev, |frame| {
+            frames.push(frame);
+            true
+        })?;
+        Ok(frames)
+    }
+
+    /// Runs a single active window, giving each frame to the callback as
+    /// soon as it's captured.
+    ///
+    /// This is like [`run_window()`](DutyCycle::run_window), but the
+    /// callback can return `false` to end the window early. The device is
+    /// still powered down. Returns `false` if the callback ended it.
+    pub fn run_window_with<F>(&self, dev: &Device, mut f: F) -> Result<bool>
+    where
+        F: FnMut(Frame) -> bool,
+    {
+        let start = Instant::now();
+        if let Err(err) = self.activate(dev) {
+            // Undo whatever part of the activation succeeded.
+            let _ = self.deactivate(dev);
+            return Err(err);
+        }
+
+        let res = loop {
+            let frame = match dev.capture_burst(self.burst_size) {
+                Ok(frame) => frame,
+                Err(err) => break Err(err),
+            };
+            if !f(frame) {
+                break Ok(false);
+            }
+            if start.elapsed() >= self.active_window {
+                break Ok(true);
+            }
+        };
+
+        // Always try to power down, but report the first error.
+        let deact = self.deactivate(dev);
+        res.and_then(|more| deact.map(|_| more))
+    }
+
+    /// Runs the duty cycle until the callback asks to stop, or an error
+    /// occurs.
+    ///
+    /// The callback is given each frame as it is captured, and should
+    /// return `false` to stop the schedule. The device is powered down
+    /// before this returns.
+    ///
+    /// If a cycle overruns its period, the cycles that should have
+    /// started in the meantime are skipped, and the schedule resumes at
+    /// the next period boundary, rather than running them back to back.
+    pub fn run<F>(&self, dev: &Device, mut f: F) -> Result<()>
+    where
+        F: FnMut(Frame) -> bool,
+    {
+        let mut start = Instant::now();
+
+        loop {
+            if !self.run_window_with(dev, &mut f)? {
+                return Ok(());
+            }
+            start = next_start_after(start, self.period, Instant::now())?;
+            if let Some(dur) = start.checked_duration_since(Instant::now()) {
+                thread::sleep(dur);
+            }
+        }
+    }
+
+    /// Powers up the device and attaches the trigger.
+    fn activate(&self, dev: &Device) -> Result<()> {
+        if let Some(pwr) = &self.power_attr {
+            dev.attr_write_str(&pwr.name, &pwr.on)?;
+        }
+        if let Some(trig) = &self.trigger {
+            dev.set_trigger(trig)?;
+        }
+        Ok(())
+    }
+
+    /// Detaches the trigger and powers down the device.
+    fn deactivate(&self, dev: &Device) -> Result<()> {
+        let res = match &self.trigger {
+            Some(_) => dev.remove_trigger(),
+            None => Ok(()),
+        };
+        if let Some(pwr) = &self.power_attr {
+            dev.attr_write_str(&pwr.name, &pwr.off)?;
+        }
+        res
+    }
+}
+
+/// Gets the start of the cycle after the one that started at `start`.
+///
+/// Each cycle is scheduled from the start of the previous one, rather
+/// than from when it ended, so that they don't drift due to the time
+/// spent capturing. This fails if the time overflows.
+pub(crate) fn next_start(start: Instant, period: Duration) -> Result<Instant> {
+    start
+        .checked_add(period)
+        .ok_or_else(|| Error::General("Cycle start time overflowed".into()))
+}
+
+/// Gets the start of the first cycle after `now`, for cycles of `period`
+/// counted from `start`.
+///
+/// The starts of any cycles that have already passed are skipped. This
+/// fails if the time overflows.
+fn next_start_after(start: Instant, period: Duration, now: Instant) -> Result<Instant> {
+    let next = next_start(start, period)?;
+    if next > now || period.is_zero() {
+        return Ok(next);
+    }
+    let missed = (now - next).as_nanos() / period.as_nanos() + 1;
+    period
+        .as_nanos()
+        .checked_mul(missed)
+        .and_then(|skip| u64::try_from(skip).ok())
+        .and_then(|skip| next.checked_add(Duration::from_nanos(skip)))
+        .ok_or_else(|| Error::General("Cycle start time overflowed".into()))
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duty() {
+        let dc = DutyCycle::new(Duration::from_secs(10), Duration::from_secs(1));
+        assert!((dc.duty() - 0.1).abs() < 1e-9);
+
+        let dc = DutyCycle::new(Duration::from_secs(1), Duration::from_secs(5));
+        assert_eq!(dc.active_window(), Duration::from_secs(1));
+        assert!((dc.duty() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn schedule() {
+        let start = Instant::now();
+        let period = Duration::from_secs(60);
+        assert_eq!(next_start(start, period).unwrap(), start + period);
+        assert!(next_start(start, Duration::MAX).is_err());
+
+        // On time, and late by two and a half periods
+        let now = start + Duration::from_secs(10);
+        assert_eq!(
+            next_start_after(start, period, now).unwrap(),
+            start + period
+        );
+        let now = start + Duration::from_secs(150);
+        assert_eq!(
+            next_start_after(start, period, now).unwrap(),
+            start + Duration::from_secs(180)
+        );
+        let now = start + Duration::from_secs(180);
+        assert_eq!(
+            next_start_after(start, period, now).unwrap(),
+            start + Duration::from_secs(240)
+        );
+    }
+}
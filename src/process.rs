@@ -0,0 +1,339 @@
+// industrial-io/src/process.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Processing stages for captured frames.
+//!
+//! A [`FrameProcessor`] transforms a [`FrameSet`] in place. Processors can
+//! be chained, in order, in a [`ProcessorChain`], or added to a sink
+//! [`Pipeline`](crate::sink::Pipeline), which runs them on each frame
+//! before it is fanned out to the sinks.
+//!
+//! This crate provides a few common stages, like the [`Decimator`], the
+//! offset [`Compensator`], and the calibration [`Corrector`]. Any closure
+//! taking a `&mut FrameSet` is also a processor, and other crates can
+//! publish their own stages by implementing the trait.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::{
+//!     process::{Decimator, FrameProcessor},
+//!     sink::{CsvSink, Pipeline},
+//! };
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ads1015").unwrap();
+//!
+//! let mut pipeline = Pipeline::new();
+//! pipeline.add_processor(Box::new(Decimator::new(4)));
+//! pipeline.add_processor(Box::new(|set: &mut iio::FrameSet| {
+//!     println!("{} samples", set.num_samples());
+//!     Ok(())
+//! }));
+//! pipeline.add_sink(Box::new(CsvSink::create("capture.csv").unwrap()));
+//!
+//! for _ in 0..100 {
+//!     pipeline.send(dev.capture_burst(1000).unwrap());
+//! }
+//! pipeline.close().unwrap();
+//! ```
+
+use crate::{calib::Corrector, Error, FrameSet, Result};
+use std::{collections::HashMap, fmt};
+
+/// A processing stage for captured frames.
+pub trait FrameProcessor: Send {
+    /// Processes the frames, in place.
+    ///
+    /// A processor may change the samples, channels, or attributes of the
+    /// frames, or even add or remove frames from the set.
+    fn process(&mut self, f: &mut FrameSet) -> Result<()>;
+}
+
+impl<F> FrameProcessor for F
+where
+    F: FnMut(&mut FrameSet) -> Result<()> + Send,
+{
+    fn process(&mut self, f: &mut FrameSet) -> Result<()> {
+        self(f)
+    }
+}
+
+impl FrameProcessor for Corrector {
+    /// Applies the corrections to each of the frames.
+    fn process(&mut self, f: &mut FrameSet) -> Result<()> {
+        for frame in f.frames.iter_mut() {
+            self.apply(frame);
+        }
+        Ok(())
+    }
+}
+
+// --------------------------------------------------------------------------
+
+/// A sequence of processors, run in order.
+#[derive(Default)]
+pub struct ProcessorChain {
+    /// The processors
+    stages: Vec<Box<dyn FrameProcessor>>,
+}
+
+impl ProcessorChain {
+    /// Creates a new, empty, chain.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a processor to the end of the chain.
+    pub fn push(&mut self, stage: Box<dyn FrameProcessor>) {
+        self.stages.push(stage);
+    }
+
+    /// Appends a processor to the end of the chain, in builder style.
+    pub fn with(mut self, stage: Box<dyn FrameProcessor>) -> Self {
+        self.push(stage);
+        self
+    }
+
+    /// Gets the number of processors in the chain.
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Determines if the chain has no processors.
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+}
+
+impl FrameProcessor for ProcessorChain {
+    /// Runs each of the processors, stopping at the first error.
+    fn process(&mut self, f: &mut FrameSet) -> Result<()> {
+        for stage in self.stages.iter_mut() {
+            stage.process(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ProcessorChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProcessorChain")
+            .field("len", &self.stages.len())
+            .finish()
+    }
+}
+
+// --------------------------------------------------------------------------
+
+/// A processor that keeps one out of every `n` samples.
+///
+/// The phase is carried from one call to the next, so that a stream of
+/// frames is decimated as if it were one long capture. The sample rate of
+/// the frames, if known, is divided by the factor.
+///
+/// There is no anti-aliasing filter. If the signal has content above the
+/// new Nyquist frequency, it should be filtered first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decimator {
+    /// The decimation factor
+    factor: usize,
+    /// The number of samples to skip before the next one is kept
+    skip: usize,
+}
+
+impl Decimator {
+    /// Creates a decimator that keeps one out of every `factor` samples.
+    ///
+    /// A factor of zero is treated as one, which keeps all the samples.
+    pub fn new(factor: usize) -> Self {
+        Self {
+            factor: factor.max(1),
+            skip: 0,
+        }
+    }
+
+    /// Gets the decimation factor.
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /// Resets the phase, so that the next sample is kept.
+    pub fn reset(&mut self) {
+        self.skip = 0;
+    }
+}
+
+impl FrameProcessor for Decimator {
+    fn process(&mut self, f: &mut FrameSet) -> Result<()> {
+        for frame in f.frames.iter_mut() {
+            let n = frame.len();
+            for ch in frame.channels.iter_mut() {
+                ch.samples = ch
+                    .samples
+                    .iter()
+                    .take(n)
+                    .skip(self.skip)
+                    .step_by(self.factor)
+                    .copied()
+                    .collect();
+            }
+            if let Some(rate) = frame.sample_rate.as_mut() {
+                *rate /= self.factor as f64;
+            }
+            // The number of samples to skip at the start of the next frame
+            self.skip = match n.checked_sub(self.skip) {
+                Some(rem) => (self.factor - rem % self.factor) % self.factor,
+                None => self.skip - n,
+            };
+        }
+        Ok(())
+    }
+}
+
+// --------------------------------------------------------------------------
+
+/// A processor that removes a fixed offset from the samples of each
+/// channel.
+///
+/// The offsets can be set explicitly or measured from a reference capture,
+/// such as one taken with the inputs shorted, with [`Compensator::zero()`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Compensator {
+    /// The offsets, by channel ID
+    offsets: HashMap<String, f64>,
+}
+
+impl Compensator {
+    /// Creates a new compensator with no offsets.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a compensator with the offsets measured from a reference
+    /// capture.
+    ///
+    /// The offset for each channel is the mean of all its samples in the
+    /// set. This fails if the set has no samples.
+    pub fn zero(reference: &FrameSet) -> Result<Self> {
+        let mut sums: HashMap<String, (f64, usize)> = HashMap::new();
+        for ch in reference.iter().flat_map(|frame| frame.channels.iter()) {
+            let (sum, n) = sums.entry(ch.id.clone()).or_default();
+            *sum += ch.samples.iter().sum::<f64>();
+            *n += ch.samples.len();
+        }
+        if sums.values().all(|(_, n)| *n == 0) {
+            return Err(Error::General("No samples in reference capture".into()));
+        }
+        let offsets = sums
+            .into_iter()
+            .filter(|(_, (_, n))| *n != 0)
+            .map(|(id, (sum, n))| (id, sum / n as f64))
+            .collect();
+        Ok(Self { offsets })
+    }
+
+    /// Sets the offset for a channel, replacing any previous one.
+    pub fn set(&mut self, chan_id: &str, offset: f64) {
+        self.offsets.insert(chan_id.to_string(), offset);
+    }
+
+    /// Adds an offset for a channel, in builder style.
+    pub fn with(mut self, chan_id: &str, offset: f64) -> Self {
+        self.set(chan_id, offset);
+        self
+    }
+
+    /// Gets the offset for a channel, if any.
+    pub fn get(&self, chan_id: &str) -> Option<f64> {
+        self.offsets.get(chan_id).copied()
+    }
+}
+
+impl FrameProcessor for Compensator {
+    fn process(&mut self, f: &mut FrameSet) -> Result<()> {
+        for ch in f
+            .frames
+            .iter_mut()
+            .flat_map(|frame| frame.channels.iter_mut())
+        {
+            if let Some(offset) = self.offsets.get(&ch.id) {
+                for x in ch.samples.iter_mut() {
+                    *x -= offset;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calib::Correction, Frame, FrameChannel};
+
+    fn frame(samples: &[f64]) -> Frame {
+        Frame {
+            sample_rate: Some(1000.0),
+            channels: vec![FrameChannel::new("voltage0", samples.to_vec())],
+            ..Frame::default()
+        }
+    }
+
+    #[test]
+    fn decimate_across_frames() {
+        let mut dec = Decimator::new(3);
+        let mut set: FrameSet = vec![
+            frame(&[0.0, 1.0, 2.0, 3.0]),
+            frame(&[4.0]),
+            frame(&[5.0, 6.0, 7.0, 8.0, 9.0]),
+        ]
+        .into_iter()
+        .collect();
+
+        dec.process(&mut set).unwrap();
+        assert_eq!(set.frames[0].samples("voltage0").unwrap(), &[0.0, 3.0]);
+        assert!(set.frames[1].is_empty());
+        assert_eq!(set.frames[2].samples("voltage0").unwrap(), &[6.0, 9.0]);
+        assert_eq!(set.frames[0].sample_rate, Some(1000.0 / 3.0));
+    }
+
+    #[test]
+    fn chain() {
+        let mut chain = ProcessorChain::new()
+            .with(Box::new(Compensator::new().with("voltage0", 1.0)))
+            .with(Box::new(
+                Corrector::new().with("voltage0", Correction::gain_offset(2.0, 0.0)),
+            ))
+            .with(Box::new(|set: &mut FrameSet| {
+                set.push(frame(&[]));
+                Ok(())
+            }));
+        assert_eq!(chain.len(), 3);
+
+        let mut set: FrameSet = std::iter::once(frame(&[1.0, 2.0, 3.0])).collect();
+        chain.process(&mut set).unwrap();
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.frames[0].samples("voltage0").unwrap(), &[0.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn zero_offsets() {
+        let set: FrameSet = vec![frame(&[0.5, 1.5]), frame(&[1.0])]
+            .into_iter()
+            .collect();
+        let comp = Compensator::zero(&set).unwrap();
+        assert_eq!(comp.get("voltage0"), Some(1.0));
+        assert!(Compensator::zero(&FrameSet::new()).is_err());
+    }
+}
@@ -0,0 +1,407 @@
+// industrial-io/src/profile.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Device configuration profiles.
+//!
+//! A [`DeviceProfile`] is a set of attribute values for a device and its
+//! channels, like the sample rate, gain, and scale for a particular
+//! measurement, that can be kept with an application and applied in one
+//! step.
+//!
+//! Before anything is written, the profile is checked against the
+//! context, and every device, channel, and attribute that it refers to
+//! but that doesn't exist is collected into a [`ProfileReport`]. So an
+//! operator sees the complete list of problems at once, rather than
+//! fixing them one failed run at a time, and a profile that doesn't
+//! match the hardware doesn't leave a device half configured.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::profile::{ChannelProfile, DeviceProfile};
+//!
+//! let ctx = iio::Context::new().unwrap();
+//!
+//! let profile = DeviceProfile::new("ads1015")
+//!     .attr("sampling_frequency", "1600")
+//!     .channel(ChannelProfile::input("voltage0").attr("scale", "2.000000"));
+//!
+//! let report = profile.apply(&ctx);
+//! println!("{}", report);
+//! report.into_result().unwrap();
+//! ```
+
+use crate::{
+    ops::{AttrOps, ContextOps, DeviceOps},
+    Direction, Error, Result,
+};
+use std::{collections::BTreeMap, fmt};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The attribute values to set on a channel.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChannelProfile {
+    /// The ID or name of the channel
+    pub id: String,
+    /// Whether it's an output channel
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub output: bool,
+    /// The attribute values, by name
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub attrs: BTreeMap<String, String>,
+}
+
+impl ChannelProfile {
+    /// Creates an empty profile for an input channel.
+    pub fn input(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Creates an empty profile for an output channel.
+    pub fn output(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            output: true,
+            ..Self::default()
+        }
+    }
+
+    /// Adds an attribute value to the profile.
+    pub fn attr(mut self, name: &str, val: &str) -> Self {
+        self.attrs.insert(name.to_string(), val.to_string());
+        self
+    }
+
+    /// Gets the direction of the channel.
+    fn direction(&self) -> Direction {
+        if self.output {
+            Direction::Output
+        }
+        else {
+            Direction::Input
+        }
+    }
+}
+
+/// The attribute values to set on a device and its channels.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceProfile {
+    /// The ID or name of the device
+    pub device: String,
+    /// The device attribute values, by name
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub attrs: BTreeMap<String, String>,
+    /// The channel profiles
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub channels: Vec<ChannelProfile>,
+}
+
+impl DeviceProfile {
+    /// Creates an empty profile for the device.
+    pub fn new(device: &str) -> Self {
+        Self {
+            device: device.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Adds a device attribute value to the profile.
+    pub fn attr(mut self, name: &str, val: &str) -> Self {
+        self.attrs.insert(name.to_string(), val.to_string());
+        self
+    }
+
+    /// Adds a channel profile.
+    pub fn channel(mut self, chan: ChannelProfile) -> Self {
+        self.channels.push(chan);
+        self
+    }
+
+    /// Checks that the device, and all the channels and attributes that
+    /// the profile refers to, exist in the context, without writing
+    /// anything.
+    ///
+    /// The report lists everything that is missing.
+    pub fn check<C: ContextOps>(&self, ctx: &C) -> ProfileReport {
+        let mut report = ProfileReport::default();
+        self.visit(ctx, &mut report, |_, _| Ok(()));
+        report.applied = 0;
+        report
+    }
+
+    /// Applies the profile to the device in the context.
+    ///
+    /// This first checks the profile, and if anything is missing, nothing
+    /// is written. Otherwise each attribute is written in turn, and any
+    /// that fail are recorded, without stopping the rest. Either way, the
+    /// report gives the complete result. See
+    /// [`ProfileReport::into_result()`] to treat any problem as an error.
+    pub fn apply<C: ContextOps>(&self, ctx: &C) -> ProfileReport {
+        let mut report = self.check(ctx);
+        if report.is_ok() {
+            self.visit(ctx, &mut report, |obj, (attr, val)| {
+                obj.attr_write_str(attr, val)
+            });
+        }
+        report
+    }
+
+    /// Walks the items of the profile, recording the missing ones in the
+    /// report, and calling the function on each attribute that exists.
+    fn visit<C, F>(&self, ctx: &C, report: &mut ProfileReport, mut f: F)
+    where
+        C: ContextOps,
+        F: FnMut(&dyn AttrOps, (&str, &str)) -> Result<()>,
+    {
+        let Some(dev) = ctx.find_device(&self.device)
+        else {
+            report
+                .missing
+                .push(MissingItem::Device(self.device.clone()));
+            return;
+        };
+
+        let mut visit_attrs = |obj: &dyn AttrOps,
+                               chan: Option<&str>,
+                               attrs: &BTreeMap<String, String>,
+                               report: &mut ProfileReport| {
+            for (attr, val) in attrs {
+                let item = ProfileItem {
+                    device: self.device.clone(),
+                    channel: chan.map(String::from),
+                    attr: attr.clone(),
+                };
+                if !obj.has_attr(attr) {
+                    report.missing.push(MissingItem::Attr(item));
+                    continue;
+                }
+                match f(obj, (attr, val)) {
+                    Ok(()) => report.applied += 1,
+                    Err(err) => report.failed.push((item, err.to_string())),
+                }
+            }
+        };
+
+        visit_attrs(&dev, None, &self.attrs, report);
+
+        for chan_prof in &self.channels {
+            match dev.find_channel(&chan_prof.id, chan_prof.direction()) {
+                Some(chan) => visit_attrs(&chan, Some(&chan_prof.id), &chan_prof.attrs, report),
+                None => report.missing.push(MissingItem::Channel {
+                    device: self.device.clone(),
+                    channel: chan_prof.id.clone(),
+                }),
+            }
+        }
+    }
+}
+
+/// Applies a set of profiles to a context.
+///
+/// All the profiles are checked first, and nothing is written unless all
+/// of them match the context.
+pub fn apply_all<C: ContextOps>(ctx: &C, profiles: &[DeviceProfile]) -> ProfileReport {
+    let mut report = ProfileReport::default();
+    for profile in profiles {
+        report.merge(profile.check(ctx));
+    }
+    if report.is_ok() {
+        for profile in profiles {
+            report.merge(profile.apply(ctx));
+        }
+    }
+    report
+}
+
+/// An attribute referred to by a profile.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProfileItem {
+    /// The ID or name of the device
+    pub device: String,
+    /// The ID or name of the channel, for a channel attribute
+    pub channel: Option<String>,
+    /// The name of the attribute
+    pub attr: String,
+}
+
+impl fmt::Display for ProfileItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.channel {
+            Some(chan) => write!(f, "{}/{}/{}", self.device, chan, self.attr),
+            None => write!(f, "{}/{}", self.device, self.attr),
+        }
+    }
+}
+
+/// Something that a profile refers to, but that doesn't exist.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MissingItem {
+    /// A device
+    Device(String),
+    /// A channel of a device
+    Channel {
+        /// The ID or name of the device
+        device: String,
+        /// The ID or name of the channel
+        channel: String,
+    },
+    /// An attribute of a device or channel
+    Attr(ProfileItem),
+}
+
+impl fmt::Display for MissingItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MissingItem::Device(dev) => write!(f, "device '{}'", dev),
+            MissingItem::Channel { device, channel } => {
+                write!(f, "channel '{}/{}'", device, channel)
+            }
+            MissingItem::Attr(item) => write!(f, "attribute '{}'", item),
+        }
+    }
+}
+
+/// The result of checking or applying profiles.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfileReport {
+    /// The items that the profiles refer to, but that don't exist
+    pub missing: Vec<MissingItem>,
+    /// The attributes that couldn't be written, with the reason
+    pub failed: Vec<(ProfileItem, String)>,
+    /// The number of attributes that were written
+    pub applied: usize,
+}
+
+impl ProfileReport {
+    /// Determines if nothing is missing and no write failed.
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.failed.is_empty()
+    }
+
+    /// Adds the results of another report to this one.
+    pub fn merge(&mut self, other: ProfileReport) {
+        self.missing.extend(other.missing);
+        self.failed.extend(other.failed);
+        self.applied += other.applied;
+    }
+
+    /// Converts the report to an error if anything is missing or failed.
+    ///
+    /// The error message is the full report.
+    pub fn into_result(self) -> Result<()> {
+        if self.is_ok() {
+            Ok(())
+        }
+        else {
+            Err(Error::General(self.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return write!(f, "Applied {} attribute(s)", self.applied);
+        }
+        if !self.missing.is_empty() {
+            write!(f, "Missing {} item(s):", self.missing.len())?;
+            for item in &self.missing {
+                write!(f, "\n  - {}", item)?;
+            }
+        }
+        if !self.failed.is_empty() {
+            if !self.missing.is_empty() {
+                writeln!(f)?;
+            }
+            write!(f, "Failed to write {} attribute(s):", self.failed.len())?;
+            for (item, err) in &self.failed {
+                write!(f, "\n  - {}: {}", item, err)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{MockChannel, MockContext, MockDevice},
+        ChannelType,
+    };
+
+    fn context() -> MockContext {
+        MockContext::new().device(
+            MockDevice::new("iio:device0", "adc")
+                .attr("sampling_frequency", "100")
+                .channel(MockChannel::input("voltage0", ChannelType::Voltage).attr("scale", "1.0")),
+        )
+    }
+
+    #[test]
+    fn preflight_reports_all() {
+        let ctx = context();
+        let profile = DeviceProfile::new("adc")
+            .attr("sampling_frequency", "200")
+            .attr("oversampling_ratio", "4")
+            .channel(ChannelProfile::input("voltage0").attr("scale", "2.0"))
+            .channel(ChannelProfile::input("voltage0").attr("calibbias", "1"))
+            .channel(ChannelProfile::output("voltage0").attr("raw", "0"));
+
+        let report = profile.apply(&ctx);
+        assert!(!report.is_ok());
+        assert_eq!(report.applied, 0);
+        assert_eq!(report.missing.len(), 3);
+        assert!(report.missing.contains(&MissingItem::Channel {
+            device: "adc".into(),
+            channel: "voltage0".into()
+        }));
+
+        // Nothing was written
+        let dev = ctx.find_device("adc").unwrap();
+        assert_eq!(dev.attr_read_str("sampling_frequency").unwrap(), "100");
+
+        let s = report.to_string();
+        assert!(s.starts_with("Missing 3 item(s):"));
+        assert!(s.contains("attribute 'adc/oversampling_ratio'"));
+        assert!(s.contains("attribute 'adc/voltage0/calibbias'"));
+
+        let report = DeviceProfile::new("dac").check(&ctx);
+        assert_eq!(report.missing, [MissingItem::Device("dac".into())]);
+        assert!(report.into_result().is_err());
+    }
+
+    #[test]
+    fn apply_profile() {
+        let ctx = context();
+        let profile = DeviceProfile::new("adc")
+            .attr("sampling_frequency", "200")
+            .channel(ChannelProfile::input("voltage0").attr("scale", "2.0"));
+
+        let report = apply_all(&ctx, &[profile]);
+        assert!(report.is_ok());
+        assert_eq!(report.applied, 2);
+
+        let dev = ctx.find_device("adc").unwrap();
+        assert_eq!(dev.attr_read_str("sampling_frequency").unwrap(), "200");
+        let chan = dev.find_channel("voltage0", Direction::Input).unwrap();
+        assert_eq!(chan.attr_read_str("scale").unwrap(), "2.0");
+    }
+}
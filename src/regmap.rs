@@ -0,0 +1,293 @@
+// industrial-io/src/regmap.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Named hardware registers and bit fields for a device.
+//!
+//! A [`RegisterMap`] gives names to the registers of a device, and to the
+//! bit fields within them, so that driver bring-up code can be written in
+//! terms of the names from the data sheet rather than raw addresses and
+//! masks.
+//!
+//! With the `regmap` feature, a map can be loaded from a TOML description,
+//! like:
+//!
+//! ```text
+//! [registers.CHIP_ID]
+//! addr = 0x00
+//!
+//! [registers.CONFIG]
+//! addr = 0x10
+//! description = "Main configuration"
+//! fields = { MODE = "1:0", RESET = "7" }
+//! ```
+//!
+//! Fields are specified as `"msb:lsb"` bit ranges, or a single bit number.
+
+use crate::{Device, Error, Result};
+use std::collections::BTreeMap;
+
+/// A bit field within a hardware register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    /// The position of the least significant bit of the field
+    pub lsb: u32,
+    /// The number of bits in the field
+    pub width: u32,
+}
+
+impl Field {
+    /// Creates a field from the position of its least significant bit
+    /// and its width, in bits.
+    ///
+    /// This fails with [`Error::InvalidValue`] if the field is empty, or
+    /// doesn't fit in a 32-bit register.
+    pub fn new(lsb: u32, width: u32) -> Result<Self> {
+        if width == 0 || lsb.checked_add(width).map_or(true, |msb| msb > 32) {
+            return Err(Error::InvalidValue(format!(
+                "Register bit field of {} bits at bit {}",
+                width, lsb
+            )));
+        }
+        Ok(Self { lsb, width })
+    }
+
+    /// Parses a field from a bit range, like "7:4", or a single bit,
+    /// like "3".
+    pub fn parse(s: &str) -> Result<Self> {
+        let err = || Error::InvalidValue(format!("Register bit field: '{}'", s));
+        let bit = |s: &str| s.trim().parse::<u32>().map_err(|_| err());
+
+        let (msb, lsb) = match s.split_once(':') {
+            Some((msb, lsb)) => (bit(msb)?, bit(lsb)?),
+            None => {
+                let b = bit(s)?;
+                (b, b)
+            }
+        };
+
+        if msb < lsb || msb > 31 {
+            return Err(err());
+        }
+        Self::new(lsb, msb - lsb + 1)
+    }
+
+    /// Gets the mask for the field, in the position of the register.
+    ///
+    /// Any bits of the field past the top of the register are dropped.
+    pub fn mask(&self) -> u32 {
+        let bits = if self.width >= 32 {
+            u32::MAX
+        }
+        else {
+            (1u32 << self.width) - 1
+        };
+        bits.checked_shl(self.lsb).unwrap_or(0)
+    }
+
+    /// Extracts the value of the field from the full register value.
+    pub fn extract(&self, reg: u32) -> u32 {
+        (reg & self.mask()).checked_shr(self.lsb).unwrap_or(0)
+    }
+
+    /// Inserts a value for the field into a full register value.
+    pub fn insert(&self, reg: u32, val: u32) -> u32 {
+        (reg & !self.mask()) | (val.checked_shl(self.lsb).unwrap_or(0) & self.mask())
+    }
+}
+
+/// A named hardware register.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Register {
+    /// The address of the register
+    pub addr: u32,
+    /// An optional description of the register
+    pub description: Option<String>,
+    /// The named bit fields in the register
+    pub fields: BTreeMap<String, Field>,
+}
+
+impl Register {
+    /// Creates a register at the specified address, with no fields.
+    pub fn new(addr: u32) -> Self {
+        Self {
+            addr,
+            ..Self::default()
+        }
+    }
+
+    /// Adds a named bit field to the register.
+    pub fn with_field(mut self, name: &str, field: Field) -> Self {
+        self.fields.insert(name.to_string(), field);
+        self
+    }
+}
+
+/// A map of the named registers of a device.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegisterMap {
+    /// The registers, by name
+    registers: BTreeMap<String, Register>,
+}
+
+impl RegisterMap {
+    /// Creates a new, empty, register map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a register map from a TOML description.
+    #[cfg(feature = "regmap")]
+    pub fn from_toml(s: &str) -> Result<Self> {
+        use serde::Deserialize;
+
+        #[derive(Deserialize)]
+        struct RegisterDef {
+            addr: u32,
+            description: Option<String>,
+            #[serde(default)]
+            fields: BTreeMap<String, String>,
+        }
+
+        #[derive(Deserialize)]
+        struct MapDef {
+            #[serde(default)]
+            registers: BTreeMap<String, RegisterDef>,
+        }
+
+        let def: MapDef = toml::from_str(s).map_err(|err| Error::InvalidValue(err.to_string()))?;
+
+        let mut map = Self::new();
+        for (name, reg_def) in def.registers {
+            let mut reg = Register::new(reg_def.addr);
+            reg.description = reg_def.description;
+            for (field_name, bits) in reg_def.fields {
+                reg.fields.insert(field_name, Field::parse(&bits)?);
+            }
+            map.add_register(&name, reg);
+        }
+        Ok(map)
+    }
+
+    /// Adds a named register to the map.
+    pub fn add_register(&mut self, name: &str, reg: Register) {
+        self.registers.insert(name.to_string(), reg);
+    }
+
+    /// Gets a register by name.
+    pub fn register(&self, name: &str) -> Option<&Register> {
+        self.registers.get(name)
+    }
+
+    /// Gets an iterator over the names and registers in the map.
+    pub fn registers(&self) -> impl Iterator<Item = (&str, &Register)> {
+        self.registers
+            .iter()
+            .map(|(name, reg)| (name.as_str(), reg))
+    }
+
+    /// Looks up a register by name, returning [`Error::InvalidIndex`] if
+    /// it's not found.
+    fn lookup(&self, name: &str) -> Result<&Register> {
+        self.register(name).ok_or(Error::InvalidIndex)
+    }
+
+    /// Looks up a register and one of its fields by name, returning
+    /// [`Error::InvalidIndex`] if either is not found.
+    fn lookup_field(&self, reg: &str, field: &str) -> Result<(&Register, Field)> {
+        let r = self.lookup(reg)?;
+        let f = r.fields.get(field).ok_or(Error::InvalidIndex)?;
+        Ok((r, *f))
+    }
+
+    /// Reads the named register from the device.
+    pub fn read(&self, dev: &Device, reg: &str) -> Result<u32> {
+        dev.reg_read(self.lookup(reg)?.addr)
+    }
+
+    /// Writes the named register of the device.
+    pub fn write(&self, dev: &Device, reg: &str, val: u32) -> Result<()> {
+        dev.reg_write(self.lookup(reg)?.addr, val)
+    }
+
+    /// Reads the value of a named field in a register of the device.
+    pub fn read_field(&self, dev: &Device, reg: &str, field: &str) -> Result<u32> {
+        let (r, f) = self.lookup_field(reg, field)?;
+        Ok(f.extract(dev.reg_read(r.addr)?))
+    }
+
+    /// Writes a value to a named field in a register of the device,
+    /// leaving the other bits in the register unchanged.
+    pub fn write_field(&self, dev: &Device, reg: &str, field: &str, val: u32) -> Result<()> {
+        let (r, f) = self.lookup_field(reg, field)?;
+        dev.reg_modify(r.addr, f.mask(), f.insert(0, val))?;
+        Ok(())
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_bits() {
+        let f = Field::parse("7:4").unwrap();
+        assert_eq!(f, Field::new(4, 4).unwrap());
+        assert_eq!(f.mask(), 0xF0);
+        assert_eq!(f.extract(0xA5), 0xA);
+        assert_eq!(f.insert(0xA5, 0x3), 0x35);
+
+        let f = Field::parse("3").unwrap();
+        assert_eq!(f.mask(), 0x08);
+
+        let f = Field::parse("31:0").unwrap();
+        assert_eq!(f.mask(), u32::MAX);
+
+        assert!(Field::parse("3:7").is_err());
+        assert!(matches!(Field::parse("x"), Err(Error::InvalidValue(_))));
+
+        assert!(Field::new(28, 4).is_ok());
+        assert!(Field::new(29, 4).is_err());
+        assert!(Field::new(0, 0).is_err());
+        assert!(Field::new(u32::MAX, 1).is_err());
+
+        // A field built by hand out of range doesn't panic
+        let f = Field { lsb: 40, width: 4 };
+        assert_eq!(f.mask(), 0);
+        assert_eq!(f.extract(u32::MAX), 0);
+        assert_eq!(f.insert(0xA5, 0xF), 0xA5);
+    }
+
+    #[cfg(feature = "regmap")]
+    #[test]
+    fn from_toml() {
+        let map = RegisterMap::from_toml(
+            r#"
+            [registers.CHIP_ID]
+            addr = 0x00
+
+            [registers.CONFIG]
+            addr = 0x10
+            fields = { MODE = "1:0", RESET = "7" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(map.register("CHIP_ID").unwrap().addr, 0);
+
+        let reg = map.register("CONFIG").unwrap();
+        assert_eq!(reg.addr, 0x10);
+        assert_eq!(reg.fields["MODE"], Field::new(0, 2).unwrap());
+        assert_eq!(reg.fields["RESET"], Field::new(7, 1).unwrap());
+    }
+}
@@ -0,0 +1,442 @@
+// industrial-io/src/replay.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! A backend that replays recorded captures.
+//!
+//! The types here implement the traits in the [`ops`](crate::ops) module,
+//! like the [`mock`](crate::mock) backend, but the samples come from a
+//! capture that was recorded earlier, from real hardware, rather than
+//! being made up for a test. So processing code written against those
+//! traits can be developed and regression-tested on a desk, without the
+//! hardware, against the signals it will really see.
+//!
+//! A [`ReplayDevice`] can be loaded from:
+//!
+//! * A CSV file, as written by [`CsvSink`](crate::sink::CsvSink)
+//! * A SigMF recording, as written by [`sigmf::Recorder`](crate::sigmf::Recorder)
+//! * A raw file of interleaved, little-endian, 64-bit floating point
+//!   values, as written by [`FileSink`](crate::sink::FileSink)
+//! * Any [`Frame`] already in memory
+//!
+//! Each capture returns the next samples of the recording, and the
+//! replay can loop back to the start, and be paced at the sample rate of
+//! the recording.
+//!
+//! ```no_run
+//! use industrial_io::{
+//!     ops::{ContextOps, DeviceOps},
+//!     replay::{ReplayContext, ReplayDevice},
+//! };
+//!
+//! let ctx = ReplayContext::new()
+//!     .device(ReplayDevice::from_csv("adc", "capture.csv").unwrap().looping(true));
+//!
+//! let dev = ctx.find_device("adc").unwrap();
+//! let frame = dev.capture(100).unwrap();
+//! println!("{:?}", frame.samples("voltage0"));
+//! ```
+
+use crate::{
+    mock::{MockBuffer, MockChannel, MockDevice},
+    ops::{AttrOps, ContextOps, DeviceOps},
+    sigmf::Recording,
+    ChannelType, Error, Frame, FrameChannel, Result,
+};
+use nix::errno::Errno;
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A context of devices that replay recorded captures.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayContext {
+    /// The devices in the context
+    devices: Vec<ReplayDevice>,
+}
+
+impl ReplayContext {
+    /// Creates an empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a device to the context.
+    pub fn device(mut self, dev: ReplayDevice) -> Self {
+        self.devices.push(dev);
+        self
+    }
+}
+
+impl ContextOps for ReplayContext {
+    type Device = ReplayDevice;
+
+    fn name(&self) -> String {
+        "replay".into()
+    }
+
+    fn devices(&self) -> Vec<ReplayDevice> {
+        self.devices.clone()
+    }
+}
+
+/// The playback position of a replay device.
+#[derive(Debug)]
+struct Playback {
+    /// The index of the next sample to replay
+    pos: usize,
+    /// The time that paced playback started
+    start: Option<Instant>,
+    /// The number of samples replayed since paced playback started
+    played: u64,
+}
+
+/// A device that replays a recorded capture.
+///
+/// Like the handles from the C library, clones of a device refer to the
+/// same object, and share the playback position.
+#[derive(Debug, Clone)]
+pub struct ReplayDevice {
+    /// The device for the ID, name, attributes, and channels
+    dev: MockDevice,
+    /// The recorded samples
+    frame: Arc<Frame>,
+    /// The playback position
+    playback: Arc<Mutex<Playback>>,
+    /// Whether to go back to the start at the end of the recording
+    looping: bool,
+    /// Whether to pace the captures at the sample rate
+    realtime: bool,
+}
+
+impl ReplayDevice {
+    /// Creates a device with the name, to replay the frame.
+    ///
+    /// The device has an input channel for each channel of the frame, and
+    /// a `sampling_frequency` attribute if the frame has a sample rate.
+    pub fn from_frame(name: &str, frame: Frame) -> Self {
+        let mut dev = MockDevice::new(&format!("replay:{}", name), name);
+        if let Some(rate) = frame.sample_rate {
+            dev = dev.attr("sampling_frequency", &rate.to_string());
+        }
+        for chan in &frame.channels {
+            let typ = chan.chan_type.unwrap_or_else(|| channel_type(&chan.id));
+            dev = dev.channel(MockChannel::input(&chan.id, typ));
+        }
+        Self {
+            dev,
+            frame: Arc::new(frame),
+            playback: Arc::new(Mutex::new(Playback {
+                pos: 0,
+                start: None,
+                played: 0,
+            })),
+            looping: false,
+            realtime: false,
+        }
+    }
+
+    /// Creates a device to replay a CSV file.
+    ///
+    /// The first line has the channel ID's, and each line after it the
+    /// samples for each channel, like the files from a
+    /// [`CsvSink`](crate::sink::CsvSink).
+    pub fn from_csv<P: AsRef<Path>>(name: &str, path: P) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(Self::from_frame(name, parse_csv(&text)?))
+    }
+
+    /// Creates a device to replay a SigMF recording, from
+    /// `<base>.sigmf-data` and `<base>.sigmf-meta`.
+    ///
+    /// The channels are named "voltage0", "voltage1", and so on, with a
+    /// pair for the I and Q components of each complex channel.
+    pub fn from_sigmf<P: AsRef<Path>>(name: &str, base: P) -> Result<Self> {
+        let rec = Recording::open(base)?;
+        let mut dev = Self::from_frame(name, rec.to_frame());
+        if let Some(freq) = rec.frequency {
+            dev.dev = dev.dev.attr("center_frequency", &freq.to_string());
+        }
+        Ok(dev)
+    }
+
+    /// Creates a device to replay a raw file of interleaved,
+    /// little-endian, 64-bit floating point values for the channels,
+    /// like the files from a [`FileSink`](crate::sink::FileSink).
+    pub fn from_raw<P: AsRef<Path>>(name: &str, path: P, chan_ids: &[&str]) -> Result<Self> {
+        if chan_ids.is_empty() {
+            return Err(Error::General("No channels to replay".into()));
+        }
+        let data = fs::read(path)?;
+        let values: Vec<f64> = data
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        Ok(Self::from_frame(name, deinterleave(&values, chan_ids)))
+    }
+
+    /// Sets whether the replay goes back to the start at the end of the
+    /// recording, rather than failing.
+    pub fn looping(mut self, on: bool) -> Self {
+        self.looping = on;
+        self
+    }
+
+    /// Sets whether each capture waits until its samples would have been
+    /// taken at the sample rate of the recording, like real hardware.
+    ///
+    /// This needs the sample rate, from the `sampling_frequency`
+    /// attribute of the device.
+    pub fn realtime(mut self, on: bool) -> Self {
+        self.realtime = on;
+        self
+    }
+
+    /// Adds an attribute to the device.
+    pub fn attr(mut self, attr: &str, val: &str) -> Self {
+        self.dev = self.dev.attr(attr, val);
+        self
+    }
+
+    /// Gets the number of samples in the recording.
+    pub fn len(&self) -> usize {
+        self.frame.len()
+    }
+
+    /// Determines if the recording has no samples.
+    pub fn is_empty(&self) -> bool {
+        self.frame.is_empty()
+    }
+
+    /// Gets the index of the next sample to replay.
+    pub fn position(&self) -> usize {
+        self.playback
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .pos
+    }
+
+    /// Goes back to the start of the recording.
+    pub fn rewind(&self) {
+        let mut pb = self.playback.lock().unwrap_or_else(|err| err.into_inner());
+        pb.pos = 0;
+        pb.start = None;
+        pb.played = 0;
+    }
+
+    /// Waits until the next `n` samples would have been taken at the
+    /// sample rate.
+    fn pace(&self, pb: &mut Playback, n: usize) {
+        let Ok(rate) = self.dev.attr_read_float("sampling_frequency")
+        else {
+            return;
+        };
+        if !rate.is_finite() || rate <= 0.0 {
+            return;
+        }
+        let start = *pb.start.get_or_insert_with(Instant::now);
+        pb.played += n as u64;
+        let due = start + Duration::from_secs_f64(pb.played as f64 / rate);
+        if let Some(dur) = due.checked_duration_since(Instant::now()) {
+            thread::sleep(dur);
+        }
+    }
+}
+
+impl AttrOps for ReplayDevice {
+    fn has_attr(&self, attr: &str) -> bool {
+        self.dev.has_attr(attr)
+    }
+
+    fn attr_names(&self) -> Vec<String> {
+        self.dev.attr_names()
+    }
+
+    fn attr_read_str(&self, attr: &str) -> Result<String> {
+        self.dev.attr_read_str(attr)
+    }
+
+    fn attr_write_str(&self, attr: &str, val: &str) -> Result<()> {
+        self.dev.attr_write_str(attr, val)
+    }
+}
+
+impl DeviceOps for ReplayDevice {
+    type Channel = MockChannel;
+    type Buffer = MockBuffer;
+
+    fn id(&self) -> Option<String> {
+        self.dev.id()
+    }
+
+    fn name(&self) -> Option<String> {
+        self.dev.name()
+    }
+
+    fn channels(&self) -> Vec<MockChannel> {
+        self.dev.channels()
+    }
+
+    /// Replays the next `n` samples of the recording.
+    ///
+    /// At the end of the recording, this wraps around to the start if the
+    /// device is looping. Otherwise, if there aren't `n` samples left, it
+    /// fails with `ENODATA`, and leaves the position unchanged.
+    fn capture(&self, n: usize) -> Result<Frame> {
+        if n == 0 {
+            return Err(Error::General("Nothing to capture".into()));
+        }
+        let len = self.frame.len();
+        let mut pb = self.playback.lock().unwrap_or_else(|err| err.into_inner());
+        if len == 0 || (!self.looping && pb.pos + n > len) {
+            return Err(Error::Nix(Errno::ENODATA));
+        }
+
+        let mut out = self.frame.slice(pb.pos..(pb.pos + n).min(len));
+        let mut pos = pb.pos + out.len();
+        while out.len() < n {
+            let chunk = self.frame.slice(0..(n - out.len()).min(len));
+            pos = chunk.len();
+            out.extend(&chunk);
+        }
+
+        if self.realtime {
+            self.pace(&mut pb, n);
+        }
+
+        pb.pos = pos % len;
+        out.sample_rate = self.frame.sample_rate;
+        Ok(out)
+    }
+
+    /// Creates a buffer that's refilled with the next samples of the
+    /// recording.
+    fn create_buffer(&self, n: usize, _cyclic: bool) -> Result<MockBuffer> {
+        let dev = self.clone();
+        MockBuffer::new(self.dev.clone(), n, move |n| dev.capture(n))
+    }
+}
+
+/// Guesses the type of a channel from its ID.
+fn channel_type(id: &str) -> ChannelType {
+    let kind = id.trim_end_matches(|c: char| c.is_ascii_digit() || c == '_');
+    match kind {
+        "timestamp" => ChannelType::Timestamp,
+        "voltage" => ChannelType::Voltage,
+        "altvoltage" => ChannelType::AltVoltage,
+        "current" => ChannelType::Current,
+        "power" => ChannelType::Power,
+        "temp" => ChannelType::Temp,
+        "pressure" => ChannelType::Pressure,
+        _ if kind.starts_with("accel") => ChannelType::Accel,
+        _ if kind.starts_with("anglvel") => ChannelType::AnglVel,
+        _ if kind.starts_with("magn") => ChannelType::Magn,
+        _ => ChannelType::Unknown,
+    }
+}
+
+/// Splits interleaved values into the channels of a frame.
+fn deinterleave(values: &[f64], chan_ids: &[&str]) -> Frame {
+    let n = chan_ids.len();
+    let values = &values[..values.len() / n * n];
+    let mut frame = Frame::new();
+    frame.channels = chan_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| FrameChannel::new(id, values.iter().skip(i).step_by(n).copied().collect()))
+        .collect();
+    frame
+}
+
+/// Parses CSV text with a header line of channel ID's into a frame.
+fn parse_csv(text: &str) -> Result<Frame> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header = lines
+        .next()
+        .ok_or_else(|| Error::General("The CSV file is empty".into()))?;
+    let ids: Vec<&str> = header.split(',').map(str::trim).collect();
+
+    let mut values = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let row: Vec<&str> = line.split(',').map(str::trim).collect();
+        if row.len() != ids.len() {
+            return Err(Error::General(format!(
+                "CSV line {} has {} values, expected {}",
+                i + 2,
+                row.len(),
+                ids.len()
+            )));
+        }
+        for val in row {
+            values.push(
+                val.parse::<f64>()
+                    .map_err(|_| Error::StringConversionError)?,
+            );
+        }
+    }
+    Ok(deinterleave(&values, &ids))
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ops::ChannelOps, Direction};
+
+    const CSV: &str = "voltage0,timestamp\n1,100\n2,200\n3,300\n";
+
+    #[test]
+    fn replay_csv() {
+        let frame = parse_csv(CSV).unwrap();
+        let ctx = ReplayContext::new().device(ReplayDevice::from_frame("adc", frame));
+
+        let dev = ctx.find_device("adc").unwrap();
+        assert_eq!(dev.len(), 3);
+        let chan = dev.find_channel("timestamp", Direction::Input).unwrap();
+        assert_eq!(chan.channel_type(), ChannelType::Timestamp);
+
+        let frame = dev.capture(2).unwrap();
+        assert_eq!(frame.samples("voltage0").unwrap(), [1.0, 2.0]);
+        assert_eq!(frame.samples("timestamp").unwrap(), [100.0, 200.0]);
+
+        // Not enough left, and not looping
+        assert!(dev.capture(2).is_err());
+        assert_eq!(dev.position(), 2);
+        assert_eq!(dev.capture(1).unwrap().samples("voltage0").unwrap(), [3.0]);
+
+        dev.rewind();
+        assert_eq!(dev.capture(1).unwrap().samples("voltage0").unwrap(), [1.0]);
+
+        assert!(parse_csv("a,b\n1\n").is_err());
+        assert!(parse_csv("a\nx\n").is_err());
+    }
+
+    #[test]
+    fn replay_looping() {
+        let frame = parse_csv(CSV).unwrap();
+        let dev = ReplayDevice::from_frame("adc", frame).looping(true);
+
+        assert_eq!(
+            dev.capture(2).unwrap().samples("voltage0").unwrap(),
+            [1.0, 2.0]
+        );
+        assert_eq!(
+            dev.capture(5).unwrap().samples("voltage0").unwrap(),
+            [3.0, 1.0, 2.0, 3.0, 1.0]
+        );
+        assert_eq!(dev.position(), 1);
+    }
+}
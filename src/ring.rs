@@ -0,0 +1,373 @@
+// industrial-io/src/ring.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! A lock-free, single-producer, single-consumer ring for handing frames
+//! between threads.
+//!
+//! The ring is a fixed-size array of slots, allocated up front, with
+//! atomic read and write positions. Pushing and popping never allocate
+//! or take a lock, which gives a real-time consumer a more deterministic
+//! latency than a general-purpose channel. A push only takes a short lock
+//! to wake a consumer that's blocked in [`Consumer::recv()`].
+//!
+//! ```
+//! use industrial_io::{ring::FrameRing, Frame};
+//! use std::thread;
+//!
+//! let (mut tx, mut rx) = FrameRing::with_capacity(8);
+//!
+//! let thr = thread::spawn(move || {
+//!     let mut n = 0;
+//!     while let Some(_frame) = rx.recv() {
+//!         n += 1;
+//!     }
+//!     n
+//! });
+//!
+//! for seq in 0..4 {
+//!     let mut frame = Frame { seq, ..Frame::default() };
+//!     while let Err(f) = tx.push(frame) {
+//!         frame = f;
+//!         thread::yield_now();
+//!     }
+//! }
+//! drop(tx);
+//! assert_eq!(thr.join().unwrap(), 4);
+//! ```
+
+use crate::Frame;
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    sync::{
+        atomic::{self, AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, Thread},
+};
+
+/// The state shared by the two ends of the ring.
+struct Shared<T> {
+    /// The preallocated slots
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// The position of the next item to read. Only moved by the consumer.
+    head: AtomicUsize,
+    /// The position of the next item to write. Only moved by the producer.
+    tail: AtomicUsize,
+    /// Set when either end is dropped
+    closed: AtomicBool,
+    /// Set while the consumer is parked, or about to park
+    parked: AtomicBool,
+    /// The consumer thread that last parked waiting for items
+    waiter: Mutex<Option<Thread>>,
+}
+
+impl<T> Shared<T> {
+    /// Gets the slot for a position.
+    fn slot(&self, pos: usize) -> *mut MaybeUninit<T> {
+        self.slots[pos % self.slots.len()].get()
+    }
+
+    /// Wakes the consumer, if it's parked.
+    ///
+    /// This is called after moving the tail or closing the ring. The fence
+    /// pairs with the one in [`Consumer::recv()`], so that either the
+    /// consumer sees the change before it parks, or this sees that it's
+    /// parked. The lock is only taken when the consumer is waiting.
+    fn wake(&self) {
+        atomic::fence(Ordering::SeqCst);
+        if self.parked.load(Ordering::Relaxed) {
+            let waiter = self.waiter.lock().unwrap_or_else(|err| err.into_inner());
+            if let Some(thr) = waiter.as_ref() {
+                thr.unpark();
+            }
+        }
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    /// Drops any items still in the ring.
+    fn drop(&mut self) {
+        let tail = *self.tail.get_mut();
+        for pos in *self.head.get_mut()..tail {
+            unsafe { (*self.slot(pos)).assume_init_drop() };
+        }
+    }
+}
+
+// The slots are only ever accessed by one end at a time, as mediated by
+// the atomic positions.
+unsafe impl<T: Send> Send for Shared<T> {}
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+/// A single-producer, single-consumer ring.
+///
+/// This is only used to create the [`Producer`] and [`Consumer`] ends.
+/// By default it carries [`Frame`]s, but can hold any type that can be
+/// sent to another thread, like `FrameRing::<Arc<Frame>>::with_capacity(n)`.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameRing<T = Frame> {
+    _marker: PhantomData<T>,
+}
+
+impl<T: Send> FrameRing<T> {
+    /// Creates a ring that can hold up to `capacity` items, returning the
+    /// producer and consumer ends.
+    pub fn with_capacity(capacity: usize) -> (Producer<T>, Consumer<T>) {
+        let slots = (0..capacity.max(1))
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect();
+
+        let shared = Arc::new(Shared {
+            slots,
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            parked: AtomicBool::new(false),
+            waiter: Mutex::new(None),
+        });
+
+        (
+            Producer {
+                shared: Arc::clone(&shared),
+            },
+            Consumer { shared },
+        )
+    }
+}
+
+/// The producer end of a [`FrameRing`].
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Producer<T> {
+    /// Pushes an item into the ring.
+    ///
+    /// If the ring is full, the item is given back as the error.
+    pub fn push(&mut self, val: T) -> Result<(), T> {
+        let sh = &*self.shared;
+        let tail = sh.tail.load(Ordering::Relaxed);
+        if tail - sh.head.load(Ordering::Acquire) == sh.slots.len() {
+            return Err(val);
+        }
+        unsafe { (*sh.slot(tail)).write(val) };
+        sh.tail.store(tail + 1, Ordering::Release);
+        sh.wake();
+        Ok(())
+    }
+
+    /// Gets the maximum number of items the ring can hold.
+    pub fn capacity(&self) -> usize {
+        self.shared.slots.len()
+    }
+
+    /// Gets the number of items in the ring.
+    pub fn len(&self) -> usize {
+        self.shared.tail.load(Ordering::Relaxed) - self.shared.head.load(Ordering::Acquire)
+    }
+
+    /// Determines if the ring is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Determines if the consumer end was dropped.
+    pub fn is_closed(&self) -> bool {
+        self.shared.closed.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Drop for Producer<T> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        self.shared.wake();
+    }
+}
+
+impl<T> fmt::Debug for Producer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Producer")
+            .field("capacity", &self.capacity())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// The consumer end of a [`FrameRing`].
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Consumer<T> {
+    /// Pops the next item from the ring, if there is one.
+    pub fn pop(&mut self) -> Option<T> {
+        let sh = &*self.shared;
+        let head = sh.head.load(Ordering::Relaxed);
+        if head == sh.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let val = unsafe { (*sh.slot(head)).assume_init_read() };
+        sh.head.store(head + 1, Ordering::Release);
+        Some(val)
+    }
+
+    /// Pops the next item from the ring, blocking until one is available.
+    ///
+    /// Returns `None` once the ring is empty and the producer has been
+    /// dropped.
+    ///
+    /// The consumer can be moved between threads; each wait registers the
+    /// thread that's calling.
+    pub fn recv(&mut self) -> Option<T> {
+        loop {
+            if let Some(val) = self.pop() {
+                return Some(val);
+            }
+            if self.shared.closed.load(Ordering::Acquire) {
+                return self.pop();
+            }
+
+            let sh = &*self.shared;
+            *sh.waiter.lock().unwrap_or_else(|err| err.into_inner()) = Some(thread::current());
+            sh.parked.store(true, Ordering::Relaxed);
+            atomic::fence(Ordering::SeqCst);
+
+            // Check again, in case a push or drop missed the flag
+            let head = sh.head.load(Ordering::Relaxed);
+            if head == sh.tail.load(Ordering::Acquire) && !sh.closed.load(Ordering::Acquire) {
+                thread::park();
+            }
+            sh.parked.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Gets the maximum number of items the ring can hold.
+    pub fn capacity(&self) -> usize {
+        self.shared.slots.len()
+    }
+
+    /// Gets the number of items in the ring.
+    pub fn len(&self) -> usize {
+        self.shared.tail.load(Ordering::Acquire) - self.shared.head.load(Ordering::Relaxed)
+    }
+
+    /// Determines if the ring is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for Consumer<T> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+    }
+}
+
+impl<T> fmt::Debug for Consumer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Consumer")
+            .field("capacity", &self.capacity())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn push_pop() {
+        let (mut tx, mut rx) = FrameRing::<i32>::with_capacity(2);
+        assert!(rx.pop().is_none());
+
+        for i in 0..5 {
+            tx.push(i).unwrap();
+            tx.push(i + 100).unwrap();
+            assert_eq!(tx.push(i + 200), Err(i + 200));
+            assert_eq!(rx.len(), 2);
+            assert_eq!(rx.pop(), Some(i));
+            assert_eq!(rx.pop(), Some(i + 100));
+            assert!(rx.is_empty());
+        }
+        drop(rx);
+        assert!(tx.is_closed());
+    }
+
+    #[test]
+    fn drops_remaining() {
+        let item = Arc::new(0);
+        let (mut tx, rx) = FrameRing::<Arc<i32>>::with_capacity(4);
+        tx.push(Arc::clone(&item)).unwrap();
+        tx.push(Arc::clone(&item)).unwrap();
+        assert_eq!(Arc::strong_count(&item), 3);
+
+        drop(tx);
+        drop(rx);
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+
+    #[test]
+    fn cross_thread() {
+        const N: u64 = 10_000;
+        let (mut tx, mut rx) = FrameRing::<u64>::with_capacity(16);
+
+        let thr = thread::spawn(move || {
+            let mut expected = 0;
+            while let Some(i) = rx.recv() {
+                assert_eq!(i, expected);
+                expected += 1;
+            }
+            expected
+        });
+
+        for mut i in 0..N {
+            while let Err(v) = tx.push(i) {
+                i = v;
+                thread::yield_now();
+            }
+        }
+        drop(tx);
+        assert_eq!(thr.join().unwrap(), N);
+    }
+
+    #[test]
+    fn recv_moved_consumer() {
+        let (mut tx, mut rx) = FrameRing::<i32>::with_capacity(4);
+
+        tx.push(1).unwrap();
+        let mut rx = thread::spawn(move || {
+            assert_eq!(rx.recv(), Some(1));
+            rx
+        })
+        .join()
+        .unwrap();
+
+        // The second wait is from another thread, which must be woken
+        let thr = thread::spawn(move || rx.recv());
+        thread::sleep(Duration::from_millis(50));
+        tx.push(2).unwrap();
+        assert_eq!(thr.join().unwrap(), Some(2));
+
+        let (tx, mut rx) = FrameRing::<i32>::with_capacity(4);
+        let thr = thread::spawn(move || rx.recv());
+        thread::sleep(Duration::from_millis(50));
+        drop(tx);
+        assert_eq!(thr.join().unwrap(), None);
+    }
+}
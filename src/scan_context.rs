@@ -9,36 +9,69 @@
 //
 
 //! Scan context to get information about the available backends.
+//!
+//! A scan can cover one backend, like "usb", or several at once, like
+//! "local:usb:ip". Each context found is given as a [`ScanResult`] with
+//! the backend, URI, and description of the context.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//!
+//! let scan = iio::ScanContext::new_all().unwrap();
+//! for res in scan.iter().filter(|res| res.backend == "usb") {
+//!     println!("{}", res);
+//! }
+//! ```
 
 use crate::{cstring_opt, ffi, Error, Result};
 use nix::errno::Errno;
-use std::ffi::CString;
+use std::{cell::Cell, ffi::CString, fmt};
+
+/// The backends that are scanned by [`ScanContext::new_all()`].
+pub const ALL_BACKENDS: &str = "local:usb:ip";
 
 /// Scan context to get information about available contexts.
 #[derive(Debug)]
 pub struct ScanContext {
     /// Pointer to a libiio scan_block object
     pub(crate) ctx: *mut ffi::iio_scan_block,
+    /// Whether the scan has been run
+    scanned: Cell<bool>,
 }
 
 impl ScanContext {
     /// Creates a scan context for the specified backend.
-    /// The backend can be "local", "ip", or "usb".
+    ///
+    /// The backend can be "local", "ip", or "usb", or several of them
+    /// separated by colons, like "local:usb:ip".
     pub fn new(backend: &str) -> Result<Self> {
         let backend = CString::new(backend)?;
         let ctx = unsafe { ffi::iio_create_scan_block(backend.as_ptr(), 0) };
         if ctx.is_null() {
             return Err(Error::from(Errno::last()));
         }
-        Ok(Self { ctx })
+        Ok(Self {
+            ctx,
+            scanned: Cell::new(false),
+        })
     }
 
-    /// Creates a scan context for the USB backend.
+    /// Creates a scan context for several backends at once.
+    pub fn with_backends(backends: &[&str]) -> Result<Self> {
+        Self::new(&backends.join(":"))
+    }
+
+    /// Creates a scan context for all of the backends.
+    pub fn new_all() -> Result<Self> {
+        Self::new(ALL_BACKENDS)
+    }
+
+    /// Creates a scan context for the local backend.
     pub fn new_local() -> Result<Self> {
         Self::new("local")
     }
 
-    /// Creates a scan context for the USB backend.
+    /// Creates a scan context for the network backend.
     pub fn new_network() -> Result<Self> {
         Self::new("ip")
     }
@@ -48,23 +81,43 @@ impl ScanContext {
         Self::new("usb")
     }
 
-    /// Gets the number of contexts in this backend
-    pub fn len(&self) -> usize {
+    /// Scans the backends, returning the number of contexts found.
+    pub fn scan(&self) -> usize {
+        self.scanned.set(true);
         match unsafe { ffi::iio_scan_block_scan(self.ctx) } {
             n if n < 0 => 0,
             n => n as usize,
         }
     }
 
+    /// Gets the number of contexts in this backend
+    ///
+    /// This runs the scan again, to pick up any changes.
+    pub fn len(&self) -> usize {
+        self.scan()
+    }
+
     /// Determines if this backend has no contexts
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
     /// Gets an iterator to the contexts
+    ///
+    /// This runs the scan first, if it hasn't been run yet.
     pub fn iter(&self) -> ScanContextIterator {
+        if !self.scanned.get() {
+            self.scan();
+        }
         ScanContextIterator { ctx: self, idx: 0 }
     }
+
+    /// Gets an iterator to the contexts that match a pattern.
+    ///
+    /// See [`ScanResult::matches()`].
+    pub fn matching<'a>(&'a self, pat: &'a str) -> impl Iterator<Item = ScanResult> + 'a {
+        self.iter().filter(move |res| res.matches(pat))
+    }
 }
 
 impl Drop for ScanContext {
@@ -74,6 +127,44 @@ impl Drop for ScanContext {
     }
 }
 
+/// A context found by a scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanResult {
+    /// The backend that found the context, like "usb"
+    pub backend: String,
+    /// The URI to create the context, like "usb:1.2.5"
+    pub uri: String,
+    /// The description of the context
+    pub description: String,
+}
+
+impl ScanResult {
+    /// Creates a scan result from the URI and description of a context.
+    ///
+    /// The backend is taken from the URI.
+    pub fn new(uri: &str, description: &str) -> Self {
+        let backend = uri.split(':').next().unwrap_or_default();
+        Self {
+            backend: backend.to_string(),
+            uri: uri.to_string(),
+            description: description.to_string(),
+        }
+    }
+
+    /// Determines if the URI or description contain the pattern,
+    /// ignoring case.
+    pub fn matches(&self, pat: &str) -> bool {
+        let pat = pat.to_lowercase();
+        self.uri.to_lowercase().contains(&pat) || self.description.to_lowercase().contains(&pat)
+    }
+}
+
+impl fmt::Display for ScanResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}]", self.uri, self.description)
+    }
+}
+
 /// Iterator over the info in a ScanContext
 #[derive(Debug)]
 pub struct ScanContextIterator<'a> {
@@ -84,7 +175,7 @@ pub struct ScanContextIterator<'a> {
 }
 
 impl Iterator for ScanContextIterator<'_> {
-    type Item = (String, String);
+    type Item = ScanResult;
 
     fn next(&mut self) -> Option<Self::Item> {
         let info = unsafe { ffi::iio_scan_block_get_info(self.ctx.ctx, self.idx) };
@@ -95,7 +186,29 @@ impl Iterator for ScanContextIterator<'_> {
             let uri = cstring_opt(unsafe { ffi::iio_context_info_get_uri(info) })?;
             let descr = cstring_opt(unsafe { ffi::iio_context_info_get_description(info) })?;
             self.idx += 1;
-            Some((uri, descr))
+            Some(ScanResult::new(&uri, &descr))
         }
     }
 }
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_result() {
+        let res = ScanResult::new("usb:1.2.5", "PlutoSDR (ADALM-PLUTO), serial=1044");
+        assert_eq!(res.backend, "usb");
+        assert!(res.matches("pluto"));
+        assert!(res.matches("USB:1.2"));
+        assert!(!res.matches("ip:"));
+        assert_eq!(
+            res.to_string(),
+            "usb:1.2.5 [PlutoSDR (ADALM-PLUTO), serial=1044]"
+        );
+    }
+}
@@ -0,0 +1,635 @@
+// industrial-io/src/scope.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! One-shot, oscilloscope-style, captures.
+//!
+//! This hides the channel, trigger, and buffer plumbing needed to take a
+//! single measurement from a device. Describe the capture with a
+//! [`Config`] and call [`acquire()`] to get back a [`Frame`] with the
+//! requested number of samples for each channel.
+//!
+//! The sample clock can come from an IIO hardware trigger (like an
+//! `hrtimer` or a sysfs trigger), if one is named in the configuration.
+//! Triggering on the _signal_ (edge or level) uses a threshold event of
+//! the device, if it has one for the channel and the context is local.
+//! The capture then starts as soon as the event is reported, so it begins
+//! shortly after the crossing. Otherwise the trigger is detected in
+//! software, by scanning the samples as they arrive.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::scope::{self, Config, Slope, TriggerMode};
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ads1015").unwrap();
+//!
+//! let cfg = Config {
+//!     trigger: TriggerMode::Edge {
+//!         channel: "voltage0".into(),
+//!         level: 1000.0,
+//!         slope: Slope::Rising,
+//!     },
+//!     ..Config::new(&["voltage0", "voltage1"], 500)
+//! };
+//! let frame = scope::acquire(&dev, &cfg).unwrap();
+//! ```
+
+use crate::{device::ChannelStateGuard, ops::BufferOps, Device, Error, Frame, Result, Trigger};
+use nix::errno::Errno;
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "linux")]
+use crate::{
+    abi,
+    events::{EventAttr, EventConfig, EventDirection, EventStream, EventType},
+    Channel,
+};
+
+/// The direction of a signal edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slope {
+    /// A rising edge; the signal crosses the level going up.
+    Rising,
+    /// A falling edge; the signal crosses the level going down.
+    Falling,
+}
+
+/// How a capture is triggered.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum TriggerMode {
+    /// Free-running. The capture starts immediately.
+    #[default]
+    Free,
+    /// Start the capture when the signal on a channel crosses a level.
+    Edge {
+        /// The ID or name of the channel to watch
+        channel: String,
+        /// The level that the signal needs to cross
+        level: f64,
+        /// The direction of the crossing
+        slope: Slope,
+    },
+    /// Start the capture when the signal on a channel is above or below
+    /// a level.
+    Level {
+        /// The ID or name of the channel to watch
+        channel: String,
+        /// The level to compare against
+        level: f64,
+        /// Whether the signal needs to be above (or below) the level
+        above: bool,
+    },
+}
+
+/// The configuration for a one-shot capture.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// The ID's or names of the channels to capture
+    pub channels: Vec<String>,
+    /// The sample rate, in Hz, if it should be set for the capture.
+    pub sample_rate: Option<f64>,
+    /// The number of samples to capture for each channel
+    pub samples: usize,
+    /// How the capture should be triggered
+    pub trigger: TriggerMode,
+    /// The name or ID of a hardware trigger to clock the samples, if any.
+    pub trigger_device: Option<String>,
+    /// The maximum time to wait for the capture to complete.
+    pub timeout: Option<Duration>,
+    /// Attributes to capture with the frame.
+    /// See [`Frame::snapshot_attrs()`]
+    pub attrs: Vec<String>,
+}
+
+impl Config {
+    /// Creates a free-running configuration to capture the specified
+    /// number of samples from the channels.
+    pub fn new(channels: &[&str], samples: usize) -> Self {
+        Self {
+            channels: channels.iter().map(|s| s.to_string()).collect(),
+            samples,
+            ..Self::default()
+        }
+    }
+}
+
+/// Captures a frame of samples from the device.
+///
+/// This enables only the requested channels for the duration of the
+/// capture, then restores the channels to their previous state. The
+/// trigger and sampling frequency of the device are restored as well, if
+/// the configuration changes them.
+///
+/// A configuration with no channels or samples is reported as
+/// [`Error::InvalidBufferSize`], and a channel or trigger that isn't
+/// found as [`Error::InvalidIndex`]. If a timeout is configured and the capture
+/// doesn't complete in time, this fails with `ETIMEDOUT`.
+pub fn acquire(dev: &Device, cfg: &Config) -> Result<Frame> {
+    if cfg.samples == 0 || cfg.channels.is_empty() {
+        return Err(Error::InvalidBufferSize {
+            samples: cfg.samples,
+            bytes: 0,
+            max: dev.context().max_buffer_size(),
+        });
+    }
+
+    let chans = cfg
+        .channels
+        .iter()
+        .map(|name| dev.find_input_channel(name).ok_or(Error::InvalidIndex))
+        .collect::<Result<Vec<_>>>()?;
+
+    let trig_idx = match &cfg.trigger {
+        TriggerMode::Free => None,
+        TriggerMode::Edge { channel, .. } | TriggerMode::Level { channel, .. } => {
+            let idx = cfg.channels.iter().position(|name| name == channel);
+            Some(idx.ok_or(Error::InvalidIndex)?)
+        }
+    };
+
+    let _state = ChannelStateGuard::new(dev);
+    for chan in dev.channels() {
+        if chan.is_scan_element() {
+            chan.disable();
+        }
+    }
+    for chan in &chans {
+        chan.enable();
+    }
+
+    let mut settings = SettingsGuard::new(dev);
+    if let Some(name) = &cfg.trigger_device {
+        let trig = dev
+            .context()
+            .find_trigger(name)
+            .ok_or(Error::InvalidIndex)?;
+        settings.set_trigger(&trig)?;
+    }
+
+    if let Some(rate) = cfg.sample_rate {
+        settings.set_sampling_frequency(rate)?;
+    }
+
+    let deadline = cfg.timeout.map(|to| Instant::now() + to);
+
+    // Wait for the signal with a threshold event, if the device has one.
+    #[cfg(target_os = "linux")]
+    let trig_idx = match trig_idx {
+        Some(i) => match HwTrigger::arm(dev, &chans[i], &cfg.trigger) {
+            Some(mut hw) => {
+                hw.wait(deadline)?;
+                None
+            }
+            None => Some(i),
+        },
+        None => None,
+    };
+
+    let mut buf = dev.create_buffer(cfg.samples, false)?;
+    collect(&mut buf, &chans, cfg, trig_idx, deadline, |buf, frame| {
+        frame.snapshot_attrs(buf, &cfg.attrs)
+    })
+}
+
+/// Restores the trigger and sampling frequency of a device, if they were
+/// changed for a capture, when dropped.
+struct SettingsGuard {
+    /// The device
+    dev: Device,
+    /// The previous trigger of the device, if it was changed, and could
+    /// be read. This is `Some(None)` if the device had no trigger.
+    trigger: Option<Option<Trigger>>,
+    /// The previous sampling frequency, if it was changed.
+    rate: Option<f64>,
+}
+
+impl SettingsGuard {
+    /// Creates a guard for the device, with nothing yet to restore.
+    fn new(dev: &Device) -> Self {
+        Self {
+            dev: dev.clone(),
+            trigger: None,
+            rate: None,
+        }
+    }
+
+    /// Sets the trigger of the device, saving the previous one.
+    fn set_trigger(&mut self, trig: &Trigger) -> Result<()> {
+        if self.trigger.is_none() {
+            self.trigger = match self.dev.trigger() {
+                Ok(prev) => Some(Some(prev)),
+                Err(err) if err.errno() == Some(Errno::ENODEV) => Some(None),
+                Err(_) => None,
+            };
+        }
+        self.dev.set_trigger(trig)
+    }
+
+    /// Sets the sampling frequency of the device, saving the previous one.
+    ///
+    /// This should be called after the trigger is set, since it's the
+    /// frequency of the trigger, if the device has one, that is changed.
+    fn set_sampling_frequency(&mut self, hz: f64) -> Result<f64> {
+        if self.rate.is_none() {
+            self.rate = self.dev.sampling_frequency().ok();
+        }
+        self.dev.set_sampling_frequency(hz)
+    }
+}
+
+impl Drop for SettingsGuard {
+    /// Restores the settings in the reverse order that they were changed.
+    fn drop(&mut self) {
+        if let Some(rate) = self.rate {
+            let _ = self.dev.set_sampling_frequency(rate);
+        }
+        match &self.trigger {
+            Some(Some(trig)) => {
+                let _ = self.dev.set_trigger(trig);
+            }
+            Some(None) => {
+                let _ = self.dev.remove_trigger();
+            }
+            None => {}
+        }
+    }
+}
+
+/// Reads blocks of samples from the buffer until the trigger fires and
+/// the frame has the requested number of samples.
+///
+/// The `trig_idx` is the position of the trigger channel in `chans`, if
+/// the trigger is detected in software. The `on_trigger` callback gets
+/// the frame once, when the trigger fires.
+fn collect<B, F>(
+    buf: &mut B,
+    chans: &[B::Channel],
+    cfg: &Config,
+    trig_idx: Option<usize>,
+    deadline: Option<Instant>,
+    mut on_trigger: F,
+) -> Result<Frame>
+where
+    B: BufferOps,
+    F: FnMut(&B, &mut Frame) -> Result<()>,
+{
+    let mut prev = None;
+    let mut frame: Option<Frame> = None;
+
+    loop {
+        refill(buf, deadline)?;
+        let mut block = buf.read_frame(chans)?;
+        block.sample_rate = cfg.sample_rate;
+        let waiting = frame.is_none();
+
+        match (&mut frame, trig_idx) {
+            (Some(frame), _) => frame.extend(&block),
+            (None, None) => frame = Some(block),
+            (None, Some(i)) => {
+                let samples = &block.channels[i].samples;
+                match find_trigger(&cfg.trigger, samples, prev) {
+                    Some(n) => {
+                        block.drain_front(n);
+                        frame = Some(block);
+                    }
+                    None => prev = samples.last().copied(),
+                }
+            }
+        }
+
+        // Snapshot the attributes once, when the trigger fires, rather
+        // than for every block discarded while waiting for it.
+        if waiting {
+            if let Some(frame) = &mut frame {
+                on_trigger(buf, frame)?;
+            }
+        }
+
+        match frame.take() {
+            Some(mut f) if f.len() >= cfg.samples => {
+                f.truncate(cfg.samples);
+                return Ok(f);
+            }
+            f => frame = f,
+        }
+    }
+}
+
+/// Refills the buffer, waiting no longer than the deadline, if any.
+///
+/// If the backend can't poll the buffer, the refill blocks for as long as
+/// the context timeout, but still fails if the deadline has passed.
+fn refill<B: BufferOps>(buf: &mut B, deadline: Option<Instant>) -> Result<usize> {
+    let Some(deadline) = deadline
+    else {
+        return buf.refill();
+    };
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(Errno::ETIMEDOUT.into());
+    }
+    buf.refill_timeout(remaining)
+}
+
+/// Gets the direction of the threshold event that matches the signal
+/// trigger mode, and the level for it.
+#[cfg(target_os = "linux")]
+fn threshold(mode: &TriggerMode) -> Option<(f64, EventDirection)> {
+    match *mode {
+        TriggerMode::Free => None,
+        TriggerMode::Edge { level, slope, .. } => match slope {
+            Slope::Rising => Some((level, EventDirection::Rising)),
+            Slope::Falling => Some((level, EventDirection::Falling)),
+        },
+        TriggerMode::Level { level, above, .. } => {
+            let dir = if above {
+                EventDirection::Rising
+            }
+            else {
+                EventDirection::Falling
+            };
+            Some((level, dir))
+        }
+    }
+}
+
+/// A threshold event of a device, used as a hardware trigger.
+///
+/// The previous configuration of the event is restored when this is
+/// dropped.
+#[cfg(target_os = "linux")]
+struct HwTrigger {
+    /// The event configuration of the device
+    events: EventConfig,
+    /// The enable attribute of the event
+    attr: EventAttr,
+    /// The previous threshold, and whether the event was enabled
+    saved: (String, bool),
+    /// The stream of events from the device
+    stream: EventStream,
+    /// The number of the channel, to match the events reported
+    number: Option<i16>,
+    /// For a level trigger, the channel and level to check before waiting
+    level: Option<(Channel, f64, bool)>,
+}
+
+#[cfg(target_os = "linux")]
+impl HwTrigger {
+    /// Arms the threshold event of the channel for the trigger mode.
+    ///
+    /// This returns `None` if the device doesn't have the event, or it
+    /// can't be configured, in which case the trigger should be detected
+    /// in software.
+    fn arm(dev: &Device, chan: &Channel, mode: &TriggerMode) -> Option<Self> {
+        let (level, dir) = threshold(mode)?;
+        let events = EventConfig::new(dev).ok()?;
+        let attr = EventAttr::new(&chan.id()?, EventType::Threshold, Some(dir));
+        let value = attr.with_info(abi::event::VALUE);
+        if !events.has_attr(&attr) || !events.has_attr(&value) {
+            return None;
+        }
+
+        let saved = (events.read(&value).ok()?, events.is_enabled(&attr).ok()?);
+        // Open the stream first, since only one can be open for a device.
+        let stream = EventStream::new(dev).ok()?;
+        let hw = Self {
+            events,
+            attr,
+            saved,
+            stream,
+            number: chan.id().as_deref().and_then(channel_number),
+            level: match *mode {
+                TriggerMode::Level { above, .. } => Some((chan.clone(), level, above)),
+                _ => None,
+            },
+        };
+        // Thresholds are in raw units, like the samples
+        hw.events
+            .write(&value, &(level.round() as i64).to_string())
+            .ok()?;
+        hw.events.set_enabled(&hw.attr, true).ok()?;
+        Some(hw)
+    }
+
+    /// Waits until the deadline, if any, for the trigger to fire.
+    ///
+    /// A threshold event is only reported when the signal crosses the
+    /// level, so a level trigger fires right away if the signal is already
+    /// past it.
+    fn wait(&mut self, deadline: Option<Instant>) -> Result<()> {
+        if let Some((chan, level, above)) = &self.level {
+            if let Ok(x) = chan.attr_read_float(abi::chan::RAW) {
+                if (*above && x > *level) || (!*above && x < *level) {
+                    return Ok(());
+                }
+            }
+        }
+
+        loop {
+            let ev = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    self.stream
+                        .read_event_timeout(remaining)?
+                        .ok_or(Errno::ETIMEDOUT)?
+                }
+                None => self.stream.read_event()?,
+            };
+            if ev.ev_type == EventType::Threshold
+                && Some(ev.direction) == self.attr.direction
+                && self.number.map_or(true, |n| n == ev.channel)
+            {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Gets the number of a channel from its ID, like 3 for "voltage3", which
+/// is how the kernel identifies the channel of an event.
+///
+/// This is not the same as the index of the channel in the device. For a
+/// differential channel, like "voltage0-voltage1", it's the first number.
+#[cfg(target_os = "linux")]
+fn channel_number(id: &str) -> Option<i16> {
+    let start = id.find(|c: char| c.is_ascii_digit())?;
+    let digits = &id[start..];
+    let end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    digits[..end].parse().ok()
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for HwTrigger {
+    /// Restores the previous configuration of the event.
+    fn drop(&mut self) {
+        let (value, enabled) = &self.saved;
+        let _ = self
+            .events
+            .write(&self.attr.with_info(abi::event::VALUE), value);
+        let _ = self.events.set_enabled(&self.attr, *enabled);
+    }
+}
+
+/// Finds the index of the sample that satisfies the trigger condition.
+///
+/// `prev` is the last sample from the previous block, if any, so that
+/// edges that span blocks can be detected.
+fn find_trigger(mode: &TriggerMode, samples: &[f64], prev: Option<f64>) -> Option<usize> {
+    match *mode {
+        TriggerMode::Free => Some(0),
+        TriggerMode::Level { level, above, .. } => samples.iter().position(|&x| {
+            if above {
+                x > level
+            }
+            else {
+                x < level
+            }
+        }),
+        TriggerMode::Edge { level, slope, .. } => {
+            let mut last = prev;
+            for (i, &x) in samples.iter().enumerate() {
+                if let Some(p) = last {
+                    let crossed = match slope {
+                        Slope::Rising => p < level && x >= level,
+                        Slope::Falling => p > level && x <= level,
+                    };
+                    if crossed {
+                        return Some(i);
+                    }
+                }
+                last = Some(x);
+            }
+            None
+        }
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{MockChannel, MockDevice},
+        ops::DeviceOps,
+        ChannelType, Direction, FrameChannel,
+    };
+
+    fn mock(blocks: &[&[f64]]) -> MockDevice {
+        let dev = MockDevice::new("iio:device0", "adc")
+            .channel(MockChannel::input("voltage0", ChannelType::Voltage));
+        for samples in blocks {
+            let mut frame = Frame::new();
+            frame
+                .channels
+                .push(FrameChannel::new("voltage0", samples.to_vec()));
+            dev.push_frame(frame);
+        }
+        dev
+    }
+
+    fn edge(level: f64, slope: Slope) -> TriggerMode {
+        TriggerMode::Edge {
+            channel: "voltage0".into(),
+            level,
+            slope,
+        }
+    }
+
+    #[test]
+    fn edge_trigger() {
+        let data = [0.0, 1.0, 5.0, 6.0, 2.0, 1.0];
+
+        assert_eq!(
+            find_trigger(&edge(3.0, Slope::Rising), &data, None),
+            Some(2)
+        );
+        assert_eq!(
+            find_trigger(&edge(3.0, Slope::Falling), &data, None),
+            Some(4)
+        );
+        assert_eq!(find_trigger(&edge(10.0, Slope::Rising), &data, None), None);
+
+        // An edge that spans the previous block
+        assert_eq!(
+            find_trigger(&edge(3.0, Slope::Falling), &[2.0, 1.0], Some(4.0)),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn level_trigger() {
+        let data = [0.0, 1.0, 5.0];
+        let mode = TriggerMode::Level {
+            channel: "voltage0".into(),
+            level: 0.5,
+            above: true,
+        };
+        assert_eq!(find_trigger(&mode, &data, None), Some(1));
+        assert_eq!(find_trigger(&TriggerMode::Free, &data, None), Some(0));
+    }
+
+    #[test]
+    fn collect_triggered() {
+        let dev = mock(&[&[0.0, 1.0, 5.0], &[6.0, 2.0, 1.0]]);
+        let chans = vec![dev.find_channel("voltage0", Direction::Input).unwrap()];
+        let cfg = Config {
+            trigger: edge(3.0, Slope::Rising),
+            ..Config::new(&["voltage0"], 3)
+        };
+
+        let mut buf = dev.create_buffer(3, false).unwrap();
+        let mut fired = 0;
+        let frame = collect(&mut buf, &chans, &cfg, Some(0), None, |_, frame| {
+            fired += 1;
+            assert_eq!(frame.samples("voltage0").unwrap(), [5.0]);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(frame.samples("voltage0").unwrap(), [5.0, 6.0, 2.0]);
+        assert_eq!(fired, 1);
+
+        // Runs out of samples without a trigger
+        let dev = mock(&[&[0.0, 1.0, 2.0]]);
+        let mut buf = dev.create_buffer(3, false).unwrap();
+        let res = collect(&mut buf, &chans, &cfg, Some(0), None, |_, _| Ok(()));
+        assert!(matches!(res, Err(Error::Nix(Errno::ETIMEDOUT))));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn threshold_event() {
+        assert_eq!(
+            threshold(&edge(3.0, Slope::Falling)),
+            Some((3.0, EventDirection::Falling))
+        );
+        let mode = TriggerMode::Level {
+            channel: "voltage0".into(),
+            level: 0.5,
+            above: true,
+        };
+        assert_eq!(threshold(&mode), Some((0.5, EventDirection::Rising)));
+        assert_eq!(threshold(&TriggerMode::Free), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn event_channel_number() {
+        assert_eq!(channel_number("voltage3"), Some(3));
+        assert_eq!(channel_number("voltage12"), Some(12));
+        assert_eq!(channel_number("voltage0-voltage1"), Some(0));
+        assert_eq!(channel_number("temp"), None);
+        assert_eq!(channel_number("accel_x"), None);
+    }
+}
@@ -0,0 +1,230 @@
+// industrial-io/src/script.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Scripted virtual channels.
+//!
+//! This requires the **script** feature, which uses the
+//! [Rhai](https://rhai.rs) scripting language.
+//!
+//! A [`ScriptProcessor`] derives new channels from the ones captured in a
+//! frame, using an expression for each, like `power = voltage0 * current0`.
+//! The expressions are compiled once, and evaluated for every sample in
+//! each block that passes through the processor, with a variable for
+//! each channel in the frame, named by its ID. The results are appended
+//! to the frames as new channels.
+//!
+//! Since the expressions are just strings, they can come from a
+//! configuration file, so a gateway can be adapted in the field without
+//! recompiling.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::{script::ScriptProcessor, sink::Pipeline};
+//!
+//! let script = ScriptProcessor::new()
+//!     .with_channel("power", "voltage0 * current0")
+//!     .unwrap()
+//!     .with_channel("energy", "power / 3600.0")
+//!     .unwrap();
+//!
+//! let mut pipeline = Pipeline::new();
+//! pipeline.add_processor(Box::new(script));
+//! ```
+
+use crate::{process::FrameProcessor, Error, Frame, FrameChannel, FrameSet, Result};
+use rhai::{Dynamic, Engine, Scope, AST};
+use std::fmt;
+
+/// A channel derived from an expression.
+struct Derived {
+    /// The ID of the new channel
+    id: String,
+    /// The source of the expression
+    expr: String,
+    /// The compiled expression
+    ast: AST,
+}
+
+/// A processor that derives channels from scripted expressions.
+///
+/// The channels are derived in the order they were added, so an
+/// expression can use the channels derived before it. If a frame already
+/// has a channel with the ID of a derived one, its samples are replaced.
+pub struct ScriptProcessor {
+    /// The script engine
+    engine: Engine,
+    /// The derived channels
+    channels: Vec<Derived>,
+}
+
+impl ScriptProcessor {
+    /// Creates a processor with no derived channels.
+    pub fn new() -> Self {
+        Self {
+            engine: Engine::new(),
+            channels: Vec::new(),
+        }
+    }
+
+    /// Adds a channel that is derived from an expression.
+    ///
+    /// `id` The ID of the new channel
+    /// `expr` The expression for each sample of the new channel
+    ///
+    /// This fails if the expression can't be compiled.
+    pub fn add_channel(&mut self, id: &str, expr: &str) -> Result<()> {
+        let ast = self
+            .engine
+            .compile_expression(expr)
+            .map_err(|err| Error::General(format!("Invalid expression for '{}': {}", id, err)))?;
+        self.channels.push(Derived {
+            id: id.to_string(),
+            expr: expr.to_string(),
+            ast,
+        });
+        Ok(())
+    }
+
+    /// Adds a channel that is derived from an expression, in builder
+    /// style.
+    pub fn with_channel(mut self, id: &str, expr: &str) -> Result<Self> {
+        self.add_channel(id, expr)?;
+        Ok(self)
+    }
+
+    /// Gets the number of derived channels.
+    pub fn num_channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Evaluates the derived channels for a frame, adding them to it.
+    pub fn apply(&self, frame: &mut Frame) -> Result<()> {
+        let n = frame.len();
+        for derived in &self.channels {
+            let mut samples = Vec::with_capacity(n);
+            let mut scope = Scope::new();
+            for i in 0..n {
+                scope.clear();
+                for ch in &frame.channels {
+                    scope.push(ch.id.as_str(), ch.samples[i]);
+                }
+                let val: Dynamic = self
+                    .engine
+                    .eval_ast_with_scope(&mut scope, &derived.ast)
+                    .map_err(|err| {
+                        Error::General(format!("Error evaluating '{}': {}", derived.id, err))
+                    })?;
+                samples.push(to_f64(&derived.id, val)?);
+            }
+
+            match frame.channels.iter_mut().find(|ch| ch.id == derived.id) {
+                Some(ch) => ch.samples = samples,
+                None => frame.channels.push(FrameChannel::new(&derived.id, samples)),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ScriptProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameProcessor for ScriptProcessor {
+    fn process(&mut self, f: &mut FrameSet) -> Result<()> {
+        for frame in f.frames.iter_mut() {
+            self.apply(frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ScriptProcessor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.channels.iter().map(|d| (&d.id, &d.expr)))
+            .finish()
+    }
+}
+
+/// Converts the result of an expression to a sample value.
+fn to_f64(id: &str, val: Dynamic) -> Result<f64> {
+    if let Ok(x) = val.as_float() {
+        Ok(x)
+    }
+    else if let Ok(n) = val.as_int() {
+        Ok(n as f64)
+    }
+    else if let Ok(b) = val.as_bool() {
+        Ok(if b { 1.0 } else { 0.0 })
+    }
+    else {
+        Err(Error::General(format!(
+            "Expression for '{}' gave a {}, not a number",
+            id,
+            val.type_name()
+        )))
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame() -> Frame {
+        Frame {
+            channels: vec![
+                FrameChannel::new("voltage0", vec![1.0, 2.0, 3.0]),
+                FrameChannel::new("current0", vec![0.5, 0.5, 2.0]),
+            ],
+            ..Frame::default()
+        }
+    }
+
+    #[test]
+    fn derive_channels() {
+        let mut script = ScriptProcessor::new()
+            .with_channel("power", "voltage0 * current0")
+            .unwrap()
+            .with_channel("high", "power > 1.0")
+            .unwrap();
+        assert_eq!(script.num_channels(), 2);
+
+        let mut set: FrameSet = std::iter::once(frame()).collect();
+        script.process(&mut set).unwrap();
+        let f = &set.frames[0];
+        assert_eq!(f.num_channels(), 4);
+        assert_eq!(f.samples("power").unwrap(), &[0.5, 1.0, 6.0]);
+        assert_eq!(f.samples("high").unwrap(), &[0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn script_errors() {
+        assert!(ScriptProcessor::new()
+            .add_channel("x", "voltage0 *")
+            .is_err());
+
+        let script = ScriptProcessor::new()
+            .with_channel("x", "voltage1 * 2.0")
+            .unwrap();
+        assert!(script.apply(&mut frame()).is_err());
+
+        let script = ScriptProcessor::new()
+            .with_channel("x", "\"volts\"")
+            .unwrap();
+        assert!(script.apply(&mut frame()).is_err());
+    }
+}
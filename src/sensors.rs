@@ -0,0 +1,828 @@
+// industrial-io/src/sensors.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Convenience facades for common kinds of sensors.
+//!
+//! These find the relevant channels on a device, and apply the unit
+//! conventions of the kernel ABI, so that applications don't need to know
+//! which combination of channels a particular driver exposes. For any
+//! other channel, a [`SensorReader`] gives its values in physical units.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::sensors::{LightSensor, ProximitySensor};
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("apds9960").unwrap();
+//!
+//! let als = LightSensor::new(&dev).unwrap();
+//! println!("Light: {:.1} lux", als.read_lux().unwrap());
+//!
+//! let prox = ProximitySensor::new(&dev).unwrap();
+//! println!("Proximity: {}", prox.read().unwrap());
+//! ```
+
+use crate::{
+    abi,
+    events::{EventAttr, EventConfig, EventDirection, EventType},
+    ops::ChannelOps,
+    power::next_start,
+    units::{self, read_value},
+    Buffer, Channel, ChannelType, Device, Error, Operation, Result,
+};
+use std::{
+    fmt,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Estimates the illuminance from a clear (or visible + IR) intensity,
+/// less the IR intensity, if known.
+fn estimate_lux(clear: f64, ir: Option<f64>, lux_per_count: f64) -> f64 {
+    (clear - ir.unwrap_or(0.0)).max(0.0) * lux_per_count
+}
+
+/// Finds the first input channel of the type with an ID that satisfies
+/// the predicate.
+fn find_input<F>(dev: &Device, typ: ChannelType, pred: F) -> Option<Channel>
+where
+    F: Fn(&str) -> bool,
+{
+    dev.channels().find(|chan| {
+        chan.is_input() && chan.channel_type() == typ && chan.id().is_some_and(|id| pred(&id))
+    })
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A reader for a single channel that gives its values in physical units.
+///
+/// This reads the `offset`, `scale`, `calibbias`, and `calibscale`
+/// attributes of the channel once, when it's created, and applies them to
+/// each value, whether polled from the `raw` attribute with
+/// [`read()`](SensorReader::read), or demultiplexed from a buffer with
+/// [`read_buffer()`](SensorReader::read_buffer). Use
+/// [`refresh()`](SensorReader::refresh) if they might have changed, such
+/// as after changing the range of the sensor.
+///
+/// The values are in the ABI units of the channel type, like millivolts
+/// or milli-degrees Celsius, as `(raw + offset) * scale`. If the channel
+/// only has a processed `input` attribute, that is read instead.
+///
+/// Per the ABI, the calibration bias and scale are applied by the
+/// hardware, so they're already included in the raw values, and are only
+/// cached here for reference. For a driver that leaves them to the
+/// application, turn on
+/// [`apply_calibration()`](SensorReader::apply_calibration).
+///
+/// This works with any channel, including mock and replayed ones, but
+/// buffered reads need a real [`Channel`].
+#[derive(Debug, Clone)]
+pub struct SensorReader<C: ChannelOps = Channel> {
+    /// The channel
+    chan: C,
+    /// Whether the channel has a `raw` attribute
+    has_raw: bool,
+    /// The offset added to a raw value
+    offset: f64,
+    /// The scale applied to a raw value, after the offset
+    scale: f64,
+    /// The calibration bias, if the channel has one
+    calibbias: Option<f64>,
+    /// The calibration scale, if the channel has one
+    calibscale: Option<f64>,
+    /// Whether to apply the calibration to raw values
+    apply_calib: bool,
+}
+
+impl<C: ChannelOps> SensorReader<C> {
+    /// Creates a reader for the channel, reading its conversion
+    /// attributes.
+    pub fn new(chan: C) -> Result<Self> {
+        let mut rdr = Self {
+            has_raw: chan.has_attr(abi::chan::RAW),
+            chan,
+            offset: 0.0,
+            scale: 1.0,
+            calibbias: None,
+            calibscale: None,
+            apply_calib: false,
+        };
+        rdr.refresh()?;
+        Ok(rdr)
+    }
+
+    /// Reads the conversion attributes of the channel again.
+    ///
+    /// Attributes that the channel doesn't have get their defaults, of
+    /// zero for the offset, and one for the scale.
+    pub fn refresh(&mut self) -> Result<()> {
+        let read_opt = |attr: &str| -> Result<Option<f64>> {
+            if self.chan.has_attr(attr) {
+                self.chan.attr_read_float(attr).map(Some)
+            }
+            else {
+                Ok(None)
+            }
+        };
+        self.offset = read_opt(abi::chan::OFFSET)?.unwrap_or(0.0);
+        self.scale = read_opt(abi::chan::SCALE)?.unwrap_or(1.0);
+        self.calibbias = read_opt(abi::chan::CALIBBIAS)?;
+        self.calibscale = read_opt(abi::chan::CALIBSCALE)?;
+        Ok(())
+    }
+
+    /// Sets whether to apply the calibration bias and scale to the raw
+    /// values, as `raw * calibscale + calibbias`, before the offset and
+    /// scale.
+    ///
+    /// This should only be turned on for drivers that don't apply them in
+    /// the hardware. The default is off.
+    pub fn apply_calibration(mut self, on: bool) -> Self {
+        self.apply_calib = on;
+        self
+    }
+
+    /// Gets the channel.
+    pub fn channel(&self) -> &C {
+        &self.chan
+    }
+
+    /// Gets the cached offset.
+    pub fn offset(&self) -> f64 {
+        self.offset
+    }
+
+    /// Gets the cached scale.
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Gets the cached calibration bias, if the channel has one.
+    pub fn calibbias(&self) -> Option<f64> {
+        self.calibbias
+    }
+
+    /// Gets the cached calibration scale, if the channel has one.
+    pub fn calibscale(&self) -> Option<f64> {
+        self.calibscale
+    }
+
+    /// Converts a raw value to physical units.
+    pub fn convert(&self, raw: f64) -> f64 {
+        let raw = if self.apply_calib {
+            raw * self.calibscale.unwrap_or(1.0) + self.calibbias.unwrap_or(0.0)
+        }
+        else {
+            raw
+        };
+        units::scaled(raw, self.offset, self.scale)
+    }
+
+    /// Reads the raw value of the channel, from its `raw` attribute.
+    pub fn read_raw(&self) -> Result<f64> {
+        self.chan.attr_read_float(abi::chan::RAW)
+    }
+
+    /// Reads the current value of the channel, in physical units.
+    ///
+    /// This converts the `raw` attribute, if the channel has one,
+    /// otherwise it reads the processed `input` attribute.
+    pub fn read(&self) -> Result<f64> {
+        if self.has_raw {
+            Ok(self.convert(self.read_raw()?))
+        }
+        else {
+            self.chan.attr_read_float(abi::chan::INPUT)
+        }
+    }
+}
+
+impl SensorReader<Channel> {
+    /// Demultiplexes the samples of the channel in the buffer, and
+    /// converts them to physical units.
+    pub fn read_buffer(&self, buf: &Buffer) -> Result<Vec<f64>> {
+        let mut v = self.chan.read_f64(buf)?;
+        for x in v.iter_mut() {
+            *x = self.convert(*x);
+        }
+        Ok(v)
+    }
+
+    /// Gets the latest sample of the channel in the buffer, in physical
+    /// units.
+    ///
+    /// This fails if the buffer is empty.
+    pub fn read_latest(&self, buf: &Buffer) -> Result<f64> {
+        self.read_buffer(buf)?
+            .last()
+            .copied()
+            .ok_or_else(|| Error::General("The buffer is empty".into()))
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// An ambient light sensor (ALS).
+///
+/// Drivers report light in one of two ways:
+///
+/// * An `illuminance` channel, which is already calibrated to lux, either
+///   as a processed `input` value, or as a `raw` value with a `scale`.
+/// * Uncalibrated `intensity` channels, typically a "clear" (or "both",
+///   for visible + IR) photodiode, and often an "ir" one.
+///
+/// If there's an illuminance channel, it is preferred. Otherwise the lux
+/// are estimated from the intensities, by subtracting the IR from the
+/// clear intensity and multiplying by a coefficient. That coefficient
+/// depends on the part, its package, and the gain and integration time,
+/// so it should be set from the datasheet with
+/// [`lux_per_count()`](LightSensor::lux_per_count) for any real accuracy.
+#[derive(Debug, Clone)]
+pub struct LightSensor {
+    /// The calibrated illuminance channel, if any
+    illuminance: Option<Channel>,
+    /// The clear or visible + IR intensity channel, if any
+    clear: Option<Channel>,
+    /// The IR intensity channel, if any
+    ir: Option<Channel>,
+    /// The coefficient to convert intensity counts to lux
+    lux_per_count: f64,
+}
+
+impl LightSensor {
+    /// Finds the light channels on the device.
+    ///
+    /// This fails if the device has neither an illuminance channel, nor a
+    /// clear or visible + IR intensity channel.
+    pub fn new(dev: &Device) -> Result<Self> {
+        let illuminance = find_input(dev, ChannelType::Ligtht, |_| true);
+        let clear = find_input(dev, ChannelType::Intensity, |id| {
+            id.ends_with("_clear") || id.ends_with("_both")
+        });
+        let ir = find_input(dev, ChannelType::Intensity, |id| id.ends_with("_ir"));
+
+        if illuminance.is_none() && clear.is_none() {
+            return Err(Error::General(format!(
+                "'{}' is not a light sensor",
+                dev.name().or_else(|| dev.id()).unwrap_or_default()
+            )));
+        }
+
+        Ok(Self {
+            illuminance,
+            clear,
+            ir,
+            lux_per_count: 1.0,
+        })
+    }
+
+    /// Sets the coefficient to convert intensity counts to lux, when the
+    /// device doesn't have a calibrated illuminance channel.
+    ///
+    /// The default is 1.0.
+    pub fn lux_per_count(mut self, coeff: f64) -> Self {
+        self.lux_per_count = coeff;
+        self
+    }
+
+    /// Determines if the lux are read from a calibrated illuminance
+    /// channel, rather than estimated from the intensities.
+    pub fn is_calibrated(&self) -> bool {
+        self.illuminance.is_some()
+    }
+
+    /// Reads the illuminance, in lux.
+    pub fn read_lux(&self) -> Result<f64> {
+        if let Some(chan) = &self.illuminance {
+            return read_value(chan);
+        }
+        // Checked in new()
+        let clear = read_value(self.clear.as_ref().unwrap())?;
+        let ir = self.read_ir()?;
+        Ok(estimate_lux(clear, ir, self.lux_per_count))
+    }
+
+    /// Reads the IR intensity, if the device has an IR channel.
+    pub fn read_ir(&self) -> Result<Option<f64>> {
+        self.ir.as_ref().map(read_value).transpose()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A proximity sensor.
+///
+/// Per the kernel ABI, proximity values are unitless, with higher values
+/// meaning that an object is closer, unless the driver provides a scale
+/// to convert them into a distance.
+#[derive(Debug, Clone)]
+pub struct ProximitySensor {
+    /// The proximity channel
+    chan: Channel,
+}
+
+impl ProximitySensor {
+    /// Finds the proximity channel on the device.
+    pub fn new(dev: &Device) -> Result<Self> {
+        find_input(dev, ChannelType::Proximity, |_| true)
+            .map(|chan| Self { chan })
+            .ok_or_else(|| {
+                Error::General(format!(
+                    "'{}' is not a proximity sensor",
+                    dev.name().or_else(|| dev.id()).unwrap_or_default()
+                ))
+            })
+    }
+
+    /// Gets the proximity channel.
+    pub fn channel(&self) -> &Channel {
+        &self.chan
+    }
+
+    /// Reads the proximity.
+    ///
+    /// This has the offset and scale applied, if the driver provides
+    /// them.
+    pub fn read(&self) -> Result<f64> {
+        read_value(&self.chan)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A power monitor, such as an INA2xx, that measures the voltage, current,
+/// and power of a supply.
+///
+/// The kernel ABI reports these in millivolts, milliamps, and milliwatts.
+/// This converts them to volts, amps, and watts.
+///
+/// Power monitors often have a voltage channel for the bus, and another
+/// for the drop across the shunt resistor. By default, the voltage is read
+/// from an input channel named "bus", if there is one, otherwise from the
+/// last voltage input channel, which is the bus voltage on the INA2xx
+/// parts. Use [`voltage_channel()`](PowerMonitor::voltage_channel) to pick
+/// another.
+#[derive(Debug, Clone)]
+pub struct PowerMonitor {
+    /// The (bus) voltage channel, if any
+    voltage: Option<Channel>,
+    /// The current channel, if any
+    current: Option<Channel>,
+    /// The power channel, if any
+    power: Option<Channel>,
+}
+
+impl PowerMonitor {
+    /// Finds the voltage, current, and power channels on the device.
+    ///
+    /// This fails if the device can't report the power, either directly
+    /// or from its voltage and current.
+    pub fn new(dev: &Device) -> Result<Self> {
+        let voltages: Vec<_> = dev
+            .channels()
+            .filter(|chan| chan.is_input() && chan.channel_type() == ChannelType::Voltage)
+            .collect();
+        let voltage = voltages
+            .iter()
+            .find(|chan| chan.name().as_deref() == Some("bus"))
+            .or(voltages.last())
+            .cloned();
+        let current = find_input(dev, ChannelType::Current, |_| true);
+        let power = find_input(dev, ChannelType::Power, |_| true);
+
+        if power.is_none() && (voltage.is_none() || current.is_none()) {
+            return Err(Error::General(format!(
+                "'{}' is not a power monitor",
+                dev.name().or_else(|| dev.id()).unwrap_or_default()
+            )));
+        }
+
+        Ok(Self {
+            voltage,
+            current,
+            power,
+        })
+    }
+
+    /// Selects the input channel, by name or ID, used to read the voltage.
+    pub fn voltage_channel(mut self, dev: &Device, name: &str) -> Result<Self> {
+        let chan = dev
+            .find_input_channel(name)
+            .ok_or_else(|| Error::General(format!("No input channel '{}'", name)))?;
+        self.voltage = Some(chan);
+        Ok(self)
+    }
+
+    /// Reads the voltage, in volts.
+    pub fn voltage_v(&self) -> Result<f64> {
+        let chan = self
+            .voltage
+            .as_ref()
+            .ok_or_else(|| Error::General("No voltage channel".into()))?;
+        Ok(read_value(chan)? / 1000.0)
+    }
+
+    /// Reads the current, in amps.
+    pub fn current_a(&self) -> Result<f64> {
+        let chan = self
+            .current
+            .as_ref()
+            .ok_or_else(|| Error::General("No current channel".into()))?;
+        Ok(read_value(chan)? / 1000.0)
+    }
+
+    /// Reads the power, in watts.
+    ///
+    /// If the device doesn't have a power channel, this is computed from
+    /// the voltage and current.
+    pub fn power_w(&self) -> Result<f64> {
+        match &self.power {
+            Some(chan) => Ok(read_value(chan)? / 1000.0),
+            None => Ok(self.voltage_v()? * self.current_a()?),
+        }
+    }
+
+    /// Creates an integrator that polls the power at a fixed interval to
+    /// accumulate the energy used.
+    pub fn energy_integrator(&self, interval: Duration) -> EnergyIntegrator {
+        EnergyIntegrator {
+            monitor: self.clone(),
+            interval,
+            next: None,
+            last: None,
+            energy_wh: 0.0,
+        }
+    }
+}
+
+/// Accumulates the energy measured by a [`PowerMonitor`], in watt-hours.
+///
+/// Each sample integrates the power since the previous one, using the
+/// trapezoidal rule. As an iterator, this polls at a fixed interval,
+/// scheduled from the first sample so that it doesn't drift, and yields
+/// the running total after each sample.
+#[derive(Debug, Clone)]
+pub struct EnergyIntegrator {
+    /// The power monitor
+    monitor: PowerMonitor,
+    /// The time between polls, when used as an iterator
+    interval: Duration,
+    /// The time of the next poll, when used as an iterator
+    next: Option<Instant>,
+    /// The time and power of the previous sample
+    last: Option<(Instant, f64)>,
+    /// The accumulated energy
+    energy_wh: f64,
+}
+
+impl EnergyIntegrator {
+    /// Reads the power and adds the energy used since the previous
+    /// sample, returning the total, in watt-hours.
+    pub fn sample(&mut self) -> Result<f64> {
+        let power = self.monitor.power_w()?;
+        let now = Instant::now();
+        if let Some((t, p)) = self.last {
+            self.energy_wh += energy_wh(p, power, now - t);
+        }
+        self.last = Some((now, power));
+        Ok(self.energy_wh)
+    }
+
+    /// Gets the energy accumulated so far, in watt-hours.
+    pub fn energy_wh(&self) -> f64 {
+        self.energy_wh
+    }
+
+    /// Gets the most recent power reading, in watts, if any.
+    pub fn last_power_w(&self) -> Option<f64> {
+        self.last.map(|(_, p)| p)
+    }
+
+    /// Clears the accumulated energy, and restarts the integration from
+    /// the next sample.
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.energy_wh = 0.0;
+    }
+}
+
+impl Iterator for EnergyIntegrator {
+    type Item = Result<f64>;
+
+    /// Blocks until the next poll, then samples the power and returns
+    /// the total energy, in watt-hours.
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = *self.next.get_or_insert_with(Instant::now);
+        if let Some(dur) = start.checked_duration_since(Instant::now()) {
+            thread::sleep(dur);
+        }
+        match next_start(start, self.interval) {
+            Ok(next) => self.next = Some(next),
+            Err(err) => return Some(Err(err)),
+        }
+        Some(self.sample())
+    }
+}
+
+/// Gets the energy, in watt-hours, for a power going from `p0` to `p1`
+/// watts over the time span.
+fn energy_wh(p0: f64, p1: f64, dt: Duration) -> f64 {
+    (p0 + p1) / 2.0 * dt.as_secs_f64() / 3600.0
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// An activity reported by an activity monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Activity {
+    /// Not moving
+    Still,
+    /// Walking
+    Walking,
+    /// Jogging
+    Jogging,
+    /// Running
+    Running,
+    /// Riding a bike
+    Biking,
+    /// Driving
+    Driving,
+}
+
+impl Activity {
+    /// All the activities.
+    const ALL: [Activity; 6] = [
+        Activity::Still,
+        Activity::Walking,
+        Activity::Jogging,
+        Activity::Running,
+        Activity::Biking,
+        Activity::Driving,
+    ];
+
+    /// Gets the name of the activity used in the ABI.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Activity::Still => "still",
+            Activity::Walking => "walking",
+            Activity::Jogging => "jogging",
+            Activity::Running => "running",
+            Activity::Biking => "biking",
+            Activity::Driving => "driving",
+        }
+    }
+
+    /// Gets the ID of the channel for the activity.
+    fn channel_id(&self) -> String {
+        format!("activity_{}", self.as_str())
+    }
+}
+
+impl FromStr for Activity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|act| act.as_str() == s)
+            .ok_or(Error::StringConversionError)
+    }
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// An activity monitor or pedometer.
+///
+/// These report the confidence, as a percentage, that the user is doing
+/// each of a number of [`Activity`] types, with an `activity_<type>`
+/// channel for each, and may count steps with a `steps` channel.
+///
+/// They can generate an event when the confidence in an activity rises
+/// above, or falls below, a threshold, and when the step count changes by
+/// some amount. Configuring the events requires the local backend; see
+/// the [`events`](crate::events) module.
+#[derive(Debug, Clone)]
+pub struct ActivityMonitor {
+    /// The activity channels
+    activities: Vec<(Activity, Channel)>,
+    /// The step counter, if any
+    steps: Option<Channel>,
+    /// The event configuration, if available
+    events: Option<EventConfig>,
+    /// The name of the backend, for errors
+    backend: String,
+}
+
+impl ActivityMonitor {
+    /// Finds the activity and step channels on the device.
+    pub fn new(dev: &Device) -> Result<Self> {
+        let activities: Vec<_> = dev
+            .channels()
+            .filter(|chan| chan.is_input() && chan.channel_type() == ChannelType::Activity)
+            .filter_map(|chan| {
+                let act = chan.id()?.strip_prefix("activity_")?.parse().ok()?;
+                Some((act, chan))
+            })
+            .collect();
+        let steps = find_input(dev, ChannelType::Steps, |_| true);
+
+        if activities.is_empty() && steps.is_none() {
+            return Err(Error::General(format!(
+                "'{}' is not an activity monitor",
+                dev.name().or_else(|| dev.id()).unwrap_or_default()
+            )));
+        }
+
+        Ok(Self {
+            activities,
+            steps,
+            events: EventConfig::new(dev).ok(),
+            backend: dev.context().name(),
+        })
+    }
+
+    /// Gets the activities that the device can detect.
+    pub fn activities(&self) -> Vec<Activity> {
+        self.activities.iter().map(|(act, _)| *act).collect()
+    }
+
+    /// Reads the confidence that the user is doing the activity, as a
+    /// percentage.
+    pub fn confidence(&self, act: Activity) -> Result<f64> {
+        read_value(self.channel(act)?)
+    }
+
+    /// Reads the activity with the highest confidence, if any.
+    pub fn current_activity(&self) -> Result<Option<(Activity, f64)>> {
+        let mut best: Option<(Activity, f64)> = None;
+        for (act, chan) in &self.activities {
+            let conf = read_value(chan)?;
+            if best.map_or(true, |(_, c)| conf > c) {
+                best = Some((*act, conf));
+            }
+        }
+        Ok(best)
+    }
+
+    /// Reads the step count.
+    pub fn steps(&self) -> Result<u64> {
+        Ok(read_value(self.steps_channel()?)? as u64)
+    }
+
+    /// Starts or stops counting steps.
+    pub fn set_step_counter_enabled(&self, on: bool) -> Result<()> {
+        self.steps_channel()?.attr_write_bool("en", on)
+    }
+
+    /// Determines if the event for the confidence in the activity
+    /// crossing its threshold in the direction is enabled.
+    pub fn is_event_enabled(&self, act: Activity, dir: EventDirection) -> Result<bool> {
+        self.events()?.is_enabled(&Self::activity_event(act, dir))
+    }
+
+    /// Enables or disables the event for the confidence in the activity
+    /// crossing its threshold in the direction.
+    pub fn set_event_enabled(&self, act: Activity, dir: EventDirection, on: bool) -> Result<()> {
+        self.events()?
+            .set_enabled(&Self::activity_event(act, dir), on)
+    }
+
+    /// Reads the threshold, as a percentage, for the activity event in
+    /// the direction.
+    pub fn threshold(&self, act: Activity, dir: EventDirection) -> Result<f64> {
+        let attr = Self::activity_event(act, dir).with_info("value");
+        self.events()?
+            .read(&attr)?
+            .parse()
+            .map_err(|_| Error::StringConversionError)
+    }
+
+    /// Sets the threshold, as a percentage, for the activity event in the
+    /// direction.
+    pub fn set_threshold(&self, act: Activity, dir: EventDirection, pct: f64) -> Result<()> {
+        let attr = Self::activity_event(act, dir).with_info("value");
+        self.events()?.write(&attr, &pct.to_string())
+    }
+
+    /// Enables or disables the event for the step count changing.
+    pub fn set_step_event_enabled(&self, on: bool) -> Result<()> {
+        self.events()?.set_enabled(&Self::step_event(), on)
+    }
+
+    /// Sets the number of steps that trigger the step event.
+    pub fn set_step_event_threshold(&self, steps: u64) -> Result<()> {
+        let attr = Self::step_event().with_info("value");
+        self.events()?.write(&attr, &steps.to_string())
+    }
+
+    /// Gets the channel for the activity.
+    fn channel(&self, act: Activity) -> Result<&Channel> {
+        self.activities
+            .iter()
+            .find(|(a, _)| *a == act)
+            .map(|(_, chan)| chan)
+            .ok_or_else(|| Error::General(format!("Activity '{}' is not supported", act)))
+    }
+
+    /// Gets the step counter channel.
+    fn steps_channel(&self) -> Result<&Channel> {
+        self.steps
+            .as_ref()
+            .ok_or_else(|| Error::General("No step counter".into()))
+    }
+
+    /// Gets the event configuration.
+    fn events(&self) -> Result<&EventConfig> {
+        self.events.as_ref().ok_or_else(|| Error::NotSupported {
+            backend: self.backend.clone(),
+            op: Operation::Events,
+        })
+    }
+
+    /// Gets the event for the confidence in an activity crossing its
+    /// threshold.
+    fn activity_event(act: Activity, dir: EventDirection) -> EventAttr {
+        EventAttr::new(&act.channel_id(), EventType::Threshold, Some(dir))
+    }
+
+    /// Gets the event for the step count changing.
+    fn step_event() -> EventAttr {
+        EventAttr::new("steps", EventType::Change, None)
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(estimate_lux(500.0, None, 0.25), 125.0);
+        assert_eq!(estimate_lux(500.0, Some(100.0), 0.25), 100.0);
+        // Strong IR can swamp the clear diode
+        assert_eq!(estimate_lux(100.0, Some(120.0), 0.25), 0.0);
+    }
+
+    #[test]
+    fn sensor_reader() {
+        use crate::mock::MockChannel;
+
+        let chan = MockChannel::input("voltage0", ChannelType::Voltage)
+            .attr("raw", "100")
+            .attr("offset", "-20")
+            .attr("scale", "0.5")
+            .attr("calibbias", "4")
+            .attr("calibscale", "2");
+        let rdr = SensorReader::new(chan.clone()).unwrap();
+        assert_eq!(rdr.offset(), -20.0);
+        assert_eq!(rdr.calibbias(), Some(4.0));
+        assert_eq!(rdr.read().unwrap(), 40.0);
+
+        let rdr = rdr.apply_calibration(true);
+        assert_eq!(rdr.read().unwrap(), 92.0);
+
+        let chan = MockChannel::input("temp", ChannelType::Temp).attr("input", "21500");
+        let rdr = SensorReader::new(chan).unwrap();
+        assert_eq!(rdr.scale(), 1.0);
+        assert_eq!(rdr.calibscale(), None);
+        assert_eq!(rdr.read().unwrap(), 21500.0);
+    }
+
+    #[test]
+    fn energy() {
+        let hour = Duration::from_secs(3600);
+        assert_eq!(energy_wh(2.0, 2.0, hour), 2.0);
+        assert_eq!(energy_wh(1.0, 3.0, hour / 2), 1.0);
+        assert_eq!(energy_wh(5.0, 5.0, Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn activity_events() {
+        assert_eq!("jogging".parse::<Activity>().unwrap(), Activity::Jogging);
+        assert!("swimming".parse::<Activity>().is_err());
+
+        let attr = ActivityMonitor::activity_event(Activity::Running, EventDirection::Rising);
+        assert_eq!(attr.to_string(), "in_activity_running_thresh_rising_en");
+        assert_eq!(
+            ActivityMonitor::step_event().with_info("value").to_string(),
+            "in_steps_change_value"
+        );
+    }
+}
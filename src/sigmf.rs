@@ -0,0 +1,841 @@
+// industrial-io/src/sigmf.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Recording captures in the SigMF format.
+//!
+//! [SigMF](https://sigmf.org) is the Signal Metadata Format used by most
+//! SDR tools, like GNU Radio, inspectrum, and SigDigger. A recording is a
+//! pair of files: the raw samples in `<name>.sigmf-data` and a JSON
+//! description of them in `<name>.sigmf-meta`, with the data type, the
+//! sample rate, the center frequency of each capture, and annotations on
+//! ranges of samples.
+//!
+//! A [`Recorder`] writes both. It can be set up from a device, taking the
+//! data type from the [`DataFormat`] of the channels, the sample rate from
+//! the device or its trigger, and the center frequency from the local
+//! oscillator of the device, if it has one. Samples are added from
+//! buffers or [`Frame`]s, and it can be used as a [`FrameSink`] in a
+//! capture pipeline.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::sigmf::Recorder;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("cf-ad9361-lpc").unwrap();
+//! let chans = [
+//!     dev.find_input_channel("voltage0").unwrap(),
+//!     dev.find_input_channel("voltage1").unwrap(),
+//! ];
+//! chans.iter().for_each(|chan| chan.enable());
+//!
+//! let mut rec = Recorder::for_device("capture", &dev, &chans, true)
+//!     .unwrap()
+//!     .description("FM band");
+//!
+//! let mut buf = dev.create_buffer(4096, false).unwrap();
+//! for _ in 0..10 {
+//!     buf.refill().unwrap();
+//!     rec.record_buffer(&buf, &chans).unwrap();
+//! }
+//! rec.finish().unwrap();
+//! ```
+
+use crate::{
+    abi, sink::FrameSink, Buffer, Channel, DataFormat, Device, Error, Frame, FrameChannel, Result,
+};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The version of the SigMF specification that is written.
+pub const SIGMF_VERSION: &str = "1.0.0";
+
+/// The extension of a SigMF data file.
+pub const DATA_EXT: &str = "sigmf-data";
+
+/// The extension of a SigMF metadata file.
+pub const META_EXT: &str = "sigmf-meta";
+
+/// The format of each component of a sample in a SigMF recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SampleFormat {
+    /// Signed 8-bit integer
+    I8,
+    /// Signed 16-bit integer
+    I16,
+    /// Signed 32-bit integer
+    I32,
+    /// Unsigned 8-bit integer
+    U8,
+    /// Unsigned 16-bit integer
+    U16,
+    /// Unsigned 32-bit integer
+    U32,
+    /// 32-bit floating point
+    F32,
+    /// 64-bit floating point
+    F64,
+}
+
+impl SampleFormat {
+    /// Gets the smallest SigMF format that holds the converted samples of
+    /// a channel with the data format.
+    pub fn from_data_format(fmt: &DataFormat) -> Result<Self> {
+        let fmt = match (fmt.byte_length(), fmt.is_signed()) {
+            (1, true) => SampleFormat::I8,
+            (1, false) => SampleFormat::U8,
+            (2, true) => SampleFormat::I16,
+            (2, false) => SampleFormat::U16,
+            (4, true) => SampleFormat::I32,
+            (4, false) => SampleFormat::U32,
+            _ => return Err(Error::WrongDataType),
+        };
+        Ok(fmt)
+    }
+
+    /// Gets the size of a value, in bytes.
+    pub fn size(&self) -> usize {
+        use SampleFormat::*;
+        match self {
+            I8 | U8 => 1,
+            I16 | U16 => 2,
+            I32 | U32 | F32 => 4,
+            F64 => 8,
+        }
+    }
+
+    /// Gets the name of the format, like "i16".
+    pub fn as_str(&self) -> &'static str {
+        use SampleFormat::*;
+        match self {
+            I8 => "i8",
+            I16 => "i16",
+            I32 => "i32",
+            U8 => "u8",
+            U16 => "u16",
+            U32 => "u32",
+            F32 => "f32",
+            F64 => "f64",
+        }
+    }
+
+    /// Appends a value to the data, in little-endian order.
+    ///
+    /// Integer values are rounded and saturated to the range of the type.
+    fn encode(&self, x: f64, out: &mut Vec<u8>) {
+        use SampleFormat::*;
+        match self {
+            I8 => out.extend((x.round() as i8).to_le_bytes()),
+            I16 => out.extend((x.round() as i16).to_le_bytes()),
+            I32 => out.extend((x.round() as i32).to_le_bytes()),
+            U8 => out.extend((x.round() as u8).to_le_bytes()),
+            U16 => out.extend((x.round() as u16).to_le_bytes()),
+            U32 => out.extend((x.round() as u32).to_le_bytes()),
+            F32 => out.extend((x as f32).to_le_bytes()),
+            F64 => out.extend(x.to_le_bytes()),
+        }
+    }
+
+    /// Decodes a little-endian value from the start of the bytes, which
+    /// must hold at least one.
+    fn decode(&self, b: &[u8]) -> f64 {
+        use SampleFormat::*;
+        match self {
+            I8 => f64::from(b[0] as i8),
+            I16 => f64::from(i16::from_le_bytes([b[0], b[1]])),
+            I32 => f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            U8 => f64::from(b[0]),
+            U16 => f64::from(u16::from_le_bytes([b[0], b[1]])),
+            U32 => f64::from(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            F32 => f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            F64 => f64::from_le_bytes(b[..8].try_into().unwrap()),
+        }
+    }
+}
+
+impl FromStr for SampleFormat {
+    type Err = Error;
+
+    /// Parses the name of a format, like "i16".
+    fn from_str(s: &str) -> Result<Self> {
+        use SampleFormat::*;
+        [I8, I16, I32, U8, U16, U32, F32, F64]
+            .into_iter()
+            .find(|fmt| fmt.as_str() == s)
+            .ok_or(Error::StringConversionError)
+    }
+}
+
+/// The data type of a SigMF recording, like "ci16_le".
+///
+/// The data is always written in little-endian order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Datatype {
+    /// The format of each component of a sample
+    pub format: SampleFormat,
+    /// Whether the samples are complex, with interleaved I and Q
+    /// components, or real
+    pub complex: bool,
+}
+
+impl Datatype {
+    /// Creates a data type for real samples.
+    pub fn real(format: SampleFormat) -> Self {
+        Self {
+            format,
+            complex: false,
+        }
+    }
+
+    /// Creates a data type for complex samples.
+    pub fn complex(format: SampleFormat) -> Self {
+        Self {
+            format,
+            complex: true,
+        }
+    }
+
+    /// Gets the number of values in each sample; two if complex.
+    pub fn components(&self) -> usize {
+        if self.complex {
+            2
+        }
+        else {
+            1
+        }
+    }
+}
+
+impl FromStr for Datatype {
+    type Err = Error;
+
+    /// Parses a SigMF data type, like "ci16_le".
+    ///
+    /// Only little-endian types can be read.
+    fn from_str(s: &str) -> Result<Self> {
+        let (complex, rest) = match (s.strip_prefix('c'), s.strip_prefix('r')) {
+            (Some(rest), _) => (true, rest),
+            (_, Some(rest)) => (false, rest),
+            _ => return Err(Error::StringConversionError),
+        };
+        let (name, endian) = rest.split_once('_').unwrap_or((rest, ""));
+        let format: SampleFormat = name.parse()?;
+        match endian {
+            "le" => {}
+            "" if format.size() == 1 => {}
+            _ => return Err(Error::StringConversionError),
+        }
+        Ok(Self { format, complex })
+    }
+}
+
+impl fmt::Display for Datatype {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.complex { 'c' } else { 'r' };
+        write!(f, "{}{}", kind, self.format.as_str())?;
+        if self.format.size() > 1 {
+            write!(f, "_le")?;
+        }
+        Ok(())
+    }
+}
+
+/// A segment of a recording, starting at a sample, with its own center
+/// frequency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    /// The index of the first sample of the segment
+    pub sample_start: u64,
+    /// The center frequency, in Hz, if known
+    pub frequency: Option<f64>,
+    /// The time of the first sample, as an ISO 8601 string
+    pub datetime: Option<String>,
+}
+
+/// A note on a range of samples in a recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    /// The index of the first sample
+    pub sample_start: u64,
+    /// The number of samples, or to the end of the recording if `None`
+    pub sample_count: Option<u64>,
+    /// A short label
+    pub label: Option<String>,
+    /// A longer, free-form comment
+    pub comment: Option<String>,
+}
+
+/// A SigMF recorder, writing the data and metadata files for a capture.
+///
+/// The metadata file is written when the recorder is flushed or
+/// finished, so it describes all the samples written up to then.
+#[derive(Debug)]
+pub struct Recorder {
+    /// The data file
+    data: BufWriter<File>,
+    /// The path to the metadata file
+    meta_path: PathBuf,
+    /// The data type of the samples
+    datatype: Datatype,
+    /// The number of channels in the recording
+    num_channels: usize,
+    /// The sample rate, in Hz
+    sample_rate: Option<f64>,
+    /// A description of the recording
+    description: Option<String>,
+    /// The author of the recording
+    author: Option<String>,
+    /// The hardware used to make the recording
+    hw: Option<String>,
+    /// The capture segments
+    captures: Vec<Capture>,
+    /// The annotations
+    annotations: Vec<Annotation>,
+    /// The number of samples written so far
+    sample_count: u64,
+    /// Scratch space for encoding the samples
+    scratch: Vec<u8>,
+}
+
+impl Recorder {
+    /// Creates a recorder that writes to `<base>.sigmf-data` and
+    /// `<base>.sigmf-meta`.
+    pub fn create<P: AsRef<Path>>(base: P, datatype: Datatype) -> Result<Self> {
+        let base = base.as_ref();
+        let data = BufWriter::new(File::create(base.with_extension(DATA_EXT))?);
+        Ok(Self {
+            data,
+            meta_path: base.with_extension(META_EXT),
+            datatype,
+            num_channels: 1,
+            sample_rate: None,
+            description: None,
+            author: None,
+            hw: None,
+            captures: vec![Capture {
+                sample_start: 0,
+                frequency: None,
+                datetime: Some(iso8601(SystemTime::now())),
+            }],
+            annotations: Vec::new(),
+            sample_count: 0,
+            scratch: Vec::new(),
+        })
+    }
+
+    /// Creates a recorder for a capture of the channels of a device.
+    ///
+    /// The data type is taken from the data format of the first channel,
+    /// which should be the same for all of them, the sample rate from the
+    /// device or its trigger, and the center frequency from the local
+    /// oscillator of the device, if it has one.
+    ///
+    /// If `complex` is set, the channels are taken in pairs as the I and
+    /// Q components of each signal, so there must be an even number.
+    pub fn for_device<P: AsRef<Path>>(
+        base: P,
+        dev: &Device,
+        chans: &[Channel],
+        complex: bool,
+    ) -> Result<Self> {
+        let chan = chans
+            .first()
+            .ok_or_else(|| Error::General("No channels to record".into()))?;
+        if complex && chans.len() % 2 != 0 {
+            return Err(Error::General(
+                "Complex samples need an even number of channels".into(),
+            ));
+        }
+
+        let format = SampleFormat::from_data_format(&chan.data_format())?;
+        let datatype = Datatype { format, complex };
+        let mut rec = Self::create(base, datatype)?;
+        rec.num_channels = chans.len() / datatype.components();
+        rec.sample_rate = dev.sampling_frequency().ok();
+        rec.captures[0].frequency = center_frequency(dev);
+        rec.hw = dev.name();
+        Ok(rec)
+    }
+
+    /// Sets the sample rate, in Hz.
+    pub fn sample_rate(mut self, hz: f64) -> Self {
+        self.sample_rate = Some(hz);
+        self
+    }
+
+    /// Sets the number of channels in the recording.
+    ///
+    /// The samples of the channels are interleaved in the data file.
+    pub fn num_channels(mut self, n: usize) -> Self {
+        self.num_channels = n.max(1);
+        self
+    }
+
+    /// Sets the center frequency of the first capture segment, in Hz.
+    pub fn frequency(mut self, hz: f64) -> Self {
+        self.captures[0].frequency = Some(hz);
+        self
+    }
+
+    /// Sets a description of the recording.
+    pub fn description(mut self, desc: &str) -> Self {
+        self.description = Some(desc.to_string());
+        self
+    }
+
+    /// Sets the author of the recording.
+    pub fn author(mut self, author: &str) -> Self {
+        self.author = Some(author.to_string());
+        self
+    }
+
+    /// Sets a description of the hardware used to make the recording.
+    pub fn hw(mut self, hw: &str) -> Self {
+        self.hw = Some(hw.to_string());
+        self
+    }
+
+    /// Gets the data type of the recording.
+    pub fn datatype(&self) -> Datatype {
+        self.datatype
+    }
+
+    /// Gets the number of samples written so far.
+    ///
+    /// With more than one channel, this is the number of samples of each.
+    pub fn sample_count(&self) -> u64 {
+        self.sample_count
+    }
+
+    /// Starts a new capture segment at the next sample, such as when the
+    /// device is retuned to another center frequency.
+    pub fn start_capture(&mut self, frequency: Option<f64>) {
+        let capture = Capture {
+            sample_start: self.sample_count,
+            frequency,
+            datetime: Some(iso8601(SystemTime::now())),
+        };
+        // Replace the last segment if no samples were written to it.
+        match self.captures.last_mut() {
+            Some(last) if last.sample_start == self.sample_count => *last = capture,
+            _ => self.captures.push(capture),
+        }
+    }
+
+    /// Adds an annotation to a range of samples.
+    pub fn annotate(&mut self, annotation: Annotation) {
+        self.annotations.push(annotation);
+    }
+
+    /// Writes interleaved values to the data file.
+    ///
+    /// The values are the components of each channel of each sample in
+    /// turn, so there must be a whole number of samples.
+    pub fn write_values(&mut self, vals: &[f64]) -> Result<()> {
+        let n = self.num_channels * self.datatype.components();
+        if vals.len() % n != 0 {
+            return Err(Error::BadReturnSize);
+        }
+        self.scratch.clear();
+        for &x in vals {
+            self.datatype.format.encode(x, &mut self.scratch);
+        }
+        self.data.write_all(&self.scratch)?;
+        self.sample_count += (vals.len() / n) as u64;
+        Ok(())
+    }
+
+    /// Demultiplexes and converts the samples of the channels in the
+    /// buffer, and writes them to the data file.
+    pub fn record_buffer(&mut self, buf: &Buffer, chans: &[Channel]) -> Result<()> {
+        self.write_frame(&Frame::from_buffer(buf, chans)?)
+    }
+
+    /// Writes the samples of all the channels in a frame to the data
+    /// file.
+    pub fn write_frame(&mut self, frame: &Frame) -> Result<()> {
+        let n = self.num_channels * self.datatype.components();
+        if frame.num_channels() != n {
+            return Err(Error::General(format!(
+                "Expected {} channels in the frame, got {}",
+                n,
+                frame.num_channels()
+            )));
+        }
+        self.write_values(&frame.interleaved())
+    }
+
+    /// Gets the contents of the metadata file for the samples written so
+    /// far.
+    pub fn meta_json(&self) -> String {
+        let mut global = vec![
+            field("core:datatype", json_str(&self.datatype.to_string())),
+            field("core:version", json_str(SIGMF_VERSION)),
+            field("core:num_channels", self.num_channels.to_string()),
+            field(
+                "core:recorder",
+                json_str(concat!("industrial-io ", env!("CARGO_PKG_VERSION"))),
+            ),
+        ];
+        if let Some(rate) = self.sample_rate.and_then(json_num) {
+            global.push(field("core:sample_rate", rate));
+        }
+        let strs = [
+            ("core:description", &self.description),
+            ("core:author", &self.author),
+            ("core:hw", &self.hw),
+        ];
+        for (key, val) in strs {
+            if let Some(val) = val {
+                global.push(field(key, json_str(val)));
+            }
+        }
+
+        let captures = self.captures.iter().map(|cap| {
+            let mut obj = vec![field("core:sample_start", cap.sample_start.to_string())];
+            if let Some(freq) = cap.frequency.and_then(json_num) {
+                obj.push(field("core:frequency", freq));
+            }
+            if let Some(dt) = &cap.datetime {
+                obj.push(field("core:datetime", json_str(dt)));
+            }
+            json_obj(&obj, 2)
+        });
+
+        let annotations = self.annotations.iter().map(|ann| {
+            let mut obj = vec![field("core:sample_start", ann.sample_start.to_string())];
+            if let Some(n) = ann.sample_count {
+                obj.push(field("core:sample_count", n.to_string()));
+            }
+            if let Some(label) = &ann.label {
+                obj.push(field("core:label", json_str(label)));
+            }
+            if let Some(comment) = &ann.comment {
+                obj.push(field("core:comment", json_str(comment)));
+            }
+            json_obj(&obj, 2)
+        });
+
+        let top = [
+            field("global", json_obj(&global, 1)),
+            field("captures", json_array(captures, 1)),
+            field("annotations", json_array(annotations, 1)),
+        ];
+        json_obj(&top, 0) + "\n"
+    }
+
+    /// Flushes the data file, and writes the metadata file.
+    pub fn flush(&mut self) -> Result<()> {
+        self.data.flush()?;
+        fs::write(&self.meta_path, self.meta_json())?;
+        Ok(())
+    }
+
+    /// Finishes the recording, flushing the data file and writing the
+    /// metadata file.
+    pub fn finish(mut self) -> Result<()> {
+        self.flush()
+    }
+}
+
+impl FrameSink for Recorder {
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        self.write_frame(frame)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Recorder::flush(self)
+    }
+}
+
+/// A SigMF recording read back from its files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    /// The data type of the samples
+    pub datatype: Datatype,
+    /// The sample rate, in Hz, if given
+    pub sample_rate: Option<f64>,
+    /// The number of channels
+    pub num_channels: usize,
+    /// The center frequency of the first capture, in Hz, if given
+    pub frequency: Option<f64>,
+    /// The interleaved values of the samples: each component of each
+    /// channel of each sample in turn
+    pub values: Vec<f64>,
+}
+
+impl Recording {
+    /// Reads the recording in `<base>.sigmf-data` and `<base>.sigmf-meta`.
+    ///
+    /// This reads the global data type, sample rate, and number of
+    /// channels, and the frequency of the first capture. The other
+    /// metadata is ignored.
+    pub fn open<P: AsRef<Path>>(base: P) -> Result<Self> {
+        let base = base.as_ref();
+        let meta = fs::read_to_string(base.with_extension(META_EXT))?;
+        let data = fs::read(base.with_extension(DATA_EXT))?;
+
+        let datatype: Datatype = json_value(&meta, "core:datatype")
+            .ok_or_else(|| Error::General("No data type in the SigMF metadata".into()))?
+            .trim_matches('"')
+            .parse()?;
+        let num = |key| json_value(&meta, key).and_then(|v| v.parse::<f64>().ok());
+
+        let size = datatype.format.size();
+        let values = data
+            .chunks_exact(size)
+            .map(|b| datatype.format.decode(b))
+            .collect();
+
+        Ok(Self {
+            datatype,
+            sample_rate: num("core:sample_rate"),
+            num_channels: num("core:num_channels").map_or(1, |n| n as usize).max(1),
+            frequency: num("core:frequency"),
+            values,
+        })
+    }
+
+    /// Converts the recording to a frame.
+    ///
+    /// Each channel is a real channel in the frame, and each complex
+    /// channel is a pair of them, for the I and Q components. They're
+    /// named like IIO channels, "voltage0", "voltage1", and so on.
+    pub fn to_frame(&self) -> Frame {
+        let n = self.num_channels * self.datatype.components();
+        let mut frame = Frame::new();
+        frame.sample_rate = self.sample_rate;
+        let values = &self.values[..self.values.len() / n * n];
+        frame.channels = (0..n)
+            .map(|i| {
+                let samples = values.iter().skip(i).step_by(n).copied().collect();
+                FrameChannel::new(&format!("voltage{}", i), samples)
+            })
+            .collect();
+        frame
+    }
+}
+
+/// Gets the center frequency of a device, in Hz, if it has one.
+///
+/// This is a best guess, since there is no standard attribute for it. It
+/// is the `frequency` attribute of the first output `altvoltage` channel
+/// of the device, which is the local oscillator of most SDR
+/// transceivers, like the AD9361, or of its PHY device in the same
+/// context.
+pub fn center_frequency(dev: &Device) -> Option<f64> {
+    let lo_freq = |dev: &Device| {
+        dev.channels()
+            .filter(|chan| chan.is_output())
+            .filter(|chan| chan.id().is_some_and(|id| id.starts_with("altvoltage")))
+            .find_map(|chan| chan.attr_read_float(abi::chan::FREQUENCY).ok())
+    };
+    lo_freq(dev).or_else(|| {
+        let name = dev.name()?;
+        let (prefix, _) = name.split_once('-').unwrap_or((&name, ""));
+        dev.context()
+            .devices()
+            .filter(|d| {
+                d.name()
+                    .is_some_and(|n| n.contains(prefix) && n.ends_with("-phy"))
+            })
+            .find_map(|d| lo_freq(&d))
+    })
+}
+
+// ----- JSON -----
+
+/// Finds the first value of a key in JSON text, as the raw text of a
+/// string or number.
+///
+/// This is not a JSON parser, but is enough to read the simple, scalar,
+/// fields of the metadata.
+fn json_value<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let pos = json.find(&json_str(key))? + key.len() + 2;
+    let rest = json[pos..].trim_start().strip_prefix(':')?.trim_start();
+    let end = if let Some(s) = rest.strip_prefix('"') {
+        s.find('"')? + 2
+    }
+    else {
+        rest.find([',', '}', ']', '\n']).unwrap_or(rest.len())
+    };
+    Some(rest[..end].trim())
+}
+
+/// Makes a JSON field from a key and an already encoded value.
+fn field(key: &str, val: String) -> (String, String) {
+    (json_str(key), val)
+}
+
+/// Encodes a number as JSON, if it can be.
+fn json_num(x: f64) -> Option<String> {
+    x.is_finite().then(|| x.to_string())
+}
+
+/// Encodes a string as JSON, with quotes and escapes.
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Encodes the fields as a JSON object, indented to the level.
+fn json_obj(fields: &[(String, String)], level: usize) -> String {
+    if fields.is_empty() {
+        return "{}".into();
+    }
+    let indent = "    ".repeat(level + 1);
+    let body: Vec<_> = fields
+        .iter()
+        .map(|(key, val)| format!("{}{}: {}", indent, key, val))
+        .collect();
+    format!("{{\n{}\n{}}}", body.join(",\n"), "    ".repeat(level))
+}
+
+/// Encodes the values as a JSON array, indented to the level.
+fn json_array<I: Iterator<Item = String>>(vals: I, level: usize) -> String {
+    let indent = "    ".repeat(level + 1);
+    let body: Vec<_> = vals.map(|val| format!("{}{}", indent, val)).collect();
+    if body.is_empty() {
+        return "[]".into();
+    }
+    format!("[\n{}\n{}]", body.join(",\n"), "    ".repeat(level))
+}
+
+/// Formats a time as an ISO 8601 string in UTC, like
+/// "2024-05-01T12:34:56.789Z".
+fn iso8601(t: SystemTime) -> String {
+    let dur = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = dur.as_secs();
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Civil date from days since the epoch, per Howard Hinnant.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        dur.subsec_millis()
+    )
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn datatypes() {
+        let fmt = DataFormat::parse("le:s12/16>>4").unwrap();
+        let format = SampleFormat::from_data_format(&fmt).unwrap();
+        assert_eq!(format, SampleFormat::I16);
+        assert_eq!(Datatype::complex(format).to_string(), "ci16_le");
+        assert_eq!(Datatype::real(SampleFormat::U8).to_string(), "ru8");
+        assert_eq!(Datatype::real(SampleFormat::F32).to_string(), "rf32_le");
+
+        let fmt = DataFormat::parse("le:s64/64>>0").unwrap();
+        assert!(SampleFormat::from_data_format(&fmt).is_err());
+
+        assert_eq!(
+            "ci16_le".parse::<Datatype>().unwrap(),
+            Datatype::complex(SampleFormat::I16)
+        );
+        assert_eq!(
+            "ru8".parse::<Datatype>().unwrap(),
+            Datatype::real(SampleFormat::U8)
+        );
+        assert!("ci16_be".parse::<Datatype>().is_err());
+        assert!("xi16_le".parse::<Datatype>().is_err());
+    }
+
+    #[test]
+    fn timestamps() {
+        let t = UNIX_EPOCH + Duration::from_millis(1_714_566_896_789);
+        assert_eq!(iso8601(t), "2024-05-01T12:34:56.789Z");
+        assert_eq!(iso8601(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn recording() {
+        let base = std::env::temp_dir().join(format!("riio_sigmf_{}", std::process::id()));
+        let mut rec = Recorder::create(&base, Datatype::complex(SampleFormat::I16))
+            .unwrap()
+            .sample_rate(1.0e6)
+            .frequency(100.0e6)
+            .description("A \"test\"");
+
+        rec.write_values(&[1.0, -1.0, 2.0, -2.0]).unwrap();
+        assert!(rec.write_values(&[1.0]).is_err());
+        rec.annotate(Annotation {
+            sample_start: 0,
+            sample_count: Some(2),
+            label: Some("tone".into()),
+            comment: None,
+        });
+        assert_eq!(rec.sample_count(), 2);
+
+        let meta = rec.meta_json();
+        assert!(meta.contains("\"core:datatype\": \"ci16_le\""));
+        assert!(meta.contains("\"core:sample_rate\": 1000000"));
+        assert!(meta.contains("\"core:frequency\": 100000000"));
+        assert!(meta.contains("\"core:description\": \"A \\\"test\\\"\""));
+        assert!(meta.contains("\"core:label\": \"tone\""));
+        rec.finish().unwrap();
+
+        let data = fs::read(base.with_extension(DATA_EXT)).unwrap();
+        assert_eq!(data, [1, 0, 255, 255, 2, 0, 254, 255]);
+
+        let rec = Recording::open(&base).unwrap();
+        assert_eq!(rec.datatype, Datatype::complex(SampleFormat::I16));
+        assert_eq!(rec.sample_rate, Some(1.0e6));
+        assert_eq!(rec.frequency, Some(100.0e6));
+        let frame = rec.to_frame();
+        assert_eq!(frame.samples("voltage0").unwrap(), [1.0, 2.0]);
+        assert_eq!(frame.samples("voltage1").unwrap(), [-1.0, -2.0]);
+
+        let _ = fs::remove_file(base.with_extension(DATA_EXT));
+        let _ = fs::remove_file(base.with_extension(META_EXT));
+    }
+}
@@ -0,0 +1,1446 @@
+// industrial-io/src/sink.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Routing captured frames to multiple destinations.
+//!
+//! A [`Pipeline`] fans frames out to any number of [`FrameSink`]s, such as
+//! a CSV file on disk and a network preview. Each sink runs in its own
+//! thread with a bounded queue. If a sink can't keep up, frames are dropped
+//! for _that_ sink only, so a slow sink doesn't stall the capture or any of
+//! the other sinks.
+//!
+//! Frames can optionally be sent with a _tag_. A sink added with a set of
+//! tags only receives frames sent with one of those tags, whereas a sink
+//! added without any tags receives untagged frames.
+//!
+//! A pipeline can also run a chain of [`FrameProcessor`]s, such as a
+//! decimator or calibration, on each frame before it's sent to the sinks.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::sink::{CsvSink, Pipeline, UdpStreamer};
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ads1015").unwrap();
+//!
+//! let mut pipeline = Pipeline::new();
+//! pipeline.add_sink(Box::new(CsvSink::create("capture.csv").unwrap()));
+//! pipeline.add_sink(Box::new(UdpStreamer::connect("192.168.1.10:5000").unwrap()));
+//!
+//! for _ in 0..100 {
+//!     let frame = dev.capture_burst(1000).unwrap();
+//!     pipeline.send(frame);
+//! }
+//! pipeline.close().unwrap();
+//! ```
+
+use crate::{
+    pool::PooledFrame,
+    process::{FrameProcessor, ProcessorChain},
+    ring::{FrameRing, Producer},
+    Error, Frame, FrameSet, Result,
+};
+use std::{
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    net::{TcpStream, ToSocketAddrs, UdpSocket},
+    path::Path,
+    sync::{
+        mpsc::{self, SyncSender, TrySendError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// A destination for captured frames.
+pub trait FrameSink: Send {
+    /// Writes a frame to the sink.
+    fn write(&mut self, frame: &Frame) -> Result<()>;
+
+    /// Flushes any buffered data.
+    ///
+    /// This is called when the pipeline is closed.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The queue feeding frames to a sink's thread.
+enum Queue {
+    /// A standard library channel
+    Channel(SyncSender<Arc<PooledFrame>>),
+    /// A lock-free ring
+    Ring(Producer<Arc<PooledFrame>>),
+}
+
+impl Queue {
+    /// Tries to queue a frame, returning `false` if the queue is full.
+    ///
+    /// A disconnected queue silently accepts the frame.
+    fn try_send(&mut self, frame: Arc<PooledFrame>) -> bool {
+        match self {
+            Queue::Channel(tx) => !matches!(tx.try_send(frame), Err(TrySendError::Full(_))),
+            Queue::Ring(tx) => tx.is_closed() || tx.push(frame).is_ok(),
+        }
+    }
+}
+
+/// A sink running in a pipeline.
+struct SinkHandle {
+    /// The tags of the frames that the sink receives
+    tags: Vec<String>,
+    /// The queue to the sink's thread
+    tx: Queue,
+    /// The number of frames dropped because the sink's queue was full.
+    dropped: u64,
+    /// The sink's thread
+    thread: JoinHandle<Result<()>>,
+}
+
+impl SinkHandle {
+    /// Determines if the sink should receive a frame with the tag.
+    fn accepts(&self, tag: Option<&str>) -> bool {
+        match tag {
+            None => self.tags.is_empty(),
+            Some(tag) => self.tags.iter().any(|t| t == tag),
+        }
+    }
+
+    /// Closes the queue and joins the sink's thread, returning the result
+    /// from the sink.
+    fn close(self) -> Result<()> {
+        drop(self.tx);
+        self.thread
+            .join()
+            .unwrap_or_else(|_| Err(Error::General("Sink thread panicked".into())))
+    }
+}
+
+impl fmt::Debug for SinkHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SinkHandle")
+            .field("tags", &self.tags)
+            .field("dropped", &self.dropped)
+            .finish()
+    }
+}
+
+/// A fan-out of frames to multiple sinks.
+#[derive(Debug)]
+pub struct Pipeline {
+    /// The processors run on each frame before it's sent
+    processors: ProcessorChain,
+    /// The first error from any of the processors
+    error: Option<Error>,
+    /// The sinks
+    sinks: Vec<SinkHandle>,
+    /// The maximum number of frames queued for each sink
+    queue_len: usize,
+    /// Whether to use lock-free rings for the sink queues
+    use_rings: bool,
+    /// Whether the pipeline is paused
+    paused: bool,
+    /// The number of frames discarded while paused
+    skipped: u64,
+}
+
+impl Pipeline {
+    /// Creates a new pipeline with no sinks.
+    pub fn new() -> Self {
+        const DFLT_QUEUE_LEN: usize = 16;
+
+        Self {
+            processors: ProcessorChain::new(),
+            error: None,
+            sinks: Vec::new(),
+            queue_len: DFLT_QUEUE_LEN,
+            use_rings: false,
+            paused: false,
+            skipped: 0,
+        }
+    }
+
+    /// Sets the maximum number of frames that can be queued for each sink
+    /// added after this.
+    pub fn queue_len(mut self, n: usize) -> Self {
+        self.queue_len = n.max(1);
+        self
+    }
+
+    /// Sets whether the sinks added after this are fed through lock-free
+    /// [`FrameRing`]s rather than standard library channels.
+    ///
+    /// This gives a more deterministic latency for real-time sinks.
+    pub fn use_rings(mut self, on: bool) -> Self {
+        self.use_rings = on;
+        self
+    }
+
+    /// Adds a processor to the end of the chain that is run on each frame
+    /// before it's sent to the sinks.
+    ///
+    /// The processors run in the caller's thread, in the order they were
+    /// added. Each frame is given to them as a set of one frame, and each
+    /// frame left in the set afterwards is sent to the sinks.
+    pub fn add_processor(&mut self, proc: Box<dyn FrameProcessor>) {
+        self.processors.push(proc);
+    }
+
+    /// Gets the number of processors in the pipeline.
+    pub fn num_processors(&self) -> usize {
+        self.processors.len()
+    }
+
+    /// Adds a sink that receives all the untagged frames.
+    ///
+    /// Returns the index of the sink in the pipeline.
+    pub fn add_sink(&mut self, sink: Box<dyn FrameSink>) -> usize {
+        self.add_tagged_sink(&[], sink)
+    }
+
+    /// Adds a sink that only receives the frames sent with one of the
+    /// tags.
+    ///
+    /// Returns the index of the sink in the pipeline.
+    pub fn add_tagged_sink(&mut self, tags: &[&str], sink: Box<dyn FrameSink>) -> usize {
+        let tags = tags.iter().map(|s| s.to_string()).collect();
+        let handle = self.spawn_sink(tags, sink);
+        self.sinks.push(handle);
+        self.sinks.len() - 1
+    }
+
+    /// Replaces the sink at the index with a new one that receives the
+    /// same frames.
+    ///
+    /// The old sink is closed first, waiting for it to write its queued
+    /// frames, and any error from it is returned, although the new sink is
+    /// installed regardless. Frames sent afterward go to the new sink.
+    pub fn replace_sink(&mut self, idx: usize, sink: Box<dyn FrameSink>) -> Result<()> {
+        let tags = self.sinks.get(idx).ok_or(Error::InvalidIndex)?.tags.clone();
+        let handle = self.spawn_sink(tags, sink);
+        std::mem::replace(&mut self.sinks[idx], handle).close()
+    }
+
+    /// Removes the sink at the index, closing it and waiting for it to
+    /// write its queued frames.
+    ///
+    /// The sinks after it move down to fill the gap, so their indexes
+    /// each drop by one.
+    pub fn remove_sink(&mut self, idx: usize) -> Result<()> {
+        if idx >= self.sinks.len() {
+            return Err(Error::InvalidIndex);
+        }
+        self.sinks.remove(idx).close()
+    }
+
+    /// Starts the thread for a sink, with a queue to feed it.
+    fn spawn_sink(&self, tags: Vec<String>, mut sink: Box<dyn FrameSink>) -> SinkHandle {
+        let (tx, thread) = if self.use_rings {
+            let (tx, mut rx) = FrameRing::<Arc<PooledFrame>>::with_capacity(self.queue_len);
+            let thread = thread::spawn(move || {
+                while let Some(frame) = rx.recv() {
+                    sink.write(&frame)?;
+                }
+                sink.flush()
+            });
+            (Queue::Ring(tx), thread)
+        }
+        else {
+            let (tx, rx) = mpsc::sync_channel::<Arc<PooledFrame>>(self.queue_len);
+            let thread = thread::spawn(move || {
+                for frame in rx {
+                    sink.write(&frame)?;
+                }
+                sink.flush()
+            });
+            (Queue::Channel(tx), thread)
+        };
+
+        SinkHandle {
+            tags,
+            tx,
+            dropped: 0,
+            thread,
+        }
+    }
+
+    /// Gets the number of sinks in the pipeline.
+    pub fn num_sinks(&self) -> usize {
+        self.sinks.len()
+    }
+
+    /// Gets the number of frames dropped by a sink because it couldn't
+    /// keep up.
+    pub fn dropped(&self, idx: usize) -> Option<u64> {
+        self.sinks.get(idx).map(|sink| sink.dropped)
+    }
+
+    /// Pauses the pipeline.
+    ///
+    /// While paused, frames that are sent are discarded, without being
+    /// processed, but the processors, sinks, and statistics are all kept,
+    /// so that the pipeline carries on where it left off when it's
+    /// resumed. See [`Capture`](crate::capture::Capture) to also stop
+    /// refilling the buffer of a device.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes a paused pipeline.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Determines if the pipeline is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Gets the number of frames discarded because the pipeline was
+    /// paused.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Sends an untagged frame to the sinks.
+    ///
+    /// This can be a plain [`Frame`] or a [`PooledFrame`], in which case
+    /// its memory is returned to the pool after the last sink is done
+    /// with it.
+    pub fn send<F: Into<PooledFrame>>(&mut self, frame: F) {
+        self.route(None, frame.into())
+    }
+
+    /// Sends a frame to the sinks that accept the tag.
+    pub fn send_tagged<F: Into<PooledFrame>>(&mut self, tag: &str, frame: F) {
+        self.route(Some(tag), frame.into())
+    }
+
+    /// Runs the processors on the frame, then queues the resulting frames
+    /// to the sinks that accept the tag.
+    ///
+    /// If a processor fails, the frame is dropped, and the error is
+    /// reported when the pipeline is closed.
+    fn route(&mut self, tag: Option<&str>, mut frame: PooledFrame) {
+        if self.paused {
+            self.skipped += 1;
+            return;
+        }
+        if self.processors.is_empty() {
+            return self.queue(tag, frame);
+        }
+
+        let mut set: FrameSet = std::iter::once(std::mem::take(&mut *frame)).collect();
+        if let Err(err) = self.processors.process(&mut set) {
+            self.error.get_or_insert(err);
+            return;
+        }
+
+        // The first frame reuses the pooled memory of the original.
+        let mut frames = set.into_iter();
+        if let Some(first) = frames.next() {
+            *frame = first;
+            self.queue(tag, frame);
+        }
+        for f in frames {
+            self.queue(tag, f.into());
+        }
+    }
+
+    /// Queues the frame to each of the sinks that accept the tag.
+    ///
+    /// This never blocks. A sink that failed is silently skipped; its
+    /// error is reported when the pipeline is closed.
+    fn queue(&mut self, tag: Option<&str>, frame: PooledFrame) {
+        let frame = Arc::new(frame);
+        for sink in self.sinks.iter_mut().filter(|sink| sink.accepts(tag)) {
+            if !sink.tx.try_send(Arc::clone(&frame)) {
+                sink.dropped += 1;
+            }
+        }
+    }
+
+    /// Closes the pipeline, waiting for all the sinks to finish writing
+    /// their queued frames.
+    ///
+    /// Returns the first error from any of the processors or sinks.
+    pub fn close(mut self) -> Result<()> {
+        self.shutdown()
+    }
+
+    /// Closes the queues and joins the sink threads.
+    fn shutdown(&mut self) -> Result<()> {
+        let mut res = self.error.take().map_or(Ok(()), Err);
+        for sink in self.sinks.drain(..) {
+            let ret = sink.close();
+            if res.is_ok() {
+                res = ret;
+            }
+        }
+        res
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Pipeline {
+    /// Dropping the pipeline closes it, ignoring any sink errors.
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+// --------------------------------------------------------------------------
+
+/// A sink that writes frames as CSV text.
+///
+/// A header line with the channel ID's is written before the first frame,
+/// then a line for each sample, with a column for each channel. A frame
+/// with different channels than the header is rejected with
+/// [`Error::InvalidValue`], since its columns wouldn't line up.
+#[derive(Debug)]
+pub struct CsvSink<W: Write + Send> {
+    /// The output
+    out: W,
+    /// The channel ID's in the header, once it has been written
+    header: Option<Vec<String>>,
+}
+
+impl CsvSink<BufWriter<File>> {
+    /// Creates a CSV sink that writes to a new file.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write + Send> CsvSink<W> {
+    /// Creates a CSV sink that writes to the output.
+    pub fn new(out: W) -> Self {
+        Self { out, header: None }
+    }
+
+    /// Consumes the sink, returning the output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write + Send> FrameSink for CsvSink<W> {
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        let ids: Vec<_> = frame.channels.iter().map(|ch| ch.id.as_str()).collect();
+        match &self.header {
+            Some(header) if *header != ids => {
+                return Err(Error::InvalidValue(format!(
+                    "Frame channels '{}' don't match the CSV header '{}'",
+                    ids.join(","),
+                    header.join(",")
+                )));
+            }
+            Some(_) => {}
+            None => {
+                writeln!(self.out, "{}", ids.join(","))?;
+                self.header = Some(ids.iter().map(|id| id.to_string()).collect());
+            }
+        }
+        for i in 0..frame.len() {
+            let row: Vec<_> = frame
+                .channels
+                .iter()
+                .map(|ch| ch.samples[i].to_string())
+                .collect();
+            writeln!(self.out, "{}", row.join(","))?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.out.flush()?)
+    }
+}
+
+/// A sink that writes the interleaved samples to a binary file, as
+/// little-endian, 64-bit floating point values.
+#[derive(Debug)]
+pub struct FileSink {
+    /// The output file
+    out: BufWriter<File>,
+}
+
+impl FileSink {
+    /// Creates a sink that writes to a new file.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+        })
+    }
+}
+
+impl FrameSink for FileSink {
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        for x in frame.interleaved() {
+            self.out.write_all(&x.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.out.flush()?)
+    }
+}
+
+/// A sink that moves on to a new output after a number of samples, or an
+/// interval of time, like a logger rotating its files.
+///
+/// Each output is created by a function that is given its index, starting
+/// at zero. The first one is created when the first frame arrives, and the
+/// previous one is flushed and dropped each time it's rotated. Rotation
+/// only happens between frames, so an output can get a few more samples
+/// than the limit.
+pub struct RotatingSink<F>
+where
+    F: FnMut(u32) -> Result<Box<dyn FrameSink>> + Send,
+{
+    /// The function to create each output
+    open: F,
+    /// The current output, if any
+    sink: Option<Box<dyn FrameSink>>,
+    /// The number of outputs created so far
+    count: u32,
+    /// The number of samples after which to rotate, if any
+    max_samples: Option<usize>,
+    /// The time after which to rotate, if any
+    max_age: Option<Duration>,
+    /// The number of samples written to the current output
+    samples: usize,
+    /// The time that the current output was created
+    opened: Instant,
+}
+
+impl<F> RotatingSink<F>
+where
+    F: FnMut(u32) -> Result<Box<dyn FrameSink>> + Send,
+{
+    /// Creates a sink that gets its outputs from the function.
+    ///
+    /// Without any limits, only a single output is created.
+    pub fn new(open: F) -> Self {
+        Self {
+            open,
+            sink: None,
+            count: 0,
+            max_samples: None,
+            max_age: None,
+            samples: 0,
+            opened: Instant::now(),
+        }
+    }
+
+    /// Rotates to a new output after this many samples.
+    pub fn max_samples(mut self, n: usize) -> Self {
+        self.max_samples = Some(n.max(1));
+        self
+    }
+
+    /// Rotates to a new output after this much time.
+    pub fn max_age(mut self, dur: Duration) -> Self {
+        self.max_age = Some(dur);
+        self
+    }
+
+    /// Gets the number of outputs created so far.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Determines if the current output has reached one of the limits.
+    fn is_full(&self) -> bool {
+        self.max_samples.is_some_and(|n| self.samples >= n)
+            || self.max_age.is_some_and(|dur| self.opened.elapsed() >= dur)
+    }
+}
+
+impl<F> FrameSink for RotatingSink<F>
+where
+    F: FnMut(u32) -> Result<Box<dyn FrameSink>> + Send,
+{
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        if self.is_full() {
+            if let Some(mut sink) = self.sink.take() {
+                sink.flush()?;
+            }
+        }
+        let sink = match self.sink {
+            Some(ref mut sink) => sink,
+            None => {
+                let sink = (self.open)(self.count)?;
+                self.count += 1;
+                self.samples = 0;
+                self.opened = Instant::now();
+                self.sink.insert(sink)
+            }
+        };
+        sink.write(frame)?;
+        self.samples += frame.len();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self.sink {
+            Some(ref mut sink) => sink.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<F> fmt::Debug for RotatingSink<F>
+where
+    F: FnMut(u32) -> Result<Box<dyn FrameSink>> + Send,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RotatingSink")
+            .field("count", &self.count)
+            .field("max_samples", &self.max_samples)
+            .field("max_age", &self.max_age)
+            .field("samples", &self.samples)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The encoding of the samples sent by a network streamer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Encoding {
+    /// The converted samples, as little-endian, 64-bit floating point
+    /// values.
+    #[default]
+    Converted = 0,
+    /// The raw integer samples, as little-endian, 32-bit signed values.
+    ///
+    /// The values in the frame are rounded to integers, so this is only
+    /// meaningful for frames that haven't been scaled or calibrated.
+    Raw = 1,
+}
+
+impl Encoding {
+    /// Gets the size, in bytes, of each sample.
+    pub fn sample_size(&self) -> usize {
+        match self {
+            Encoding::Converted => 8,
+            Encoding::Raw => 4,
+        }
+    }
+}
+
+/// The header that precedes each block of samples sent by a network
+/// streamer.
+///
+/// All the fields are little-endian, in this order, for a total of
+/// [`StreamHeader::SIZE`] bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamHeader {
+    /// The sequence number of the frame
+    pub seq: u64,
+    /// The time the frame was sent, in nanoseconds since the Unix epoch
+    pub timestamp: u64,
+    /// A bit mask of the scan indexes of the channels in the frame, or of
+    /// their positions in the frame, if [`StreamHeader::UNINDEXED`] is set
+    pub channel_mask: u64,
+    /// The offset of the first sample in the block, within the frame
+    pub offset: u32,
+    /// The number of samples, per channel, in the block
+    pub num_samples: u32,
+    /// The number of channels in the frame
+    pub num_channels: u16,
+    /// The encoding of the samples
+    pub encoding: u8,
+    /// Flags describing the block
+    pub flags: u8,
+}
+
+impl StreamHeader {
+    /// The size of the header, in bytes
+    pub const SIZE: usize = 36;
+
+    /// A flag that the channel mask has the positions of the channels in
+    /// the frame, since some of them don't have a scan index below 64.
+    pub const UNINDEXED: u8 = 0x01;
+
+    /// Appends the header to a byte buffer.
+    pub fn write_to(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_bytes());
+    }
+
+    /// Gets the encoded bytes of the header.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0u8; Self::SIZE];
+        buf[0..8].copy_from_slice(&self.seq.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.timestamp.to_le_bytes());
+        buf[16..24].copy_from_slice(&self.channel_mask.to_le_bytes());
+        buf[24..28].copy_from_slice(&self.offset.to_le_bytes());
+        buf[28..32].copy_from_slice(&self.num_samples.to_le_bytes());
+        buf[32..34].copy_from_slice(&self.num_channels.to_le_bytes());
+        buf[34] = self.encoding;
+        buf[35] = self.flags;
+        buf
+    }
+
+    /// Parses a header from the start of a byte buffer.
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.len() < Self::SIZE {
+            return None;
+        }
+        let u64_at = |i: usize| u64::from_le_bytes(buf[i..i + 8].try_into().unwrap());
+        let u32_at = |i: usize| u32::from_le_bytes(buf[i..i + 4].try_into().unwrap());
+        Some(Self {
+            seq: u64_at(0),
+            timestamp: u64_at(8),
+            channel_mask: u64_at(16),
+            offset: u32_at(24),
+            num_samples: u32_at(28),
+            num_channels: u16::from_le_bytes([buf[32], buf[33]]),
+            encoding: buf[34],
+            flags: buf[35],
+        })
+    }
+}
+
+/// Gets the size, in bytes, of a block of `n` samples from each channel
+/// of a frame, with its header.
+pub(crate) fn block_size(frame: &Frame, n: usize, enc: Encoding) -> usize {
+    StreamHeader::SIZE + n * frame.num_channels() * enc.sample_size()
+}
+
+/// Encodes a block of samples from a frame, with its header, into a byte
+/// buffer.
+///
+/// See [`encode_block_into()`].
+pub(crate) fn encode_block(
+    frame: &Frame,
+    offset: usize,
+    n: usize,
+    enc: Encoding,
+    buf: &mut Vec<u8>,
+) -> Result<()> {
+    buf.clear();
+    buf.resize(block_size(frame, n, enc), 0);
+    encode_block_into(frame, offset, n, enc, buf)
+}
+
+/// Encodes a block of samples from a frame, with its header, into the
+/// start of a byte slice.
+///
+/// The slice must hold at least [`block_size()`] bytes. This fails with
+/// [`Error::InvalidValue`] if the offset, the number of samples, or the
+/// number of channels doesn't fit in its field of the header.
+pub(crate) fn encode_block_into(
+    frame: &Frame,
+    offset: usize,
+    n: usize,
+    enc: Encoding,
+    buf: &mut [u8],
+) -> Result<()> {
+    let field = |name: &str, val: usize| {
+        Error::InvalidValue(format!(
+            "{} of {} doesn't fit in the stream header",
+            name, val
+        ))
+    };
+    let hdr_offset = u32::try_from(offset).map_err(|_| field("Block offset", offset))?;
+    let num_samples = u32::try_from(n).map_err(|_| field("Block length", n))?;
+    let num_channels = u16::try_from(frame.num_channels())
+        .map_err(|_| field("Channel count", frame.num_channels()))?;
+
+    // The mask is of the scan indexes, unless any channel doesn't have
+    // one that fits, in which case the positions can't collide with them.
+    let indexes = frame
+        .channels
+        .iter()
+        .map(|ch| ch.index.filter(|&i| i < 64))
+        .collect::<Option<Vec<_>>>();
+    let (mask, flags) = match indexes {
+        Some(indexes) => (indexes.into_iter().fold(0u64, |mask, i| mask | (1 << i)), 0),
+        None => {
+            let n = frame.num_channels().min(64);
+            let mask = (0..n).fold(0u64, |mask, i| mask | (1 << i));
+            (mask, StreamHeader::UNINDEXED)
+        }
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|dur| dur.as_nanos() as u64)
+        .unwrap_or_default();
+
+    let hdr = StreamHeader {
+        seq: frame.seq,
+        timestamp,
+        channel_mask: mask,
+        offset: hdr_offset,
+        num_samples,
+        num_channels,
+        encoding: enc as u8,
+        flags,
+    };
+    buf[..StreamHeader::SIZE].copy_from_slice(&hdr.to_bytes());
+
+    let size = block_size(frame, n, enc);
+    let vals =
+        (offset..offset + n).flat_map(|i| frame.channels.iter().map(move |ch| ch.samples[i]));
+    let outs = buf[StreamHeader::SIZE..size].chunks_exact_mut(enc.sample_size());
+    for (x, out) in vals.zip(outs) {
+        match enc {
+            Encoding::Converted => out.copy_from_slice(&x.to_le_bytes()),
+            Encoding::Raw => out.copy_from_slice(&(x.round() as i32).to_le_bytes()),
+        }
+    }
+    Ok(())
+}
+
+/// A sink that streams the interleaved samples to a remote host in UDP
+/// datagrams.
+///
+/// Each frame is split into as many datagrams as needed. Each datagram
+/// starts with a [`StreamHeader`], followed by complete samples for all
+/// the channels.
+#[derive(Debug)]
+pub struct UdpStreamer {
+    /// The socket, connected to the remote host
+    sock: UdpSocket,
+    /// The sample encoding
+    encoding: Encoding,
+    /// The buffer for each datagram
+    buf: Vec<u8>,
+}
+
+impl UdpStreamer {
+    /// The maximum size of the payload of each datagram.
+    const MAX_DATAGRAM: usize = 8192;
+
+    /// Creates a streamer that sends to the remote address.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        let sock = UdpSocket::bind("0.0.0.0:0")?;
+        sock.connect(addr)?;
+        Ok(Self {
+            sock,
+            encoding: Encoding::default(),
+            buf: Vec::with_capacity(Self::MAX_DATAGRAM),
+        })
+    }
+
+    /// Sets the encoding of the samples.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+}
+
+impl FrameSink for UdpStreamer {
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        let row_size = self.encoding.sample_size() * frame.num_channels().max(1);
+        let rows = ((Self::MAX_DATAGRAM - StreamHeader::SIZE) / row_size).max(1);
+
+        let n = frame.len();
+        let mut offset = 0;
+        while offset < n {
+            let k = rows.min(n - offset);
+            encode_block(frame, offset, k, self.encoding, &mut self.buf)?;
+            self.sock.send(&self.buf)?;
+            offset += k;
+        }
+        Ok(())
+    }
+}
+
+/// A sink that streams the interleaved samples to a remote host over a
+/// TCP connection.
+///
+/// Each frame is sent as a single [`StreamHeader`] followed by all of its
+/// samples.
+#[derive(Debug)]
+pub struct TcpStreamer {
+    /// The connection to the remote host
+    stream: TcpStream,
+    /// The sample encoding
+    encoding: Encoding,
+    /// The buffer for each frame
+    buf: Vec<u8>,
+}
+
+impl TcpStreamer {
+    /// Creates a streamer connected to the remote address.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            encoding: Encoding::default(),
+            buf: Vec::new(),
+        })
+    }
+
+    /// Sets the encoding of the samples.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+}
+
+impl FrameSink for TcpStreamer {
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        encode_block(frame, 0, frame.len(), self.encoding, &mut self.buf)?;
+        self.stream.write_all(&self.buf)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.stream.flush()?)
+    }
+}
+
+/// A sink that passes each frame to a callback function.
+pub struct CallbackSink<F>
+where
+    F: FnMut(&Frame) -> Result<()> + Send,
+{
+    /// The callback
+    f: F,
+}
+
+impl<F> CallbackSink<F>
+where
+    F: FnMut(&Frame) -> Result<()> + Send,
+{
+    /// Creates a sink from the callback.
+    pub fn new(f: F) -> Self {
+        Self { f }
+    }
+}
+
+impl<F> FrameSink for CallbackSink<F>
+where
+    F: FnMut(&Frame) -> Result<()> + Send,
+{
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        (self.f)(frame)
+    }
+}
+
+impl<F> fmt::Debug for CallbackSink<F>
+where
+    F: FnMut(&Frame) -> Result<()> + Send,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackSink").finish_non_exhaustive()
+    }
+}
+
+// --------------------------------------------------------------------------
+
+/// The format of the messages published by a [`ZmqPublisher`].
+#[cfg(feature = "zmq")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZmqFormat {
+    /// A CBOR map with the "seq", "sample_rate", "attrs", and "channels"
+    /// of the frame. The channels are a map of channel ID to an array
+    /// of samples.
+    Cbor,
+    /// A [`StreamHeader`] followed by the interleaved samples.
+    Raw(Encoding),
+}
+
+/// Options for a [`ZmqPublisher`].
+#[cfg(feature = "zmq")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZmqOptions {
+    /// The topic for the messages.
+    pub topic: String,
+    /// Whether to publish each channel as a separate message, with the
+    /// topic "<topic>/<channel ID>".
+    pub topic_per_channel: bool,
+    /// The message format
+    pub format: ZmqFormat,
+    /// Whether to only keep the latest message for slow subscribers.
+    ///
+    /// ZeroMQ can't conflate multi-part messages, so when set, the topic
+    /// is sent at the start of a single-part message, immediately
+    /// followed by the payload. Otherwise the topic and payload are sent
+    /// as a two-part message.
+    ///
+    /// A conflating socket keeps a single message regardless of topic, so
+    /// this can't be combined with `topic_per_channel`.
+    pub conflate: bool,
+}
+
+#[cfg(feature = "zmq")]
+impl Default for ZmqOptions {
+    fn default() -> Self {
+        Self {
+            topic: "iio".into(),
+            topic_per_channel: false,
+            format: ZmqFormat::Cbor,
+            conflate: false,
+        }
+    }
+}
+
+/// A sink that publishes frames on a ZeroMQ PUB socket.
+#[cfg(feature = "zmq")]
+pub struct ZmqPublisher {
+    /// The ZeroMQ context, which must outlive the socket
+    ctx: zmq::Context,
+    /// The PUB socket
+    sock: zmq::Socket,
+    /// The publisher options
+    opts: ZmqOptions,
+    /// The buffer for each message payload
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "zmq")]
+impl ZmqPublisher {
+    /// Creates a publisher bound to the endpoint, like "tcp://*:5555".
+    ///
+    /// This fails if the options ask to conflate messages with a topic
+    /// per channel, since the subscriber would only ever see the last
+    /// channel of each frame.
+    pub fn bind(endpoint: &str, opts: ZmqOptions) -> Result<Self> {
+        if opts.conflate && opts.topic_per_channel {
+            return Err(Error::General(
+                "ZeroMQ conflate can't be used with a topic per channel".into(),
+            ));
+        }
+        let ctx = zmq::Context::new();
+        let sock = ctx.socket(zmq::PUB).map_err(zmq_error)?;
+        sock.set_conflate(opts.conflate).map_err(zmq_error)?;
+        sock.bind(endpoint).map_err(zmq_error)?;
+        Ok(Self {
+            ctx,
+            sock,
+            opts,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Gets the ZeroMQ context of the publisher.
+    ///
+    /// Subscribers must use this context to connect to an "inproc://"
+    /// endpoint.
+    pub fn context(&self) -> &zmq::Context {
+        &self.ctx
+    }
+
+    /// Encodes the frame into the payload buffer.
+    fn encode(&mut self, frame: &Frame) -> Result<()> {
+        match self.opts.format {
+            ZmqFormat::Raw(enc) => encode_block(frame, 0, frame.len(), enc, &mut self.buf)?,
+            ZmqFormat::Cbor => {
+                use ciborium::value::Value;
+
+                let text = |s: &str| Value::Text(s.to_string());
+                let attrs = frame
+                    .attrs
+                    .iter()
+                    .map(|(k, v)| (text(k), text(v)))
+                    .collect();
+                let chans = frame
+                    .channels
+                    .iter()
+                    .map(|ch| {
+                        let samples = ch.samples.iter().map(|&x| Value::Float(x)).collect();
+                        (text(&ch.id), Value::Array(samples))
+                    })
+                    .collect();
+                let val = Value::Map(vec![
+                    (text("seq"), Value::Integer(frame.seq.into())),
+                    (
+                        text("sample_rate"),
+                        frame.sample_rate.map_or(Value::Null, Value::Float),
+                    ),
+                    (text("attrs"), Value::Map(attrs)),
+                    (text("channels"), Value::Map(chans)),
+                ]);
+
+                self.buf.clear();
+                ciborium::ser::into_writer(&val, &mut self.buf)
+                    .map_err(|err| Error::General(err.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Publishes the payload buffer with the topic.
+    fn publish(&mut self, topic: &str) -> Result<()> {
+        let res = if self.opts.conflate {
+            let mut msg = Vec::with_capacity(topic.len() + self.buf.len());
+            msg.extend_from_slice(topic.as_bytes());
+            msg.extend_from_slice(&self.buf);
+            self.sock.send(msg, 0)
+        }
+        else {
+            self.sock
+                .send_multipart([topic.as_bytes(), self.buf.as_slice()], 0)
+        };
+        res.map_err(zmq_error)
+    }
+}
+
+#[cfg(feature = "zmq")]
+impl FrameSink for ZmqPublisher {
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        if !self.opts.topic_per_channel {
+            self.encode(frame)?;
+            let topic = self.opts.topic.clone();
+            return self.publish(&topic);
+        }
+
+        for ch in &frame.channels {
+            let single = Frame {
+                channels: vec![ch.clone()],
+                ..frame.clone()
+            };
+            self.encode(&single)?;
+            let topic = format!("{}/{}", self.opts.topic, ch.id);
+            self.publish(&topic)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "zmq")]
+impl fmt::Debug for ZmqPublisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZmqPublisher")
+            .field("opts", &self.opts)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Converts a ZeroMQ error to a library error.
+///
+/// The errors that are specific to ZeroMQ don't have a system errno, so
+/// they're reported as general errors.
+#[cfg(feature = "zmq")]
+fn zmq_error(err: zmq::Error) -> Error {
+    use zmq::Error::*;
+    match err {
+        EFSM | ENOCOMPATPROTO | ETERM | EMTHREAD => Error::General(err.to_string()),
+        _ => nix::errno::Errno::from_raw(err.to_raw()).into(),
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pool::FramePool, FrameChannel};
+    use std::sync::{mpsc::Receiver, Mutex};
+
+    fn frame(seq: u64, a: &[f64], b: &[f64]) -> Frame {
+        Frame {
+            seq,
+            channels: vec![
+                FrameChannel::new("voltage0", a.to_vec()),
+                FrameChannel::new("voltage1", b.to_vec()),
+            ],
+            ..Frame::default()
+        }
+    }
+
+    fn collector() -> (Box<dyn FrameSink>, Arc<Mutex<Vec<u64>>>) {
+        let seqs = Arc::new(Mutex::new(Vec::new()));
+        let v = Arc::clone(&seqs);
+        let sink = CallbackSink::new(move |frame: &Frame| {
+            v.lock().unwrap().push(frame.seq);
+            Ok(())
+        });
+        (Box::new(sink), seqs)
+    }
+
+    #[test]
+    fn csv() {
+        let mut sink = CsvSink::new(Vec::new());
+        sink.write(&frame(0, &[1.0, 2.0], &[10.0, 20.5])).unwrap();
+        sink.write(&frame(1, &[3.0], &[30.0])).unwrap();
+
+        let s = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(s, "voltage0,voltage1\n1,10\n2,20.5\n3,30\n");
+    }
+
+    #[test]
+    fn csv_channel_mismatch() {
+        let mut sink = CsvSink::new(Vec::new());
+        sink.write(&frame(0, &[1.0], &[10.0])).unwrap();
+
+        let mut f = frame(1, &[2.0], &[20.0]);
+        f.channels[1].id = "voltage2".into();
+        assert!(matches!(sink.write(&f), Err(Error::InvalidValue(_))));
+
+        let s = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(s, "voltage0,voltage1\n1,10\n");
+    }
+
+    #[test]
+    fn rotating() {
+        let opened = Arc::new(Mutex::new(Vec::new()));
+        let v = Arc::clone(&opened);
+        let mut sink = RotatingSink::new(move |n| {
+            let (sink, seqs) = collector();
+            v.lock().unwrap().push((n, seqs));
+            Ok(sink)
+        })
+        .max_samples(3);
+
+        for seq in 0..5 {
+            sink.write(&frame(seq, &[1.0, 2.0], &[3.0, 4.0])).unwrap();
+        }
+        sink.flush().unwrap();
+        assert_eq!(sink.count(), 3);
+
+        let opened = opened.lock().unwrap();
+        let seqs: Vec<_> = opened
+            .iter()
+            .map(|(n, seqs)| (*n, seqs.lock().unwrap().clone()))
+            .collect();
+        assert_eq!(seqs, vec![(0, vec![0, 1]), (1, vec![2, 3]), (2, vec![4])]);
+    }
+
+    #[test]
+    fn udp_stream() {
+        let rx = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut tx = UdpStreamer::connect(rx.local_addr().unwrap())
+            .unwrap()
+            .encoding(Encoding::Raw);
+
+        let mut f = frame(7, &[1.0, -2.0], &[10.0, 20.0]);
+        f.channels[0].index = Some(0);
+        f.channels[1].index = Some(3);
+        tx.write(&f).unwrap();
+
+        let mut buf = [0u8; 1024];
+        let n = rx.recv(&mut buf).unwrap();
+        assert_eq!(n, StreamHeader::SIZE + 4 * 4);
+
+        let hdr = StreamHeader::parse(&buf).unwrap();
+        assert_eq!(hdr.seq, 7);
+        assert_eq!(hdr.channel_mask, 0b1001);
+        assert_eq!(hdr.flags, 0);
+        assert_eq!(hdr.offset, 0);
+        assert_eq!(hdr.num_samples, 2);
+        assert_eq!(hdr.num_channels, 2);
+        assert_eq!(hdr.encoding, Encoding::Raw as u8);
+
+        let data = &buf[StreamHeader::SIZE..n];
+        let x: Vec<_> = data
+            .chunks(4)
+            .map(|b| i32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(x, vec![1, 10, -2, 20]);
+
+        // Without an index, the first channel can't be mistaken for the
+        // channel at index 1.
+        f.channels[0].index = None;
+        f.channels[1].index = Some(1);
+        tx.write(&f).unwrap();
+        rx.recv(&mut buf).unwrap();
+        let hdr = StreamHeader::parse(&buf).unwrap();
+        assert_eq!(hdr.channel_mask, 0b11);
+        assert_eq!(hdr.flags, StreamHeader::UNINDEXED);
+    }
+
+    #[cfg(feature = "zmq")]
+    #[test]
+    fn zmq_publish() {
+        let endpoint = "inproc://iio-sink-test";
+        let opts = ZmqOptions {
+            topic_per_channel: true,
+            format: ZmqFormat::Raw(Encoding::Converted),
+            ..ZmqOptions::default()
+        };
+        let mut publisher = ZmqPublisher::bind(endpoint, opts).unwrap();
+
+        let sub = publisher.context().socket(zmq::SUB).unwrap();
+        sub.connect(endpoint).unwrap();
+        sub.set_subscribe(b"iio/voltage1").unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        publisher.write(&frame(3, &[1.0], &[2.0])).unwrap();
+
+        let msg = sub.recv_multipart(0).unwrap();
+        assert_eq!(msg[0], b"iio/voltage1");
+        let hdr = StreamHeader::parse(&msg[1]).unwrap();
+        assert_eq!(hdr.seq, 3);
+        assert_eq!(hdr.num_channels, 1);
+        assert_eq!(&msg[1][StreamHeader::SIZE..], &2.0f64.to_le_bytes());
+    }
+
+    #[cfg(feature = "zmq")]
+    #[test]
+    fn zmq_conflate_per_channel() {
+        let opts = ZmqOptions {
+            topic_per_channel: true,
+            conflate: true,
+            ..ZmqOptions::default()
+        };
+        assert!(ZmqPublisher::bind("inproc://iio-sink-conflate", opts).is_err());
+    }
+
+    #[test]
+    fn tagged_routing() {
+        let mut pipeline = Pipeline::new();
+        let (all, all_seqs) = collector();
+        let (rf, rf_seqs) = collector();
+        pipeline.add_sink(all);
+        pipeline.add_tagged_sink(&["rf"], rf);
+
+        pipeline.send(frame(1, &[1.0], &[1.0]));
+        pipeline.send_tagged("rf", frame(2, &[1.0], &[1.0]));
+        pipeline.send_tagged("other", frame(3, &[1.0], &[1.0]));
+        pipeline.close().unwrap();
+
+        assert_eq!(*all_seqs.lock().unwrap(), vec![1]);
+        assert_eq!(*rf_seqs.lock().unwrap(), vec![2]);
+    }
+
+    #[test]
+    fn swap_sinks() {
+        let mut pipeline = Pipeline::new();
+        let (old, old_seqs) = collector();
+        let (new, new_seqs) = collector();
+        let (other, other_seqs) = collector();
+        pipeline.add_tagged_sink(&["rf"], old);
+        pipeline.add_sink(other);
+
+        pipeline.send_tagged("rf", frame(1, &[1.0], &[1.0]));
+        pipeline.replace_sink(0, new).unwrap();
+        pipeline.send_tagged("rf", frame(2, &[1.0], &[1.0]));
+        pipeline.send(frame(3, &[1.0], &[1.0]));
+
+        // The old sink is done as soon as it's replaced.
+        assert_eq!(*old_seqs.lock().unwrap(), vec![1]);
+
+        pipeline.remove_sink(1).unwrap();
+        assert_eq!(pipeline.num_sinks(), 1);
+        assert!(pipeline.remove_sink(1).is_err());
+        pipeline.send(frame(4, &[1.0], &[1.0]));
+        pipeline.close().unwrap();
+
+        assert_eq!(*new_seqs.lock().unwrap(), vec![2]);
+        assert_eq!(*other_seqs.lock().unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn pause_resume() {
+        let mut pipeline = Pipeline::new();
+        let (sink, seqs) = collector();
+        pipeline.add_sink(sink);
+
+        pipeline.send(frame(1, &[1.0], &[1.0]));
+        pipeline.pause();
+        assert!(pipeline.is_paused());
+        pipeline.send(frame(2, &[1.0], &[1.0]));
+        pipeline.send(frame(3, &[1.0], &[1.0]));
+        pipeline.resume();
+        pipeline.send(frame(4, &[1.0], &[1.0]));
+
+        assert_eq!(pipeline.skipped(), 2);
+        assert_eq!(pipeline.num_sinks(), 1);
+        pipeline.close().unwrap();
+        assert_eq!(*seqs.lock().unwrap(), vec![1, 4]);
+    }
+
+    #[test]
+    fn pooled_frames() {
+        let pool = FramePool::new(4);
+        let mut pipeline = Pipeline::new();
+        let (sink, seqs) = collector();
+        pipeline.add_sink(sink);
+
+        pipeline.send(pool.lease(frame(1, &[1.0], &[1.0])));
+        pipeline.close().unwrap();
+
+        assert_eq!(*seqs.lock().unwrap(), vec![1]);
+        assert_eq!(pool.available(), 2);
+    }
+
+    #[test]
+    fn processors() {
+        let mut pipeline = Pipeline::new();
+        let (sink, seqs) = collector();
+        pipeline.add_sink(sink);
+        pipeline.add_processor(Box::new(|set: &mut FrameSet| {
+            let f = set.frames[0].clone();
+            match f.seq {
+                1 => set.frames.clear(),
+                2 => set.push(Frame { seq: 20, ..f }),
+                3 => return Err(Error::General("bad frame".into())),
+                _ => {}
+            }
+            Ok(())
+        }));
+        assert_eq!(pipeline.num_processors(), 1);
+
+        for seq in 0..5 {
+            pipeline.send(frame(seq, &[1.0], &[1.0]));
+        }
+        assert!(pipeline.close().is_err());
+        assert_eq!(*seqs.lock().unwrap(), vec![0, 2, 20, 4]);
+    }
+
+    #[test]
+    fn ring_queues() {
+        let mut pipeline = Pipeline::new().use_rings(true);
+        let (sink, seqs) = collector();
+        pipeline.add_sink(sink);
+
+        for seq in 0..5 {
+            pipeline.send(frame(seq, &[1.0], &[1.0]));
+        }
+        pipeline.close().unwrap();
+        assert_eq!(*seqs.lock().unwrap(), (0..5).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn slow_sink_isolation() {
+        let (fast, fast_seqs) = collector();
+
+        // A sink that blocks until told to continue
+        let (go_tx, go_rx) = mpsc::channel::<()>();
+        let go_rx = Mutex::new(go_rx);
+        let slow = CallbackSink::new(move |_: &Frame| {
+            let rx: &Receiver<()> = &go_rx.lock().unwrap();
+            let _ = rx.recv();
+            Ok(())
+        });
+
+        let mut pipeline = Pipeline::new().queue_len(16);
+        let fast_idx = pipeline.add_sink(fast);
+        let mut pipeline = pipeline.queue_len(1);
+        let slow_idx = pipeline.add_sink(Box::new(slow));
+
+        for seq in 0..10 {
+            pipeline.send(frame(seq, &[1.0], &[1.0]));
+        }
+        assert_eq!(pipeline.dropped(fast_idx), Some(0));
+        assert!(pipeline.dropped(slow_idx).unwrap() >= 8);
+
+        drop(go_tx);
+        pipeline.close().unwrap();
+        assert_eq!(*fast_seqs.lock().unwrap(), (0..10).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,263 @@
+// industrial-io/src/sw_trigger.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Software triggers.
+//!
+//! A triggered buffer needs a trigger, but many boards don't have a
+//! hardware one. The kernel can provide software triggers instead:
+//!
+//! - An **hrtimer** trigger fires periodically from a high-resolution
+//!   timer, at the rate set with [`Trigger::set_frequency()`]. These are
+//!   created by making a directory under
+//!   `/sys/kernel/config/iio/triggers/hrtimer/`, and need the
+//!   `iio-trig-hrtimer` module and configfs to be mounted.
+//! - A **sysfs** trigger fires each time it is written from user space.
+//!   These are created through the `iio_sysfs_trigger` device, and need
+//!   the `iio-trig-sysfs` module.
+//!
+//! A [`SoftTrigger`] creates one of these, finds the resulting
+//! [`Trigger`] device, and removes the trigger again when it's destroyed
+//! or dropped. This only works with the local backend, and generally
+//! needs root permissions.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::sw_trigger::SoftTrigger;
+//!
+//! let ctx = iio::Context::with_backend(iio::Backend::Local).unwrap();
+//! let dev = ctx.find_device("ads1015").unwrap();
+//!
+//! let trig = SoftTrigger::create_hrtimer(&ctx, "capture").unwrap();
+//! trig.set_frequency(100.0).unwrap();
+//! dev.set_trigger(&trig).unwrap();
+//! ```
+
+use crate::{Backend, Context, Device, Error, Operation, Result, Trigger};
+use std::{fs, io, ops::Deref, path::PathBuf, thread, time::Duration};
+
+/// The configfs directory for hrtimer triggers.
+pub const HRTIMER_DIR: &str = "/sys/kernel/config/iio/triggers/hrtimer";
+
+/// The sysfs directory of the device that creates sysfs triggers.
+pub const SYSFS_TRIGGER_DIR: &str = "/sys/bus/iio/devices/iio_sysfs_trigger";
+
+/// The kind of software trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoftTriggerKind {
+    /// A periodic, high-resolution timer trigger.
+    HrTimer,
+    /// A trigger fired by writing to its `trigger_now` attribute.
+    Sysfs,
+}
+
+/// A software trigger, created by the application.
+///
+/// This dereferences to the [`Trigger`] device. By default the trigger is
+/// removed from the system when this is dropped.
+#[derive(Debug)]
+pub struct SoftTrigger {
+    /// The kind of trigger
+    kind: SoftTriggerKind,
+    /// The name of an hrtimer trigger, or the ID of a sysfs trigger
+    name: String,
+    /// The trigger device
+    trig: Trigger,
+    /// Whether to remove the trigger when dropped
+    remove_on_drop: bool,
+}
+
+impl SoftTrigger {
+    /// Creates an hrtimer trigger with the specified name.
+    ///
+    /// The context is used to check that the local backend is in use.
+    /// The trigger is found in a new local context, since the existing
+    /// one won't know about it.
+    pub fn create_hrtimer(ctx: &Context, name: &str) -> Result<Self> {
+        check_local(ctx)?;
+        validate_name(name)?;
+
+        let dir = PathBuf::from(HRTIMER_DIR).join(name);
+        fs::create_dir(&dir)?;
+
+        match find_trigger(name) {
+            Ok(trig) => Ok(Self {
+                kind: SoftTriggerKind::HrTimer,
+                name: name.to_string(),
+                trig,
+                remove_on_drop: true,
+            }),
+            Err(err) => {
+                let _ = fs::remove_dir(&dir);
+                Err(err)
+            }
+        }
+    }
+
+    /// Creates a sysfs trigger with the specified ID.
+    ///
+    /// The kernel names the trigger `sysfstrig<id>`. The context is used to
+    /// check that the local backend is in use.
+    pub fn create_sysfs(ctx: &Context, id: u32) -> Result<Self> {
+        check_local(ctx)?;
+
+        let dir = PathBuf::from(SYSFS_TRIGGER_DIR);
+        fs::write(dir.join("add_trigger"), id.to_string())?;
+
+        match find_trigger(&format!("sysfstrig{}", id)) {
+            Ok(trig) => Ok(Self {
+                kind: SoftTriggerKind::Sysfs,
+                name: id.to_string(),
+                trig,
+                remove_on_drop: true,
+            }),
+            Err(err) => {
+                let _ = fs::write(dir.join("remove_trigger"), id.to_string());
+                Err(err)
+            }
+        }
+    }
+
+    /// Sets whether the trigger is removed from the system when this
+    /// object is dropped.
+    ///
+    /// This is on by default. Turn it off to leave the trigger in place
+    /// for other applications.
+    pub fn remove_on_drop(mut self, on: bool) -> Self {
+        self.remove_on_drop = on;
+        self
+    }
+
+    /// Gets the kind of trigger.
+    pub fn kind(&self) -> SoftTriggerKind {
+        self.kind
+    }
+
+    /// Gets the trigger device.
+    pub fn trigger(&self) -> &Trigger {
+        &self.trig
+    }
+
+    /// Fires a sysfs trigger once.
+    ///
+    /// This fails for an hrtimer trigger, which fires on its own.
+    pub fn fire(&self) -> Result<()> {
+        match self.kind {
+            SoftTriggerKind::Sysfs => self.trig.attr_write_int("trigger_now", 1),
+            SoftTriggerKind::HrTimer => Err(Error::General(
+                "An hrtimer trigger can't be fired manually".into(),
+            )),
+        }
+    }
+
+    /// Removes the trigger from the system.
+    ///
+    /// This fails if the trigger is still in use, such as when it's
+    /// assigned to a device.
+    pub fn destroy(mut self) -> Result<()> {
+        self.remove_on_drop = false;
+        self.remove()
+    }
+
+    /// Removes the trigger.
+    fn remove(&self) -> Result<()> {
+        match self.kind {
+            SoftTriggerKind::HrTimer => fs::remove_dir(PathBuf::from(HRTIMER_DIR).join(&self.name)),
+            SoftTriggerKind::Sysfs => fs::write(
+                PathBuf::from(SYSFS_TRIGGER_DIR).join("remove_trigger"),
+                &self.name,
+            ),
+        }
+        .map_err(Error::from)
+    }
+}
+
+impl Deref for SoftTrigger {
+    type Target = Trigger;
+
+    fn deref(&self) -> &Trigger {
+        &self.trig
+    }
+}
+
+impl AsRef<Device> for SoftTrigger {
+    fn as_ref(&self) -> &Device {
+        self.trig.device()
+    }
+}
+
+impl Drop for SoftTrigger {
+    fn drop(&mut self) {
+        if self.remove_on_drop {
+            let _ = self.remove();
+        }
+    }
+}
+
+/// Returns an error if the context doesn't use the local backend.
+fn check_local(ctx: &Context) -> Result<()> {
+    if ctx.name() == "local" {
+        Ok(())
+    }
+    else {
+        Err(Error::NotSupported {
+            backend: ctx.name(),
+            op: Operation::SoftwareTriggers,
+        })
+    }
+}
+
+/// Checks that a name can be used as a configfs directory.
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\0']) {
+        Err(Error::General(format!("Invalid trigger name: '{}'", name)))
+    }
+    else {
+        Ok(())
+    }
+}
+
+/// Finds a newly created trigger, in a new local context.
+///
+/// The kernel may take a moment to register the device, so this retries
+/// a few times before giving up.
+fn find_trigger(name: &str) -> Result<Trigger> {
+    const RETRIES: u32 = 10;
+    const RETRY_DELAY: Duration = Duration::from_millis(20);
+
+    for _ in 0..RETRIES {
+        let ctx = Context::with_backend(Backend::Local)?;
+        if let Some(trig) = ctx.find_trigger(name) {
+            return Ok(trig);
+        }
+        thread::sleep(RETRY_DELAY);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Trigger not found after creation: '{}'", name),
+    )
+    .into())
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_names() {
+        assert!(validate_name("capture").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("..").is_err());
+        assert!(validate_name("a/b").is_err());
+    }
+}
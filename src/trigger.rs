@@ -0,0 +1,401 @@
+// industrial-io/src/trigger.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! IIO triggers.
+//!
+//! A trigger is a special kind of IIO device, like an `hrtimer` or a
+//! data-ready interrupt, that signals another device to take a sample
+//! for a triggered buffer. A [`Trigger`] wraps a [`Device`] that is known
+//! to be a trigger, and gives typed access to its sampling frequency, so
+//! that applications don't need to juggle raw attribute strings to set up
+//! a buffered capture.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ads1015").unwrap();
+//!
+//! let trig = ctx.find_trigger("trigger0").unwrap();
+//! trig.set_frequency(100.0).unwrap();
+//! dev.set_trigger(&trig).unwrap();
+//! ```
+
+use crate::{
+    abi::dev::{SAMPLING_FREQUENCY, TRIGGER_NOW},
+    ffi, sys_result, Context, Device, DeviceIterator, Error, Result,
+};
+use nix::errno::Errno;
+use std::{fmt, ops::Deref, ptr};
+
+/// The kind of a trigger, as best it can be told from its name and
+/// attributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TriggerKind {
+    /// A periodic, high-resolution timer, with a settable frequency
+    HrTimer,
+    /// A trigger fired by the application, through its `trigger_now`
+    /// attribute
+    Sysfs,
+    /// A hardware interrupt, like an `irqtrig` or the data-ready signal
+    /// of a device
+    Interrupt,
+    /// Any other kind of trigger
+    Other,
+}
+
+impl TriggerKind {
+    /// Determines the kind of trigger from its name, and whether it has
+    /// the `trigger_now` and sampling frequency attributes.
+    fn classify(name: &str, has_trigger_now: bool, has_freq: bool) -> Self {
+        let is_data_ready = name
+            .rsplit_once("-dev")
+            .is_some_and(|(_, n)| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+
+        if has_trigger_now || name.starts_with("sysfstrig") {
+            TriggerKind::Sysfs
+        }
+        else if name.starts_with("irqtrig") || is_data_ready {
+            TriggerKind::Interrupt
+        }
+        else if has_freq {
+            TriggerKind::HrTimer
+        }
+        else {
+            TriggerKind::Other
+        }
+    }
+}
+
+impl fmt::Display for TriggerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            TriggerKind::HrTimer => "hrtimer",
+            TriggerKind::Sysfs => "sysfs",
+            TriggerKind::Interrupt => "interrupt",
+            TriggerKind::Other => "other",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// An IIO trigger.
+///
+/// This dereferences to the underlying [`Device`], for access to the
+/// other attributes of the trigger.
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    /// The trigger device
+    dev: Device,
+}
+
+impl Trigger {
+    /// Wraps a device that is a trigger.
+    ///
+    /// This fails if the device is not a trigger.
+    pub fn new(dev: Device) -> Result<Self> {
+        if !dev.is_trigger() {
+            return Err(Error::General(format!(
+                "Device is not a trigger: '{}'",
+                dev.id().unwrap_or_default()
+            )));
+        }
+        Ok(Self { dev })
+    }
+
+    /// Gets the underlying device.
+    pub fn device(&self) -> &Device {
+        &self.dev
+    }
+
+    /// Consumes the trigger, returning the underlying device.
+    pub fn into_device(self) -> Device {
+        self.dev
+    }
+
+    /// Determines if the frequency of the trigger can be set.
+    ///
+    /// Some triggers, like those driven by a hardware interrupt, fire at a
+    /// rate that is set elsewhere, if at all.
+    pub fn has_frequency(&self) -> bool {
+        self.dev.has_attr(SAMPLING_FREQUENCY)
+    }
+
+    /// Gets the frequency of the trigger, in Hz.
+    pub fn frequency(&self) -> Result<f64> {
+        self.dev.attr_read_float(SAMPLING_FREQUENCY)
+    }
+
+    /// Gets the lowest and highest frequencies that the trigger accepts,
+    /// in Hz, if the driver reports them.
+    pub fn frequency_range(&self) -> Result<Option<(f64, f64)>> {
+        Ok(self
+            .dev
+            .attr_available(SAMPLING_FREQUENCY)?
+            .and_then(|avail| avail.bounds()))
+    }
+
+    /// Sets the frequency of the trigger, in Hz.
+    ///
+    /// The frequency must be positive and finite, and the trigger must
+    /// have a settable frequency. If the trigger reports the frequencies
+    /// that it accepts, the value is checked against them before it is
+    /// written.
+    pub fn set_frequency(&self, hz: f64) -> Result<()> {
+        check_frequency(hz)?;
+        if !self.has_frequency() {
+            return Err(Error::General(format!(
+                "Trigger has no settable frequency: '{}'",
+                self.dev.id().unwrap_or_default()
+            )));
+        }
+        self.dev.validated_write(SAMPLING_FREQUENCY, hz)
+    }
+
+    /// Gets the kind of the trigger.
+    ///
+    /// This is a best guess from the name and attributes of the trigger,
+    /// since the kernel doesn't report it directly.
+    pub fn kind(&self) -> TriggerKind {
+        TriggerKind::classify(
+            &self.dev.name().unwrap_or_default(),
+            self.dev.has_attr(TRIGGER_NOW),
+            self.has_frequency(),
+        )
+    }
+
+    /// Determines if the trigger can be fired by the application.
+    pub fn can_fire(&self) -> bool {
+        self.dev.has_attr(TRIGGER_NOW)
+    }
+
+    /// Fires the trigger once, by writing its `trigger_now` attribute.
+    ///
+    /// This lets the application pace an acquisition, taking one sample
+    /// with each call. It fails for a trigger that can't be fired
+    /// manually, like a timer or interrupt.
+    pub fn fire(&self) -> Result<()> {
+        if !self.can_fire() {
+            return Err(Error::General(format!(
+                "Trigger can't be fired manually: '{}'",
+                self.dev.id().unwrap_or_default()
+            )));
+        }
+        self.dev.attr_write_int(TRIGGER_NOW, 1)
+    }
+}
+
+impl Deref for Trigger {
+    type Target = Device;
+
+    fn deref(&self) -> &Device {
+        &self.dev
+    }
+}
+
+impl AsRef<Device> for Trigger {
+    fn as_ref(&self) -> &Device {
+        &self.dev
+    }
+}
+
+impl TryFrom<Device> for Trigger {
+    type Error = Error;
+
+    fn try_from(dev: Device) -> Result<Self> {
+        Self::new(dev)
+    }
+}
+
+impl From<Trigger> for Device {
+    fn from(trig: Trigger) -> Self {
+        trig.dev
+    }
+}
+
+/// A device of a context, told apart by whether it is a trigger.
+///
+/// This lets code that walks the devices of a context handle triggers
+/// and the devices they drive separately, with the right type for each.
+/// See [`DeviceIterator::by_kind()`].
+#[derive(Debug, Clone)]
+pub enum DeviceKindRef {
+    /// A trigger device
+    Trigger(Trigger),
+    /// Any other device, like a sensor, ADC, or DAC
+    Sensor(Device),
+}
+
+impl DeviceKindRef {
+    /// Gets the underlying device, whatever its kind.
+    pub fn device(&self) -> &Device {
+        match self {
+            DeviceKindRef::Trigger(trig) => trig.device(),
+            DeviceKindRef::Sensor(dev) => dev,
+        }
+    }
+
+    /// Gets the trigger, if the device is one.
+    pub fn as_trigger(&self) -> Option<&Trigger> {
+        match self {
+            DeviceKindRef::Trigger(trig) => Some(trig),
+            DeviceKindRef::Sensor(_) => None,
+        }
+    }
+
+    /// Gets the device, if it is not a trigger.
+    pub fn as_sensor(&self) -> Option<&Device> {
+        match self {
+            DeviceKindRef::Trigger(_) => None,
+            DeviceKindRef::Sensor(dev) => Some(dev),
+        }
+    }
+}
+
+impl From<Device> for DeviceKindRef {
+    fn from(dev: Device) -> Self {
+        if dev.is_trigger() {
+            DeviceKindRef::Trigger(Trigger { dev })
+        }
+        else {
+            DeviceKindRef::Sensor(dev)
+        }
+    }
+}
+
+impl<'a> DeviceIterator<'a> {
+    /// Converts the iterator to one that tells the triggers apart from
+    /// the other devices.
+    pub fn by_kind(self) -> impl Iterator<Item = DeviceKindRef> + 'a {
+        self.map(DeviceKindRef::from)
+    }
+}
+
+/// Checks that a sampling frequency is positive and finite.
+fn check_frequency(hz: f64) -> Result<()> {
+    if !hz.is_finite() || hz <= 0.0 {
+        return Err(Error::General(format!(
+            "Invalid sampling frequency: {}",
+            hz
+        )));
+    }
+    Ok(())
+}
+
+impl Device {
+    /// Gets the device as a trigger, if it is one.
+    pub fn as_trigger(&self) -> Option<Trigger> {
+        Trigger::new(self.clone()).ok()
+    }
+
+    /// Gets the trigger currently associated with the device.
+    pub fn trigger(&self) -> Result<Trigger> {
+        let mut trig: *const ffi::iio_device = ptr::null();
+        let ret = unsafe { ffi::iio_device_get_trigger(self.dev, &mut trig) };
+        sys_result(ret, ())?;
+        if trig.is_null() {
+            return Err(Errno::ENODEV.into());
+        }
+        Trigger::new(Device {
+            dev: trig.cast_mut(),
+            ctx: self.context(),
+        })
+    }
+
+    /// Gets the trigger assigned to the device, if it has a settable
+    /// frequency.
+    fn clock_trigger(&self) -> Option<Trigger> {
+        self.trigger().ok().filter(|trig| trig.has_frequency())
+    }
+
+    /// Gets the rate at which the device is sampled, in Hz.
+    ///
+    /// This is the frequency of the trigger assigned to the device, if it
+    /// has one, otherwise the sampling frequency of the device itself. See
+    /// [`set_sampling_frequency()`](Device::set_sampling_frequency).
+    pub fn sampling_frequency(&self) -> Result<f64> {
+        match self.clock_trigger() {
+            Some(trig) => trig.frequency(),
+            None => self.attr_read_float(SAMPLING_FREQUENCY),
+        }
+    }
+
+    /// Sets the rate at which the device is sampled, in Hz, returning the
+    /// rate that the driver actually set.
+    ///
+    /// A triggered device takes a sample each time its trigger fires, so
+    /// if the device has a trigger with a settable frequency, that is
+    /// set. Otherwise the `sampling_frequency` attribute of the device
+    /// itself is set. Either way, the value is checked against the
+    /// frequencies that the driver reports it accepts, if any, then read
+    /// back, since drivers often round it to the nearest one they support.
+    pub fn set_sampling_frequency(&self, hz: f64) -> Result<f64> {
+        check_frequency(hz)?;
+        match self.clock_trigger() {
+            Some(trig) => {
+                trig.set_frequency(hz)?;
+                trig.frequency()
+            }
+            None if self.has_attr(SAMPLING_FREQUENCY) => {
+                self.validated_write(SAMPLING_FREQUENCY, hz)?;
+                self.attr_read_float(SAMPLING_FREQUENCY)
+            }
+            None => Err(Error::General(format!(
+                "Device has no settable sampling frequency: '{}'",
+                self.id().unwrap_or_default()
+            ))),
+        }
+    }
+}
+
+impl Context {
+    /// Finds a trigger by ID, name, or label.
+    ///
+    /// Returns `None` if there is no such device, or if it isn't a
+    /// trigger.
+    pub fn find_trigger(&self, name: &str) -> Option<Trigger> {
+        self.find_device(name).and_then(|dev| dev.as_trigger())
+    }
+
+    /// Gets all the triggers in the context.
+    pub fn triggers(&self) -> Vec<Trigger> {
+        self.devices()
+            .by_kind()
+            .filter_map(|kind| match kind {
+                DeviceKindRef::Trigger(trig) => Some(trig),
+                DeviceKindRef::Sensor(_) => None,
+            })
+            .collect()
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_kind() {
+        use TriggerKind::*;
+        assert_eq!(TriggerKind::classify("sysfstrig0", false, false), Sysfs);
+        assert_eq!(TriggerKind::classify("mytrig", true, false), Sysfs);
+        assert_eq!(TriggerKind::classify("irqtrig12", false, false), Interrupt);
+        assert_eq!(
+            TriggerKind::classify("ads1015-dev0", false, false),
+            Interrupt
+        );
+        assert_eq!(TriggerKind::classify("capture", false, true), HrTimer);
+        assert_eq!(TriggerKind::classify("ads1015-devx", false, false), Other);
+        assert_eq!(HrTimer.to_string(), "hrtimer");
+    }
+}
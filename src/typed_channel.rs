@@ -0,0 +1,142 @@
+// industrial-io/src/typed_channel.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Channels with a known sample type.
+//!
+//! The sample functions of a [`Channel`], like [`Channel::read()`] and
+//! [`Channel::convert()`], check that the requested type matches the
+//! data format of the channel on every call, which queries the format
+//! from the library each time. In a tight capture loop, that's repeated
+//! work for an answer that never changes.
+//!
+//! A [`TypedChannel`] checks the type once, when it is created, and then
+//! reads, writes, and converts samples of that type without checking
+//! again. A mismatch is reported up front, as [`Error::WrongDataType`].
+//! The layout of the samples in each buffer is still checked, since it
+//! depends on which channels are enabled, but that doesn't need the
+//! data format.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::TypedChannel;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ads1015").unwrap();
+//! let chan = dev.find_input_channel("voltage0").unwrap();
+//! chan.enable();
+//!
+//! let chan = TypedChannel::<i16>::new(chan).unwrap();
+//! let mut buf = dev.create_buffer(128, false).unwrap();
+//!
+//! for _ in 0..10 {
+//!     buf.refill().unwrap();
+//!     let samples = chan.read(&buf).unwrap();
+//!     println!("{:?}", samples);
+//! }
+//! ```
+
+use crate::{Buffer, Channel, Error, Result};
+use std::{marker::PhantomData, ops::Deref};
+
+/// A channel with samples of type `T`.
+///
+/// This dereferences to the underlying [`Channel`], for the attributes
+/// and other functions that don't depend on the sample type. The sample
+/// type can't change behind it, since a data format override with
+/// [`Channel::with_data_format()`] consumes the channel and applies only
+/// to the handle that it returns.
+#[derive(Debug, Clone)]
+pub struct TypedChannel<T> {
+    /// The channel
+    chan: Channel,
+    /// The sample type
+    _typ: PhantomData<T>,
+}
+
+impl<T> TypedChannel<T>
+where
+    T: Default + Copy + 'static,
+{
+    /// Wraps a channel with samples of type `T`.
+    ///
+    /// This fails with [`Error::WrongDataType`] if the samples of the
+    /// channel are not of type `T`, including size and sign.
+    pub fn new(chan: Channel) -> Result<Self> {
+        chan.check_type::<T>()?;
+        Ok(Self {
+            chan,
+            _typ: PhantomData,
+        })
+    }
+
+    /// Gets the underlying channel.
+    pub fn into_inner(self) -> Channel {
+        self.chan
+    }
+
+    /// Demultiplex and convert the samples of the channel.
+    ///
+    /// This fails if the samples of the channel can't be accessed as type
+    /// `T` in the buffer. See [`Buffer::check_layout()`].
+    pub fn read(&self, buf: &Buffer) -> Result<Vec<T>> {
+        buf.check_layout::<T>(&self.chan)?;
+        self.chan.read_unchecked(buf)
+    }
+
+    /// Demultiplex the samples of the channel, without converting them.
+    pub fn read_raw(&self, buf: &Buffer) -> Result<Vec<T>> {
+        buf.check_layout::<T>(&self.chan)?;
+        self.chan.read_raw_unchecked(buf)
+    }
+
+    /// Convert and multiplex the samples of the channel.
+    /// Returns the number of items written.
+    pub fn write(&self, buf: &Buffer, data: &[T]) -> Result<usize> {
+        buf.check_layout::<T>(&self.chan)?;
+        Ok(self.chan.write_unchecked(buf, data))
+    }
+
+    /// Multiplex the samples of the channel, without converting them.
+    /// Returns the number of items written.
+    pub fn write_raw(&self, buf: &Buffer, data: &[T]) -> Result<usize> {
+        buf.check_layout::<T>(&self.chan)?;
+        Ok(self.chan.write_raw_unchecked(buf, data))
+    }
+
+    /// Converts a single sample from the hardware format to the host
+    /// format.
+    pub fn convert(&self, val: T) -> T {
+        self.chan.convert_unchecked(val)
+    }
+
+    /// Converts a sample from the host format to the hardware format.
+    pub fn convert_inverse(&self, val: T) -> T {
+        self.chan.convert_inverse_unchecked(val)
+    }
+}
+
+impl<T> Deref for TypedChannel<T> {
+    type Target = Channel;
+
+    fn deref(&self) -> &Channel {
+        &self.chan
+    }
+}
+
+impl<T> TryFrom<Channel> for TypedChannel<T>
+where
+    T: Default + Copy + 'static,
+{
+    type Error = Error;
+
+    fn try_from(chan: Channel) -> Result<Self> {
+        Self::new(chan)
+    }
+}
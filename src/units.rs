@@ -0,0 +1,380 @@
+// industrial-io/src/units.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! The units of channel values.
+//!
+//! The kernel ABI defines the units of each type of channel, after the
+//! `offset` and `scale` are applied to the `raw` value. These are not
+//! always SI units; voltages are in millivolts and temperatures in
+//! milli-degrees Celsius, for example. A [`Unit`] describes the ABI units
+//! for a type of channel, and how to convert them to the base units.
+//!
+//! The electrochemical channels used for water quality monitoring follow
+//! the same pattern:
+//!
+//! * **Ph** - Unitless pH.
+//! * **ElectricalConductivity** - Siemens per meter.
+//! * **Concentration** - Percent, which can be converted to parts per
+//!   million with [`percent_to_ppm()`].
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("atlas-ph-sensor").unwrap();
+//! let chan = dev.find_input_channel("ph").unwrap();
+//!
+//! let val = iio::units::read(&chan).unwrap();
+//! println!("{}", val);
+//! ```
+//!
+//! Attributes that hold a time or a frequency have their units set by the
+//! ABI as well, which can differ from one attribute to the next. These are
+//! found by name with [`attr_unit()`], and used by functions like
+//! [`Device::attr_read_duration()`](crate::Device::attr_read_duration) and
+//! [`Device::attr_read_freq()`](crate::Device::attr_read_freq) to give a
+//! [`Duration`] or a [`Hertz`] value, which avoids mixing up the
+//! magnitudes.
+
+use crate::{abi, ops::ChannelOps, ChannelType, Error, Result, ToAttribute};
+use std::{fmt, time::Duration};
+
+/// The units of the values of a type of channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    /// The symbol for the units used by the ABI, like "mV"
+    pub symbol: &'static str,
+    /// The symbol for the base units, like "V"
+    pub si_symbol: &'static str,
+    /// The factor to convert from the ABI units to the base units
+    pub si_factor: f64,
+}
+
+impl Unit {
+    /// Creates a unit that is its own base unit.
+    const fn base(symbol: &'static str) -> Self {
+        Self::scaled(symbol, symbol, 1.0)
+    }
+
+    /// Creates a unit that is a multiple of a base unit.
+    const fn scaled(symbol: &'static str, si_symbol: &'static str, si_factor: f64) -> Self {
+        Self {
+            symbol,
+            si_symbol,
+            si_factor,
+        }
+    }
+
+    /// Converts a value in these units to the base units.
+    pub fn to_si(&self, val: f64) -> f64 {
+        val * self.si_factor
+    }
+
+    /// Converts a value in the base units to these units.
+    pub fn from_si(&self, val: f64) -> f64 {
+        val / self.si_factor
+    }
+}
+
+impl ChannelType {
+    /// Gets the units of the values of this type of channel, per the
+    /// kernel ABI, if they are defined.
+    pub fn unit(&self) -> Option<Unit> {
+        use ChannelType::*;
+        let unit = match self {
+            Voltage | AltVoltage => Unit::scaled("mV", "V", 1e-3),
+            Current => Unit::scaled("mA", "A", 1e-3),
+            Power => Unit::scaled("mW", "W", 1e-3),
+            Accel => Unit::base("m/s²"),
+            AnglVel => Unit::base("rad/s"),
+            Magn => Unit::scaled("G", "T", 1e-4),
+            Ligtht => Unit::base("lx"),
+            Temp => Unit::scaled("m°C", "°C", 1e-3),
+            Incli | Rot => Unit::base("°"),
+            Angl => Unit::base("rad"),
+            Capacitance => Unit::scaled("nF", "F", 1e-9),
+            Pressure => Unit::scaled("kPa", "Pa", 1e3),
+            HumidityRelative => Unit::scaled("m%", "%", 1e-3),
+            Energy => Unit::base("J"),
+            Distance => Unit::base("m"),
+            Velocity => Unit::base("m/s"),
+            Resistance => Unit::base("Ω"),
+            Gravity => Unit::base("m/s²"),
+            Ph => Unit::base("pH"),
+            ElectricalConductivity => Unit::base("S/m"),
+            Concentration => Unit::base("%"),
+            _ => return None,
+        };
+        Some(unit)
+    }
+}
+
+/// Gets the units of an attribute, by its name, if they are known.
+///
+/// This covers the attributes that hold a time or a frequency. The name
+/// can be the short name of the attribute, like "sampling_frequency", or
+/// the full sysfs name, like "in_voltage_sampling_frequency".
+pub fn attr_unit(attr: &str) -> Option<Unit> {
+    const SUFFIXES: &[(&str, Unit)] = &[
+        ("_ns", Unit::scaled("ns", "s", 1e-9)),
+        ("_us", Unit::scaled("µs", "s", 1e-6)),
+        ("_ms", Unit::scaled("ms", "s", 1e-3)),
+        ("integration_time", Unit::base("s")),
+        ("period", Unit::base("s")),
+        ("frequency", Unit::base("Hz")),
+        ("bandwidth", Unit::base("Hz")),
+    ];
+    SUFFIXES
+        .iter()
+        .find(|(suffix, _)| attr.ends_with(suffix))
+        .map(|(_, unit)| *unit)
+}
+
+/// Gets the units of an attribute, checking that they have the base
+/// units.
+fn attr_unit_of(attr: &str, si_symbol: &str) -> Result<Unit> {
+    attr_unit(attr)
+        .filter(|unit| unit.si_symbol == si_symbol)
+        .ok_or_else(|| {
+            Error::General(format!(
+                "Attribute '{}' doesn't have known units of {}",
+                attr, si_symbol
+            ))
+        })
+}
+
+/// Converts the value of a time-like attribute to a duration.
+pub(crate) fn duration_from_attr(attr: &str, val: f64) -> Result<Duration> {
+    let unit = attr_unit_of(attr, "s")?;
+    Duration::try_from_secs_f64(unit.to_si(val))
+        .map_err(|_| Error::General(format!("Invalid duration for '{}': {}", attr, val)))
+}
+
+/// Converts a duration to the value of a time-like attribute.
+pub(crate) fn duration_to_attr(attr: &str, dur: Duration) -> Result<f64> {
+    let unit = attr_unit_of(attr, "s")?;
+    // Scaling from nanoseconds keeps whole values exact
+    Ok(dur.as_nanos() as f64 / (unit.si_factor * 1e9).round())
+}
+
+/// Converts the value of a frequency attribute to Hertz.
+pub(crate) fn hertz_from_attr(attr: &str, val: f64) -> Result<Hertz> {
+    Ok(Hertz(attr_unit_of(attr, "Hz")?.to_si(val)))
+}
+
+/// Converts a frequency to the value of a frequency attribute.
+pub(crate) fn hertz_to_attr(attr: &str, freq: Hertz) -> Result<f64> {
+    Ok(attr_unit_of(attr, "Hz")?.from_si(freq.0))
+}
+
+/// A frequency, in Hertz.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Hertz(pub f64);
+
+impl Hertz {
+    /// Creates a frequency from a value in kHz.
+    pub fn from_khz(khz: f64) -> Self {
+        Self(khz * 1e3)
+    }
+
+    /// Creates a frequency from a value in MHz.
+    pub fn from_mhz(mhz: f64) -> Self {
+        Self(mhz * 1e6)
+    }
+
+    /// Gets the frequency in Hz.
+    pub fn hz(&self) -> f64 {
+        self.0
+    }
+
+    /// Gets the frequency in kHz.
+    pub fn khz(&self) -> f64 {
+        self.0 / 1e3
+    }
+
+    /// Gets the frequency in MHz.
+    pub fn mhz(&self) -> f64 {
+        self.0 / 1e6
+    }
+
+    /// Gets the period of the frequency, if it's positive.
+    pub fn period(&self) -> Option<Duration> {
+        Duration::try_from_secs_f64(1.0 / self.0).ok()
+    }
+}
+
+impl fmt::Display for Hertz {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} Hz", self.0)
+    }
+}
+
+impl ToAttribute for Hertz {
+    /// Converts the frequency to an attribute string, in Hz.
+    fn to_attr(&self) -> Result<String> {
+        Ok(self.0.to_string())
+    }
+}
+
+/// A value with its units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quantity {
+    /// The value, in the ABI units
+    pub value: f64,
+    /// The units, if known
+    pub unit: Option<Unit>,
+}
+
+impl Quantity {
+    /// Gets the value in the base units.
+    ///
+    /// If the units aren't known, this is the value as-is.
+    pub fn si(&self) -> f64 {
+        self.unit.map_or(self.value, |unit| unit.to_si(self.value))
+    }
+}
+
+impl fmt::Display for Quantity {
+    /// Displays the value in the base units.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.unit {
+            Some(unit) => write!(f, "{} {}", unit.to_si(self.value), unit.si_symbol),
+            None => write!(f, "{}", self.value),
+        }
+    }
+}
+
+/// Converts a concentration in percent to parts per million.
+pub fn percent_to_ppm(pct: f64) -> f64 {
+    pct * 1e4
+}
+
+/// Reads the value of an input channel in its ABI units.
+///
+/// This uses the processed `input` attribute if the driver provides one,
+/// otherwise it applies the `offset` and `scale`, if any, to the `raw`
+/// value, as `(raw + offset) * scale`.
+pub(crate) fn read_value<C: ChannelOps>(chan: &C) -> Result<f64> {
+    if chan.has_attr(abi::chan::INPUT) {
+        return chan.attr_read_float(abi::chan::INPUT);
+    }
+    let raw = chan.attr_read_float(abi::chan::RAW)?;
+    let offset = chan.attr_read_float(abi::chan::OFFSET).unwrap_or(0.0);
+    let scale = chan.attr_read_float(abi::chan::SCALE).unwrap_or(1.0);
+    Ok(scaled(raw, offset, scale))
+}
+
+/// Applies the ABI offset and scale to a raw value.
+pub(crate) fn scaled(raw: f64, offset: f64, scale: f64) -> f64 {
+    (raw + offset) * scale
+}
+
+/// Reads the value of an input channel, with its units.
+///
+/// This works with any [`ChannelOps`], like a [`Channel`](crate::Channel).
+pub fn read<C: ChannelOps>(chan: &C) -> Result<Quantity> {
+    Ok(Quantity {
+        value: read_value(chan)?,
+        unit: chan.channel_type().unit(),
+    })
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(scaled(100.0, 0.0, 1.0), 100.0);
+        assert_eq!(scaled(100.0, -20.0, 0.5), 40.0);
+
+        let volts = ChannelType::Voltage.unit().unwrap();
+        assert_eq!(volts.symbol, "mV");
+        assert_eq!(volts.to_si(1500.0), 1.5);
+        assert_eq!(volts.from_si(1.5), 1500.0);
+
+        let q = Quantity {
+            value: 25125.0,
+            unit: ChannelType::Temp.unit(),
+        };
+        assert_eq!(q.si(), 25.125);
+        assert_eq!(q.to_string(), "25.125 °C");
+        assert!(ChannelType::Timestamp.unit().is_none());
+    }
+
+    #[test]
+    fn electrochemical() {
+        let ph = Quantity {
+            value: 7.25,
+            unit: ChannelType::Ph.unit(),
+        };
+        assert_eq!(ph.to_string(), "7.25 pH");
+
+        let ec = ChannelType::ElectricalConductivity.unit().unwrap();
+        assert_eq!(ec.si_symbol, "S/m");
+        assert_eq!(ec.to_si(0.055), 0.055);
+
+        let conc = ChannelType::Concentration.unit().unwrap();
+        assert_eq!(conc.symbol, "%");
+        assert_eq!(percent_to_ppm(0.04), 400.0);
+    }
+
+    #[test]
+    fn attr_units() {
+        assert_eq!(attr_unit("sampling_frequency").unwrap().symbol, "Hz");
+        assert_eq!(
+            attr_unit("in_voltage_sampling_frequency").unwrap().symbol,
+            "Hz"
+        );
+        assert_eq!(attr_unit("autosuspend_delay_ms").unwrap().symbol, "ms");
+        assert!(attr_unit("sampling_frequency_available").is_none());
+        assert!(attr_unit("raw").is_none());
+
+        let dur = duration_from_attr("autosuspend_delay_ms", 250.0).unwrap();
+        assert_eq!(dur, Duration::from_millis(250));
+        assert_eq!(
+            duration_to_attr("autosuspend_delay_ms", Duration::from_millis(100)).unwrap(),
+            100.0
+        );
+        assert_eq!(
+            duration_from_attr("integration_time", 0.5).unwrap(),
+            Duration::from_millis(500)
+        );
+        assert!(duration_from_attr("sampling_frequency", 100.0).is_err());
+
+        let freq = hertz_from_attr("sampling_frequency", 1600.0).unwrap();
+        assert_eq!(freq.khz(), 1.6);
+        assert_eq!(freq.to_attr().unwrap(), "1600");
+        assert_eq!(freq.to_string(), "1600 Hz");
+        assert_eq!(Hertz(100.0).period(), Some(Duration::from_millis(10)));
+        assert!(hertz_from_attr("integration_time", 0.1).is_err());
+    }
+
+    #[test]
+    fn read_channel() {
+        use crate::mock::MockChannel;
+
+        let chan = MockChannel::input("temp", ChannelType::Temp)
+            .attr("raw", "1000")
+            .attr("offset", "-500")
+            .attr("scale", "50");
+        assert_eq!(read(&chan).unwrap().to_string(), "25 °C");
+
+        let chan = MockChannel::input("ph", ChannelType::Ph).attr("input", "6.5");
+        assert_eq!(read(&chan).unwrap().si(), 6.5);
+
+        let chan = MockChannel::input("voltage0", ChannelType::Voltage);
+        assert!(read(&chan).is_err());
+    }
+}
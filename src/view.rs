@@ -0,0 +1,287 @@
+// industrial-io/src/view.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! A view of a device with virtual channels.
+//!
+//! Applications often derive quantities from the channels of a device,
+//! like power from a voltage and a current. A [`DeviceView`] wraps a
+//! [`Device`] and lets these be registered as [`VirtualChannel`]s, each
+//! computed from the frame captured from the hardware channels. The
+//! derived samples are added to each frame that goes through the view,
+//! with their channel type (and so their units), so the sinks and
+//! exporters downstream treat them just like the hardware channels.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::{view::DeviceView, ChannelType};
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("ina219").unwrap();
+//!
+//! let mut view = DeviceView::new(dev);
+//! view.add_virtual_channel("power", |frame| {
+//!     let v = frame.samples("voltage0").unwrap_or_default();
+//!     let i = frame.samples("current0").unwrap_or_default();
+//!     Ok(v.iter().zip(i).map(|(v, i)| v * i * 1e-3).collect())
+//! })
+//! .set_type(ChannelType::Power);
+//!
+//! let frame = view.capture_burst(100).unwrap();
+//! println!("{:?}", frame.samples("power"));
+//! ```
+
+use crate::{
+    process::FrameProcessor, units::Unit, Buffer, Channel, ChannelType, Device, Error, Frame,
+    FrameChannel, FrameSet, Result,
+};
+use std::{fmt, ops::Deref, sync::Arc};
+
+/// The function that computes the samples of a virtual channel.
+type DeriveFn = dyn Fn(&Frame) -> Result<Vec<f64>> + Send + Sync;
+
+/// A channel computed from the other channels in a frame.
+#[derive(Clone)]
+pub struct VirtualChannel {
+    /// The ID of the channel
+    id: String,
+    /// The type of the channel, if known
+    chan_type: Option<ChannelType>,
+    /// The function to compute the samples
+    func: Arc<DeriveFn>,
+}
+
+impl VirtualChannel {
+    /// Creates a virtual channel computed by the function.
+    ///
+    /// The function is given the frame with the hardware channels, and
+    /// any virtual channels added before this one, and should return a
+    /// sample for each sample in the frame.
+    pub fn new<F>(id: &str, f: F) -> Self
+    where
+        F: Fn(&Frame) -> Result<Vec<f64>> + Send + Sync + 'static,
+    {
+        Self {
+            id: id.to_string(),
+            chan_type: None,
+            func: Arc::new(f),
+        }
+    }
+
+    /// Gets the ID of the channel.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Sets the type of the channel.
+    ///
+    /// This determines its units, as for a hardware channel.
+    pub fn set_type(&mut self, chan_type: ChannelType) -> &mut Self {
+        self.chan_type = Some(chan_type);
+        self
+    }
+
+    /// Gets the type of the channel, if set.
+    pub fn channel_type(&self) -> Option<ChannelType> {
+        self.chan_type
+    }
+
+    /// Gets the units of the channel, if its type is set and the type has
+    /// defined units.
+    pub fn unit(&self) -> Option<Unit> {
+        self.chan_type.and_then(|typ| typ.unit())
+    }
+
+    /// Computes the channel from the samples in a frame.
+    ///
+    /// This fails if the function doesn't return the same number of
+    /// samples as are in the frame.
+    pub fn eval(&self, frame: &Frame) -> Result<FrameChannel> {
+        let samples = (self.func)(frame)?;
+        if samples.len() != frame.len() {
+            return Err(Error::General(format!(
+                "Virtual channel '{}' gave {} samples for a frame of {}",
+                self.id,
+                samples.len(),
+                frame.len()
+            )));
+        }
+        Ok(FrameChannel {
+            chan_type: self.chan_type,
+            ..FrameChannel::new(&self.id, samples)
+        })
+    }
+}
+
+impl fmt::Debug for VirtualChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VirtualChannel")
+            .field("id", &self.id)
+            .field("chan_type", &self.chan_type)
+            .finish()
+    }
+}
+
+/// A device with virtual channels.
+///
+/// This dereferences to the underlying [`Device`]. It's also a
+/// [`FrameProcessor`] that adds the virtual channels to the frames passing
+/// through a pipeline.
+#[derive(Debug, Clone)]
+pub struct DeviceView {
+    /// The device
+    dev: Device,
+    /// The virtual channels, in the order they're computed
+    virtuals: Vec<VirtualChannel>,
+}
+
+impl DeviceView {
+    /// Creates a view of the device, with no virtual channels.
+    pub fn new(dev: Device) -> Self {
+        Self {
+            dev,
+            virtuals: Vec::new(),
+        }
+    }
+
+    /// Gets the underlying device.
+    pub fn device(&self) -> &Device {
+        &self.dev
+    }
+
+    /// Adds a virtual channel computed by the function.
+    ///
+    /// The channel is returned so that its metadata can be set. If there
+    /// is already a virtual channel with the name, it is replaced.
+    pub fn add_virtual_channel<F>(&mut self, name: &str, f: F) -> &mut VirtualChannel
+    where
+        F: Fn(&Frame) -> Result<Vec<f64>> + Send + Sync + 'static,
+    {
+        let vchan = VirtualChannel::new(name, f);
+        match self.virtuals.iter().position(|v| v.id == name) {
+            Some(i) => {
+                self.virtuals[i] = vchan;
+                &mut self.virtuals[i]
+            }
+            None => {
+                self.virtuals.push(vchan);
+                self.virtuals.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Removes a virtual channel, returning it, if it exists.
+    pub fn remove_virtual_channel(&mut self, name: &str) -> Option<VirtualChannel> {
+        let i = self.virtuals.iter().position(|v| v.id == name)?;
+        Some(self.virtuals.remove(i))
+    }
+
+    /// Finds a virtual channel by name.
+    pub fn find_virtual_channel(&self, name: &str) -> Option<&VirtualChannel> {
+        self.virtuals.iter().find(|v| v.id == name)
+    }
+
+    /// Gets the virtual channels, in the order they're computed.
+    pub fn virtual_channels(&self) -> &[VirtualChannel] {
+        &self.virtuals
+    }
+
+    /// Adds the virtual channels to a frame captured from the device.
+    pub fn apply(&self, frame: &mut Frame) -> Result<()> {
+        let dev_id = self.dev.id();
+        for vchan in &self.virtuals {
+            let mut ch = vchan.eval(frame)?;
+            ch.device = dev_id.clone();
+            match frame.channels.iter_mut().find(|c| c.id == ch.id) {
+                Some(c) => *c = ch,
+                None => frame.channels.push(ch),
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a frame from the samples in a buffer, with the virtual
+    /// channels.
+    ///
+    /// See [`Frame::from_buffer()`].
+    pub fn frame_from_buffer(&self, buf: &Buffer, chans: &[Channel]) -> Result<Frame> {
+        let mut frame = Frame::from_buffer(buf, chans)?;
+        self.apply(&mut frame)?;
+        Ok(frame)
+    }
+
+    /// Captures a burst of samples from the enabled channels, with the
+    /// virtual channels.
+    ///
+    /// See [`Device::capture_burst()`].
+    pub fn capture_burst(&self, n: usize) -> Result<Frame> {
+        let mut frame = self.dev.capture_burst(n)?;
+        self.apply(&mut frame)?;
+        Ok(frame)
+    }
+}
+
+impl Deref for DeviceView {
+    type Target = Device;
+
+    fn deref(&self) -> &Device {
+        &self.dev
+    }
+}
+
+impl From<Device> for DeviceView {
+    fn from(dev: Device) -> Self {
+        Self::new(dev)
+    }
+}
+
+impl FrameProcessor for DeviceView {
+    fn process(&mut self, f: &mut FrameSet) -> Result<()> {
+        for frame in f.frames.iter_mut() {
+            self.apply(frame)?;
+        }
+        Ok(())
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_virtual() {
+        let frame = Frame {
+            channels: vec![
+                FrameChannel::new("voltage0", vec![1000.0, 2000.0]),
+                FrameChannel::new("current0", vec![10.0, 20.0]),
+            ],
+            ..Frame::default()
+        };
+
+        let mut vchan = VirtualChannel::new("power", |f: &Frame| {
+            let v = f.samples("voltage0").unwrap_or_default();
+            let i = f.samples("current0").unwrap_or_default();
+            Ok(v.iter().zip(i).map(|(v, i)| v * i * 1e-3).collect())
+        });
+        vchan.set_type(ChannelType::Power);
+        assert_eq!(vchan.unit().unwrap().symbol, "mW");
+
+        let ch = vchan.eval(&frame).unwrap();
+        assert_eq!(ch.id, "power");
+        assert_eq!(ch.chan_type, Some(ChannelType::Power));
+        assert_eq!(ch.samples, [10.0, 40.0]);
+
+        let short = VirtualChannel::new("bad", |_: &Frame| Ok(vec![0.0]));
+        assert!(short.eval(&frame).is_err());
+    }
+}
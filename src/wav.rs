@@ -0,0 +1,374 @@
+// industrial-io/src/wav.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Exporting audio-rate channels to WAV files.
+//!
+//! Many sensors, like microphones, vibration sensors, and hydrophones,
+//! are sampled in the audio band. Writing their samples to a PCM WAV file
+//! is a quick way to listen to them, or to look at them in a standard
+//! tool like Audacity or Sonic Visualiser.
+//!
+//! A [`WavWriter`] writes PCM samples to any seekable output, and fixes
+//! up the sizes in the header when it's finished. A [`ChannelWav`] streams
+//! one or two channels of a device from refilled buffers into a file,
+//! taking the sample rate from the device, and the sample size from the
+//! data format of the channels.
+//!
+//! This requires the `wav` feature.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::wav::ChannelWav;
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("adau1761").unwrap();
+//! let chan = dev.find_input_channel("voltage0").unwrap();
+//! chan.enable();
+//!
+//! let mut wav = ChannelWav::create("mic.wav", &dev, &[chan]).unwrap();
+//! let mut buf = dev.create_buffer(4800, false).unwrap();
+//! for _ in 0..100 {
+//!     buf.refill().unwrap();
+//!     wav.write_buffer(&buf).unwrap();
+//! }
+//! wav.finish().unwrap();
+//! ```
+
+use crate::{Buffer, Channel, DataFormat, Device, Error, Result};
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// The size of the RIFF and format headers, up to the data chunk.
+const HEADER_LEN: u32 = 44;
+
+/// The PCM format code in a WAV file.
+const WAVE_FORMAT_PCM: u16 = 1;
+
+/// The format of the samples in a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WavSpec {
+    /// The number of channels
+    pub channels: u16,
+    /// The sample rate, in Hz
+    pub sample_rate: u32,
+    /// The number of bits in each sample: 8, 16, 24, or 32
+    pub bits_per_sample: u16,
+}
+
+impl WavSpec {
+    /// Gets the number of bytes in each sample of one channel.
+    pub fn sample_size(&self) -> usize {
+        usize::from(self.bits_per_sample / 8)
+    }
+
+    /// Gets the number of bytes in each frame, a sample for every
+    /// channel.
+    pub fn frame_size(&self) -> usize {
+        self.sample_size() * usize::from(self.channels)
+    }
+
+    /// Checks that the format can be written.
+    fn validate(&self) -> Result<()> {
+        if self.channels == 0 || self.sample_rate == 0 {
+            return Err(Error::General(format!("Invalid WAV format: {:?}", self)));
+        }
+        if !matches!(self.bits_per_sample, 8 | 16 | 24 | 32) {
+            return Err(Error::General(format!(
+                "Unsupported WAV sample size: {} bits",
+                self.bits_per_sample
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A writer of PCM samples into a WAV file.
+///
+/// The sizes in the header are written when the writer is finished. If
+/// it's dropped instead, they're updated on a best-effort basis.
+#[derive(Debug)]
+pub struct WavWriter<W: Write + Seek> {
+    /// The output. This is only `None` after it's finished.
+    out: Option<W>,
+    /// The format of the samples
+    spec: WavSpec,
+    /// The number of bytes of sample data written
+    data_len: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Creates a writer, writing the header to the output.
+    pub fn new(mut out: W, spec: WavSpec) -> Result<Self> {
+        spec.validate()?;
+        write_header(&mut out, &spec, 0)?;
+        Ok(Self {
+            out: Some(out),
+            spec,
+            data_len: 0,
+        })
+    }
+
+    /// Gets the format of the samples.
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    /// Gets the number of frames written so far.
+    pub fn num_frames(&self) -> u64 {
+        u64::from(self.data_len) / self.spec.frame_size() as u64
+    }
+
+    /// Writes interleaved samples, one for each channel in turn.
+    ///
+    /// The values must be in the range of the sample size, and are
+    /// truncated otherwise. For 8-bit samples, they're unsigned, with
+    /// silence at 128, and for the others, they're signed.
+    pub fn write_samples(&mut self, samples: &[i32]) -> Result<()> {
+        if samples.len() % usize::from(self.spec.channels) != 0 {
+            return Err(Error::BadReturnSize);
+        }
+        let size = self.spec.sample_size();
+        let len = u32::try_from(samples.len() * size)
+            .ok()
+            .and_then(|n| self.data_len.checked_add(n))
+            .filter(|&n| n <= u32::MAX - HEADER_LEN)
+            .ok_or_else(|| Error::General("WAV file is full".into()))?;
+
+        let mut bytes = Vec::with_capacity(samples.len() * size);
+        for x in samples {
+            bytes.extend_from_slice(&x.to_le_bytes()[..size]);
+        }
+        let out = self.out.as_mut().ok_or(Error::InvalidIndex)?;
+        out.write_all(&bytes)?;
+        self.data_len = len;
+        Ok(())
+    }
+
+    /// Writes the sizes into the header, then seeks back to the end.
+    fn update_header(&mut self) -> Result<()> {
+        if let Some(out) = self.out.as_mut() {
+            out.seek(SeekFrom::Start(0))?;
+            write_header(out, &self.spec, self.data_len)?;
+            out.seek(SeekFrom::End(0))?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the file, writing the sizes into the header, and gives
+    /// back the output.
+    pub fn finish(mut self) -> Result<W> {
+        self.update_header()?;
+        Ok(self.out.take().unwrap())
+    }
+}
+
+impl WavWriter<BufWriter<File>> {
+    /// Creates a new WAV file.
+    pub fn create<P: AsRef<Path>>(path: P, spec: WavSpec) -> Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), spec)
+    }
+}
+
+impl<W: Write + Seek> Drop for WavWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.update_header();
+    }
+}
+
+/// Writes the RIFF, format, and data chunk headers.
+fn write_header<W: Write>(out: &mut W, spec: &WavSpec, data_len: u32) -> Result<()> {
+    let frame_size = spec.frame_size() as u32;
+    let mut hdr = Vec::with_capacity(HEADER_LEN as usize);
+    hdr.extend_from_slice(b"RIFF");
+    hdr.extend_from_slice(&(HEADER_LEN - 8 + data_len).to_le_bytes());
+    hdr.extend_from_slice(b"WAVEfmt ");
+    hdr.extend_from_slice(&16u32.to_le_bytes());
+    hdr.extend_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+    hdr.extend_from_slice(&spec.channels.to_le_bytes());
+    hdr.extend_from_slice(&spec.sample_rate.to_le_bytes());
+    hdr.extend_from_slice(&(spec.sample_rate * frame_size).to_le_bytes());
+    hdr.extend_from_slice(&(frame_size as u16).to_le_bytes());
+    hdr.extend_from_slice(&spec.bits_per_sample.to_le_bytes());
+    hdr.extend_from_slice(b"data");
+    hdr.extend_from_slice(&data_len.to_le_bytes());
+    out.write_all(&hdr)?;
+    Ok(())
+}
+
+/// Converts a raw sample in a data format to a PCM value of the size.
+///
+/// The value is shifted up so that the full range of the channel fills
+/// the range of the PCM sample, and unsigned values are centered on zero,
+/// or on 128 for 8-bit samples.
+pub fn to_pcm(raw: f64, fmt: &DataFormat, bits_per_sample: u16) -> i32 {
+    let bits = fmt.bits().clamp(1, 32) as i32;
+    let out_bits = i32::from(bits_per_sample);
+    let mut x = raw;
+    if !fmt.is_signed() {
+        x -= 2f64.powi(bits - 1);
+    }
+    let x = x * 2f64.powi(out_bits - bits);
+    let max = 2f64.powi(out_bits - 1);
+    let x = x.round().clamp(-max, max - 1.0) as i64;
+    if bits_per_sample == 8 {
+        (x + 128) as i32
+    }
+    else {
+        x as i32
+    }
+}
+
+/// Streams one or two channels of a device into a WAV file.
+#[derive(Debug)]
+pub struct ChannelWav {
+    /// The writer for the file
+    writer: WavWriter<BufWriter<File>>,
+    /// The channels to write
+    chans: Vec<Channel>,
+}
+
+impl ChannelWav {
+    /// Creates a WAV file for the channels of a device.
+    ///
+    /// There can be one or two channels, for a mono or stereo file. The
+    /// sample rate is the sampling frequency of the device, or its
+    /// trigger, and the samples are 8, 16, or 32 bits, whatever holds the
+    /// widest channel.
+    pub fn create<P: AsRef<Path>>(path: P, dev: &Device, chans: &[Channel]) -> Result<Self> {
+        let rate = dev.sampling_frequency()?.round();
+        if !(1.0..=f64::from(u32::MAX)).contains(&rate) {
+            return Err(Error::General(format!("Invalid sample rate: {}", rate)));
+        }
+        Self::with_rate(path, chans, rate as u32)
+    }
+
+    /// Creates a WAV file for the channels, with the sample rate, in Hz.
+    pub fn with_rate<P: AsRef<Path>>(path: P, chans: &[Channel], sample_rate: u32) -> Result<Self> {
+        if !(1..=2).contains(&chans.len()) {
+            return Err(Error::General(format!(
+                "A WAV file needs one or two channels, not {}",
+                chans.len()
+            )));
+        }
+        let bits = chans
+            .iter()
+            .map(|chan| chan.data_format().bits())
+            .max()
+            .unwrap_or_default();
+        let bits_per_sample = match bits {
+            0..=8 => 8,
+            9..=16 => 16,
+            _ => 32,
+        };
+        let spec = WavSpec {
+            channels: chans.len() as u16,
+            sample_rate,
+            bits_per_sample,
+        };
+        Ok(Self {
+            writer: WavWriter::create(path, spec)?,
+            chans: chans.to_vec(),
+        })
+    }
+
+    /// Gets the format of the samples in the file.
+    pub fn spec(&self) -> WavSpec {
+        self.writer.spec()
+    }
+
+    /// Demultiplexes and converts the samples of the channels in the
+    /// buffer, and appends them to the file.
+    pub fn write_buffer(&mut self, buf: &Buffer) -> Result<()> {
+        let bits_per_sample = self.writer.spec().bits_per_sample;
+        let mut chans = Vec::with_capacity(self.chans.len());
+        for chan in &self.chans {
+            let fmt = chan.data_format();
+            let samples: Vec<i32> = chan
+                .read_f64(buf)?
+                .into_iter()
+                .map(|x| to_pcm(x, &fmt, bits_per_sample))
+                .collect();
+            chans.push(samples);
+        }
+
+        let n = chans.iter().map(Vec::len).min().unwrap_or_default();
+        let samples: Vec<i32> = (0..n)
+            .flat_map(|i| chans.iter().map(move |v| v[i]))
+            .collect();
+        self.writer.write_samples(&samples)
+    }
+
+    /// Finishes the file, writing the sizes into the header.
+    pub fn finish(self) -> Result<()> {
+        self.writer.finish()?.flush()?;
+        Ok(())
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn header() {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+        };
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), spec).unwrap();
+        wav.write_samples(&[1, -1, 2, -2]).unwrap();
+        assert!(wav.write_samples(&[1]).is_err());
+        assert_eq!(wav.num_frames(), 2);
+
+        let v = wav.finish().unwrap().into_inner();
+        assert_eq!(v.len(), 44 + 8);
+        assert_eq!(&v[0..4], b"RIFF");
+        assert_eq!(&v[4..8], &44u32.to_le_bytes());
+        assert_eq!(&v[8..16], b"WAVEfmt ");
+        assert_eq!(&v[22..24], &2u16.to_le_bytes());
+        assert_eq!(&v[24..28], &8000u32.to_le_bytes());
+        assert_eq!(&v[28..32], &32000u32.to_le_bytes());
+        assert_eq!(&v[32..34], &4u16.to_le_bytes());
+        assert_eq!(&v[36..40], b"data");
+        assert_eq!(&v[40..44], &8u32.to_le_bytes());
+        assert_eq!(&v[44..], &[1, 0, 255, 255, 2, 0, 254, 255]);
+
+        let bad = WavSpec {
+            bits_per_sample: 12,
+            ..spec
+        };
+        assert!(WavWriter::new(Cursor::new(Vec::new()), bad).is_err());
+    }
+
+    #[test]
+    fn pcm_values() {
+        let fmt = DataFormat::parse("le:s12/16>>4").unwrap();
+        assert_eq!(to_pcm(2047.0, &fmt, 16), 32752);
+        assert_eq!(to_pcm(-2048.0, &fmt, 16), -32768);
+
+        let fmt = DataFormat::parse("le:u12/16>>0").unwrap();
+        assert_eq!(to_pcm(2048.0, &fmt, 16), 0);
+        assert_eq!(to_pcm(0.0, &fmt, 16), -32768);
+
+        let fmt = DataFormat::parse("le:u8/8>>0").unwrap();
+        assert_eq!(to_pcm(128.0, &fmt, 8), 128);
+        assert_eq!(to_pcm(255.0, &fmt, 8), 255);
+    }
+}
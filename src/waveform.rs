@@ -0,0 +1,306 @@
+// industrial-io/src/waveform.rs
+//
+// Copyright (c) 2024, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Waveform generation for cyclic DAC output.
+//!
+//! A common use of an output device is as a DDS: a buffer holding a whole
+//! number of periods of a waveform is pushed once in cyclic mode, and the
+//! hardware plays it out repeatedly until the buffer is destroyed. A
+//! [`Waveform`] describes a sine, square, ramp, or triangle wave at a
+//! frequency and sample rate. It works out how many samples it takes to
+//! repeat seamlessly, scales them to the data format of the output
+//! channels, and creates and pushes the cyclic buffer.
+//!
+//! ```no_run
+//! use industrial_io as iio;
+//! use iio::waveform::{Shape, Waveform};
+//!
+//! let ctx = iio::Context::new().unwrap();
+//! let dev = ctx.find_device("axi-ad9361-dds-core-lpc").unwrap();
+//! let chan = dev.find_output_channel("voltage0").unwrap();
+//!
+//! // Keep the buffer alive for as long as the waveform should play.
+//! let buf = Waveform::new(Shape::Sine, 1.0e6, 30.72e6)
+//!     .amplitude(0.5)
+//!     .output(&dev, &[chan], 65536)
+//!     .unwrap();
+//! ```
+
+use crate::{Buffer, Channel, DataFormat, Device, Error, Result};
+use std::f64::consts::TAU;
+
+/// The tolerance for a number of samples to be considered a whole number.
+const WHOLE_TOLERANCE: f64 = 1.0e-6;
+
+/// The shape of a waveform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Shape {
+    /// A sine wave
+    Sine,
+    /// A square wave, with a 50% duty cycle
+    Square,
+    /// A ramp, or sawtooth, rising from the minimum to the maximum
+    Ramp,
+    /// A triangle wave
+    Triangle,
+}
+
+/// A periodic waveform to be played out of a DAC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Waveform {
+    /// The shape of the wave
+    shape: Shape,
+    /// The frequency of the wave, in Hz
+    freq: f64,
+    /// The sample rate of the output, in Hz
+    sample_rate: f64,
+    /// The amplitude, as a fraction of full scale
+    amplitude: f64,
+    /// The offset, as a fraction of full scale
+    offset: f64,
+    /// The phase at the first sample, in radians
+    phase: f64,
+}
+
+impl Waveform {
+    /// Creates a full-scale waveform at the frequency, for an output
+    /// with the sample rate, both in Hz.
+    pub fn new(shape: Shape, freq: f64, sample_rate: f64) -> Self {
+        Self {
+            shape,
+            freq,
+            sample_rate,
+            amplitude: 1.0,
+            offset: 0.0,
+            phase: 0.0,
+        }
+    }
+
+    /// Sets the amplitude, as a fraction of full scale, from 0 to 1.
+    pub fn amplitude(mut self, amplitude: f64) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    /// Sets the offset, as a fraction of full scale, from -1 to 1.
+    pub fn offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets the phase at the first sample, in radians.
+    pub fn phase(mut self, phase: f64) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    /// Checks that the parameters make sense.
+    fn validate(&self) -> Result<()> {
+        let ok = self.freq.is_finite()
+            && self.freq > 0.0
+            && self.sample_rate.is_finite()
+            && self.freq <= self.sample_rate / 2.0
+            && (0.0..=1.0).contains(&self.amplitude)
+            && (-1.0..=1.0).contains(&self.offset)
+            && self.phase.is_finite();
+        if ok {
+            Ok(())
+        }
+        else {
+            Err(Error::General(format!("Invalid waveform: {:?}", self)))
+        }
+    }
+
+    /// Gets the number of samples, and the number of periods of the wave
+    /// that they hold, to repeat seamlessly in a cyclic buffer of up to
+    /// `max_samples`.
+    ///
+    /// This looks for the fewest periods that fit in a whole number of
+    /// samples. If there are none, the most periods that fit are used,
+    /// rounded to the nearest sample, so the frequency that is played is
+    /// slightly off. See [`actual_frequency()`](Waveform::actual_frequency).
+    pub fn cycle_len(&self, max_samples: usize) -> Result<(usize, usize)> {
+        self.validate()?;
+        let period = self.sample_rate / self.freq;
+        let max_cycles = (max_samples as f64 / period).floor() as usize;
+        if max_cycles == 0 {
+            return Err(Error::General(format!(
+                "A period of {} samples doesn't fit in {} samples",
+                period, max_samples
+            )));
+        }
+
+        let n = |cycles: usize| cycles as f64 * period;
+        let cycles = (1..=max_cycles)
+            .find(|&k| (n(k) - n(k).round()).abs() < WHOLE_TOLERANCE)
+            .unwrap_or(max_cycles);
+        Ok((n(cycles).round() as usize, cycles))
+    }
+
+    /// Gets the frequency that is actually played from a cyclic buffer of
+    /// up to `max_samples`, in Hz.
+    pub fn actual_frequency(&self, max_samples: usize) -> Result<f64> {
+        let (n, cycles) = self.cycle_len(max_samples)?;
+        Ok(cycles as f64 * self.sample_rate / n as f64)
+    }
+
+    /// Gets the value of the wave at a phase, from -1 to 1 before the
+    /// amplitude and offset are applied.
+    fn shape_value(&self, phase: f64) -> f64 {
+        let frac = (phase / TAU).rem_euclid(1.0);
+        match self.shape {
+            Shape::Sine => phase.sin(),
+            Shape::Square if frac < 0.5 => 1.0,
+            Shape::Square => -1.0,
+            Shape::Ramp => 2.0 * frac - 1.0,
+            Shape::Triangle => 1.0 - 4.0 * (frac - 0.5).abs(),
+        }
+    }
+
+    /// Generates `n` samples of the wave, with `cycles` periods, as
+    /// fractions of full scale, from -1 to 1.
+    pub fn samples(&self, n: usize, cycles: usize) -> Vec<f64> {
+        (0..n)
+            .map(|i| {
+                let phase = self.phase + TAU * (cycles * i) as f64 / n as f64;
+                let x = self.offset + self.amplitude * self.shape_value(phase);
+                x.clamp(-1.0, 1.0)
+            })
+            .collect()
+    }
+
+    /// Creates a cyclic buffer for the device with the waveform on each
+    /// of the channels, and pushes it to the hardware.
+    ///
+    /// The channels are enabled, and the samples are scaled to the full
+    /// range of the data format of each one. The hardware plays the
+    /// waveform until the returned buffer is dropped.
+    pub fn output(&self, dev: &Device, chans: &[Channel], max_samples: usize) -> Result<Buffer> {
+        let (n, cycles) = self.cycle_len(max_samples)?;
+        let samples = self.samples(n, cycles);
+
+        for chan in chans {
+            if !chan.is_output() {
+                return Err(Error::General(format!(
+                    "'{}' is not an output channel",
+                    chan.id().unwrap_or_default()
+                )));
+            }
+            chan.enable();
+        }
+
+        let buf = dev.create_buffer(n, true)?;
+        for chan in chans {
+            write_scaled(chan, &buf, &samples)?;
+        }
+        buf.push()?;
+        Ok(buf)
+    }
+}
+
+/// Converts a fraction of full scale, from -1 to 1, to a raw value in
+/// the data format.
+///
+/// Signed formats are centered on zero, and unsigned ones on mid-scale.
+pub fn to_raw(x: f64, fmt: &DataFormat) -> i64 {
+    let bits = fmt.bits().clamp(1, 63);
+    let x = x.clamp(-1.0, 1.0);
+    if fmt.is_signed() {
+        let max = ((1u64 << (bits - 1)) - 1) as f64;
+        (x * max).round() as i64
+    }
+    else {
+        let max = ((1u64 << bits) - 1) as f64;
+        ((x + 1.0) / 2.0 * max).round() as i64
+    }
+}
+
+/// Writes the samples, as fractions of full scale, to a channel of the
+/// buffer, in the raw type of the channel.
+fn write_scaled(chan: &Channel, buf: &Buffer, samples: &[f64]) -> Result<usize> {
+    let fmt = chan.data_format();
+    let raw = samples.iter().map(|&x| to_raw(x, &fmt));
+
+    match (fmt.byte_length(), fmt.is_signed()) {
+        (1, true) => chan.write(buf, &raw.map(|v| v as i8).collect::<Vec<_>>()),
+        (1, false) => chan.write(buf, &raw.map(|v| v as u8).collect::<Vec<_>>()),
+        (2, true) => chan.write(buf, &raw.map(|v| v as i16).collect::<Vec<_>>()),
+        (2, false) => chan.write(buf, &raw.map(|v| v as u16).collect::<Vec<_>>()),
+        (4, true) => chan.write(buf, &raw.map(|v| v as i32).collect::<Vec<_>>()),
+        (4, false) => chan.write(buf, &raw.map(|v| v as u32).collect::<Vec<_>>()),
+        (8, true) => chan.write(buf, &raw.collect::<Vec<_>>()),
+        (8, false) => chan.write(buf, &raw.map(|v| v as u64).collect::<Vec<_>>()),
+        _ => Err(Error::WrongDataType),
+    }
+}
+
+// --------------------------------------------------------------------------
+//                              Unit Tests
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_len() {
+        // 1 MHz at 30.72 MHz is 30.72 samples per period, so it takes 25
+        // periods to fill a whole number of samples.
+        let wave = Waveform::new(Shape::Sine, 1.0e6, 30.72e6);
+        assert_eq!(wave.cycle_len(65536).unwrap(), (768, 25));
+        assert_eq!(wave.actual_frequency(65536).unwrap(), 1.0e6);
+
+        // Too small to fit the 25 periods
+        let (n, cycles) = wave.cycle_len(500).unwrap();
+        assert_eq!((n, cycles), (492, 16));
+
+        assert!(wave.cycle_len(10).is_err());
+        assert!(Waveform::new(Shape::Sine, 20.0e6, 30.72e6)
+            .cycle_len(1024)
+            .is_err());
+        assert!(wave.amplitude(2.0).cycle_len(1024).is_err());
+    }
+
+    #[test]
+    fn shapes() {
+        let close = |a: &[f64], b: &[f64]| a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-9);
+
+        let v = Waveform::new(Shape::Sine, 1.0, 4.0).samples(4, 1);
+        assert!(close(&v, &[0.0, 1.0, 0.0, -1.0]));
+
+        let v = Waveform::new(Shape::Square, 1.0, 4.0).samples(4, 1);
+        assert_eq!(v, [1.0, 1.0, -1.0, -1.0]);
+
+        let v = Waveform::new(Shape::Ramp, 1.0, 4.0).samples(4, 1);
+        assert!(close(&v, &[-1.0, -0.5, 0.0, 0.5]));
+
+        let v = Waveform::new(Shape::Triangle, 1.0, 4.0).samples(4, 1);
+        assert!(close(&v, &[-1.0, 0.0, 1.0, 0.0]));
+
+        let v = Waveform::new(Shape::Square, 1.0, 4.0)
+            .amplitude(0.5)
+            .offset(0.75)
+            .samples(4, 1);
+        assert_eq!(v, [1.0, 1.0, 0.25, 0.25]);
+    }
+
+    #[test]
+    fn raw_values() {
+        let fmt = DataFormat::parse("le:s12/16>>4").unwrap();
+        assert_eq!(to_raw(1.0, &fmt), 2047);
+        assert_eq!(to_raw(-1.0, &fmt), -2047);
+        assert_eq!(to_raw(0.0, &fmt), 0);
+
+        let fmt = DataFormat::parse("le:u12/16>>0").unwrap();
+        assert_eq!(to_raw(1.0, &fmt), 4095);
+        assert_eq!(to_raw(-1.0, &fmt), 0);
+        assert_eq!(to_raw(0.0, &fmt), 2048);
+    }
+}